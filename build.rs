@@ -0,0 +1,8 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // 使用 protobuf-src 編譯的 protoc，不需要系統安裝
+    std::env::set_var("PROTOC", protobuf_src::protoc());
+    tonic_build::configure()
+        .build_client(false)
+        .compile_protos(&["proto/wallet_monitor.proto"], &["proto"])?;
+    Ok(())
+}
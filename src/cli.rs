@@ -0,0 +1,351 @@
+// 命令列介面：serve 啟動監控服務，其餘子命令直接操作配置文件與資料庫，方便以腳本管理
+use {
+    crate::{
+        is_sol_domain, is_valid_color, load_config, open_history_store, resolve_sol_domain, serve,
+        set_wallet_config_field, update_config_file, validate_wallet_address, AccountType, Config, Language, RpcPool,
+        SharedDatabase,
+    },
+    chrono::{DateTime, Duration, Utc},
+    clap::{Args, Parser, Subcommand, ValueEnum},
+    std::{
+        collections::HashSet,
+        io::{self, Write},
+    },
+};
+
+// 匯出 JSON 時每次從資料庫讀取的筆數
+const EXPORT_PAGE_SIZE: usize = 10_000;
+
+/// Solana 錢包餘額監控器
+#[derive(Debug, Parser)]
+#[command(name = "sol-wallet-monitor", version)]
+pub(crate) struct Cli {
+    /// 配置文件路徑
+    #[arg(long, short, global = true, default_value = "config.toml")]
+    pub(crate) config: String,
+    /// 資料庫檔案路徑，覆寫配置中的 [database].path
+    #[arg(long, global = true)]
+    database: Option<String>,
+    /// 未指定子命令時等同 serve
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// 啟動監控服務 (Web / WebSocket / gRPC)
+    Serve(ServeArgs),
+    /// 在配置文件中新增錢包
+    AddWallet(AddWalletArgs),
+    /// 列出配置中的錢包與資料庫中的最新餘額
+    List(ListArgs),
+    /// 匯出錢包的原始歷史記錄
+    ExportHistory(ExportHistoryArgs),
+    /// 壓縮資料庫檔案，回收刪除記錄後的空間
+    CompactDb,
+    /// 檢查配置文件，有問題時以非零狀態碼結束
+    VerifyConfig,
+}
+
+#[derive(Debug, Args, Default)]
+struct ServeArgs {
+    /// 覆寫 [server].host
+    #[arg(long)]
+    host: Option<String>,
+    /// 覆寫 [server].port
+    #[arg(long, short)]
+    port: Option<u16>,
+}
+
+#[derive(Debug, Args)]
+struct AddWalletArgs {
+    /// 錢包地址 (base58) 或 .sol 網域
+    #[arg(long)]
+    address: String,
+    /// 顯示名稱
+    #[arg(long)]
+    name: String,
+    /// 所屬群組
+    #[arg(long)]
+    group: Option<String>,
+    /// 允許不在 ed25519 曲線上的地址 (PDA，例如多簽金庫)
+    #[arg(long)]
+    allow_off_curve: bool,
+    /// 帳戶類型：wallet、account (只追蹤 lamports)、token_account 或 validator (驗證者 identity)
+    #[arg(long, value_enum, default_value_t = AccountType::Wallet)]
+    account_type: AccountType,
+    /// 驗證者的 vote account，只適用於 validator 類型
+    #[arg(long)]
+    vote_account: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct ListArgs {
+    /// 以 JSON 輸出
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Args)]
+struct ExportHistoryArgs {
+    /// 錢包地址
+    #[arg(long)]
+    address: String,
+    /// 起始時間 (Unix 秒)
+    #[arg(long)]
+    from: Option<i64>,
+    /// 結束時間 (Unix 秒)
+    #[arg(long)]
+    to: Option<i64>,
+    #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+    format: ExportFormat,
+    /// 輸出檔案，省略時寫到 stdout
+    #[arg(long, short)]
+    output: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Csv,
+    Json, // 與 /api/history 相同的記錄格式，可直接用於 /api/admin/restore
+}
+
+pub(crate) async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    match cli.command {
+        None => serve(load_cli_config(&cli.database)?).await,
+        Some(Command::Serve(args)) => {
+            let mut config = load_cli_config(&cli.database)?;
+            if let Some(host) = args.host {
+                config.server.host = host;
+            }
+            if let Some(port) = args.port {
+                config.server.port = port;
+            }
+            serve(config).await
+        }
+        Some(Command::AddWallet(args)) => add_wallet(args).await,
+        Some(Command::List(args)) => list_wallets(&load_cli_config(&cli.database)?, args),
+        Some(Command::ExportHistory(args)) => export_history(&load_cli_config(&cli.database)?, args).await,
+        Some(Command::CompactDb) => compact_database(&load_cli_config(&cli.database)?),
+        Some(Command::VerifyConfig) => verify_config(&cli.config),
+    }
+}
+
+fn load_cli_config(database: &Option<String>) -> Result<Config, Box<dyn std::error::Error>> {
+    let mut config = load_config()?;
+    if let Some(path) = database {
+        config.database.path = Some(path.clone());
+    }
+    Ok(config)
+}
+
+// redb 同一時間只能由一個程式開啟，服務執行中時請改用 HTTP API
+fn open_database(config: &Config) -> Result<SharedDatabase, Box<dyn std::error::Error>> {
+    open_history_store(&config.database).map_err(|e| format!("無法開啟資料庫 {} (服務是否正在執行?): {}", config.database.path(), e).into())
+}
+
+async fn add_wallet(args: AddWalletArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config()?;
+    let name = args.name.trim();
+    if name.is_empty() {
+        return Err("錢包名稱不能為空".into());
+    }
+    let input = args.address.trim();
+    let domain = is_sol_domain(input).then(|| input.to_ascii_lowercase());
+    let address = match &domain {
+        Some(domain) => resolve_sol_domain(domain, &RpcPool::new(&config.rpc))
+            .await
+            .map_err(|e| format!("解析網域 {} 失敗: {}", domain, e))?
+            .ok_or_else(|| format!("找不到此 .sol 網域: {}", domain))?,
+        None => input.to_string(),
+    };
+    let address = address.as_str();
+    if let Err(code) = validate_wallet_address(address, args.allow_off_curve || args.account_type.allows_off_curve()) {
+        return Err(format!("{}: {}", code.message(Language::Zh), address).into());
+    }
+    let vote_account = args.vote_account.as_deref().map(str::trim).filter(|v| !v.is_empty());
+    if let Some(vote_account) = vote_account {
+        if args.account_type != AccountType::Validator {
+            return Err("--vote-account 只適用於 --account-type validator".into());
+        }
+        if let Err(code) = validate_wallet_address(vote_account, false) {
+            return Err(format!("{}: {}", code.message(Language::Zh), vote_account).into());
+        }
+    }
+    if config.wallets.iter().any(|w| w.address == address) {
+        return Err(format!("此錢包地址已存在: {}", address).into());
+    }
+    if config.wallets.iter().any(|w| w.name == name) {
+        return Err(format!("此錢包名稱已存在: {}", name).into());
+    }
+
+    update_config_file(address, name).await?;
+    if let Some(domain) = &domain {
+        set_wallet_config_field(address, "domain", Some(toml::Value::String(domain.clone()))).await?;
+    }
+    if args.account_type != AccountType::Wallet {
+        set_wallet_config_field(address, "account_type", Some(toml::Value::String(args.account_type.as_str().to_string()))).await?;
+    }
+    if let Some(vote_account) = vote_account {
+        set_wallet_config_field(address, "vote_account", Some(toml::Value::String(vote_account.to_string()))).await?;
+    }
+    if args.allow_off_curve {
+        set_wallet_config_field(address, "allow_off_curve", Some(toml::Value::Boolean(true))).await?;
+    }
+    if let Some(group) = args.group.as_deref().map(str::trim).filter(|g| !g.is_empty()) {
+        set_wallet_config_field(address, "group", Some(toml::Value::String(group.to_string()))).await?;
+    }
+    println!("✅ 已新增錢包 {} ({})，執行中的服務會自動載入 (watch_config = false 時需重新啟動)", name, address);
+    Ok(())
+}
+
+fn list_wallets(config: &Config, args: ListArgs) -> Result<(), Box<dyn std::error::Error>> {
+    // 資料庫無法開啟時仍列出配置中的錢包
+    let database = match open_database(config) {
+        Ok(database) => Some(database),
+        Err(e) => {
+            eprintln!("⚠️ {}，只列出配置中的錢包", e);
+            None
+        }
+    };
+
+    let mut rows = Vec::new();
+    for wallet in &config.wallets {
+        let latest = match &database {
+            Some(database) => database.load_latest(&wallet.address)?,
+            None => None,
+        };
+        rows.push(serde_json::json!({
+            "address": wallet.address,
+            "name": wallet.name,
+            "group": wallet.group,
+            "archived": wallet.archived,
+            "total_balance": latest.as_ref().map(|r| r.total_balance),
+            "last_update": latest.as_ref().map(|r| r.timestamp),
+        }));
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(());
+    }
+    println!("{:<20} {:<44} {:<12} {:>18}  最後更新", "名稱", "地址", "群組", "總餘額 (SOL)");
+    for (wallet, row) in config.wallets.iter().zip(&rows) {
+        let balance = row["total_balance"].as_f64().map(|b| format!("{:.9}", b)).unwrap_or_else(|| "-".to_string());
+        let updated = row["last_update"].as_str().unwrap_or("-");
+        let name = if wallet.archived { format!("{} (封存)", wallet.name) } else { wallet.name.clone() };
+        println!("{:<20} {:<44} {:<12} {:>18}  {}", name, wallet.address, wallet.group.as_deref().unwrap_or("-"), balance, updated);
+    }
+    Ok(())
+}
+
+async fn export_history(config: &Config, args: ExportHistoryArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let database = open_database(config)?;
+    let from = args.from.and_then(|t| DateTime::<Utc>::from_timestamp(t, 0));
+    let to = args.to.and_then(|t| DateTime::<Utc>::from_timestamp(t, 0));
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(io::BufWriter::new(std::fs::File::create(path)?)),
+        None => Box::new(io::BufWriter::new(io::stdout().lock())),
+    };
+
+    let mut exported = 0;
+    match args.format {
+        ExportFormat::Csv => {
+            // 與 /history.csv 相同，由背景執行緒逐段讀取資料庫
+            let (sender, mut receiver) = tokio::sync::mpsc::channel(16);
+            let address = args.address.clone();
+            let producer = tokio::task::spawn_blocking(move || {
+                database.stream_history_csv(&address, from, to, &sender).map_err(|e| e.to_string())
+            });
+            while let Some(chunk) = receiver.recv().await {
+                let chunk = chunk?;
+                exported += chunk.lines().count();
+                output.write_all(chunk.as_bytes())?;
+            }
+            producer.await??;
+            exported = exported.saturating_sub(1); // 扣除標題列
+        }
+        ExportFormat::Json => {
+            output.write_all(b"[")?;
+            let mut cursor = from;
+            loop {
+                let page = database.load_history(&args.address, cursor, to, EXPORT_PAGE_SIZE)?;
+                for record in &page {
+                    if exported > 0 {
+                        output.write_all(b",")?;
+                    }
+                    output.write_all(b"\n")?;
+                    serde_json::to_writer(&mut output, record)?;
+                    exported += 1;
+                }
+                match page.last() {
+                    Some(last) if page.len() == EXPORT_PAGE_SIZE => cursor = Some(last.timestamp + Duration::milliseconds(1)),
+                    _ => break,
+                }
+            }
+            output.write_all(b"\n]\n")?;
+        }
+    }
+    output.flush()?;
+    eprintln!("✅ 已匯出 {} 筆歷史記錄", exported);
+    Ok(())
+}
+
+fn compact_database(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let result = open_database(config)?.compact_file()?;
+    if result.compacted {
+        println!("✅ 資料庫壓縮完成: {} → {} bytes", result.size_before, result.size_after);
+    } else {
+        println!("✅ 資料庫不需要壓縮 ({} bytes)", result.size_before);
+    }
+    Ok(())
+}
+
+// 解析失敗時直接返回錯誤；可解析時逐項檢查並列出所有問題
+fn verify_config(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config().map_err(|e| format!("無法解析配置文件 {}: {}", path, e))?;
+    let mut problems = Vec::new();
+
+    if let Err(e) = tracing_subscriber::EnvFilter::try_new(&config.logging.level) {
+        problems.push(format!("[logging].level 無效: {}", e));
+    }
+    if !matches!(config.database.backend.as_str(), "redb" | "sqlite") {
+        problems.push(format!("不支援的資料庫後端: {}", config.database.backend));
+    }
+    if let Some(tls) = &config.server.tls {
+        for file in [&tls.cert_path, &tls.key_path] {
+            if !std::path::Path::new(file).exists() {
+                problems.push(format!("找不到 TLS 檔案: {}", file));
+            }
+        }
+    }
+
+    let mut addresses = HashSet::new();
+    let mut names = HashSet::new();
+    for wallet in &config.wallets {
+        if let Err(code) = validate_wallet_address(&wallet.address, wallet.allows_off_curve()) {
+            problems.push(format!("錢包 {} 的地址 {} 無效: {}", wallet.name, wallet.address, code.message(Language::Zh)));
+        }
+        if wallet.name.trim().is_empty() {
+            problems.push(format!("錢包 {} 的名稱為空", wallet.address));
+        }
+        if !addresses.insert(wallet.address.as_str()) {
+            problems.push(format!("錢包地址重複: {}", wallet.address));
+        }
+        if !names.insert(wallet.name.as_str()) {
+            problems.push(format!("錢包名稱重複: {}", wallet.name));
+        }
+        if wallet.color.as_deref().is_some_and(|c| !is_valid_color(c)) {
+            problems.push(format!("錢包 {} 的顏色格式不正確，請使用 #RRGGBB", wallet.name));
+        }
+    }
+
+    if problems.is_empty() {
+        println!("✅ 配置文件 {} 檢查通過 ({} 個錢包)", path, config.wallets.len());
+        return Ok(());
+    }
+    for problem in &problems {
+        eprintln!("❌ {}", problem);
+    }
+    Err(format!("配置文件有 {} 個問題", problems.len()).into())
+}
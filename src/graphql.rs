@@ -0,0 +1,422 @@
+// GraphQL API：一次查詢取得錢包、歷史區間與統計，並可訂閱即時更新
+use {
+    crate::{
+        aggregate_history, build_chart_data, chart_range, compute_balance_stats, default_chart_interval,
+        default_chart_sampling, default_flow_interval, group_summaries, interval_to_duration, load_chart_series,
+        portfolio_summary, wallet_snapshot_events, AccountType, AppState, BalanceStats, ChartDataPoint, ErrorCode, GroupSummary,
+        Language, LstHolding, PortfolioSummary, TokenAccountBalance, TransactionsResponse, WalletEvent, WalletHistoryRecord,
+        WalletSummary, DATA_TYPES,
+    },
+    async_graphql::{
+        http::GraphiQLSource, ComplexObject, Context, EmptyMutation, Enum, ErrorExtensions, Object, Schema, SimpleObject,
+        Subscription,
+    },
+    async_graphql_axum::{GraphQLRequest, GraphQLResponse},
+    axum::response::Html,
+    chrono::{DateTime, Utc},
+    futures::stream::{self, Stream, StreamExt},
+    tracing::{error, warn},
+    std::{collections::HashSet, sync::Arc},
+    tokio::sync::broadcast::error::RecvError,
+};
+
+const MAX_QUERY_DEPTH: usize = 10;
+const MAX_QUERY_COMPLEXITY: usize = 500; // 每個欄位計 1，避免單一查詢展開過多欄位
+const MAX_HISTORY_LIMIT: usize = 100_000;
+
+pub(crate) type WalletSchema = Schema<QueryRoot, EmptyMutation, SubscriptionRoot>;
+
+pub(crate) fn build_schema(state: AppState) -> WalletSchema {
+    Schema::build(QueryRoot, EmptyMutation, SubscriptionRoot)
+        .data(state)
+        .limit_depth(MAX_QUERY_DEPTH)
+        .limit_complexity(MAX_QUERY_COMPLEXITY)
+        .finish()
+}
+
+// 請求的 Accept-Language 放入查詢資料，錯誤訊息依此選擇語言
+pub(crate) async fn graphql_handler(
+    axum::extract::State(schema): axum::extract::State<WalletSchema>,
+    headers: axum::http::HeaderMap,
+    request: GraphQLRequest,
+) -> GraphQLResponse {
+    let mut request = request.into_inner();
+    if let Some(language) = Language::from_headers(&headers) {
+        request = request.data(language);
+    }
+    schema.execute(request).await.into()
+}
+
+pub(crate) async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint("/graphql").subscription_endpoint("/graphql/ws").finish())
+}
+
+// 資料庫讀取在 blocking 執行緒進行，避免阻塞 async runtime
+async fn read_database<T, F>(ctx: &Context<'_>, code: ErrorCode, read: F) -> async_graphql::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+{
+    let error = match tokio::task::spawn_blocking(read).await {
+        Ok(Ok(value)) => return Ok(value),
+        Ok(Err(e)) => e,
+        Err(e) => e.to_string(),
+    };
+    error!("❌ {}: {}", code.message(Language::Zh), error);
+    Err(graphql_error(ctx, code))
+}
+
+// 錯誤訊息使用請求的語言 (未指定時為 logging.language)，extensions.code 與 REST API 的錯誤代碼相同
+fn graphql_error(ctx: &Context<'_>, code: ErrorCode) -> async_graphql::Error {
+    let language = ctx
+        .data_opt::<Language>()
+        .copied()
+        .or_else(|| ctx.data_opt::<AppState>().map(|state| state.config.logging.language))
+        .unwrap_or_default();
+    async_graphql::Error::new(code.message(language)).extend_with(|_, extensions| extensions.set("code", code.name()))
+}
+
+// 錢包目前狀態；歷史、統計與交易記錄只在查詢到該欄位時才讀取資料庫
+#[derive(SimpleObject)]
+#[graphql(complex)]
+struct Wallet {
+    address: String,
+    name: String,
+    group: Option<String>,
+    tags: Vec<String>,
+    note: Option<String>,
+    color: Option<String>,
+    domain: Option<String>,
+    account_type: AccountType,
+    token: Option<TokenAccountBalance>,
+    archived: bool,
+    sol_balance: f64,
+    wsol_balance: f64,
+    staked_balance: f64,
+    lst_balance: f64,
+    lst_holdings: Vec<LstHolding>,
+    total_balance: f64,
+    last_update: DateTime<Utc>,
+    last_slot: u64,
+    current_slot: u64,
+    slot_lag: Option<u64>,
+    initializing: bool,
+}
+
+impl From<WalletSummary> for Wallet {
+    fn from(summary: WalletSummary) -> Self {
+        Self {
+            address: summary.address,
+            name: summary.name,
+            group: summary.group,
+            tags: summary.tags,
+            note: summary.note,
+            color: summary.color,
+            domain: summary.domain,
+            account_type: summary.account_type,
+            token: summary.token,
+            archived: summary.archived,
+            sol_balance: summary.sol_balance,
+            wsol_balance: summary.wsol_balance,
+            staked_balance: summary.staked_balance,
+            lst_balance: summary.lst_balance,
+            lst_holdings: summary.lst_holdings,
+            total_balance: summary.total_balance,
+            last_update: summary.last_update,
+            last_slot: summary.last_slot,
+            current_slot: summary.current_slot,
+            slot_lag: summary.slot_lag,
+            initializing: summary.initializing,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct DataTypeStats {
+    data_type: String,
+    stats: BalanceStats,
+}
+
+#[ComplexObject]
+impl Wallet {
+    /// 原始歷史記錄，from / to 為 Unix 秒，依時間排序並最多返回 limit 筆
+    async fn history(
+        &self,
+        ctx: &Context<'_>,
+        from: Option<i64>,
+        to: Option<i64>,
+        limit: Option<usize>,
+    ) -> async_graphql::Result<Vec<WalletHistoryRecord>> {
+        let state = ctx.data::<AppState>()?;
+        let from = from.and_then(|t| DateTime::from_timestamp(t, 0));
+        let to = to.and_then(|t| DateTime::from_timestamp(t, 0));
+        let limit = limit.unwrap_or(MAX_HISTORY_LIMIT).clamp(1, MAX_HISTORY_LIMIT);
+        let database = state.database.clone();
+        let address = self.address.clone();
+        read_database(ctx, ErrorCode::HistoryReadFailed, move || database.load_history(&address, from, to, limit).map_err(|e| e.to_string())).await
+    }
+
+    /// 圖表資料，參數與 /api/v1/chart 相同
+    async fn chart(
+        &self,
+        ctx: &Context<'_>,
+        data_type: String,
+        interval: Option<String>,
+        from: Option<i64>,
+        to: Option<i64>,
+        sampling: Option<String>,
+    ) -> async_graphql::Result<Vec<ChartDataPoint>> {
+        let state = ctx.data::<AppState>()?;
+        let interval = interval.unwrap_or_else(default_chart_interval);
+        let (from, to, interval) = chart_range(&interval, from, to);
+        let database = state.database.clone();
+        let address = self.address.clone();
+        let history = read_database(ctx, ErrorCode::HistoryReadFailed, move || {
+            database.load_history_series(&address, from, to).map_err(|e| e.to_string())
+        })
+        .await?;
+        let sampling = sampling.unwrap_or_else(default_chart_sampling);
+        Ok(build_chart_data(history.iter().collect(), &data_type, interval, &sampling, None, None))
+    }
+
+    /// 期間內各數據類型的統計值，interval 如 "1H"、"1D"、"ALL"
+    async fn stats(&self, ctx: &Context<'_>, interval: Option<String>) -> async_graphql::Result<Vec<DataTypeStats>> {
+        let state = ctx.data::<AppState>()?;
+        let interval = interval.unwrap_or_else(default_flow_interval);
+        let from = interval_to_duration(&interval).map(|d| Utc::now() - d);
+        let database = state.database.clone();
+        let address = self.address.clone();
+        let history = read_database(ctx, ErrorCode::HistoryReadFailed, move || {
+            database.load_history_series(&address, from, None).map_err(|e| e.to_string())
+        })
+        .await?;
+        Ok(DATA_TYPES
+            .iter()
+            .filter_map(|&data_type| {
+                compute_balance_stats(&history, data_type).map(|stats| DataTypeStats {
+                    data_type: data_type.to_string(),
+                    stats,
+                })
+            })
+            .collect())
+    }
+
+    /// 交易記錄 (新到舊分頁)
+    async fn transactions(
+        &self,
+        ctx: &Context<'_>,
+        page: Option<usize>,
+        page_size: Option<usize>,
+    ) -> async_graphql::Result<TransactionsResponse> {
+        let state = ctx.data::<AppState>()?;
+        let page = page.unwrap_or(1).max(1);
+        let page_size = page_size.unwrap_or(50).clamp(1, 500);
+        let database = state.database.clone();
+        let address = self.address.clone();
+        let (total, transactions) = read_database(ctx, ErrorCode::TransactionsReadFailed, move || {
+            database.load_transactions(&address, page, page_size).map_err(|e| e.to_string())
+        })
+        .await?;
+        Ok(TransactionsResponse {
+            address: self.address.clone(),
+            page,
+            page_size,
+            total,
+            transactions,
+        })
+    }
+}
+
+pub(crate) struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// 錢包列表 (依名稱排序)，archived 為 true 時只列出已封存的錢包
+    async fn wallets(
+        &self,
+        ctx: &Context<'_>,
+        archived: Option<bool>,
+        tag: Option<String>,
+        q: Option<String>,
+    ) -> async_graphql::Result<Vec<Wallet>> {
+        let state = ctx.data::<AppState>()?;
+        let archived = archived.unwrap_or(false);
+        let query = q.map(|q| q.trim().to_lowercase()).filter(|q| !q.is_empty());
+
+        let wallets_guard = state.wallets.read().await;
+        let mut wallets: Vec<Wallet> = wallets_guard
+            .values()
+            .filter(|w| w.archived == archived)
+            .filter(|w| tag.as_ref().is_none_or(|tag| w.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))))
+            .filter(|w| query.as_ref().is_none_or(|q| w.name.to_lowercase().contains(q) || w.address.to_lowercase().contains(q)))
+            .map(|w| Wallet::from(w.to_summary(false, state.health.current_slot())))
+            .collect();
+        wallets.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(wallets)
+    }
+
+    async fn wallet(&self, ctx: &Context<'_>, address: String) -> async_graphql::Result<Option<Wallet>> {
+        let state = ctx.data::<AppState>()?;
+        let wallets_guard = state.wallets.read().await;
+        Ok(wallets_guard.get(&address).map(|w| Wallet::from(w.to_summary(false, state.health.current_slot()))))
+    }
+
+    /// 所有未封存錢包的合計
+    async fn portfolio(&self, ctx: &Context<'_>) -> async_graphql::Result<PortfolioSummary> {
+        let state = ctx.data::<AppState>()?;
+        Ok(portfolio_summary(&state.wallets.read().await))
+    }
+
+    /// 所有未封存錢包的合計圖表資料
+    async fn portfolio_chart(
+        &self,
+        ctx: &Context<'_>,
+        data_type: String,
+        interval: Option<String>,
+        from: Option<i64>,
+        to: Option<i64>,
+    ) -> async_graphql::Result<Vec<ChartDataPoint>> {
+        let state = ctx.data::<AppState>()?;
+        let addresses: Vec<String> = state
+            .wallets
+            .read()
+            .await
+            .values()
+            .filter(|w| !w.archived)
+            .map(|w| w.address.clone())
+            .collect();
+        let interval = interval.unwrap_or_else(default_chart_interval);
+        let (from, to, interval) = chart_range(&interval, from, to);
+        let database = state.database.clone();
+        let series = read_database(ctx, ErrorCode::HistoryReadFailed, move || {
+            load_chart_series(database.as_ref(), &addresses, from, to).map_err(|e| e.to_string())
+        })
+        .await?;
+        let history = aggregate_history(series.iter());
+        Ok(build_chart_data(history.iter().collect(), &data_type, interval, &default_chart_sampling(), None, None))
+    }
+
+    async fn groups(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<GroupSummary>> {
+        let state = ctx.data::<AppState>()?;
+        Ok(group_summaries(&state.wallets.read().await))
+    }
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+enum WalletUpdateKind {
+    Update,
+    Added,
+    Removed,
+    Renamed,
+    Lagged, // 訂閱落後而遺漏事件，之後會重新送出所有錢包的完整狀態
+}
+
+#[derive(SimpleObject)]
+struct WalletUpdate {
+    kind: WalletUpdateKind,
+    address: Option<String>,
+    name: Option<String>,
+    old_name: Option<String>,
+    wallet: Option<Wallet>, // UPDATE / ADDED 時為錢包目前狀態
+}
+
+pub(crate) struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// 錢包即時更新：先送出所有錢包的目前狀態，之後推送變更；未指定 wallets 時訂閱全部
+    async fn wallet_updates(&self, ctx: &Context<'_>, wallets: Option<Vec<String>>) -> impl Stream<Item = WalletUpdate> {
+        let state = ctx.data_unchecked::<AppState>().clone();
+        let filter: Arc<Option<HashSet<String>>> = Arc::new(wallets.filter(|w| !w.is_empty()).map(|w| w.into_iter().collect()));
+
+        // 先訂閱再取完整狀態，避免遺漏兩者之間的事件
+        let receiver = state.events.subscribe();
+        let snapshot: Vec<Option<WalletEvent>> = wallet_snapshot_events(&state.wallets).await.into_iter().map(Some).collect();
+        let shared_wallets = state.wallets.clone();
+        let live = stream::unfold(receiver, move |mut receiver| {
+            let shared_wallets = shared_wallets.clone();
+            async move {
+                let events: Vec<Option<WalletEvent>> = match receiver.recv().await {
+                    Ok(event) => vec![Some(event)],
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("⚠️ GraphQL 訂閱落後 {} 個事件，重新發送完整狀態", skipped);
+                        std::iter::once(None)
+                            .chain(wallet_snapshot_events(&shared_wallets).await.into_iter().map(Some))
+                            .collect()
+                    }
+                    Err(RecvError::Closed) => return None,
+                };
+                Some((stream::iter(events), receiver))
+            }
+        })
+        .flatten();
+
+        stream::iter(snapshot)
+            .chain(live)
+            .filter_map(move |event| {
+                let state = state.clone();
+                let filter = filter.clone();
+                async move { wallet_update(&state, filter.as_ref().as_ref(), event).await }
+            })
+    }
+}
+
+// 將廣播事件轉為 GraphQL 更新；None 代表事件落後
+async fn wallet_update(state: &AppState, filter: Option<&HashSet<String>>, event: Option<WalletEvent>) -> Option<WalletUpdate> {
+    let Some(event) = event else {
+        return Some(WalletUpdate {
+            kind: WalletUpdateKind::Lagged,
+            address: None,
+            name: None,
+            old_name: None,
+            wallet: None,
+        });
+    };
+    if filter.is_some_and(|f| !f.contains(event.address())) {
+        return None;
+    }
+
+    // 事件中的 JSON 可能只含訂閱欄位，直接讀取目前狀態以提供完整型別
+    let with_wallet = |kind: WalletUpdateKind, address: String, wallet: Option<Wallet>| WalletUpdate {
+        kind,
+        name: wallet.as_ref().map(|w| w.name.clone()),
+        address: Some(address),
+        old_name: None,
+        wallet,
+    };
+    let current = match &event {
+        WalletEvent::Update { address, .. } | WalletEvent::Added { address, .. } => state
+            .wallets
+            .read()
+            .await
+            .get(address)
+            .map(|w| Wallet::from(w.to_summary(false, state.health.current_slot()))),
+        _ => None,
+    };
+    let update = match event {
+        WalletEvent::Update { address, .. } => with_wallet(WalletUpdateKind::Update, address, current),
+        WalletEvent::Added { address, .. } => with_wallet(WalletUpdateKind::Added, address, current),
+        WalletEvent::Delete { address } => WalletUpdate {
+            kind: WalletUpdateKind::Removed,
+            address: Some(address),
+            name: None,
+            old_name: None,
+            wallet: None,
+        },
+        WalletEvent::Removed { address, name } => WalletUpdate {
+            kind: WalletUpdateKind::Removed,
+            address: Some(address),
+            name: Some(name),
+            old_name: None,
+            wallet: None,
+        },
+        WalletEvent::Renamed { address, old_name, name } => WalletUpdate {
+            kind: WalletUpdateKind::Renamed,
+            address: Some(address),
+            name: Some(name),
+            old_name: Some(old_name),
+            wallet: None,
+        },
+    };
+    Some(update)
+}
@@ -1,60 +1,240 @@
 use {
     axum::{
-        extract::{Path, Query, ws::{WebSocket, WebSocketUpgrade}},
-        http::StatusCode,
-        response::{Html, Response},
-        routing::{get, post, delete},
+        extract::{DefaultBodyLimit, Path, Query, ws::{WebSocket, WebSocketUpgrade}},
+        body::Body,
+        http::{header, StatusCode},
+        response::{Html, IntoResponse, Response},
+        routing::{delete, get, post},
         Json, Router,
     },
-    bs58,
     chrono::{DateTime, Utc},
     futures::{stream::StreamExt, sink::SinkExt},
-    log::{debug, error, info, warn},
-    redb::{Database, TableDefinition, ReadableTable},
+    redb::{Database, TableDefinition, TableHandle, ReadableTable, ReadableTableMetadata},
     serde::{Deserialize, Serialize},
-    solana_client::rpc_client::RpcClient,
-    solana_program::{program_pack::Pack, pubkey::Pubkey as ProgramPubkey},
-    solana_sdk::pubkey::Pubkey,
+    solana_client::{
+        client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+        nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
+        rpc_client::GetConfirmedSignaturesForAddress2Config,
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcTransactionConfig},
+        rpc_filter::{Memcmp, RpcFilterType},
+    },
+    solana_account_decoder::{UiAccount, UiAccountEncoding, UiDataSliceConfig},
+    solana_program::pubkey::Pubkey as ProgramPubkey,
+    solana_sdk::{epoch_info::EpochInfo, epoch_schedule::EpochSchedule, pubkey::Pubkey, signature::Signature},
+    solana_transaction_status::{
+        option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction, UiMessage, UiTransactionEncoding,
+        UiTransactionTokenBalance,
+    },
     spl_associated_token_account::get_associated_token_address,
-    spl_token::state::Account as TokenAccount,
     std::{
-        collections::{HashMap, VecDeque},
+        collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
         fs,
+        io::Write,
         str::FromStr,
         sync::{Arc, Mutex},
         time::Duration,
     },
 
+    tonic::transport::{Certificate, ClientTlsConfig},
     tower_http::cors::CorsLayer,
-    yellowstone_grpc_client::GeyserGrpcClient,
+    tracing::{debug, error, info, info_span, warn, Instrument},
+    yellowstone_grpc_client::{GeyserGrpcBuilder, GeyserGrpcClient},
     yellowstone_grpc_proto::{
         geyser::SubscribeUpdate,
         prelude::{
-            CommitmentLevel, SubscribeRequest, SubscribeRequestFilterAccounts,
+            CommitmentLevel, RewardType, SubscribeRequest, SubscribeRequestAccountsDataSlice, SubscribeRequestFilterAccounts,
+            SubscribeRequestFilterAccountsFilter, SubscribeRequestFilterAccountsFilterMemcmp, SubscribeRequestFilterBlocksMeta, SubscribeRequestFilterSlots,
+            SubscribeRequestFilterTransactions, SubscribeRequestPing, SubscribeUpdateBlockMeta,
+            subscribe_request_filter_accounts_filter, subscribe_request_filter_accounts_filter_memcmp,
             subscribe_update::UpdateOneof,
         },
     },
 };
 
+#[cfg(feature = "sqlite")]
+mod sqlite_store;
+#[cfg(feature = "rhai")]
+mod script;
+mod graphql;
+mod cli;
+
+// 由 proto/wallet_monitor.proto 產生的 gRPC 服務定義
+mod wallet_monitor_proto {
+    tonic::include_proto!("wallet_monitor");
+}
+
 // 常數定義
 const WSOL_MINT: &str = "So11111111111111111111111111111111111111112";
 const TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
 const ASSOCIATED_TOKEN_PROGRAM_ID: &str = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
-const MAX_HISTORY_SIZE: usize = 10000000;
+const STAKE_PROGRAM_ID: &str = "Stake11111111111111111111111111111111111111";
+// Jito tip distribution 程式，驗證者與質押者從其 tip distribution account 領取 MEV tips
+const JITO_TIP_DISTRIBUTION_PROGRAM_ID: &str = "4R3gSG8BpU4t19KYj8CfnbtRpnT8gtk4dvTHxVRwc2r7";
+const STAKE_WITHDRAWER_OFFSET: usize = 44; // 4 (enum) + 8 (rent_exempt_reserve) + 32 (staker)
+const TOKEN_AMOUNT_OFFSET: usize = 64; // 32 (mint) + 32 (owner)
+const TOKEN_AMOUNT_LEN: usize = 8;
+const MINT_DECIMALS_OFFSET: usize = 44; // 36 (mint_authority COption) + 8 (supply)
+const TOKEN_OWNER_OFFSET: usize = 32; // token account 的 owner 欄位 (mint 之後)
+const TOKEN_ACCOUNT_LEN: usize = 165; // SPL Token 的 token account 大小
+const SLOT_STATE_SAVE_INTERVAL_SECS: u64 = 10;
+const STALE_CHECK_INTERVAL_SECS: u64 = 60; // stale_wallet 告警的檢查間隔
+const VALIDATOR_CHECK_INTERVAL_SECS: u64 = 60; // 驗證者狀態與 validator_runway 告警的檢查間隔
+const REPORT_CHECK_INTERVAL_SECS: u64 = 600; // 每日報表排程的檢查間隔
+const EPOCH_CHECK_INTERVAL_SECS: u64 = 600; // 檢查 epoch 是否切換的間隔
+const MAX_REWARD_BACKFILL_EPOCHS: u64 = 10; // 質押獎勵從最後一筆記錄往後補查的 epoch 數上限
+const NOTIFY_HTTP_TIMEOUT_SECS: u64 = 10; // 通知與事件平台 HTTP 請求的逾時，避免單一請求卡住整個傳送佇列
+const SLOT_DURATION_MS: i64 = 400; // 無法取得區塊時間時用來推算 epoch 開始時間
+const RPC_MULTIPLE_ACCOUNTS_LIMIT: usize = 100; // getMultipleAccounts 單次最多查詢的帳戶數
+const RETRYABLE_RPC_ERRORS: &[&str] = &["429", "Too Many Requests", "node is behind", "Node is unhealthy", "timed out"]; // 換下一個端點重試的錯誤
+const RPC_MAX_RETRY_BACKOFF_MS: u64 = 30_000; // 指數退避的單次等待上限
+const RECONCILIATION_EVENT_LIMIT: usize = 50; // 健康檢查中保留的最近核對修正記錄數
+const MARINADE_PRICE_URL: &str = "https://api.marinade.finance/msol/price_sol";
+
+// Solana Name Service (.sol 網域)
+const SNS_NAME_PROGRAM_ID: &str = "namesLPneVptA9Z5rqUDD9tMTWEJwofgaYwp8cawRkX";
+const SNS_SOL_TLD: &str = "58PwtjSDuFHuUkYjH9BYnnQKHfwo9reZhC2zMJv9JPkx"; // .sol 頂級網域的名稱帳戶
+const SNS_HASH_PREFIX: &str = "SPL Name Service";
+const SNS_OWNER_OFFSET: usize = 32; // 名稱帳戶標頭：parent (32) + owner (32) + class (32)
+const SNS_TOKENIZER_PROGRAM_ID: &str = "nftD3vbNkNqfj2Sd3HZwbpw4BxxKWr4AjGb9X38JeZk"; // 將網域代幣化為 NFT 的程式
+const SNS_TOKENIZER_MINT_PREFIX: &str = "tokenized_name";
+
+// SPL stake pool 帳戶欄位偏移量
+const STAKE_POOL_MINT_OFFSET: usize = 162; // 1 (account_type) + 32*3 + 1 (bump) + 32*2
+const STAKE_POOL_TOTAL_LAMPORTS_OFFSET: usize = 258;
+const STAKE_POOL_TOKEN_SUPPLY_OFFSET: usize = 266;
+
+// 內建支援的流動質押代幣 (symbol, 匯率來源)
+const KNOWN_LSTS: &[(&str, LstRateSource)] = &[
+    ("mSOL", LstRateSource::Marinade("mSoLzYCxHdYgdzU16g5QSh3i5K3z3KZK7ytfqcJm7So")),
+    ("jitoSOL", LstRateSource::SplStakePool("Jito4APyf642JPZPx3hGc6WWJ8zPKtRbRs4P815Awbb")),
+    ("bSOL", LstRateSource::SplStakePool("stk9ApL5HeVAwPLr3TLhDXdZS8ptVu7zp6ov8HFDuMi")),
+];
+const MAX_HISTORY_SIZE: usize = 10000; // 記憶體中只保留最近的記錄，圖表查詢直接讀取資料庫
+const MAX_BACKFILL_DAYS: u32 = 365;
+const MAX_BACKFILL_TRANSACTIONS: usize = 5000; // 單次回補最多讀取的交易數，每筆交易需要一次 RPC 請求
+const BACKFILL_SIGNATURES_PAGE: usize = 1000; // getSignaturesForAddress 每頁上限
+const ALL_WALLETS: &str = "__all__"; // 圖表查詢中代表所有錢包合計
+const UNGROUPED: &str = "__ungrouped__"; // 佔比分析中未設定群組的錢包
+const DATA_TYPES: &[&str] = &["sol", "wsol", "staked", "lst", "total"]; // 圖表與統計支援的數據類型
+const FEES_DATA_TYPE: &str = "fees"; // 圖表的每日手續費，不屬於餘額歷史
+const PNL_DATA_TYPE: &str = "pnl"; // 圖表的 USD 損益，需設定成本與價格來源
+const JITO_TIPS_DATA_TYPE: &str = "jito_tips"; // 圖表的每日 Jito tips 收入
+const BLOCK_REWARDS_DATA_TYPE: &str = "block_rewards"; // 圖表的每日出塊獎勵
+const RECENT_BLOCK_SLOTS: usize = 1024; // 記住最近處理過的區塊，重連補回時不重複記錄出塊獎勵
+const USD_CURRENCY: &str = "USD"; // 價格來源的計價幣別，其他法幣以匯率換算
+const LAMPORTS_PER_SIGNATURE: u64 = 5000; // 每個簽章的基本手續費，超出的部分為優先費
 const DB_FILE: &str = "wallet_history.redb";
+// 配置文件路徑，由命令列 --config 指定
+static CONFIG_PATH: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+const WALLET_EVENT_CAPACITY: usize = 1024; // WebSocket 事件廣播緩衝，落後超過此數量的客戶端會收到完整狀態
 
 // 資料庫表格定義
-const WALLET_HISTORY_TABLE: TableDefinition<&str, &str> = TableDefinition::new("wallet_history");
+// 歷史記錄以 bincode 編碼儲存
+type HistoryTable = TableDefinition<'static, (&'static str, u64), &'static [u8]>;
+type JsonHistoryTable = TableDefinition<'static, (&'static str, u64), &'static str>;
+
+// 以 (地址, 毫秒時間戳) 為鍵，同一錢包的記錄按時間排序，可直接做範圍查詢
+const WALLET_HISTORY_TABLE: HistoryTable = TableDefinition::new("wallet_history_bin");
+// 降採樣後的彙總表格 (鍵為 (地址, 區間起點毫秒))
+const WALLET_HISTORY_1M_TABLE: HistoryTable = TableDefinition::new("wallet_history_1m_bin");
+const WALLET_HISTORY_1H_TABLE: HistoryTable = TableDefinition::new("wallet_history_1h_bin");
+const WALLET_HISTORY_1D_TABLE: HistoryTable = TableDefinition::new("wallet_history_1d_bin");
+// 所有歷史表格，由細到粗
+const HISTORY_TIERS: [(HistoryTable, bool); 4] = [
+    (WALLET_HISTORY_TABLE, false),
+    (WALLET_HISTORY_1M_TABLE, true),
+    (WALLET_HISTORY_1H_TABLE, true),
+    (WALLET_HISTORY_1D_TABLE, true),
+];
+const MINUTE_MS: u64 = 60 * 1000;
+// 降採樣時每個寫入交易最多處理的記錄數，避免單一交易過大並長時間阻擋其他寫入
+const COMPACTION_CHUNK_SIZE: usize = 10_000;
+const HOUR_MS: u64 = 60 * MINUTE_MS;
+const DAY_MS: u64 = 24 * HOUR_MS;
+// 背景寫入批次：累積達到筆數或等待超過時間即提交
+const WRITE_BATCH_SIZE: usize = 500;
+const WRITE_BATCH_INTERVAL_MS: u64 = 1000;
+// 批次寫入失敗時的重試次數與首次重試間隔 (每次加倍)
+const WRITE_BATCH_MAX_ATTEMPTS: u32 = 5;
+const WRITE_RETRY_DELAY_MS: u64 = 200;
+// 重試仍失敗時保留在記憶體中等待下一批的記錄上限，超過時捨棄最舊的記錄
+const WRITE_RETRY_BUFFER_LIMIT: usize = 100 * WRITE_BATCH_SIZE;
+// 匯出 CSV 時每次從資料庫讀取的筆數，讀完一頁即釋放鎖再送出
+const CSV_PAGE_SIZE: usize = 2000;
+// 舊版以 JSON 字串儲存的表格，啟動時自動轉換為 bincode
+const JSON_HISTORY_TIERS: [(JsonHistoryTable, HistoryTable, bool); 4] = [
+    (TableDefinition::new("wallet_history_v2"), WALLET_HISTORY_TABLE, false),
+    (TableDefinition::new("wallet_history_1m"), WALLET_HISTORY_1M_TABLE, true),
+    (TableDefinition::new("wallet_history_1h"), WALLET_HISTORY_1H_TABLE, true),
+    (TableDefinition::new("wallet_history_1d"), WALLET_HISTORY_1D_TABLE, true),
+];
+// 更早期以 "{地址}_{毫秒}" 字串為鍵的表格，啟動時自動遷移
+const LEGACY_WALLET_HISTORY_TABLE: TableDefinition<&str, &str> = TableDefinition::new("wallet_history");
+const WALLET_TRANSACTIONS_TABLE: TableDefinition<&str, &str> = TableDefinition::new("wallet_transactions");
+// 每個錢包最新一筆記錄 (bincode)，啟動時只需讀取此表
+const WALLET_LATEST_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("wallet_latest");
+// 錢包清單變更的稽核記錄 (JSON)，以遞增序號為鍵
+const AUDIT_LOG_TABLE: TableDefinition<u64, &str> = TableDefinition::new("audit_log");
+// token account 事件 (新代幣、WSOL 帳戶關閉 / 開啟)，鍵為 {address}_{毫秒時間戳補零}_{token account}
+const TOKEN_EVENTS_TABLE: TableDefinition<&str, &str> = TableDefinition::new("token_events");
+// 錢包曾持有過的 mint，鍵為 {address}_{mint}，值為首次看到的毫秒時間戳
+const SEEN_MINTS_TABLE: TableDefinition<&str, u64> = TableDefinition::new("seen_mints");
+// 每個錢包每天 (UTC) 支付的手續費，鍵為 (地址, 當天 0 點毫秒時間戳)，值為 (基本費, 優先費, 交易數)，單位 lamports
+const WALLET_FEES_TABLE: TableDefinition<(&str, u64), (u64, u64, u64)> = TableDefinition::new("wallet_fees");
+// 每個錢包每天 (UTC) 的驗證者收入，鍵為 (地址, 當天 0 點毫秒時間戳)，值為 (Jito tips, 領取次數, 出塊獎勵, 區塊數)，單位 lamports
+const WALLET_INCOME_TABLE: TableDefinition<(&str, u64), (u64, u64, u64, u64)> = TableDefinition::new("wallet_income");
+// SOL/USD 價格歷史，鍵為毫秒時間戳
+const SOL_PRICES_TABLE: TableDefinition<u64, f64> = TableDefinition::new("sol_prices");
+// 錢包的成本設定 (JSON)，鍵為地址
+const COST_BASIS_TABLE: TableDefinition<&str, &str> = TableDefinition::new("cost_basis");
+// USD 對其他法幣的匯率歷史，鍵為 (幣別, 毫秒時間戳)，值為 1 USD 可兌換的單位
+const FX_RATES_TABLE: TableDefinition<(&str, u64), f64> = TableDefinition::new("fx_rates");
+// epoch 邊界，鍵為 epoch，值為 (第一個 slot, 開始時間毫秒時間戳)
+const EPOCHS_TABLE: TableDefinition<u64, (u64, u64)> = TableDefinition::new("epochs");
+// 錢包每個 epoch 的質押獎勵 (JSON)，鍵為 (地址, epoch)
+const STAKE_REWARDS_TABLE: TableDefinition<(&str, u64), &str> = TableDefinition::new("stake_rewards");
+const ALERTS_TABLE: TableDefinition<u64, &str> = TableDefinition::new("alerts");
+// 告警靜音，鍵為 (規則名稱, 錢包地址)，值為靜音結束的毫秒時間戳
+const ALERT_SNOOZES_TABLE: TableDefinition<(&str, &str), u64> = TableDefinition::new("alert_snoozes");
+// token_account 類型非 WSOL 代幣的數量歷史，鍵為 (地址, 毫秒時間戳)
+const TOKEN_AMOUNTS_TABLE: TableDefinition<(&str, u64), f64> = TableDefinition::new("token_amounts");
+// 每日報表，鍵為 UTC 日期 (YYYY-MM-DD)
+const DAILY_REPORTS_TABLE: TableDefinition<&str, &str> = TableDefinition::new("daily_reports");
 
 // API 相關結構
 #[derive(Debug, Serialize, Deserialize)]
 struct WalletSummary {
     address: String,
     name: String,
+    group: Option<String>,
+    tags: Vec<String>,
+    note: Option<String>,
+    color: Option<String>,
+    #[serde(default)]
+    domain: Option<String>, // 透過 .sol 網域新增的錢包，顯示用
+    #[serde(default)]
+    account_type: AccountType,
+    #[serde(default)]
+    token: Option<TokenAccountBalance>, // 只有 token_account 類型才有值
+    #[serde(default)]
+    archived: bool,
     sol_balance: f64,
     wsol_balance: f64,
+    #[serde(default)]
+    wsol_account_open: bool, // WSOL ATA 是否存在
+    staked_balance: f64,
+    lst_balance: f64, // LST 折合 SOL
+    lst_holdings: Vec<LstHolding>,
     total_balance: f64,
     last_update: DateTime<Utc>,
+    #[serde(default)]
+    last_slot: u64, // 最近一次帳戶更新所在的 slot，0 表示尚未從 gRPC 收到更新
+    #[serde(default)]
+    current_slot: u64, // gRPC 串流目前的最新 slot
+    #[serde(default)]
+    slot_lag: Option<u64>, // current_slot - last_slot，兩者皆已知時才有值
+    #[serde(default)]
+    initializing: bool, // 新增後仍在背景從 RPC 取得初始餘額
     sampled_history: Vec<BalanceHistory>, // 採樣後的歷史數據
 }
 
@@ -63,1266 +243,14075 @@ struct BalanceHistory {
     timestamp: DateTime<Utc>,
     sol_balance: f64,
     wsol_balance: f64,
+    #[serde(default)]
+    staked_balance: f64,
+    #[serde(default)]
+    lst_balance: f64,
     total_balance: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, async_graphql::SimpleObject)]
+#[graphql(name = "ChartPoint")]
 struct ChartDataPoint {
     time: i64, // Unix timestamp in seconds
     value: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ma: Option<f64>, // 簡單移動平均 (需指定 ma 參數)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ema: Option<f64>, // 指數移動平均 (需指定 ema 參數)
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, async_graphql::SimpleObject)]
+#[graphql(name = "HistoryRecord")]
 struct WalletHistoryRecord {
     timestamp: DateTime<Utc>,
     address: String,
     sol_balance: f64,
     wsol_balance: f64,
+    #[serde(default)]
+    staked_balance: f64,
+    #[serde(default)]
+    lst_balance: f64,
     total_balance: f64,
 }
 
 impl WalletHistoryRecord {
-    fn new(address: String, sol_balance: f64, wsol_balance: f64) -> Self {
+    fn new(address: String, sol_balance: f64, wsol_balance: f64, staked_balance: f64, lst_balance: f64) -> Self {
         Self {
             timestamp: Utc::now(),
             address,
             sol_balance,
             wsol_balance,
-            total_balance: sol_balance + wsol_balance,
+            staked_balance,
+            lst_balance,
+            total_balance: sol_balance + wsol_balance + staked_balance + lst_balance,
         }
     }
 }
 
-#[derive(Debug, Deserialize)]
-struct ChartQueryParams {
-    wallet: String,
-    data_type: String, // "sol", "wsol", or "total"
-    interval: String,  // "5M", "10M", "30M", "1H", "2H", "4H", "8H", "12H", "1D", "1W", "ALL"
+impl From<WalletHistoryRecord> for BalanceHistory {
+    fn from(record: WalletHistoryRecord) -> Self {
+        Self {
+            timestamp: record.timestamp,
+            sol_balance: record.sol_balance,
+            wsol_balance: record.wsol_balance,
+            staked_balance: record.staked_balance,
+            lst_balance: record.lst_balance,
+            total_balance: record.total_balance,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct AddWalletRequest {
-    name: String,
+// 單一數值在區間內的 open/high/low/close/avg
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct OhlcValue {
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    avg: f64,
+}
+
+impl OhlcValue {
+    fn single(value: f64) -> Self {
+        Self { open: value, high: value, low: value, close: value, avg: value }
+    }
+
+    // 合併時間上較晚的區間，avg 依樣本數加權
+    fn merge(&mut self, later: &OhlcValue, samples: u64, later_samples: u64) {
+        self.high = self.high.max(later.high);
+        self.low = self.low.min(later.low);
+        self.close = later.close;
+        let total = (samples + later_samples).max(1) as f64;
+        self.avg = (self.avg * samples as f64 + later.avg * later_samples as f64) / total;
+    }
+}
+
+// 降採樣後的歷史彙總記錄
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct HistoryAggregate {
     address: String,
+    timestamp: DateTime<Utc>, // 區間起點
+    samples: u64,
+    sol_balance: OhlcValue,
+    wsol_balance: OhlcValue,
+    staked_balance: OhlcValue,
+    lst_balance: OhlcValue,
+    total_balance: OhlcValue,
 }
 
-#[derive(Debug, Serialize)]
-struct ApiResponse {
+impl HistoryAggregate {
+    fn merge(&mut self, later: &HistoryAggregate) {
+        self.sol_balance.merge(&later.sol_balance, self.samples, later.samples);
+        self.wsol_balance.merge(&later.wsol_balance, self.samples, later.samples);
+        self.staked_balance.merge(&later.staked_balance, self.samples, later.samples);
+        self.lst_balance.merge(&later.lst_balance, self.samples, later.samples);
+        self.total_balance.merge(&later.total_balance, self.samples, later.samples);
+        self.samples += later.samples;
+    }
+}
+
+impl From<WalletHistoryRecord> for HistoryAggregate {
+    fn from(record: WalletHistoryRecord) -> Self {
+        Self {
+            address: record.address,
+            timestamp: record.timestamp,
+            samples: 1,
+            sol_balance: OhlcValue::single(record.sol_balance),
+            wsol_balance: OhlcValue::single(record.wsol_balance),
+            staked_balance: OhlcValue::single(record.staked_balance),
+            lst_balance: OhlcValue::single(record.lst_balance),
+            total_balance: OhlcValue::single(record.total_balance),
+        }
+    }
+}
+
+// 彙總記錄在圖表中以區間收盤值呈現
+impl From<HistoryAggregate> for BalanceHistory {
+    fn from(aggregate: HistoryAggregate) -> Self {
+        Self {
+            timestamp: aggregate.timestamp,
+            sol_balance: aggregate.sol_balance.close,
+            wsol_balance: aggregate.wsol_balance.close,
+            staked_balance: aggregate.staked_balance.close,
+            lst_balance: aggregate.lst_balance.close,
+            total_balance: aggregate.total_balance.close,
+        }
+    }
+}
+
+// 錢包相關交易記錄
+#[derive(Debug, Serialize, Deserialize, Clone, async_graphql::SimpleObject)]
+#[graphql(name = "Transaction")]
+struct WalletTransactionRecord {
+    signature: String,
+    slot: u64,
+    timestamp: DateTime<Utc>,
+    address: String,
+    direction: String, // "incoming", "outgoing", or "other"
+    amount: f64,       // 不含手續費的 SOL 轉帳金額
+    change: f64,       // 錢包 SOL 餘額淨變化 (含手續費)
+    fee: f64,          // 錢包作為 fee payer 時支付的手續費
+    #[serde(default)]
+    priority_fee: f64, // 手續費中超出基本費 (每個簽章 5000 lamports) 的優先費
+    counterparty: Option<String>,
     success: bool,
-    message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    income: Option<IncomeKind>, // 轉入被歸類為驗證者收入時的類型
+    #[serde(default, skip_serializing_if = "is_zero")]
+    account_tips: f64, // 同一筆交易中入帳到錢包 vote account / stake account 的 Jito tips (SOL)
 }
 
-#[derive(Debug, Serialize)]
-struct ErrorResponse {
-    error: String,
+fn is_zero(value: &f64) -> bool {
+    *value == 0.0
+}
+
+// 與一般轉入分開統計的收入類型
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, async_graphql::Enum)]
+#[serde(rename_all = "snake_case")]
+enum IncomeKind {
+    JitoTip,     // 從 Jito tip distribution 程式領取的 MEV tips
+    BlockReward, // 出塊時入帳的手續費獎勵，不屬於任何交易
+}
+
+impl IncomeKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            IncomeKind::JitoTip => "jito_tip",
+            IncomeKind::BlockReward => "block_reward",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "jito_tip" => Some(IncomeKind::JitoTip),
+            "block_reward" => Some(IncomeKind::BlockReward),
+            _ => None,
+        }
+    }
+}
+
+// 驗證者 identity 的出塊獎勵，由 Geyser 區塊 metadata 取得
+#[derive(Debug, Clone)]
+struct BlockRewardRecord {
+    address: String,
+    lamports: u64,
+    timestamp: DateTime<Utc>,
+}
+
+// token_account 類型的代幣數量變化；非 WSOL 代幣不是 SOL 計價，不寫入餘額歷史而另外保存
+#[derive(Debug, Clone, Serialize)]
+struct TokenAmountRecord {
+    #[serde(skip)]
+    address: String,
+    timestamp: DateTime<Utc>,
+    amount: f64,
 }
 
 #[derive(Debug, Deserialize)]
-struct ProfitsQueryParams {
-    interval: String,  // "5M", "10M", "30M", "1H", "2H", "4H", "8H", "12H", "1D", "1W", "ALL"
-    data_type: String, // "sol", "wsol", or "total"
+struct TransactionsQueryParams {
+    #[serde(default = "default_page")]
+    page: usize,
+    #[serde(default = "default_page_size")]
+    page_size: usize,
+}
+
+fn default_page() -> usize {
+    1
+}
+
+fn default_page_size() -> usize {
+    50
+}
+
+#[derive(Debug, Deserialize)]
+struct FlowsQueryParams {
+    #[serde(default = "default_flow_interval")]
+    interval: String, // 每個統計區間的長度，如 "1H", "1D", "1W"
+    #[serde(default = "default_data_type")]
+    data_type: String,
+    #[serde(default = "default_flow_buckets")]
+    buckets: usize, // 返回最近的區間數量
+}
+
+fn default_flow_interval() -> String {
+    "1D".to_string()
+}
+
+fn default_data_type() -> String {
+    "total".to_string()
+}
+
+fn default_flow_buckets() -> usize {
+    30
 }
 
 #[derive(Debug, Serialize)]
-struct WalletProfitInfo {
-    address: String,
-    profit: f64,
-    profit_class: String, // "positive", "negative", "neutral"
+struct FlowBucket {
+    start: i64, // Unix timestamp in seconds
+    end: i64,
+    inflow: f64,
+    outflow: f64,
+    net: f64,
 }
 
 #[derive(Debug, Serialize)]
-struct ProfitsResponse {
+struct FlowsResponse {
+    address: String,
     interval: String,
     data_type: String,
-    profits: Vec<WalletProfitInfo>,
+    total_inflow: f64,
+    total_outflow: f64,
+    net: f64,
+    buckets: Vec<FlowBucket>,
 }
 
-// 配置結構
-#[derive(Debug, Deserialize, Clone)]
-struct Config {
-    grpc: GrpcConfig,
-    rpc: RpcConfig,
-    wallets: Vec<WalletConfig>,
-    logging: LoggingConfig,
-    server: ServerConfig,
+#[derive(Debug, Deserialize)]
+struct StatsQueryParams {
+    #[serde(default = "default_flow_interval")]
+    interval: String, // 統計期間，如 "1H", "1D", "1W", "ALL"
 }
 
-#[derive(Debug, Deserialize, Clone)]
-struct GrpcConfig {
-    endpoint: String,
+#[derive(Debug, Serialize, async_graphql::SimpleObject)]
+struct BalanceStats {
+    min: f64,
+    max: f64,
+    mean: f64,
+    std_dev: f64,
+    largest_change: f64, // 單次變化絕對值最大者 (保留正負號)
+    change: f64,         // 期間結束值 - 期間起始值
 }
 
-#[derive(Debug, Deserialize, Clone)]
-struct RpcConfig {
-    endpoint: String,
+#[derive(Debug, Serialize)]
+struct WalletStatsResponse {
+    address: String,
+    interval: String,
+    samples: usize,
+    stats: BTreeMap<String, BalanceStats>, // 以 data_type 為鍵
+    fees: FeeStats,
+    rewards: RewardStats,
+    income: IncomeStats,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-struct WalletConfig {
-    address: String,
-    name: String,
+// 期間內入帳的質押獎勵 (SOL)；non_reward_change 為質押餘額變化扣除獎勵，即存入、解除質押等其他變化
+#[derive(Debug, Serialize, Default)]
+struct RewardStats {
+    total: f64,
+    epochs: usize,
+    epoch_average: f64,
+    non_reward_change: f64,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-struct LoggingConfig {
-    level: String,
+impl RewardStats {
+    fn new(rewards: &[StakeReward], staked_change: f64) -> Self {
+        let total: f64 = rewards.iter().map(|r| r.amount).sum();
+        Self {
+            total,
+            epochs: rewards.len(),
+            epoch_average: if rewards.is_empty() { 0.0 } else { total / rewards.len() as f64 },
+            non_reward_change: staked_change - total,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize, Clone)]
-struct ServerConfig {
-    host: String,
-    port: u16,
+// 驗證者的 identity 與 vote account 狀態，由 validator_task 定期更新
+#[derive(Debug, Clone, Serialize)]
+struct ValidatorStatus {
+    identity: String,
+    identity_balance: f64,
+    vote_account: Option<String>,
+    vote_balance: Option<f64>, // 未設定 vote account 或查詢失敗時為 null
+    epoch: u64,
+    slots_in_epoch: u64,
+    vote_cost_per_epoch: f64, // 每個 slot 一筆投票交易的基本手續費估算 (SOL)，不含優先費
+    runway_epochs: f64, // identity 餘額可支付投票手續費的 epoch 數
+    checked_at: DateTime<Utc>,
 }
 
-impl Default for ServerConfig {
-    fn default() -> Self {
+// epoch 的第一個 slot 與開始時間
+#[derive(Debug, Clone, Copy, Serialize)]
+struct EpochBoundary {
+    epoch: u64,
+    start_slot: u64,
+    started_at: DateTime<Utc>,
+}
+
+// 錢包的 stake accounts 在一個 epoch 獲得的質押獎勵，於下一個 epoch 開始時入帳
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StakeReward {
+    epoch: u64,
+    credited_at: DateTime<Utc>, // 下一個 epoch 的開始時間
+    amount: f64,
+    accounts: Vec<StakeAccountReward>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StakeAccountReward {
+    stake_account: String,
+    amount: f64,
+    post_balance: f64, // 入帳後的 stake account 餘額
+    commission: Option<u8>, // 驗證者當時的佣金 (%)
+}
+
+// 錢包作為 fee payer 支付的手續費 (SOL)
+#[derive(Debug, Serialize, Default)]
+struct FeeStats {
+    total: f64,
+    base: f64,
+    priority: f64,
+    transactions: u64,
+    daily_average: f64, // 依期間內有手續費記錄的天數平均
+}
+
+// 單一錢包一天 (UTC) 的手續費
+#[derive(Debug, Clone, Copy)]
+struct DailyFees {
+    day_ms: u64, // 當天 0 點的毫秒時間戳
+    base_lamports: u64,
+    priority_lamports: u64,
+    transactions: u64,
+}
+
+impl DailyFees {
+    fn total_sol(&self) -> f64 {
+        (self.base_lamports + self.priority_lamports) as f64 / 1_000_000_000.0
+    }
+}
+
+impl FeeStats {
+    fn from_days(days: &[DailyFees]) -> Self {
+        let base = days.iter().map(|d| d.base_lamports).sum::<u64>() as f64 / 1_000_000_000.0;
+        let priority = days.iter().map(|d| d.priority_lamports).sum::<u64>() as f64 / 1_000_000_000.0;
         Self {
-            host: "127.0.0.1".to_string(),
-            port: 3000,
+            total: base + priority,
+            base,
+            priority,
+            transactions: days.iter().map(|d| d.transactions).sum(),
+            daily_average: if days.is_empty() { 0.0 } else { (base + priority) / days.len() as f64 },
         }
     }
 }
 
-// 錢包餘額追蹤器
-#[derive(Debug, Clone)]
-struct WalletBalance {
-    address: String,
-    name: String,
-    sol_balance: f64,
-    wsol_balance: f64,
-    wsol_initialized: bool,
-    last_update: DateTime<Utc>,
-    history: VecDeque<BalanceHistory>,
+// 驗證者收入 (SOL)：Jito tips 與出塊獎勵，與其他轉入分開統計
+#[derive(Debug, Serialize, Default)]
+struct IncomeStats {
+    jito_tips: f64,
+    tip_claims: u64, // 領取 Jito tips 的交易數
+    block_rewards: f64,
+    blocks: u64, // 有出塊獎勵的區塊數
+    daily_average: f64, // 依期間內有收入記錄的天數平均
 }
 
-impl WalletBalance {
-    fn new(address: String, name: String) -> Self {
+// 單一錢包一天 (UTC) 的驗證者收入
+#[derive(Debug, Clone, Copy)]
+struct DailyIncome {
+    day_ms: u64, // 當天 0 點的毫秒時間戳
+    jito_lamports: u64,
+    tip_claims: u64,
+    block_reward_lamports: u64,
+    blocks: u64,
+}
+
+impl DailyIncome {
+    fn sol(&self, kind: IncomeKind) -> f64 {
+        let lamports = match kind {
+            IncomeKind::JitoTip => self.jito_lamports,
+            IncomeKind::BlockReward => self.block_reward_lamports,
+        };
+        lamports as f64 / 1_000_000_000.0
+    }
+}
+
+impl IncomeStats {
+    fn from_days(days: &[DailyIncome]) -> Self {
+        let jito_tips = days.iter().map(|d| d.sol(IncomeKind::JitoTip)).sum::<f64>();
+        let block_rewards = days.iter().map(|d| d.sol(IncomeKind::BlockReward)).sum::<f64>();
         Self {
-            address,
-            name,
-            sol_balance: 0.0,
-            wsol_balance: 0.0,
-            wsol_initialized: false,
-            last_update: Utc::now(),
-            history: VecDeque::new(),
+            jito_tips,
+            tip_claims: days.iter().map(|d| d.tip_claims).sum(),
+            block_rewards,
+            blocks: days.iter().map(|d| d.blocks).sum(),
+            daily_average: if days.is_empty() { 0.0 } else { (jito_tips + block_rewards) / days.len() as f64 },
         }
     }
+}
 
-    fn update_sol(&mut self, lamports: u64) {
-        self.sol_balance = lamports as f64 / 1_000_000_000.0;
-        self.last_update = Utc::now();
-        // 只有在WSOL已初始化後才記錄歷史
-        if self.wsol_initialized {
-            self.add_to_history();
-        }
+#[derive(Debug, Serialize, Clone, Copy)]
+struct PricePoint {
+    timestamp: DateTime<Utc>,
+    usd: f64, // 1 SOL 的 USD 價格
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FxRate {
+    timestamp: DateTime<Utc>,
+    rate: f64, // 1 USD 可兌換的目標幣別單位
+}
+
+// 法幣換算：以 SOL/USD 價格序列與 USD 匯率序列取得任一時間點 1 SOL 的目標幣別價格，USD 不需要匯率
+struct FiatConverter {
+    currency: String,
+    prices: Vec<PricePoint>,
+    rates: Vec<FxRate>,
+}
+
+impl FiatConverter {
+    fn load(db: &dyn HistoryStore, currency: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Self, Box<dyn std::error::Error>> {
+        let prices = db.load_prices(from, to)?;
+        let rates = if currency == USD_CURRENCY { Vec::new() } else { db.load_fx_rates(currency, from, to)? };
+        Ok(Self { currency: currency.to_string(), prices, rates })
     }
 
-    fn update_wsol(&mut self, amount: f64) {
-        self.wsol_balance = amount;
-        self.wsol_initialized = true;
-        self.last_update = Utc::now();
-        // 只有在WSOL已初始化後才記錄歷史
-        if self.wsol_initialized {
-            self.add_to_history();
+    // 只載入完整的匯率歷史 (不含價格)，用於以每筆成交當時的匯率換算成本
+    fn load_rates(db: &dyn HistoryStore, currency: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let rates = if currency == USD_CURRENCY { Vec::new() } else { db.load_fx_rates(currency, None, None)? };
+        Ok(Self { currency: currency.to_string(), prices: Vec::new(), rates })
+    }
+
+    // 時間點之前的最後一筆匯率，早於第一筆記錄時沒有匯率
+    fn usd_rate_at(&self, timestamp: DateTime<Utc>) -> Result<f64, ErrorCode> {
+        if self.currency == USD_CURRENCY {
+            return Ok(1.0);
         }
+        let rates = &self.rates[..self.rates.partition_point(|r| r.timestamp <= timestamp)];
+        rates.last().map(|r| r.rate).ok_or(ErrorCode::FxRateUnavailable)
     }
 
-    fn initialize_wsol(&mut self, amount: f64) {
-        self.wsol_balance = amount;
-        self.wsol_initialized = true;
-        self.last_update = Utc::now();
-        // 只有在沒有歷史記錄時才添加第一條記錄
-        if self.history.is_empty() {
-            self.add_to_history();
+    fn sol_price_at(&self, timestamp: DateTime<Utc>) -> Result<f64, ErrorCode> {
+        let prices = &self.prices[..self.prices.partition_point(|p| p.timestamp <= timestamp)];
+        let usd = prices.last().map(|p| p.usd).ok_or(ErrorCode::PriceUnavailable)?;
+        Ok(usd * self.usd_rate_at(timestamp)?)
+    }
+
+    // 時間點之前的最後一筆匯率；早於第一筆記錄或沒有時間 (初始持倉) 時以最早的一筆近似
+    fn usd_rate_near(&self, timestamp: Option<DateTime<Utc>>) -> Result<f64, ErrorCode> {
+        if self.currency == USD_CURRENCY {
+            return Ok(1.0);
         }
+        let before = timestamp.map_or(0, |t| self.rates.partition_point(|r| r.timestamp <= t));
+        self.rates[..before].last().or(self.rates.first()).map(|r| r.rate).ok_or(ErrorCode::FxRateUnavailable)
     }
 
-    fn total_balance(&self) -> f64 {
-        if !self.wsol_initialized {
-            self.sol_balance
+    fn sol_price_near(&self, timestamp: DateTime<Utc>) -> Result<f64, ErrorCode> {
+        let before = self.prices.partition_point(|p| p.timestamp <= timestamp);
+        let usd = self.prices[..before].last().or(self.prices.first()).map(|p| p.usd).ok_or(ErrorCode::PriceUnavailable)?;
+        Ok(usd * self.usd_rate_near(Some(timestamp))?)
+    }
+
+    // 將餘額序列換算為法幣價值，早於第一筆價格或匯率的時間點以最早的一筆近似；完全沒有價格或匯率時返回錯誤
+    fn convert_history(&self, history: &[BalanceHistory]) -> Result<Vec<BalanceHistory>, ErrorCode> {
+        history
+            .iter()
+            .map(|h| {
+                let price = self.sol_price_near(h.timestamp)?;
+                Ok(BalanceHistory {
+                    timestamp: h.timestamp,
+                    sol_balance: h.sol_balance * price,
+                    wsol_balance: h.wsol_balance * price,
+                    staked_balance: h.staked_balance * price,
+                    lst_balance: h.lst_balance * price,
+                    total_balance: h.total_balance * price,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CurrencyQueryParams {
+    currency: Option<String>, // EUR、JPY、TWD 等，需在 [price.fx] 中設定
+}
+
+// 錢包的成本設定：初始持倉 (數量 + 單價) 與之後匯入的買賣成交記錄
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct CostBasis {
+    #[serde(default)]
+    amount: f64, // 初始持倉 (SOL)
+    #[serde(default)]
+    price: f64, // 初始持倉的單價 (USD)
+    #[serde(default)]
+    fills: Vec<CostFill>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct CostFill {
+    timestamp: DateTime<Utc>,
+    amount: f64, // 正數為買入，負數為賣出 (SOL)
+    price: f64,  // 成交單價 (USD)
+}
+
+// 以平均成本法累計的持倉
+#[derive(Debug, Default)]
+struct CostPosition {
+    amount: f64,
+    cost: f64, // 剩餘持倉的總成本 (USD)
+    realized: f64,
+}
+
+impl CostPosition {
+    fn apply(&mut self, amount: f64, price: f64) {
+        if amount >= 0.0 {
+            self.amount += amount;
+            self.cost += amount * price;
         } else {
-            self.sol_balance + self.wsol_balance
+            // 賣出超過持倉的部分沒有成本，全數計為已實現損益
+            let sold = -amount;
+            let covered = sold.min(self.amount);
+            let average = self.average_cost();
+            self.realized += covered * (price - average) + (sold - covered) * price;
+            self.cost -= covered * average;
+            self.amount -= covered;
         }
     }
 
-    fn add_to_history(&mut self) {
-        let history_point = BalanceHistory {
-            timestamp: self.last_update,
-            sol_balance: self.sol_balance,
-            wsol_balance: if self.wsol_initialized { self.wsol_balance } else { 0.0 },
-            total_balance: self.total_balance(),
-        };
+    fn average_cost(&self) -> f64 {
+        if self.amount > 0.0 { self.cost / self.amount } else { 0.0 }
+    }
 
-        self.history.push_back(history_point);
-        
-        // 限制歷史記錄大小
-        while self.history.len() > MAX_HISTORY_SIZE {
-            self.history.pop_front();
-        }
+    // 未實現損益以錢包實際餘額計算，交易獲得的 SOL 也視為獲利
+    fn unrealized(&self, balance: f64, price: f64) -> f64 {
+        balance * price - self.cost
     }
+}
 
-    fn load_history_from_db(&mut self, records: Vec<WalletHistoryRecord>) {
-        self.history.clear();
-        for record in records {
-            let history_point = BalanceHistory {
-                timestamp: record.timestamp,
-                sol_balance: record.sol_balance,
-                wsol_balance: record.wsol_balance,
-                total_balance: record.total_balance,
-            };
-            self.history.push_back(history_point);
+impl CostBasis {
+    fn validate(&self) -> Result<(), String> {
+        let valid = |value: f64| value.is_finite() && value >= 0.0;
+        if !valid(self.amount) || !valid(self.price) {
+            return Err("amount 與 price 必須為非負數".to_string());
         }
-        
-        // 注意：不從歷史記錄設置餘額，因為WSOL餘額可能過時
-        // 餘額將從RPC重新獲取以確保準確性
-        
-        // 限制歷史記錄大小
-        while self.history.len() > MAX_HISTORY_SIZE {
-            self.history.pop_front();
+        if let Some(fill) = self.fills.iter().find(|fill| !fill.amount.is_finite() || fill.amount == 0.0 || !valid(fill.price)) {
+            return Err(format!("{} 的成交記錄不正確", fill.timestamp));
         }
+        Ok(())
     }
 
-    fn to_summary(&self) -> WalletSummary {
-        // 對歷史數據進行採樣到100筆
-        let sampled_history = if self.history.len() > 100 {
-            let step = self.history.len() / 100;
-            self.history.iter()
-                .enumerate()
-                .filter(|(i, _)| i % step == 0)
-                .map(|(_, h)| h.clone())
-                .take(100)
-                .collect()
-        } else {
-            self.history.iter().cloned().collect()
-        };
-        
-        WalletSummary {
-            address: self.address.clone(),
-            name: self.name.clone(),
-            sol_balance: self.sol_balance,
-            wsol_balance: if self.wsol_initialized { self.wsol_balance } else { 0.0 },
-            total_balance: self.total_balance(),
+    // 套用 at 之前 (含) 的成交記錄，單價乘上 rate 返回的成交當時匯率 (初始持倉沒有時間，傳入 None)
+    fn position_at(&self, at: DateTime<Utc>, rate: impl Fn(Option<DateTime<Utc>>) -> Result<f64, ErrorCode>) -> Result<CostPosition, ErrorCode> {
+        let mut position = CostPosition::default();
+        position.apply(self.amount, self.price * rate(None)?);
+        for fill in self.fills.iter().take_while(|fill| fill.timestamp <= at) {
+            position.apply(fill.amount, fill.price * rate(Some(fill.timestamp))?);
+        }
+        Ok(position)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct PnlResponse {
+    address: String,
+    currency: String, // 以下價格與金額的幣別，成本與已實現損益依每筆成交當時的匯率換算，價格與市值依目前匯率換算
+    balance: f64, // 目前總餘額 (SOL)
+    price: f64,
+    price_updated_at: DateTime<Utc>,
+    position: f64, // 依成本設定計算的持倉 (SOL)
+    average_cost: f64,
+    cost_basis: f64, // 剩餘持倉的總成本 (USD)
+    market_value: f64,
+    realized_pnl: f64,
+    unrealized_pnl: f64,
+    total_pnl: f64,
+}
+
+#[derive(Debug, Serialize, async_graphql::SimpleObject)]
+#[graphql(name = "Portfolio")]
+struct PortfolioSummary {
+    wallet_count: usize,
+    sol_balance: f64,
+    wsol_balance: f64,
+    staked_balance: f64,
+    lst_balance: f64,
+    total_balance: f64,
+    last_update: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+struct PortfolioResponse {
+    #[serde(flatten)]
+    summary: PortfolioSummary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fiat: Option<FiatValue>, // 指定 currency 時以目前價格換算
+}
+
+#[derive(Debug, Deserialize)]
+struct BreakdownQueryParams {
+    #[serde(default = "default_flow_interval")]
+    interval: String, // 歷史佔比每個點的間隔，如 "1H", "1D", "1W"
+    #[serde(default = "default_data_type")]
+    data_type: String,
+    #[serde(default = "default_flow_buckets")]
+    buckets: usize, // 返回最近的點數
+}
+
+// 各錢包與群組佔所有未封存錢包合計的比例 (0 ~ 1)
+#[derive(Debug, Serialize)]
+struct PortfolioBreakdown {
+    interval: String,
+    data_type: String,
+    total: f64,
+    wallets: Vec<WalletShare>, // 依餘額由大到小
+    groups: Vec<GroupShare>,
+    history: Vec<ShareSnapshot>, // 由舊到新，最後一個點為目前
+}
+
+#[derive(Debug, Serialize)]
+struct WalletShare {
+    address: String,
+    name: String,
+    group: Option<String>,
+    balance: f64,
+    share: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct GroupShare {
+    name: String, // 未設定群組的錢包合併為 __ungrouped__
+    wallets: usize,
+    balance: f64,
+    share: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct ShareSnapshot {
+    time: i64, // Unix timestamp in seconds
+    total: f64,
+    wallets: BTreeMap<String, f64>, // 地址 -> 佔比
+    groups: BTreeMap<String, f64>,  // 群組 -> 佔比
+}
+
+#[derive(Debug, Serialize)]
+struct FiatValue {
+    currency: String,
+    price: f64, // 1 SOL 的價格
+    sol_value: f64,
+    wsol_value: f64,
+    staked_value: f64,
+    lst_value: f64,
+    total_value: f64,
+}
+
+#[derive(Debug, Serialize, async_graphql::SimpleObject)]
+#[graphql(name = "TransactionPage")]
+struct TransactionsResponse {
+    address: String,
+    page: usize,
+    page_size: usize,
+    total: usize,
+    transactions: Vec<WalletTransactionRecord>,
+}
+
+// 流動質押代幣 (LST)
+#[derive(Debug, Clone, Copy)]
+enum LstRateSource {
+    Marinade(&'static str),     // mint 地址，匯率來自 Marinade API
+    SplStakePool(&'static str), // stake pool 地址，mint 與匯率從 pool 帳戶讀取
+}
+
+#[derive(Debug, Clone)]
+struct LstToken {
+    symbol: String,
+    mint: String,
+    rate_source: LstRateSource,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct OwnedTokenAccount {
+    mint: String,
+    amount: u64,
+}
+
+// 解析 token account 的 mint、owner 與 amount；資料需至少包含前 72 bytes (訂閱時的 data slice)
+fn parse_owned_token_account(data: &[u8]) -> Option<(String, OwnedTokenAccount)> {
+    let mint = data.get(..TOKEN_OWNER_OFFSET)?;
+    let owner = data.get(TOKEN_OWNER_OFFSET..TOKEN_AMOUNT_OFFSET)?;
+    let amount = data.get(TOKEN_AMOUNT_OFFSET..TOKEN_AMOUNT_OFFSET + TOKEN_AMOUNT_LEN)?;
+    Some((
+        bs58::encode(owner).into_string(),
+        OwnedTokenAccount {
+            mint: bs58::encode(mint).into_string(),
+            amount: u64::from_le_bytes(amount.try_into().ok()?),
+        },
+    ))
+}
+
+// token_account 類型的代幣餘額；mint 為 WSOL 時 amount 同時計入 wsol_balance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, async_graphql::SimpleObject)]
+struct TokenAccountBalance {
+    mint: String,
+    amount: f64,
+    decimals: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, async_graphql::SimpleObject)]
+struct LstHolding {
+    symbol: String,
+    mint: String,
+    amount: f64,
+    rate: f64, // 1 LST = rate SOL
+    sol_value: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChartQueryParams {
+    wallet: String,
+    data_type: String, // "sol", "wsol", "staked", "lst", or "total"
+    #[serde(default = "default_chart_interval")]
+    interval: String,  // "5M", "10M", "30M", "1H", "2H", "4H", "8H", "12H", "1D", "1W", "ALL"
+    from: Option<i64>, // Unix timestamp in seconds，指定時從資料庫讀取
+    to: Option<i64>,
+    #[serde(default = "default_chart_sampling")]
+    sampling: String, // "lttb" or "uniform"
+    ma: Option<usize>,  // 簡單移動平均的點數
+    ema: Option<usize>, // 指數移動平均的點數
+    currency: Option<String>, // 指定時以當時的價格換算為法幣價值
+}
+
+fn default_chart_interval() -> String {
+    "ALL".to_string()
+}
+
+fn default_chart_sampling() -> String {
+    "lttb".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryQueryParams {
+    wallet: String,
+    from: Option<i64>, // Unix timestamp in seconds
+    to: Option<i64>,
+    #[serde(default = "default_history_limit")]
+    limit: usize,
+    cursor: Option<u64>, // 上一頁返回的 x-next-cursor (Unix 毫秒)，優先於 from
+}
+
+fn default_history_limit() -> usize {
+    10000
+}
+
+#[derive(Debug, Deserialize)]
+struct AddWalletRequest {
+    name: String,
+    address: String, // 也可以是 .sol 網域，新增時解析為擁有者地址
+    #[serde(default)]
+    allow_off_curve: bool, // 允許不在 ed25519 曲線上的地址 (PDA，例如多簽金庫)
+    #[serde(default)]
+    account_type: AccountType,
+    #[serde(default)]
+    vote_account: Option<String>, // 只適用於 validator 類型
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateWalletRequest {
+    name: Option<String>,
+    group: Option<String>, // 空字串代表移出群組
+    tags: Option<Vec<String>>,
+    note: Option<String>, // 空字串代表清除備註
+    color: Option<String>, // "#RRGGBB"，空字串代表清除
+    archived: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WalletsQueryParams {
+    #[serde(default)]
+    archived: bool, // true 時只列出已封存的錢包
+    tag: Option<String>,
+    q: Option<String>,    // 名稱或地址搜尋 (不分大小寫)
+    sort: Option<String>, // name (預設) | total_balance | last_update
+    order: Option<String>, // asc | desc，預設 name 為 asc，其餘為 desc
+    #[serde(default = "default_page")]
+    page: usize,
+    page_size: Option<usize>, // 未指定時返回全部
+    #[serde(default)]
+    include_history: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryCsvQueryParams {
+    from: Option<i64>, // Unix timestamp in seconds
+    to: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeleteHistoryQueryParams {
+    after: Option<i64>, // Unix timestamp in seconds (含)
+    before: Option<i64>, // Unix timestamp in seconds (不含)
+    #[serde(default)]
+    all: bool, // 沒有 before 時需明確指定才會清空全部歷史
+}
+
+#[derive(Debug, Serialize)]
+struct DeleteHistoryResponse {
+    deleted: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportQueryParams {
+    #[serde(default = "default_export_format")]
+    format: String, // "json" or "csv"
+}
+
+fn default_export_format() -> String {
+    "json".to_string()
+}
+
+#[derive(Debug, Serialize)]
+struct WalletExportEntry {
+    address: String,
+    name: String,
+    group: Option<String>,
+    tags: Vec<String>,
+    note: Option<String>,
+    color: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CandlesQueryParams {
+    wallet: String,
+    #[serde(default = "default_data_type")]
+    data_type: String,
+    #[serde(default = "default_candle_bucket")]
+    bucket: String, // "1m", "5m", "15m", "30m", "1h", "4h", "1d", "1w"
+    #[serde(default = "default_candle_limit")]
+    limit: usize, // 未指定 from 時返回最近的 K 線數量
+    from: Option<i64>, // Unix timestamp in seconds
+    to: Option<i64>,
+}
+
+fn default_candle_bucket() -> String {
+    "5m".to_string()
+}
+
+fn default_candle_limit() -> usize {
+    200
+}
+
+#[derive(Debug, Serialize)]
+struct BackupInfo {
+    file: String,
+    size: u64,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RestoreRequest {
+    backup: Option<String>, // 備份目錄中的檔名 (見 /api/admin/backups)
+    #[serde(default)]
+    records: Vec<WalletHistoryRecord>, // 或直接匯入 /api/history 格式的記錄
+}
+
+#[derive(Debug, Deserialize)]
+struct AuditQueryParams {
+    address: Option<String>,
+    before: Option<u64>, // 上一頁最後一筆的 id，返回更早的記錄
+    #[serde(default = "default_audit_limit")]
+    limit: usize,
+}
+
+fn default_audit_limit() -> usize {
+    100
+}
+
+#[derive(Debug, Deserialize)]
+struct DailyReportQueryParams {
+    date: Option<chrono::NaiveDate>, // YYYY-MM-DD，預設為前一天 (UTC)
+    currency: Option<String>,
+}
+
+// 每日報表：各錢包與群組當天 (UTC) 的開盤、收盤、最高、最低總餘額與淨變化 (SOL)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DailyReport {
+    date: chrono::NaiveDate,
+    generated_at: DateTime<Utc>,
+    wallets: Vec<WalletDailySummary>,
+    groups: Vec<GroupDailySummary>,
+    // 法幣換算只在讀取或寄送時附加，保存的報表一律以 SOL 計算
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    currency: Option<String>,
+    // 排程寄送完成的時間；透過 API 產生並保存的報表尚未寄送，排程仍會寄出
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    delivered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WalletDailySummary {
+    address: String,
+    name: String,
+    group: Option<String>,
+    #[serde(flatten)]
+    balance: DailyBalance,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fiat: Option<FiatDailyBalance>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct GroupDailySummary {
+    group: String,
+    wallets: usize,
+    #[serde(flatten)]
+    balance: DailyBalance,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fiat: Option<FiatDailyBalance>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct DailyBalance {
+    open: f64,
+    close: f64,
+    high: f64,
+    low: f64,
+    net_change: f64,
+}
+
+impl DailyBalance {
+    fn from_series(series: &[BalanceHistory]) -> Option<Self> {
+        let open = series.first()?.total_balance;
+        let close = series.last()?.total_balance;
+        let (high, low) = series
+            .iter()
+            .fold((f64::NEG_INFINITY, f64::INFINITY), |(high, low), point| (high.max(point.total_balance), low.min(point.total_balance)));
+        Some(Self { open, close, high, low, net_change: close - open })
+    }
+}
+
+// 開盤與收盤餘額分別以當天開始與結束時的價格換算，淨變化包含價格波動
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct FiatDailyBalance {
+    open: f64,
+    close: f64,
+    net_change: f64,
+}
+
+impl FiatDailyBalance {
+    fn new(balance: &DailyBalance, open_price: f64, close_price: f64) -> Self {
+        let (open, close) = (balance.open * open_price, balance.close * close_price);
+        Self { open, close, net_change: close - open }
+    }
+}
+
+impl DailyReport {
+    // 以當天開始與結束時 1 SOL 的法幣價格換算各錢包與群組的開盤與收盤價值
+    fn apply_fiat(&mut self, converter: &FiatConverter) -> Result<(), ErrorCode> {
+        let (from, to) = report_day_range(self.date);
+        let open_price = converter.sol_price_at(from)?;
+        let close_price = converter.sol_price_at(to.min(Utc::now()))?;
+        for wallet in &mut self.wallets {
+            wallet.fiat = Some(FiatDailyBalance::new(&wallet.balance, open_price, close_price));
+        }
+        for group in &mut self.groups {
+            group.fiat = Some(FiatDailyBalance::new(&group.balance, open_price, close_price));
+        }
+        self.currency = Some(converter.currency.clone());
+        Ok(())
+    }
+}
+
+// 報表日期的時間範圍 (UTC)，包含當天最後一毫秒
+fn report_day_range(date: chrono::NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+    let from = date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+    (from, from + chrono::Duration::days(1) - chrono::Duration::milliseconds(1))
+}
+
+#[derive(Debug, Deserialize)]
+struct AlertsQueryParams {
+    state: Option<AlertState>,
+    before: Option<u64>, // 上一頁最後一筆的 id，返回更早的告警
+    #[serde(default = "default_audit_limit")]
+    limit: usize,
+}
+
+// 已觸發的告警；open 為尚未處理，acknowledged 為已確認，resolved 為條件已解除
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AlertRecord {
+    #[serde(default)]
+    id: u64,
+    rule: String,
+    kind: AlertKind,
+    address: String,
+    name: String,
+    state: AlertState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    fired_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    acknowledged_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    resolved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum AlertState {
+    Open,
+    Acknowledged,
+    Resolved,
+}
+
+impl AlertRecord {
+    // 狀態只會往前推進：已解除的告警不會再變回已確認
+    fn transition(&mut self, state: AlertState) {
+        let now = Some(Utc::now());
+        match state {
+            AlertState::Acknowledged if self.state == AlertState::Open => {
+                self.state = state;
+                self.acknowledged_at = now;
+            }
+            AlertState::Resolved if self.state != AlertState::Resolved => {
+                self.state = state;
+                self.resolved_at = now;
+            }
+            _ => {}
+        }
+    }
+}
+
+// 錢包新增 / 刪除 / 改名 / 封存的稽核記錄，失敗的操作同樣記錄並附上錯誤代碼
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AuditEntry {
+    #[serde(default)]
+    id: u64,
+    timestamp: DateTime<Utc>,
+    action: AuditAction,
+    address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    detail: Option<String>, // 錢包名稱、改名前後或修改的欄位
+    actor: AuditActor,
+    success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorCode>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum AuditAction {
+    Add,
+    Delete,
+    Rename,
+    Archive,
+    Unarchive,
+    Update,
+}
+
+// 變更的發起者：通過驗證的完整權杖 (以設定中的序號表示，不記錄權杖本身) 與來源 IP
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct AuditActor {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ip: Option<String>,
+}
+
+#[axum::async_trait]
+impl<S: Send + Sync> axum::extract::FromRequestParts<S> for AuditActor {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut axum::http::request::Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self {
+            token: parts.extensions.get::<ApiTokenId>().map(|id| id.0.clone()),
+            ip: parts
+                .extensions
+                .get::<axum::extract::ConnectInfo<std::net::SocketAddr>>()
+                .map(|info| info.0.ip().to_string()),
+        })
+    }
+}
+
+// 驗證通過的完整權杖，例如 "tokens[0]"，由 check_api_token 放入請求
+#[derive(Debug, Clone)]
+struct ApiTokenId(String);
+
+#[derive(Debug, Deserialize)]
+struct BackfillQueryParams {
+    days: Option<u32>, // 回補天數，預設 30
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+struct BackfillResponse {
+    transactions: usize, // 讀取的交易數
+    imported: usize,
+    skipped: usize,
+    truncated: bool, // 交易數超過上限，只回補了較近的部分
+    #[serde(skip_serializing_if = "Option::is_none")]
+    oldest: Option<DateTime<Utc>>, // 回補的最早一筆記錄
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum BackfillState {
+    Running,
+    Completed,
+    Failed,
+}
+
+// 錢包最近一次回補任務的狀態，由 GET /wallets/:address/backfill 查詢
+#[derive(Debug, Clone, Serialize)]
+struct BackfillStatus {
+    state: BackfillState,
+    days: u32,
+    started_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    finished_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<BackfillResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+// 回補任務結束時若狀態仍為執行中 (任務 panic 或被中止)，改記為失敗，避免錢包永遠無法再次回補
+struct BackfillGuard {
+    backfills: Arc<Mutex<HashMap<String, BackfillStatus>>>,
+    address: String,
+}
+
+impl BackfillGuard {
+    fn finish(&self, result: Result<BackfillResponse, String>) {
+        let mut backfills = self.backfills.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(status) = backfills.get_mut(&self.address) {
+            status.finished_at = Some(Utc::now());
+            match result {
+                Ok(response) => {
+                    status.state = BackfillState::Completed;
+                    status.result = Some(response);
+                }
+                Err(e) => {
+                    status.state = BackfillState::Failed;
+                    status.error = Some(e);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for BackfillGuard {
+    fn drop(&mut self) {
+        let running = self
+            .backfills
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&self.address)
+            .is_some_and(|status| status.state == BackfillState::Running);
+        if running {
+            self.finish(Err("回補任務異常中止".to_string()));
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Default)]
+struct RestoreResponse {
+    imported: usize,
+    skipped: usize, // 相同 (地址, 時間戳) 已存在的記錄
+}
+
+#[derive(Debug, Serialize)]
+struct DatabaseStats {
+    backend: &'static str,
+    path: String,
+    file_size: u64,
+    history_records: usize,
+    transactions: usize,
+    wallets: Vec<WalletDatabaseStats>,
+}
+
+#[derive(Debug, Serialize, Default)]
+struct WalletDatabaseStats {
+    address: String,
+    history_records: usize,    // 原始記錄
+    aggregated_records: usize, // 1 分鐘 / 1 小時 / 1 天彙總
+    transactions: usize,
+    oldest: Option<DateTime<Utc>>,
+    newest: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+struct CompactResponse {
+    compacted: bool,
+    size_before: u64,
+    size_after: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct Candle {
+    time: i64, // 區間起點 Unix timestamp in seconds
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct GroupChartQueryParams {
+    data_type: String,
+    interval: String,
+    #[serde(default = "default_chart_sampling")]
+    sampling: String,
+    ma: Option<usize>,
+    ema: Option<usize>,
+    currency: Option<String>,
+}
+
+#[derive(Debug, Serialize, async_graphql::SimpleObject)]
+#[graphql(name = "Group")]
+struct GroupSummary {
+    name: String,
+    wallets: Vec<String>,
+    sol_balance: f64,
+    wsol_balance: f64,
+    staked_balance: f64,
+    lst_balance: f64,
+    total_balance: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct ApiResponse {
+    success: bool,
+    message: String,
+}
+
+// code 供程式判斷錯誤種類，error 為依語言翻譯的說明，detail 保留底層錯誤等額外資訊
+#[derive(Debug, Serialize, Deserialize)]
+struct ErrorResponse {
+    code: ErrorCode,
+    error: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+impl ErrorResponse {
+    // 預設以中文建立，請求要求其他語言時由 localize_errors 中介層改寫
+    fn new(code: ErrorCode) -> Self {
+        Self::localized(code, None, Language::Zh)
+    }
+
+    fn with_detail(code: ErrorCode, detail: impl std::fmt::Display) -> Self {
+        Self::localized(code, Some(detail.to_string()), Language::Zh)
+    }
+
+    fn localized(code: ErrorCode, detail: Option<String>, language: Language) -> Self {
+        let message = code.message(language);
+        let error = match &detail {
+            Some(detail) => format!("{}: {}", message, detail),
+            None => message.to_string(),
+        };
+        Self { code, error, detail }
+    }
+}
+
+// 機器可讀的錯誤代碼，序列化為 snake_case，新增代碼時不可更改既有名稱
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum ErrorCode {
+    WalletNotFound,
+    WalletExists,
+    WalletArchived,
+    WalletNameRequired,
+    WalletNameExists,
+    AddressRequired,
+    InvalidAddress,
+    InvalidAddressLength,
+    InvalidBase58,
+    AddressOffCurve,
+    DomainNotFound,
+    DomainResolveFailed,
+    WalletRefreshFailed,
+    InvalidColor,
+    InvalidTimeRange,
+    HistoryRangeRequired,
+    RangeTooLarge,
+    UnsupportedFormat,
+    UnsupportedExportFormat,
+    UnsupportedInterval,
+    UnsupportedDataType,
+    UnsupportedBucket,
+    UnsupportedSortField,
+    UnsupportedSortOrder,
+    HistoryReadFailed,
+    HistoryDeleteFailed,
+    TransactionsReadFailed,
+    SerializeFailed,
+    BackupFailed,
+    BackupNotFound,
+    BackupListFailed,
+    RestoreSourceRequired,
+    ImportFailed,
+    DatabaseStatsFailed,
+    CompactFailed,
+    AuditReadFailed,
+    TokenEventsReadFailed,
+    AlertNotFound,
+    AlertsReadFailed,
+    AlertUpdateFailed,
+    InvalidReportDate,
+    ReportReadFailed,
+    InvalidBackfillDays,
+    BackfillInProgress,
+    BackfillNotFound,
+    PriceUnavailable,
+    CostBasisNotSet,
+    InvalidCostBasis,
+    CostBasisReadFailed,
+    CostBasisUpdateFailed,
+    UnsupportedCurrency,
+    FxRateUnavailable,
+    FxRateReadFailed,
+    RewardsReadFailed,
+    EpochsReadFailed,
+    IncomeReadFailed,
+    TokenHistoryReadFailed,
+    InvalidVoteAccount,
+    NotValidator,
+    ValidatorStatusUnavailable,
+    StreamingDisabled,
+    TokenRequired,
+    InvalidToken,
+    ReadOnlyToken,
+    RateLimited,
+    TooManyConnections,
+    MissingHostHeader,
+}
+
+impl ErrorCode {
+    fn message(self, language: Language) -> &'static str {
+        let (zh, en) = match self {
+            ErrorCode::WalletNotFound => ("錢包不存在", "Wallet not found"),
+            ErrorCode::WalletExists => ("此錢包地址已存在", "A wallet with this address already exists"),
+            ErrorCode::WalletArchived => ("錢包已封存", "Wallet is archived"),
+            ErrorCode::WalletNameRequired => ("錢包名稱不能為空", "Wallet name must not be empty"),
+            ErrorCode::WalletNameExists => ("此錢包名稱已存在", "A wallet with this name already exists"),
+            ErrorCode::AddressRequired => ("錢包地址不能為空", "Wallet address must not be empty"),
+            ErrorCode::InvalidAddress => ("錢包地址格式不正確", "Invalid wallet address"),
+            ErrorCode::InvalidAddressLength => ("錢包地址長度不正確", "Invalid wallet address length"),
+            ErrorCode::InvalidBase58 => ("錢包地址不是有效的 base58 字串", "Wallet address is not valid base58"),
+            ErrorCode::AddressOffCurve => (
+                "地址不在 ed25519 曲線上 (PDA)，若確定要監控請設定 allow_off_curve",
+                "Address is off the ed25519 curve (PDA); set allow_off_curve to monitor it anyway",
+            ),
+            ErrorCode::DomainNotFound => ("找不到此 .sol 網域", "The .sol domain is not registered"),
+            ErrorCode::DomainResolveFailed => ("解析 .sol 網域失敗", "Failed to resolve the .sol domain"),
+            ErrorCode::WalletRefreshFailed => ("從 RPC 重新整理錢包失敗", "Failed to refresh the wallet from RPC"),
+            ErrorCode::InvalidColor => ("顏色格式不正確，請使用 #RRGGBB", "Invalid color, expected #RRGGBB"),
+            ErrorCode::InvalidTimeRange => ("after 必須早於 before", "after must be earlier than before"),
+            ErrorCode::HistoryRangeRequired => ("需指定 before，或以 all=true 確認刪除全部歷史", "Specify before, or all=true to delete the entire history"),
+            ErrorCode::RangeTooLarge => ("時間範圍過大，請使用較大的 K 線區間", "Time range too large, use a larger candle bucket"),
+            ErrorCode::UnsupportedFormat => ("不支援的格式", "Unsupported format"),
+            ErrorCode::UnsupportedExportFormat => ("不支援的匯出格式", "Unsupported export format"),
+            ErrorCode::UnsupportedInterval => ("不支援的時間間隔", "Unsupported interval"),
+            ErrorCode::UnsupportedDataType => ("不支援的數據類型", "Unsupported data type"),
+            ErrorCode::UnsupportedBucket => ("不支援的 K 線區間", "Unsupported candle bucket"),
+            ErrorCode::UnsupportedSortField => ("不支援的排序欄位", "Unsupported sort field"),
+            ErrorCode::UnsupportedSortOrder => ("不支援的排序方向", "Unsupported sort order"),
+            ErrorCode::HistoryReadFailed => ("讀取歷史數據失敗", "Failed to read history"),
+            ErrorCode::HistoryDeleteFailed => ("刪除歷史記錄失敗", "Failed to delete history"),
+            ErrorCode::TransactionsReadFailed => ("讀取交易記錄失敗", "Failed to read transactions"),
+            ErrorCode::SerializeFailed => ("序列化失敗", "Serialization failed"),
+            ErrorCode::BackupFailed => ("備份失敗", "Backup failed"),
+            ErrorCode::BackupNotFound => ("找不到備份", "Backup not found"),
+            ErrorCode::BackupListFailed => ("讀取備份列表失敗", "Failed to list backups"),
+            ErrorCode::RestoreSourceRequired => ("請指定 backup 或 records", "Specify either backup or records"),
+            ErrorCode::ImportFailed => ("匯入失敗", "Import failed"),
+            ErrorCode::DatabaseStatsFailed => ("讀取資料庫統計失敗", "Failed to read database statistics"),
+            ErrorCode::CompactFailed => ("壓縮失敗", "Compaction failed"),
+            ErrorCode::AuditReadFailed => ("讀取稽核記錄失敗", "Failed to read audit log"),
+            ErrorCode::TokenEventsReadFailed => ("讀取 token account 事件失敗", "Failed to read token events"),
+            ErrorCode::AlertNotFound => ("告警不存在", "Alert not found"),
+            ErrorCode::AlertsReadFailed => ("讀取告警記錄失敗", "Failed to read alerts"),
+            ErrorCode::AlertUpdateFailed => ("更新告警狀態失敗", "Failed to update alert"),
+            ErrorCode::InvalidReportDate => ("日期不可晚於今天 (UTC)", "Date must not be later than today (UTC)"),
+            ErrorCode::ReportReadFailed => ("讀取每日報表失敗", "Failed to read daily report"),
+            ErrorCode::InvalidBackfillDays => ("days 必須介於 1 到 365", "days must be between 1 and 365"),
+            ErrorCode::BackfillInProgress => ("此錢包正在回補歷史", "A backfill is already running for this wallet"),
+            ErrorCode::BackfillNotFound => ("此錢包沒有回補記錄", "No backfill has been started for this wallet"),
+            ErrorCode::PriceUnavailable => ("尚未取得 SOL 價格，請確認已設定 [price]", "SOL price unavailable, check the [price] section"),
+            ErrorCode::CostBasisNotSet => ("此錢包尚未設定成本", "No cost basis set for this wallet"),
+            ErrorCode::InvalidCostBasis => ("成本設定不正確", "Invalid cost basis"),
+            ErrorCode::CostBasisReadFailed => ("讀取成本設定失敗", "Failed to read cost basis"),
+            ErrorCode::CostBasisUpdateFailed => ("更新成本設定失敗", "Failed to update cost basis"),
+            ErrorCode::UnsupportedCurrency => ("不支援的幣別，請確認已在 [price.fx] 中設定", "Unsupported currency, check the [price.fx] section"),
+            ErrorCode::FxRateUnavailable => ("尚未取得匯率", "Exchange rate unavailable"),
+            ErrorCode::FxRateReadFailed => ("讀取匯率失敗", "Failed to read exchange rates"),
+            ErrorCode::RewardsReadFailed => ("讀取質押獎勵失敗", "Failed to read staking rewards"),
+            ErrorCode::EpochsReadFailed => ("讀取 epoch 記錄失敗", "Failed to read epochs"),
+            ErrorCode::IncomeReadFailed => ("讀取驗證者收入失敗", "Failed to read validator income"),
+            ErrorCode::TokenHistoryReadFailed => ("讀取代幣數量歷史失敗", "Failed to read token amount history"),
+            ErrorCode::InvalidVoteAccount => ("vote account 設定不正確", "Invalid vote account"),
+            ErrorCode::NotValidator => ("此帳戶不是 validator 類型", "This account is not a validator"),
+            ErrorCode::ValidatorStatusUnavailable => ("尚未取得驗證者狀態", "Validator status not available yet"),
+            ErrorCode::StreamingDisabled => ("RPC 輪詢模式沒有可重新連接的串流", "No stream to restart in RPC polling mode"),
+            ErrorCode::TokenRequired => ("需要 API 權杖", "API token required"),
+            ErrorCode::InvalidToken => ("API 權杖無效", "Invalid API token"),
+            ErrorCode::ReadOnlyToken => ("此權杖只能讀取", "This token is read-only"),
+            ErrorCode::RateLimited => ("請求過於頻繁，請稍後再試", "Too many requests, please retry later"),
+            ErrorCode::TooManyConnections => ("WebSocket 連線數已達上限", "WebSocket connection limit reached"),
+            ErrorCode::MissingHostHeader => ("缺少 Host 標頭", "Missing Host header"),
+        };
+        match language {
+            Language::Zh => zh,
+            Language::En => en,
+        }
+    }
+
+    // 與 REST 回應相同的代碼字串，用於 GraphQL extensions 與 gRPC metadata
+    fn name(self) -> String {
+        serde_json::to_value(self).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default()
+    }
+}
+
+// API 錯誤訊息語言；logging.language 為預設值，請求的 Accept-Language 優先
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Language {
+    #[default]
+    Zh,
+    En,
+}
+
+impl Language {
+    // 依 q 權重挑選第一個支援的語言，例如 "en-US,en;q=0.9,zh-TW;q=0.8"
+    fn from_accept_language(value: &str) -> Option<Self> {
+        let mut candidates: Vec<(f32, Language)> = value
+            .split(',')
+            .filter_map(|item| {
+                let mut parts = item.split(';');
+                let tag = parts.next()?.trim().to_ascii_lowercase();
+                let quality = parts
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                let language = match tag.split('-').next()? {
+                    "zh" => Language::Zh,
+                    "en" => Language::En,
+                    _ => return None,
+                };
+                (quality > 0.0).then_some((quality, language))
+            })
+            .collect();
+        // 穩定排序，同權重時保留標頭中的順序
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+        candidates.first().map(|(_, language)| *language)
+    }
+
+    fn from_headers(headers: &header::HeaderMap) -> Option<Self> {
+        headers.get(header::ACCEPT_LANGUAGE).and_then(|v| v.to_str().ok()).and_then(Language::from_accept_language)
+    }
+
+    fn tag(self) -> &'static str {
+        match self {
+            Language::Zh => "zh-TW",
+            Language::En => "en",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ProfitsQueryParams {
+    interval: String,  // "5M", "10M", "30M", "1H", "2H", "4H", "8H", "12H", "1D", "1W", "ALL"
+    data_type: String, // "sol", "wsol", "staked", "lst", or "total"
+}
+
+#[derive(Debug, Serialize)]
+struct WalletProfitInfo {
+    address: String,
+    profit: f64,
+    profit_class: String, // "positive", "negative", "neutral"
+}
+
+#[derive(Debug, Serialize)]
+struct ProfitsResponse {
+    interval: String,
+    data_type: String,
+    profits: Vec<WalletProfitInfo>,
+}
+
+// 餘額變化事件 (用於通知)
+#[derive(Debug, Clone, Serialize)]
+struct BalanceChangeEvent {
+    address: String,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    balance_type: String, // "sol", "wsol", "staked", "lst" or "token" (代幣數量，非 SOL 計價)
+    old_balance: f64,
+    new_balance: f64,
+    delta: f64,
+    total_balance: f64,
+    timestamp: DateTime<Utc>,
+    slot: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account_event: Option<AccountEvent>, // 帳戶開啟 / 關閉時附帶，不受 min_delta 限制
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mint: Option<String>, // 新代幣事件的 mint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alert: Option<String>, // 觸發的告警規則名稱，不受 min_delta 限制
+    #[serde(skip_serializing_if = "Option::is_none")]
+    severity: Option<AlertSeverity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transfer: Option<TransferDetail>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    idle_secs: Option<u64>, // 無活動告警：距離上次更新的秒數
+    #[serde(skip_serializing_if = "Option::is_none")]
+    runway_epochs: Option<f64>, // 驗證者告警：identity 餘額可支付投票手續費的 epoch 數
+    #[serde(skip_serializing_if = "Option::is_none")]
+    condition: Option<String>, // script 告警：成立的條件運算式
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alert_id: Option<u64>, // 告警記錄 id，可用於 POST /api/alerts/{id}/ack
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>, // 符合的通知路由以模板產生的內容
+}
+
+// 大額轉帳告警附帶的交易資訊
+#[derive(Debug, Clone, Serialize)]
+struct TransferDetail {
+    direction: String,
+    amount: f64,
+    counterparty: Option<String>,
+    signature: String,
+}
+
+impl BalanceChangeEvent {
+    fn new(wallet: &WalletBalance, balance_type: &str, old_balance: f64, new_balance: f64, slot: u64) -> Self {
+        Self {
+            address: wallet.address.clone(),
+            name: wallet.name.clone(),
+            group: wallet.group.clone(),
+            balance_type: balance_type.to_string(),
+            old_balance,
+            new_balance,
+            delta: new_balance - old_balance,
+            total_balance: wallet.total_balance(),
+            timestamp: wallet.last_update,
+            slot,
+            account_event: None,
+            mint: None,
+            alert: None,
+            severity: None,
+            transfer: None,
+            idle_secs: None,
+            runway_epochs: None,
+            condition: None,
+            alert_id: None,
+            message: None,
+        }
+    }
+
+    // 無活動告警：餘額不變，timestamp 為錢包最後一次更新的時間
+    fn stale(wallet: &WalletBalance, rule: &AlertRule, idle_secs: u64) -> Self {
+        let total = wallet.total_balance();
+        Self {
+            alert: Some(rule.name.clone()),
+            idle_secs: Some(idle_secs),
+            ..Self::new(wallet, "total", total, total, wallet.last_slot)
+        }
+    }
+
+    // 驗證者告警：餘額不變，old / new 皆為 identity 的 SOL 餘額
+    fn validator_runway(wallet: &WalletBalance, rule: &AlertRule, runway_epochs: f64) -> Self {
+        Self {
+            alert: Some(rule.name.clone()),
+            runway_epochs: Some(runway_epochs),
+            ..Self::new(wallet, "sol", wallet.sol_balance, wallet.sol_balance, wallet.last_slot)
+        }
+    }
+
+    // 低餘額告警：old / new 為這次更新前後的總餘額
+    fn balance_below(wallet: &WalletBalance, rule: &AlertRule, previous: &BalanceHistory) -> Self {
+        Self {
+            alert: Some(rule.name.clone()),
+            ..Self::new(wallet, "total", previous.total_balance, wallet.total_balance(), wallet.last_slot)
+        }
+    }
+
+    // script 告警：old / new 為這次更新前後的總餘額
+    #[cfg(feature = "rhai")]
+    fn script(wallet: &WalletBalance, rule: &AlertRule, previous: &BalanceHistory) -> Self {
+        Self {
+            alert: Some(rule.name.clone()),
+            condition: rule.condition.clone(),
+            ..Self::new(wallet, "total", previous.total_balance, wallet.total_balance(), wallet.last_slot)
+        }
+    }
+
+    // 大額轉帳告警：old / new 為交易前後的 SOL 餘額
+    fn transfer(wallet: &WalletBalance, rule: &AlertRule, record: &WalletTransactionRecord, old_balance: f64, new_balance: f64) -> Self {
+        Self {
+            alert: Some(rule.name.clone()),
+            transfer: Some(TransferDetail {
+                direction: record.direction.clone(),
+                amount: record.amount,
+                counterparty: record.counterparty.clone(),
+                signature: record.signature.clone(),
+            }),
+            ..Self::new(wallet, "sol", old_balance, new_balance, record.slot)
+        }
+    }
+
+    fn with_account_event(mut self, account_event: AccountEvent) -> Self {
+        self.account_event = Some(account_event);
+        self
+    }
+
+    fn with_mint(mut self, mint: &str) -> Self {
+        self.mint = Some(mint.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum AccountEvent {
+    WsolAccountOpened,
+    WsolAccountClosed,
+    #[default]
+    NewTokenReceived, // 舊版記錄沒有 event 欄位，皆為新代幣事件
+}
+
+impl AccountEvent {
+    fn description(self) -> &'static str {
+        match self {
+            AccountEvent::WsolAccountOpened => "🔓 WSOL 帳戶已開啟",
+            AccountEvent::WsolAccountClosed => "🔒 WSOL 帳戶已關閉",
+            AccountEvent::NewTokenReceived => "🎁 收到新代幣",
+        }
+    }
+}
+
+// token account 事件：錢包首次出現某個 mint (空投或粉塵攻擊)，或 WSOL 帳戶關閉 / 重新開啟；amount 為最小單位的原始數量
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TokenAccountEvent {
+    #[serde(default)]
+    event: AccountEvent,
+    address: String,
+    name: String,
+    mint: String,
+    token_account: String,
+    amount: u64,
+    slot: u64,
+    timestamp: DateTime<Utc>,
+}
+
+impl TokenAccountEvent {
+    // WSOL 帳戶關閉時記錄關閉前的餘額，開啟時記錄開啟後的餘額
+    fn wsol(wallet: &WalletBalance, event: AccountEvent, token_account: &str, old_balance: f64, new_balance: f64, slot: u64) -> Self {
+        let balance = if event == AccountEvent::WsolAccountClosed { old_balance } else { new_balance };
+        TokenAccountEvent {
+            event,
+            address: wallet.address.clone(),
+            name: wallet.name.clone(),
+            mint: WSOL_MINT.to_string(),
+            token_account: token_account.to_string(),
+            amount: (balance * 1_000_000_000.0).round() as u64,
+            slot,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenEventsQueryParams {
+    #[serde(default = "default_audit_limit")]
+    limit: usize,
+}
+
+// 將時間間隔字串轉換為時間長度
+fn interval_to_duration(interval: &str) -> Option<chrono::Duration> {
+    match interval {
+        "1M" => Some(chrono::Duration::minutes(1)),
+        "5M" => Some(chrono::Duration::minutes(5)),
+        "10M" => Some(chrono::Duration::minutes(10)),
+        "15M" => Some(chrono::Duration::minutes(15)),
+        "30M" => Some(chrono::Duration::minutes(30)),
+        "1H" => Some(chrono::Duration::hours(1)),
+        "2H" => Some(chrono::Duration::hours(2)),
+        "4H" => Some(chrono::Duration::hours(4)),
+        "8H" => Some(chrono::Duration::hours(8)),
+        "12H" => Some(chrono::Duration::hours(12)),
+        "1D" => Some(chrono::Duration::days(1)),
+        "1W" => Some(chrono::Duration::weeks(1)),
+        _ => None,
+    }
+}
+
+// 合併多個錢包的歷史數據為單一合計序列
+// 每個時間點的數值 = 各錢包在該時間點之前最後一筆記錄的總和
+fn aggregate_history<'a, S>(series: impl Iterator<Item = S>) -> Vec<BalanceHistory>
+where
+    S: IntoIterator<Item = &'a BalanceHistory>,
+{
+    let mut events: Vec<(usize, &BalanceHistory)> = series
+        .enumerate()
+        .flat_map(|(i, history)| history.into_iter().map(move |h| (i, h)))
+        .collect();
+    events.sort_by_key(|(_, h)| h.timestamp);
+
+    let mut latest: HashMap<usize, &BalanceHistory> = HashMap::new();
+    let mut sum = BalanceHistory {
+        timestamp: Utc::now(),
+        sol_balance: 0.0,
+        wsol_balance: 0.0,
+        staked_balance: 0.0,
+        lst_balance: 0.0,
+        total_balance: 0.0,
+    };
+    let mut aggregated = Vec::with_capacity(events.len());
+
+    for (wallet_index, point) in events {
+        if let Some(previous) = latest.insert(wallet_index, point) {
+            sum.sol_balance -= previous.sol_balance;
+            sum.wsol_balance -= previous.wsol_balance;
+            sum.staked_balance -= previous.staked_balance;
+            sum.lst_balance -= previous.lst_balance;
+            sum.total_balance -= previous.total_balance;
+        }
+        sum.sol_balance += point.sol_balance;
+        sum.wsol_balance += point.wsol_balance;
+        sum.staked_balance += point.staked_balance;
+        sum.lst_balance += point.lst_balance;
+        sum.total_balance += point.total_balance;
+        sum.timestamp = point.timestamp;
+        aggregated.push(sum.clone());
+    }
+
+    aggregated
+}
+
+// 依數據類型取出歷史點的數值
+fn history_value(h: &BalanceHistory, data_type: &str) -> f64 {
+    match data_type {
+        "sol" => h.sol_balance,
+        "wsol" => h.wsol_balance,
+        "staked" => h.staked_balance,
+        "lst" => h.lst_balance,
+        _ => h.total_balance,
+    }
+}
+
+// 計算單一數據類型在期間內的統計值
+fn compute_balance_stats(history: &[BalanceHistory], data_type: &str) -> Option<BalanceStats> {
+    let values: Vec<f64> = history
+        .iter()
+        .map(|h| history_value(h, data_type))
+        .filter(|value| value.is_finite())
+        .collect();
+    let first = *values.first()?;
+    let last = *values.last()?;
+
+    let count = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / count;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count;
+    let largest_change = values
+        .windows(2)
+        .map(|pair| pair[1] - pair[0])
+        .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+        .unwrap_or(0.0);
+
+    Some(BalanceStats {
+        min: values.iter().copied().fold(f64::INFINITY, f64::min),
+        max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        mean,
+        std_dev: variance.sqrt(),
+        largest_change,
+        change: last - first,
+    })
+}
+
+// 配置結構
+#[derive(Debug, Deserialize, Clone)]
+struct Config {
+    #[serde(default)]
+    transport: Transport,
+    #[serde(default = "default_true")]
+    watch_config: bool, // 監看配置文件，變更時套用日誌等級、通知規則與錢包清單
+    #[serde(default)]
+    grpc: Option<GrpcConfig>, // 未設定時改用 RPC 輪詢
+    rpc: RpcConfig,
+    wallets: Vec<WalletConfig>,
+    logging: LoggingConfig,
+    server: ServerConfig,
+    #[serde(default)]
+    notifications: NotificationsConfig,
+    #[serde(default)]
+    lst: LstConfig,
+    #[serde(default)]
+    sns: SnsConfig,
+    #[serde(default)]
+    retention: RetentionConfig,
+    #[serde(default)]
+    database: DatabaseConfig,
+    #[serde(default)]
+    backup: BackupConfig,
+    #[serde(default)]
+    export: Option<ExportConfig>,
+    #[serde(default)]
+    auth: AuthConfig,
+    #[serde(default)]
+    rate_limit: RateLimitConfig,
+    #[serde(default)]
+    health: HealthConfig,
+    #[serde(default)]
+    reconciliation: ReconciliationConfig,
+    #[serde(default)]
+    history: HistoryConfig,
+    #[serde(default)]
+    websocket: WebSocketConfig,
+    #[serde(default)]
+    grpc_server: Option<GrpcServerConfig>,
+    #[serde(default)]
+    reports: ReportsConfig,
+    #[serde(default)]
+    price: Option<PriceConfig>,
+}
+
+// SOL/USD 價格來源，預設使用 CoinGecko
+#[derive(Debug, Deserialize, Clone)]
+struct PriceConfig {
+    #[serde(default = "default_price_url")]
+    url: String,
+    #[serde(default = "default_price_pointer")]
+    pointer: String, // 價格在回應 JSON 中的位置 (JSON Pointer)，例如 "/solana/usd"
+    #[serde(default = "default_price_interval_secs")]
+    interval_secs: u64,
+    #[serde(default = "default_price_retention_days")]
+    retention_days: u64, // 超過天數的價格每小時只保留一筆，0 表示不清理
+    #[serde(default)]
+    fx: Option<FxConfig>, // USD 以外的法幣匯率
+}
+
+// 法幣匯率來源，預設使用 open.er-api.com，匯率以 1 USD 可兌換的單位表示
+#[derive(Debug, Deserialize, Clone)]
+struct FxConfig {
+    #[serde(default = "default_fx_url")]
+    url: String,
+    #[serde(default = "default_fx_pointer")]
+    pointer: String, // 匯率物件在回應 JSON 中的位置，物件的鍵為幣別代碼
+    #[serde(default = "default_fx_currencies")]
+    currencies: Vec<String>,
+    #[serde(default = "default_fx_interval_secs")]
+    interval_secs: u64,
+}
+
+fn default_fx_url() -> String {
+    "https://open.er-api.com/v6/latest/USD".to_string()
+}
+
+fn default_fx_pointer() -> String {
+    "/rates".to_string()
+}
+
+fn default_fx_currencies() -> Vec<String> {
+    vec!["EUR".to_string(), "JPY".to_string(), "TWD".to_string()]
+}
+
+fn default_fx_interval_secs() -> u64 {
+    3600
+}
+
+fn default_price_url() -> String {
+    "https://api.coingecko.com/api/v3/simple/price?ids=solana&vs_currencies=usd".to_string()
+}
+
+fn default_price_pointer() -> String {
+    "/solana/usd".to_string()
+}
+
+fn default_price_interval_secs() -> u64 {
+    60
+}
+
+fn default_price_retention_days() -> u64 {
+    30
+}
+
+// 每日報表排程
+#[derive(Debug, Deserialize, Clone, Default)]
+struct ReportsConfig {
+    #[serde(default)]
+    daily: bool, // 每天自動產生前一天的報表並保存
+    #[serde(default)]
+    hour: u32, // 產生報表的時間 (UTC 小時)
+    #[serde(default)]
+    channels: Vec<String>, // 透過這些通知目標寄送報表：telegram、discord、webhook、email，為空時只保存
+    #[serde(default)]
+    currency: Option<String>, // 寄送的報表附加法幣價值，例如 "USD"、"TWD"
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct GrpcConfig {
+    endpoint: String,
+    #[serde(default)]
+    x_token: Option<String>, // Triton / Helius 等商業 Geyser 節點要求的 x-token
+    #[serde(default)]
+    tls: Option<GrpcTlsConfig>, // 未設定時 https:// 端點自動以系統根憑證啟用 TLS
+    #[serde(default = "default_grpc_connect_timeout_secs")]
+    connect_timeout_secs: u64,
+    #[serde(default = "default_grpc_timeout_secs")]
+    timeout_secs: u64,
+    #[serde(default = "default_grpc_max_decoding_message_size")]
+    max_decoding_message_size: usize,
+    #[serde(default)]
+    commitment: GrpcCommitment,
+    #[serde(default = "default_grpc_ping_interval_secs")]
+    ping_interval_secs: u64, // 定期在訂閱串流上發送 ping，0 表示不發送
+    #[serde(default = "default_grpc_stale_timeout_secs")]
+    stale_timeout_secs: u64, // 超過此秒數未收到任何訊息即強制重新連接，0 表示停用
+    #[serde(default = "default_true")]
+    replay_from_slot: bool, // 重連時以 from_slot 補回斷線期間的更新 (需端點支援)
+    #[serde(default)]
+    slot_state_path: Option<String>, // 定期保存最新 slot，重啟後也能從該 slot 補回
+    #[serde(default)]
+    max_wallets_per_stream: usize, // 錢包數超過此值時分散到多條訂閱串流，0 表示只用一條
+    #[serde(default)]
+    shard_endpoints: Vec<String>, // 分片串流輪流使用的額外端點 (與 endpoint 共用其他設定)
+    #[serde(default)]
+    owner_token_accounts: bool, // 以 owner 過濾訂閱錢包的所有 token account (含非 ATA)，取代預先計算的 WSOL / LST ATA
+}
+
+// 訂閱的確認等級：processed 延遲最低，finalized 不會被回滾
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+enum GrpcCommitment {
+    Processed,
+    #[default]
+    Confirmed,
+    Finalized,
+}
+
+impl From<GrpcCommitment> for CommitmentLevel {
+    fn from(commitment: GrpcCommitment) -> Self {
+        match commitment {
+            GrpcCommitment::Processed => CommitmentLevel::Processed,
+            GrpcCommitment::Confirmed => CommitmentLevel::Confirmed,
+            GrpcCommitment::Finalized => CommitmentLevel::Finalized,
+        }
+    }
+}
+
+// 帳戶更新的來源：Yellowstone Geyser gRPC，或一般 RPC 節點的 WebSocket 訂閱
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+enum Transport {
+    #[default]
+    Geyser,
+    WsRpc,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+struct GrpcTlsConfig {
+    #[serde(default)]
+    ca_cert_path: Option<String>, // 自簽憑證的 CA (PEM)，未設定時使用系統根憑證
+    #[serde(default)]
+    domain_name: Option<String>, // 覆寫憑證驗證用的網域名稱
+}
+
+fn default_grpc_connect_timeout_secs() -> u64 {
+    10
+}
+
+fn default_grpc_timeout_secs() -> u64 {
+    30
+}
+
+fn default_grpc_max_decoding_message_size() -> usize {
+    64 * 1024 * 1024
+}
+
+fn default_grpc_ping_interval_secs() -> u64 {
+    15
+}
+
+fn default_grpc_stale_timeout_secs() -> u64 {
+    60
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct RpcConfig {
+    endpoint: String,
+    #[serde(default)]
+    endpoints: Vec<String>, // 額外的備援端點，與 endpoint 一起輪替使用
+    #[serde(default = "default_rpc_timeout_secs")]
+    timeout_secs: u64,
+    #[serde(default = "default_rpc_failover_cooldown_secs")]
+    failover_cooldown_secs: u64, // 端點被限流或逾時後暫停使用的秒數
+    #[serde(default)]
+    requests_per_second: f64, // 所有 RPC 請求合計的速率上限，0 表示不限制
+    #[serde(default = "default_rpc_burst")]
+    burst: u32,
+    #[serde(default = "default_rpc_max_retries")]
+    max_retries: u32, // 所有端點皆被限流或落後時，整輪重試的次數
+    #[serde(default = "default_rpc_retry_backoff_ms")]
+    retry_backoff_ms: u64, // 第一次重試前的等待時間，之後每次加倍
+    #[serde(default = "default_rpc_poll_interval_secs")]
+    poll_interval_secs: u64, // 未設定 [grpc] 時輪詢餘額的間隔
+    #[serde(default)]
+    ws_endpoint: Option<String>, // transport = "ws-rpc" 使用的 WebSocket 端點，未設定時由 endpoint 推算
+    #[serde(default = "default_rpc_max_concurrent_requests")]
+    max_concurrent_requests: usize, // 啟動初始化時同時進行的 RPC 請求數
+}
+
+fn default_rpc_max_concurrent_requests() -> usize {
+    8
+}
+
+fn default_rpc_timeout_secs() -> u64 {
+    30
+}
+
+fn default_rpc_failover_cooldown_secs() -> u64 {
+    30
+}
+
+fn default_rpc_burst() -> u32 {
+    10
+}
+
+fn default_rpc_max_retries() -> u32 {
+    3
+}
+
+fn default_rpc_retry_backoff_ms() -> u64 {
+    500
+}
+
+impl RpcConfig {
+    // http(s) 改為 ws(s)，並依 Solana 慣例將 8899 埠改為 8900
+    fn websocket_endpoint(&self) -> String {
+        if let Some(endpoint) = &self.ws_endpoint {
+            return endpoint.clone();
+        }
+        let endpoint = if let Some(rest) = self.endpoint.strip_prefix("https://") {
+            format!("wss://{}", rest)
+        } else if let Some(rest) = self.endpoint.strip_prefix("http://") {
+            format!("ws://{}", rest)
+        } else {
+            self.endpoint.clone()
+        };
+        endpoint.replacen(":8899", ":8900", 1)
+    }
+}
+
+fn default_rpc_poll_interval_secs() -> u64 {
+    30
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct WalletConfig {
+    address: String,
+    name: String,
+    #[serde(default)]
+    group: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    note: Option<String>,
+    #[serde(default)]
+    color: Option<String>, // 前端顯示用的顏色，例如 "#f7931a"
+    #[serde(default)]
+    archived: bool, // 封存的錢包保留歷史但不再訂閱
+    #[serde(default)]
+    history: Option<HistoryConfig>, // 覆寫全域的 [history] 設定
+    #[serde(default)]
+    allow_off_curve: bool, // 地址為 PDA (例如多簽金庫) 時需設為 true
+    #[serde(default)]
+    domain: Option<String>, // 新增時輸入的 .sol 網域，定期重新解析確認仍指向此地址
+    #[serde(default)]
+    account_type: AccountType,
+    #[serde(default)]
+    vote_account: Option<String>, // validator 類型：追蹤此 vote account 的餘額
+}
+
+impl Config {
+    // owner 訂閱只有 Geyser 支援；WebSocket 與輪詢模式仍使用 ATA
+    fn owner_token_accounts(&self) -> bool {
+        self.transport == Transport::Geyser && self.grpc.as_ref().is_some_and(|grpc| grpc.owner_token_accounts)
+    }
+
+    // 啟動與重新載入時提示目前傳輸模式無法提供的通知
+    fn warn_unsupported_notifications(&self) {
+        if self.notifications.new_token_alerts && !self.owner_token_accounts() {
+            warn!("⚠️ new_token_alerts 需要以 Geyser 傳輸並啟用 grpc.owner_token_accounts，目前不會偵測新代幣");
+        }
+    }
+}
+
+impl WalletConfig {
+    fn allows_off_curve(&self) -> bool {
+        self.allow_off_curve || self.account_type.allows_off_curve()
+    }
+}
+
+// 監控的帳戶類型；只有 wallet 會額外追蹤 WSOL ATA、stake account 與 LST
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, async_graphql::Enum, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+enum AccountType {
+    #[default]
+    Wallet, // 一般系統帳戶錢包
+    Account, // 任意帳戶，只追蹤 lamports (程式手續費金庫、多簽 PDA 等)
+    TokenAccount, // SPL token account，追蹤 lamports 與代幣數量
+    Validator, // 驗證者的 identity 帳戶 (投票交易的 fee payer)，只追蹤 lamports，可另外設定 vote_account
+}
+
+impl AccountType {
+    fn as_str(self) -> &'static str {
+        match self {
+            AccountType::Wallet => "wallet",
+            AccountType::Account => "account",
+            AccountType::TokenAccount => "token_account",
+            AccountType::Validator => "validator",
+        }
+    }
+
+    // account、token_account 多半是 PDA (ATA、多簽金庫)，不要求地址在 ed25519 曲線上；identity 一定是公鑰
+    fn allows_off_curve(self) -> bool {
+        !matches!(self, AccountType::Wallet | AccountType::Validator)
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct LoggingConfig {
+    level: String, // 可加上模組等級，例如 "info,sol_wallet_monitor=debug,h2=warn"
+    #[serde(default)]
+    format: LogFormat,
+    #[serde(default)]
+    language: Language, // API 錯誤訊息的預設語言 ("zh" / "en")，日誌本身維持中文
+    #[serde(default)]
+    file: Option<String>, // 設定後除了 stdout 也寫入此檔案，超過大小時輪替
+    #[serde(default = "default_log_max_size_mb")]
+    max_size_mb: u64,
+    #[serde(default = "default_log_max_files")]
+    max_files: usize, // 保留的舊日誌檔數量 (file.1 為最新)
+    #[serde(default = "default_request_log")]
+    request_log: String, // 每個 HTTP 請求的日誌等級："off"、"debug"、"info"...
+    #[serde(default = "default_slow_request_ms")]
+    slow_request_ms: u64, // 超過此耗時的請求以 warn 記錄，0 表示不檢查
+}
+
+// 日誌輸出格式；json 每行一個物件，方便匯入 Loki / ELK
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+fn default_request_log() -> String {
+    "info".to_string()
+}
+
+fn default_slow_request_ms() -> u64 {
+    1000
+}
+
+fn default_log_max_size_mb() -> u64 {
+    100
+}
+
+fn default_log_max_files() -> usize {
+    5
+}
+
+// 請求日誌設定，啟動時從 LoggingConfig 解析一次
+#[derive(Debug, Clone, Copy)]
+struct RequestLogConfig {
+    level: tracing::level_filters::LevelFilter,
+    slow_threshold: Option<Duration>,
+}
+
+impl RequestLogConfig {
+    fn from_logging(logging: &LoggingConfig) -> Self {
+        let level = tracing::level_filters::LevelFilter::from_str(&logging.request_log).unwrap_or_else(|_| {
+            warn!("⚠️ 無效的 request_log 等級 \"{}\"，改用 info", logging.request_log);
+            tracing::level_filters::LevelFilter::INFO
+        });
+        Self {
+            level,
+            slow_threshold: (logging.slow_request_ms > 0).then(|| Duration::from_millis(logging.slow_request_ms)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct ServerConfig {
+    host: String,
+    port: u16,
+    #[serde(default)]
+    tls: Option<TlsConfig>,
+    #[serde(default)]
+    unix_socket: Option<String>, // 設定後改為監聽 Unix domain socket，忽略 host/port
+}
+
+// HTTPS 設定，憑證與私鑰皆為 PEM 格式
+#[derive(Debug, Deserialize, Clone)]
+struct TlsConfig {
+    cert_path: String,
+    key_path: String,
+    #[serde(default)]
+    redirect_http_port: Option<u16>, // 在此埠接受 HTTP 並轉址到 HTTPS
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct LstConfig {
+    #[serde(default = "default_true")]
+    enabled: bool,
+    #[serde(default = "default_lst_refresh_secs")]
+    refresh_secs: u64, // 匯率更新間隔
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct SnsConfig {
+    #[serde(default = "default_sns_refresh_secs")]
+    refresh_secs: u64, // 重新解析 .sol 網域的間隔，0 表示不檢查
+}
+
+impl Default for SnsConfig {
+    fn default() -> Self {
+        Self {
+            refresh_secs: default_sns_refresh_secs(),
+        }
+    }
+}
+
+impl Default for LstConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            refresh_secs: default_lst_refresh_secs(),
+        }
+    }
+}
+
+// 資料庫設定：上限為 0 代表不限制
+#[derive(Debug, Deserialize, Clone)]
+struct DatabaseConfig {
+    #[serde(default = "default_database_backend")]
+    backend: String, // "redb" 或 "sqlite" (需啟用 sqlite feature)
+    path: Option<String>,
+    #[serde(default)]
+    max_history_per_wallet: usize, // 每個錢包最多保留的原始記錄數
+    #[serde(default)]
+    retention_days: u64, // 超過此天數的記錄 (含彙總) 一律刪除
+    #[serde(default = "default_retention_interval_secs")]
+    prune_interval_secs: u64,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            backend: default_database_backend(),
+            path: None,
+            max_history_per_wallet: 0,
+            retention_days: 0,
+            prune_interval_secs: default_retention_interval_secs(),
+        }
+    }
+}
+
+impl DatabaseConfig {
+    fn path(&self) -> String {
+        match (&self.path, self.backend.as_str()) {
+            (Some(path), _) => path.clone(),
+            (None, "sqlite") => "wallet_history.sqlite".to_string(),
+            (None, _) => DB_FILE.to_string(),
+        }
+    }
+}
+
+fn default_database_backend() -> String {
+    "redb".to_string()
+}
+
+// 資料庫備份設定
+#[derive(Debug, Deserialize, Clone)]
+struct BackupConfig {
+    #[serde(default = "default_backup_dir")]
+    dir: String,
+    #[serde(default)]
+    interval_hours: u64, // 定期備份間隔，0 為停用
+    #[serde(default = "default_backup_keep")]
+    keep: usize, // 保留最近的備份數量，0 為全部保留
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            dir: default_backup_dir(),
+            interval_hours: 0,
+            keep: default_backup_keep(),
+        }
+    }
+}
+
+fn default_backup_dir() -> String {
+    "backups".to_string()
+}
+
+fn default_backup_keep() -> usize {
+    7
+}
+
+// API 權杖驗證，未設定任何權杖時不啟用
+#[derive(Debug, Deserialize, Clone, Default)]
+struct AuthConfig {
+    #[serde(default)]
+    tokens: Vec<String>, // 可執行所有操作
+    #[serde(default)]
+    read_tokens: Vec<String>, // 只能讀取，修改操作返回 403
+    #[serde(default)]
+    protect_reads: bool, // 讀取 API 與 WebSocket 也需要權杖
+}
+
+impl AuthConfig {
+    fn enabled(&self) -> bool {
+        !self.tokens.is_empty() || !self.read_tokens.is_empty()
+    }
+}
+
+// 定期以 RPC 核對串流累積的餘額，修正重連期間遺漏的更新
+#[derive(Debug, Deserialize, Clone)]
+struct ReconciliationConfig {
+    #[serde(default = "default_reconciliation_interval_minutes")]
+    interval_minutes: u64, // 0 表示停用
+}
+
+impl Default for ReconciliationConfig {
+    fn default() -> Self {
+        Self { interval_minutes: default_reconciliation_interval_minutes() }
+    }
+}
+
+fn default_reconciliation_interval_minutes() -> u64 {
+    10
+}
+
+// 高頻變化錢包的歷史記錄合併，即時推送不受影響
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+struct HistoryConfig {
+    #[serde(default)]
+    min_interval_ms: u64, // 同一錢包兩筆記錄的最短間隔，期間內的變化只保留最後狀態；0 表示不限制
+    #[serde(default)]
+    dust_threshold: f64, // 各項餘額相對上一筆記錄的變化都小於此值 (SOL) 時不記錄
+}
+
+impl HistoryConfig {
+    fn is_dust(&self, last: &BalanceHistory, point: &BalanceHistory) -> bool {
+        let changes = [
+            point.sol_balance - last.sol_balance,
+            point.wsol_balance - last.wsol_balance,
+            point.staked_balance - last.staked_balance,
+            point.lst_balance - last.lst_balance,
+        ];
+        // 未設定門檻時仍略過沒有實際變化的更新
+        changes.iter().all(|change| change.abs() < self.dust_threshold.max(0.000001))
+    }
+}
+
+// 健康檢查設定
+#[derive(Debug, Deserialize, Clone)]
+struct HealthConfig {
+    #[serde(default = "default_health_stale_after_secs")]
+    stale_after_secs: u64, // 超過此秒數未收到 Geyser 更新視為串流已中斷
+    #[serde(default = "default_health_rpc_timeout_secs")]
+    rpc_timeout_secs: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            stale_after_secs: default_health_stale_after_secs(),
+            rpc_timeout_secs: default_health_rpc_timeout_secs(),
+        }
+    }
+}
+
+fn default_health_stale_after_secs() -> u64 {
+    120
+}
+
+fn default_health_rpc_timeout_secs() -> u64 {
+    5
+}
+
+// 對外提供的 gRPC 串流服務 (proto/wallet_monitor.proto)
+#[derive(Debug, Deserialize, Clone)]
+struct GrpcServerConfig {
+    listen: String, // 例如 "0.0.0.0:50051"
+}
+
+// WebSocket 心跳設定，超過 idle_timeout_secs 未收到任何訊息 (含 pong) 的連線會被關閉
+#[derive(Debug, Deserialize, Clone)]
+struct WebSocketConfig {
+    #[serde(default = "default_ws_ping_interval_secs")]
+    ping_interval_secs: u64, // 0 表示不發送 ping
+    #[serde(default = "default_ws_idle_timeout_secs")]
+    idle_timeout_secs: u64,
+    #[serde(default)]
+    max_connections: usize, // 同時連線數上限，0 表示不限制
+    #[serde(default = "default_ws_send_queue_size")]
+    send_queue_size: usize, // 每個客戶端的發送佇列長度
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            ping_interval_secs: default_ws_ping_interval_secs(),
+            idle_timeout_secs: default_ws_idle_timeout_secs(),
+            max_connections: 0,
+            send_queue_size: default_ws_send_queue_size(),
+        }
+    }
+}
+
+fn default_ws_ping_interval_secs() -> u64 {
+    30
+}
+
+fn default_ws_idle_timeout_secs() -> u64 {
+    90
+}
+
+fn default_ws_send_queue_size() -> usize {
+    64
+}
+
+// 每個來源 IP 的 API 請求速率限制，requests_per_second 為 0 時不啟用
+#[derive(Debug, Deserialize, Clone)]
+struct RateLimitConfig {
+    #[serde(default)]
+    requests_per_second: f64,
+    #[serde(default = "default_rate_limit_burst")]
+    burst: u32, // 短時間內允許的突發請求數
+    #[serde(default = "default_true")]
+    exempt_localhost: bool,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 0.0,
+            burst: default_rate_limit_burst(),
+            exempt_localhost: true,
+        }
+    }
+}
+
+fn default_rate_limit_burst() -> u32 {
+    20
+}
+
+// 每秒請求數換算成兩次請求之間的間隔；非有限值、負數或換算後無法表示 (含為 0) 時返回 None
+fn rate_period(requests_per_second: f64) -> Option<Duration> {
+    Duration::try_from_secs_f64(1.0 / requests_per_second).ok().filter(|period| !period.is_zero())
+}
+
+// 依來源 IP 計算的 governor 限流器 (GCRA)；requests_per_second 為 0 時不建立
+struct RateLimiter {
+    config: RateLimitConfig,
+    limiter: Option<governor::DefaultKeyedRateLimiter<std::net::IpAddr>>,
+}
+
+impl RateLimiter {
+    fn new(config: RateLimitConfig) -> Self {
+        // load_config 已確認 requests_per_second 為 0 或可換算成有效間隔
+        let quota = (config.requests_per_second > 0.0)
+            .then(|| rate_period(config.requests_per_second))
+            .flatten()
+            .and_then(governor::Quota::with_period)
+            .map(|quota| quota.allow_burst(std::num::NonZeroU32::new(config.burst).unwrap_or(std::num::NonZeroU32::MIN)));
+        Self { config, limiter: quota.map(governor::RateLimiter::keyed) }
+    }
+
+    fn enabled(&self) -> bool {
+        self.limiter.is_some()
+    }
+
+    // 取得一個權杖；被限制時返回需等待的秒數
+    fn check(&self, ip: std::net::IpAddr) -> Result<(), u64> {
+        let Some(limiter) = &self.limiter else {
+            return Ok(());
+        };
+        // 清理已補滿的閒置 IP，避免表無限增長
+        if limiter.len() > 10_000 {
+            limiter.retain_recent();
+        }
+        limiter.check_key(&ip).map_err(|not_until| {
+            let now = governor::clock::Clock::now(&governor::clock::DefaultClock::default());
+            not_until.wait_time_from(now).as_secs_f64().ceil().max(1.0) as u64
+        })
+    }
+}
+
+// 限制每個來源 IP 的請求速率，避免輪詢腳本拖慢 gRPC 處理與 WebSocket 推送
+async fn rate_limit_requests(
+    axum::extract::State(limiter): axum::extract::State<Arc<RateLimiter>>,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Response {
+    let ip = request
+        .extensions()
+        .get::<axum::extract::ConnectInfo<std::net::SocketAddr>>()
+        .map(|info| info.0.ip());
+    let Some(ip) = ip.filter(|ip| limiter.enabled() && !(limiter.config.exempt_localhost && ip.is_loopback())) else {
+        return next.run(request).await;
+    };
+
+    match limiter.check(ip) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => {
+            debug!("🚦 {} 請求過於頻繁，已限制", ip);
+            let mut response = (
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(ErrorResponse::new(ErrorCode::RateLimited)),
+            )
+                .into_response();
+            response.headers_mut().insert(header::RETRY_AFTER, header::HeaderValue::from(retry_after));
+            response
+        }
+    }
+}
+
+// 將歷史記錄同步匯出到外部分析資料庫
+#[derive(Debug, Deserialize, Clone)]
+struct ExportConfig {
+    kind: String, // "clickhouse" 或 "postgres" (需啟用 postgres feature)
+    dsn: String,  // ClickHouse 為 HTTP 介面 URL，Postgres 為連線字串
+    #[serde(default = "default_export_table")]
+    table: String,
+    #[serde(default = "default_export_batch_size")]
+    batch_size: usize,
+    #[serde(default = "default_export_flush_interval_secs")]
+    flush_interval_secs: u64,
+    #[serde(default = "default_export_queue_size")]
+    queue_size: usize,
+    #[serde(default = "default_notify_max_retries")]
+    max_retries: u32,
+}
+
+fn default_export_table() -> String {
+    "wallet_history".to_string()
+}
+
+fn default_export_batch_size() -> usize {
+    500
+}
+
+fn default_export_flush_interval_secs() -> u64 {
+    5
+}
+
+fn default_export_queue_size() -> usize {
+    10000
+}
+
+// 歷史資料保留策略：原始 → 1 分鐘 → 1 小時 → 1 天，天數為 0 代表不再往下彙總 / 永久保留
+#[derive(Debug, Deserialize, Clone)]
+struct RetentionConfig {
+    #[serde(default)]
+    enabled: bool, // 降採樣會永久刪除原始記錄，需明確啟用
+    #[serde(default = "default_retention_raw_days")]
+    raw_days: u64,
+    #[serde(default = "default_retention_minute_days")]
+    minute_days: u64,
+    #[serde(default = "default_retention_hour_days")]
+    hour_days: u64,
+    #[serde(default)]
+    day_days: u64,
+    #[serde(default = "default_retention_interval_secs")]
+    interval_secs: u64, // 壓縮任務執行間隔
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            raw_days: default_retention_raw_days(),
+            minute_days: default_retention_minute_days(),
+            hour_days: default_retention_hour_days(),
+            day_days: 0,
+            interval_secs: default_retention_interval_secs(),
+        }
+    }
+}
+
+fn default_retention_raw_days() -> u64 {
+    7
+}
+
+fn default_retention_minute_days() -> u64 {
+    30
+}
+
+fn default_retention_hour_days() -> u64 {
+    365
+}
+
+fn default_retention_interval_secs() -> u64 {
+    3600
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_lst_refresh_secs() -> u64 {
+    300
+}
+
+fn default_sns_refresh_secs() -> u64 {
+    3600
+}
+
+// 通知配置
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+struct NotificationsConfig {
+    telegram: Option<TelegramConfig>,
+    webhook: Option<WebhookConfig>,
+    discord: Option<DiscordConfig>,
+    email: Option<EmailConfig>,
+    mqtt: Option<MqttConfig>,
+    stream: Option<StreamConfig>,
+    pagerduty: Option<PagerDutyConfig>,
+    opsgenie: Option<OpsgenieConfig>,
+    #[serde(default)]
+    new_token_alerts: bool, // 錢包收到未見過的代幣時通知 (需啟用 grpc.owner_token_accounts)
+    #[serde(default)]
+    alerts: Vec<AlertRule>,
+    #[serde(default)]
+    routes: Vec<NotificationRoute>, // 設定後事件只送到符合路由所列的通知目標
+}
+
+// 通知路由：依錢包 / 群組與告警等級決定送往哪些通知目標，可自訂模板與靜音時段
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+struct NotificationRoute {
+    name: String,
+    #[serde(default)]
+    wallets: Vec<String>, // 地址或名稱；wallets 與 groups 皆為空時套用到所有錢包
+    #[serde(default)]
+    groups: Vec<String>,
+    #[serde(default)]
+    severities: Vec<AlertSeverity>, // 為空時不限；一般餘額變化視為 info
+    channels: Vec<String>, // telegram、webhook、discord、email
+    template: Option<String>,
+    quiet_hours: Option<String>, // 例如 "22:00-07:00"，期間內只送出 incident 等級的事件
+    #[serde(default)]
+    utc_offset_hours: i32, // quiet_hours 使用的時區
+}
+
+impl NotificationRoute {
+    // 路由是否適用於此事件 (錢包、群組與告警等級)，與通知目標無關
+    fn matches(&self, event: &BalanceChangeEvent) -> bool {
+        let severity = event.severity.unwrap_or_default();
+        let wallet_matches = (self.wallets.is_empty() && self.groups.is_empty())
+            || self.wallets.iter().any(|w| *w == event.address || *w == event.name)
+            || event.group.as_ref().is_some_and(|group| self.groups.contains(group));
+        wallet_matches && (self.severities.is_empty() || self.severities.contains(&severity))
+    }
+
+    // 已選定的路由是否送到此通知目標
+    fn delivers(&self, event: &BalanceChangeEvent, channel: &str, now: DateTime<Utc>) -> bool {
+        self.channels.iter().any(|c| c.eq_ignore_ascii_case(channel))
+            && (event.severity.unwrap_or_default() == AlertSeverity::Incident || !self.in_quiet_hours(now))
+    }
+
+    fn in_quiet_hours(&self, now: DateTime<Utc>) -> bool {
+        let Some((start, end)) = self.quiet_hours.as_deref().and_then(parse_quiet_hours) else {
+            return false;
+        };
+        let local = (now + chrono::Duration::hours(self.utc_offset_hours as i64)).time();
+        if start <= end {
+            local >= start && local < end
+        } else {
+            local >= start || local < end
+        }
+    }
+}
+
+fn parse_quiet_hours(quiet_hours: &str) -> Option<(chrono::NaiveTime, chrono::NaiveTime)> {
+    let (start, end) = quiet_hours.split_once('-')?;
+    let parse = |t: &str| chrono::NaiveTime::parse_from_str(t.trim(), "%H:%M").ok();
+    Some((parse(start)?, parse(end)?))
+}
+
+// 告警規則，透過已設定的通知目標發送
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+struct AlertRule {
+    name: String,
+    kind: AlertKind,
+    #[serde(default)]
+    min_amount: f64, // large_transfer：單筆轉帳達到此值 (SOL) 才告警
+    #[serde(default)]
+    direction: TransferDirection,
+    #[serde(default = "default_stale_secs")]
+    stale_secs: u64, // stale_wallet：超過此秒數沒有任何更新即告警
+    #[serde(default)]
+    min_balance: f64, // balance_below：總餘額低於此值 (SOL) 即告警
+    #[serde(default = "default_min_epochs")]
+    min_epochs: f64, // validator_runway：identity 餘額不足以支付此數量 epoch 的投票手續費即告警
+    #[serde(default)]
+    snooze_secs: u64, // 同一錢包觸發後在此期間內不再告警，0 表示不靜音
+    #[serde(default)]
+    severity: AlertSeverity,
+    #[serde(default)]
+    condition: Option<String>, // script：Rhai 運算式，結果為 true 時告警 (需以 --features rhai 編譯)
+    #[serde(default)]
+    wallets: Vec<String>, // 為空時套用到所有錢包 (地址或名稱)
+}
+
+fn default_stale_secs() -> u64 {
+    86400
+}
+
+fn default_min_epochs() -> f64 {
+    3.0
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum AlertKind {
+    LargeTransfer,
+    StaleWallet,
+    Script,
+    ValidatorRunway,
+    BalanceBelow,
+}
+
+// incident 等級的告警另外透過 PagerDuty / Opsgenie 開立事件，條件解除時自動關閉
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum AlertSeverity {
+    #[default]
+    Info,
+    Warning,
+    Incident,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum TransferDirection {
+    #[default]
+    Any,
+    Incoming,
+    Outgoing,
+}
+
+impl AlertRule {
+    fn applies_to(&self, wallet: &WalletBalance) -> bool {
+        self.wallets.is_empty() || self.wallets.iter().any(|w| *w == wallet.address || *w == wallet.name)
+    }
+
+    fn matches_transfer(&self, record: &WalletTransactionRecord) -> bool {
+        let direction = match self.direction {
+            TransferDirection::Any => record.direction != "other",
+            TransferDirection::Incoming => record.direction == "incoming",
+            TransferDirection::Outgoing => record.direction == "outgoing",
+        };
+        self.kind == AlertKind::LargeTransfer && record.success && direction && record.amount >= self.min_amount
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+struct TelegramConfig {
+    bot_token: String,
+    chat_id: String,
+    #[serde(default = "default_notify_min_delta")]
+    min_delta: f64, // 總餘額變化超過此值才發送通知 (SOL)
+    #[serde(default = "default_notify_queue_size")]
+    queue_size: usize,
+    #[serde(default = "default_notify_max_retries")]
+    max_retries: u32,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+struct WebhookConfig {
+    urls: Vec<String>,
+    #[serde(default = "default_notify_min_delta")]
+    min_delta: f64,
+    #[serde(default = "default_notify_queue_size")]
+    queue_size: usize,
+    #[serde(default = "default_notify_max_retries")]
+    max_retries: u32,
+    #[serde(default = "default_webhook_timeout_secs")]
+    timeout_secs: u64,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+struct DiscordConfig {
+    webhook_url: String,
+    username: Option<String>,
+    #[serde(default = "default_notify_min_delta")]
+    min_delta: f64,
+    #[serde(default)]
+    wallets: Vec<String>, // 只通知這些錢包 (地址或名稱)，為空時通知全部
+    #[serde(default)]
+    exclude_wallets: Vec<String>,
+    #[serde(default = "default_notify_queue_size")]
+    queue_size: usize,
+    #[serde(default = "default_notify_max_retries")]
+    max_retries: u32,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+struct EmailConfig {
+    smtp_host: String,
+    smtp_port: Option<u16>, // 預設依 tls：starttls 587、tls 465、none 25
+    #[serde(default)]
+    tls: SmtpTls,
+    username: Option<String>,
+    password: Option<String>,
+    from: String,
+    to: Vec<String>,
+    #[serde(default = "default_email_subject")]
+    subject: String, // 可使用通知模板佔位符，例如 {{wallet.name}}、{{balance_type}}、{{delta}}
+    #[serde(default = "default_email_body")]
+    body: String, // {{text}} 為與 Telegram 相同的通知內容
+    #[serde(default)]
+    digest: bool, // 一般餘額變化合併為每日摘要，告警仍立即寄出
+    #[serde(default)]
+    digest_hour: u32, // 寄出每日摘要的時間 (UTC 小時)
+    #[serde(default = "default_email_digest_subject")]
+    digest_subject: String, // 可使用 {{date}} 與 {{count}}
+    #[serde(default = "default_notify_min_delta")]
+    min_delta: f64,
+    #[serde(default)]
+    wallets: Vec<String>,
+    #[serde(default)]
+    exclude_wallets: Vec<String>,
+    #[serde(default = "default_notify_queue_size")]
+    queue_size: usize,
+    #[serde(default = "default_notify_max_retries")]
+    max_retries: u32,
+    #[serde(default = "default_webhook_timeout_secs")]
+    timeout_secs: u64,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum SmtpTls {
+    #[default]
+    Starttls,
+    Tls,
+    None,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+struct MqttConfig {
+    host: String,
+    #[serde(default = "default_mqtt_port")]
+    port: u16,
+    #[serde(default = "default_mqtt_client_id")]
+    client_id: String,
+    username: Option<String>,
+    password: Option<String>,
+    #[serde(default = "default_mqtt_topic_prefix")]
+    topic_prefix: String,
+    #[serde(default)]
+    qos: u8, // 0、1 或 2，其他值在載入配置時拒絕
+    #[serde(default = "default_true")]
+    retain_state: bool, // {prefix}/wallets/{address}/state 以 retained 發布，訂閱時立即取得最新餘額
+    #[serde(default = "default_notify_min_delta")]
+    min_delta: f64,
+    #[serde(default)]
+    wallets: Vec<String>,
+    #[serde(default)]
+    exclude_wallets: Vec<String>,
+    #[serde(default = "default_notify_queue_size")]
+    queue_size: usize,
+}
+
+// 事件串流：每個餘額變化事件與告警都送出，不受 min_delta 與通知路由限制
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+struct StreamConfig {
+    kind: String,    // "kafka" (需啟用 kafka feature) 或 "nats" (需啟用 nats feature)
+    servers: String, // Kafka bootstrap servers (以逗號分隔) 或 NATS URL
+    topic: String,   // Kafka topic 或 NATS subject 前綴
+    alerts_topic: Option<String>, // 告警另外送到此 topic，未設定時與餘額事件相同
+    #[serde(default = "default_stream_format")]
+    format: String, // "json" 或 "avro"
+    #[serde(default = "default_notify_queue_size")]
+    queue_size: usize,
+}
+
+fn default_stream_format() -> String {
+    "json".to_string()
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+// 預設 client_id 加上隨機後綴，避免多個實例以相同 id 連線時被 broker 互相踢下線；
+// 同一行程內固定，重新載入配置時不會因此視為設定變更
+fn default_mqtt_client_id() -> String {
+    static CLIENT_ID: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+    CLIENT_ID
+        .get_or_init(|| {
+            use std::hash::{BuildHasher, Hasher};
+            let suffix = std::collections::hash_map::RandomState::new().build_hasher().finish();
+            format!("sol-wallet-monitor-{:08x}", suffix as u32)
+        })
+        .clone()
+}
+
+fn default_mqtt_topic_prefix() -> String {
+    "sol-wallet-monitor".to_string()
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+struct PagerDutyConfig {
+    routing_key: String, // Events API v2 integration key
+    #[serde(default = "default_pagerduty_url")]
+    url: String,
+    #[serde(default = "default_notify_queue_size")]
+    queue_size: usize,
+    #[serde(default = "default_notify_max_retries")]
+    max_retries: u32,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+struct OpsgenieConfig {
+    api_key: String,
+    #[serde(default = "default_opsgenie_url")]
+    url: String, // EU 帳號使用 https://api.eu.opsgenie.com/v2/alerts
+    #[serde(default = "default_opsgenie_priority")]
+    priority: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default = "default_notify_queue_size")]
+    queue_size: usize,
+    #[serde(default = "default_notify_max_retries")]
+    max_retries: u32,
+}
+
+fn default_pagerduty_url() -> String {
+    "https://events.pagerduty.com/v2/enqueue".to_string()
+}
+
+fn default_opsgenie_url() -> String {
+    "https://api.opsgenie.com/v2/alerts".to_string()
+}
+
+fn default_opsgenie_priority() -> String {
+    "P1".to_string()
+}
+
+fn default_email_subject() -> String {
+    "[SOL 監控] {{wallet.name}} {{balance_type}} 餘額變化 {{delta}}".to_string()
+}
+
+fn default_email_body() -> String {
+    "{{text}}".to_string()
+}
+
+fn default_email_digest_subject() -> String {
+    "[SOL 監控] 每日摘要 {{date}} ({{count}} 筆)".to_string()
+}
+
+fn default_webhook_timeout_secs() -> u64 {
+    10
+}
+
+fn default_notify_min_delta() -> f64 {
+    0.0
+}
+
+fn default_notify_queue_size() -> usize {
+    1000
+}
+
+fn default_notify_max_retries() -> u32 {
+    5
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 3000,
+            tls: None,
+            unix_socket: None,
+        }
+    }
+}
+
+// 錢包餘額追蹤器
+#[derive(Debug, Clone)]
+struct WalletBalance {
+    address: String,
+    name: String,
+    group: Option<String>,
+    tags: Vec<String>,
+    note: Option<String>,
+    color: Option<String>,
+    domain: Option<String>,
+    account_type: AccountType,
+    token: Option<TokenAccountBalance>, // token_account 類型在初始化時從 RPC 取得 mint 與 decimals
+    vote_account: Option<String>, // validator 類型的 vote account
+    archived: bool,
+    sol_balance: f64,
+    wsol_balance: f64,
+    wsol_initialized: bool,
+    wsol_account_open: bool, // WSOL ATA 是否存在；關閉後餘額為 0 但仍視為已初始化
+    token_accounts: HashMap<String, OwnedTokenAccount>, // 啟用 owner_token_accounts 時錢包擁有的所有 token account
+    seen_mints: std::collections::HashSet<String>, // 曾持有過的 mint，用於偵測新代幣
+    stake_accounts: HashMap<String, u64>, // stake account -> lamports
+    lst_holdings: HashMap<String, LstHolding>, // symbol -> holding
+    last_update: DateTime<Utc>,
+    last_slot: u64, // 最近一次 gRPC 帳戶更新的 slot
+    history: VecDeque<BalanceHistory>,
+    history_loaded: bool, // 啟動時只載入最新快照，完整歷史在需要時才從資料庫讀取
+    history_config: HistoryConfig,
+    history_pending: bool, // 有因間隔限制而尚未記錄的變化
+}
+
+impl WalletBalance {
+    fn new(address: String, name: String) -> Self {
+        Self {
+            address,
+            name,
+            group: None,
+            tags: Vec::new(),
+            note: None,
+            color: None,
+            domain: None,
+            account_type: AccountType::Wallet,
+            token: None,
+            vote_account: None,
+            archived: false,
+            sol_balance: 0.0,
+            wsol_balance: 0.0,
+            wsol_initialized: false,
+            wsol_account_open: false,
+            token_accounts: HashMap::new(),
+            seen_mints: std::collections::HashSet::new(),
+            stake_accounts: HashMap::new(),
+            lst_holdings: HashMap::new(),
+            last_update: Utc::now(),
+            last_slot: 0,
+            history: VecDeque::new(),
+            history_loaded: true,
+            history_config: HistoryConfig::default(),
+            history_pending: false,
+        }
+    }
+
+    // 返回是否新增了歷史記錄
+    fn update_sol(&mut self, lamports: u64) -> bool {
+        self.sol_balance = lamports as f64 / 1_000_000_000.0;
+        self.last_update = Utc::now();
+        // 只有在WSOL已初始化後才記錄歷史
+        self.wsol_initialized && self.add_to_history()
+    }
+
+    fn update_wsol(&mut self, amount: f64) -> bool {
+        self.wsol_balance = amount;
+        self.wsol_initialized = true;
+        self.last_update = Utc::now();
+        self.add_to_history()
+    }
+
+    // token_account 的代幣數量；WSOL 計入 wsol_balance 並記錄歷史，其他代幣不是 SOL 計價，只更新目前數量
+    fn update_token(&mut self, amount: f64) -> bool {
+        let Some(token) = &mut self.token else {
+            return false;
+        };
+        token.amount = amount;
+        if token.mint == WSOL_MINT {
+            return self.update_wsol(amount);
+        }
+        self.last_update = Utc::now();
+        false
+    }
+
+    fn initialize_wsol(&mut self, amount: f64) {
+        self.wsol_balance = amount;
+        self.wsol_initialized = true;
+        self.last_update = Utc::now();
+        // 只有在沒有歷史記錄時才添加第一條記錄
+        if self.history.is_empty() {
+            self.add_to_history();
+        }
+    }
+
+    fn initialize_stake_accounts(&mut self, stake_accounts: HashMap<String, u64>) {
+        self.stake_accounts = stake_accounts;
+    }
+
+    fn update_stake(&mut self, stake_account: &str, lamports: u64) -> bool {
+        // 餘額為 0 代表 stake account 已關閉
+        if lamports == 0 {
+            self.stake_accounts.remove(stake_account);
+        } else {
+            self.stake_accounts.insert(stake_account.to_string(), lamports);
+        }
+        self.last_update = Utc::now();
+        self.wsol_initialized && self.add_to_history()
+    }
+
+    fn staked_balance(&self) -> f64 {
+        self.stake_accounts.values().sum::<u64>() as f64 / 1_000_000_000.0
+    }
+
+    fn initialize_lst_holdings(&mut self, holdings: HashMap<String, LstHolding>) {
+        self.lst_holdings = holdings;
+    }
+
+    fn update_lst(&mut self, token: &LstToken, amount: f64, rate: f64) -> bool {
+        // 餘額為 0 時移除持倉，避免摘要中出現空項目
+        if amount <= 0.0 {
+            self.lst_holdings.remove(&token.symbol);
+        } else {
+            self.lst_holdings.insert(token.symbol.clone(), LstHolding {
+                symbol: token.symbol.clone(),
+                mint: token.mint.clone(),
+                amount,
+                rate,
+                sol_value: amount * rate,
+            });
+        }
+        self.last_update = Utc::now();
+        self.wsol_initialized && self.add_to_history()
+    }
+
+    // 由 token_accounts 彙總 WSOL 與各 LST 的數量 (decimals 皆為 9)，LST 依 lst_tokens 的順序
+    fn owned_token_totals(&self, lst_tokens: &[LstToken]) -> (f64, Vec<f64>) {
+        let total = |mint: &str| {
+            self.token_accounts.values().filter(|account| account.mint == mint).map(|account| account.amount).sum::<u64>() as f64
+                / 1_000_000_000.0
+        };
+        (total(WSOL_MINT), lst_tokens.iter().map(|token| total(&token.mint)).collect())
+    }
+
+    fn update_lst_rate(&mut self, symbol: &str, rate: f64) {
+        if let Some(holding) = self.lst_holdings.get_mut(symbol) {
+            holding.rate = rate;
+            holding.sol_value = holding.amount * rate;
+        }
+    }
+
+    fn lst_balance(&self) -> f64 {
+        self.lst_holdings.values().map(|h| h.sol_value).sum()
+    }
+
+    fn total_balance(&self) -> f64 {
+        if !self.wsol_initialized {
+            self.sol_balance + self.staked_balance() + self.lst_balance()
+        } else {
+            self.sol_balance + self.wsol_balance + self.staked_balance() + self.lst_balance()
+        }
+    }
+
+    // 依 [history] 設定合併高頻變化；返回是否新增了記錄
+    fn add_to_history(&mut self) -> bool {
+        self.record_history(self.history_config, false)
+    }
+
+    // 間隔到期後補記暫存的最後狀態
+    fn flush_pending_history(&mut self) -> bool {
+        let due = self.history.back().is_none_or(|last| {
+            Utc::now().signed_duration_since(last.timestamp).num_milliseconds() >= self.history_config.min_interval_ms as i64
+        });
+        self.history_pending && due && self.record_history(self.history_config, true)
+    }
+
+    // 關閉前記錄尚未寫入的最新狀態，包含合併中與低於門檻的變化
+    fn persist_latest_history(&mut self) -> bool {
+        self.wsol_initialized && !self.archived && self.record_history(HistoryConfig::default(), true)
+    }
+
+    // 目前各項餘額，作為歷史記錄點或 script 告警的前後比較
+    fn balance_point(&self) -> BalanceHistory {
+        BalanceHistory {
+            timestamp: self.last_update,
+            sol_balance: self.sol_balance,
+            wsol_balance: if self.wsol_initialized { self.wsol_balance } else { 0.0 },
+            staked_balance: self.staked_balance(),
+            lst_balance: self.lst_balance(),
+            total_balance: self.total_balance(),
+        }
+    }
+
+    fn record_history(&mut self, config: HistoryConfig, flush: bool) -> bool {
+        let history_point = self.balance_point();
+
+        if let Some(last) = self.history.back() {
+            // 累積變化超過門檻前不記錄
+            if config.is_dust(last, &history_point) {
+                self.history_pending = false;
+                return false;
+            }
+            let elapsed = history_point.timestamp.signed_duration_since(last.timestamp).num_milliseconds();
+            if !flush && elapsed < config.min_interval_ms as i64 {
+                self.history_pending = true;
+                return false;
+            }
+        }
+
+        self.history_pending = false;
+        self.history.push_back(history_point);
+        
+        // 限制歷史記錄大小
+        while self.history.len() > MAX_HISTORY_SIZE {
+            self.history.pop_front();
+        }
+        true
+    }
+
+    // 與最新記憶體記錄相同時間戳的資料庫記錄
+    fn history_record(&self) -> WalletHistoryRecord {
+        let mut record = WalletHistoryRecord::new(
+            self.address.clone(),
+            self.sol_balance,
+            self.wsol_balance,
+            self.staked_balance(),
+            self.lst_balance(),
+        );
+        record.timestamp = self.last_update;
+        record
+    }
+
+    fn load_history_from_db(&mut self, records: Vec<WalletHistoryRecord>) {
+        self.history.clear();
+        self.history.extend(records.into_iter().map(BalanceHistory::from));
+        
+        // 注意：不從歷史記錄設置餘額，因為WSOL餘額可能過時
+        // 餘額將從RPC重新獲取以確保準確性
+        
+        // 限制歷史記錄大小
+        while self.history.len() > MAX_HISTORY_SIZE {
+            self.history.pop_front();
+        }
+    }
+
+    // 以資料庫中的歷史記錄補齊啟動後才產生的記憶體記錄
+    fn merge_history_from_db(&mut self, records: Vec<WalletHistoryRecord>) {
+        let last_stored = records.last().map(|r| r.timestamp);
+        let newer: Vec<BalanceHistory> = self.history
+            .iter()
+            .filter(|h| last_stored.is_none_or(|t| h.timestamp > t))
+            .cloned()
+            .collect();
+        self.load_history_from_db(records);
+        self.history.extend(newer);
+        self.history_loaded = true;
+    }
+
+    fn to_summary(&self, include_history: bool, current_slot: u64) -> WalletSummary {
+        // 對歷史數據進行採樣到100筆
+        let sampled_history = if !include_history {
+            Vec::new()
+        } else if self.history.len() > 100 {
+            let step = self.history.len() / 100;
+            self.history.iter()
+                .enumerate()
+                .filter(|(i, _)| i % step == 0)
+                .map(|(_, h)| h.clone())
+                .take(100)
+                .collect()
+        } else {
+            self.history.iter().cloned().collect()
+        };
+        
+        let mut summary = WalletSummary {
+            address: self.address.clone(),
+            name: self.name.clone(),
+            group: self.group.clone(),
+            tags: self.tags.clone(),
+            note: self.note.clone(),
+            color: self.color.clone(),
+            domain: self.domain.clone(),
+            account_type: self.account_type,
+            token: self.token.clone(),
+            archived: self.archived,
+            sol_balance: self.sol_balance,
+            wsol_balance: if self.wsol_initialized { self.wsol_balance } else { 0.0 },
+            wsol_account_open: self.wsol_account_open,
+            staked_balance: self.staked_balance(),
+            lst_balance: self.lst_balance(),
+            lst_holdings: self.lst_holdings.values().cloned().collect(),
+            total_balance: self.total_balance(),
             last_update: self.last_update,
+            last_slot: self.last_slot,
+            current_slot,
+            slot_lag: (self.last_slot > 0 && current_slot > 0).then(|| current_slot.saturating_sub(self.last_slot)),
+            initializing: !self.wsol_initialized,
             sampled_history,
+        };
+
+        // 封存的錢包不再即時更新，以最後一筆記錄顯示餘額
+        if self.archived {
+            if let Some(last) = self.history.back() {
+                summary.sol_balance = last.sol_balance;
+                summary.wsol_balance = last.wsol_balance;
+                summary.staked_balance = last.staked_balance;
+                summary.lst_balance = last.lst_balance;
+                summary.total_balance = last.total_balance;
+                summary.last_update = last.timestamp;
+            }
+        }
+        summary
+    }
+
+    fn print_balance(&self, reason: &str) {
+        if !self.wsol_initialized {
+            info!(
+                "💰 {} | {} ({}) | SOL: {:.6} | WSOL: 未初始化 | 質押: {:.6} | LST: {:.6} | 總計: {:.6}",
+                reason,
+                self.name,
+                &self.address[..8],
+                self.sol_balance,
+                self.staked_balance(),
+                self.lst_balance(),
+                self.total_balance()
+            );
+        } else {
+            info!(
+                "💰 {} | {} ({}) | SOL: {:.6} | WSOL: {:.6} | 質押: {:.6} | LST: {:.6} | 總計: {:.6}",
+                reason,
+                self.name,
+                &self.address[..8],
+                self.sol_balance,
+                self.wsol_balance,
+                self.staked_balance(),
+                self.lst_balance(),
+                self.total_balance()
+            );
+        }
+    }
+}
+
+type SharedWallets = Arc<tokio::sync::RwLock<HashMap<String, WalletBalance>>>;
+type SharedDatabase = Arc<dyn HistoryStore>;
+type SharedPrice = Arc<std::sync::RwLock<Option<PricePoint>>>; // 最新的 SOL/USD 價格
+type SharedValidators = Arc<tokio::sync::RwLock<HashMap<String, ValidatorStatus>>>; // identity 地址 -> 狀態
+type SharedLstTokens = Arc<Vec<LstToken>>;
+type SharedLstRates = Arc<Mutex<HashMap<String, f64>>>; // symbol -> SOL 匯率
+
+// gRPC 訂閱更新信號：錢包清單變更時通知所有分片串流，在同一連線上重新送出訂閱請求
+type GrpcResubscribeSignal = Arc<tokio::sync::watch::Sender<()>>;
+
+// 管理員要求重新連線：中斷目前的 gRPC / WebSocket RPC 串流並立即重連，不等待重試間隔
+type GrpcRestartSignal = Arc<tokio::sync::watch::Sender<()>>;
+
+// 推送給 WebSocket 客戶端的錢包事件，錢包 JSON 在發布時只序列化一次
+#[derive(Debug, Clone)]
+enum WalletEvent {
+    Update { address: String, wallet: Arc<serde_json::Value> },
+    Delete { address: String }, // 錢包不再推送 (例如已封存)
+    // 透過 REST API 新增 / 刪除 / 改名時的明確事件，讓多個瀏覽器頁面保持同步
+    Added { address: String, wallet: Arc<serde_json::Value> },
+    Removed { address: String, name: String },
+    Renamed { address: String, old_name: String, name: String },
+}
+
+impl WalletEvent {
+    fn address(&self) -> &str {
+        match self {
+            WalletEvent::Update { address, .. }
+            | WalletEvent::Delete { address }
+            | WalletEvent::Added { address, .. }
+            | WalletEvent::Removed { address, .. }
+            | WalletEvent::Renamed { address, .. } => address,
+        }
+    }
+}
+
+// 錢包事件廣播；沒有 WebSocket 客戶端時發布會直接丟棄
+#[derive(Clone)]
+struct WalletEvents {
+    sender: tokio::sync::broadcast::Sender<WalletEvent>,
+}
+
+impl WalletEvents {
+    fn new() -> Self {
+        let (sender, _) = tokio::sync::broadcast::channel(WALLET_EVENT_CAPACITY);
+        Self { sender }
+    }
+
+    fn subscribe(&self) -> tokio::sync::broadcast::Receiver<WalletEvent> {
+        self.sender.subscribe()
+    }
+
+    // 已封存的錢包不再推送，對客戶端而言等同刪除
+    fn publish_update(&self, wallet: &WalletBalance) {
+        if wallet.archived {
+            self.publish_delete(&wallet.address);
+            return;
+        }
+        let _ = self.sender.send(WalletEvent::Update {
+            address: wallet.address.clone(),
+            wallet: Arc::new(wallet_update_json(wallet)),
+        });
+    }
+
+    fn publish_delete(&self, address: &str) {
+        let _ = self.sender.send(WalletEvent::Delete { address: address.to_string() });
+    }
+
+    fn publish_added(&self, wallet: &WalletBalance) {
+        let _ = self.sender.send(WalletEvent::Added {
+            address: wallet.address.clone(),
+            wallet: Arc::new(wallet_update_json(wallet)),
+        });
+    }
+
+    fn publish_removed(&self, address: &str, name: &str) {
+        let _ = self.sender.send(WalletEvent::Removed { address: address.to_string(), name: name.to_string() });
+    }
+
+    fn publish_renamed(&self, address: &str, old_name: &str, name: &str) {
+        let _ = self.sender.send(WalletEvent::Renamed {
+            address: address.to_string(),
+            old_name: old_name.to_string(),
+            name: name.to_string(),
+        });
+    }
+}
+
+// 服務健康狀態，由 gRPC 流與 WebSocket 連線更新，供 /healthz 與 /readyz 查詢
+struct HealthState {
+    started_at: std::time::Instant,
+    grpc_streams: std::sync::atomic::AtomicUsize,
+    grpc_connected_streams: std::sync::atomic::AtomicUsize,
+    last_grpc_update_ms: std::sync::atomic::AtomicI64, // 0 表示尚未收到任何更新
+    grpc_stale_reconnects: std::sync::atomic::AtomicU64, // watchdog 因串流無回應而強制重連的次數
+    current_slot: std::sync::atomic::AtomicU64, // gRPC 串流收到的最新 slot
+    websocket_clients: std::sync::atomic::AtomicUsize,
+    reconciliation_runs: std::sync::atomic::AtomicU64,
+    reconciliation_corrections: std::sync::atomic::AtomicU64,
+    last_reconciliation_ms: std::sync::atomic::AtomicI64, // 0 表示尚未執行
+    reconciliation_events: Mutex<VecDeque<ReconciliationEvent>>,
+}
+
+// 核對時發現串流狀態與 RPC 不一致並已修正的記錄
+#[derive(Debug, Clone, Serialize)]
+struct ReconciliationEvent {
+    address: String,
+    name: String,
+    data_type: &'static str,
+    streamed: f64, // 修正前串流累積的餘額
+    actual: f64,   // RPC 查詢到的餘額
+    slot: u64,
+    timestamp: DateTime<Utc>,
+}
+
+type SharedHealth = Arc<HealthState>;
+
+impl HealthState {
+    fn new() -> Self {
+        Self {
+            started_at: std::time::Instant::now(),
+            grpc_streams: std::sync::atomic::AtomicUsize::new(0),
+            grpc_connected_streams: std::sync::atomic::AtomicUsize::new(0),
+            last_grpc_update_ms: std::sync::atomic::AtomicI64::new(0),
+            grpc_stale_reconnects: std::sync::atomic::AtomicU64::new(0),
+            current_slot: std::sync::atomic::AtomicU64::new(0),
+            websocket_clients: std::sync::atomic::AtomicUsize::new(0),
+            reconciliation_runs: std::sync::atomic::AtomicU64::new(0),
+            reconciliation_corrections: std::sync::atomic::AtomicU64::new(0),
+            last_reconciliation_ms: std::sync::atomic::AtomicI64::new(0),
+            reconciliation_events: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn record_reconciliation(&self, events: Vec<ReconciliationEvent>) {
+        self.reconciliation_runs.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        self.reconciliation_corrections.fetch_add(events.len() as u64, std::sync::atomic::Ordering::Relaxed);
+        self.last_reconciliation_ms.store(Utc::now().timestamp_millis(), std::sync::atomic::Ordering::Relaxed);
+        let mut recent = self.reconciliation_events.lock().unwrap();
+        recent.extend(events);
+        while recent.len() > RECONCILIATION_EVENT_LIMIT {
+            recent.pop_front();
+        }
+    }
+
+    fn set_grpc_connected(&self, connected: bool) {
+        if connected {
+            self.grpc_connected_streams.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        } else {
+            self.grpc_connected_streams.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+        }
+    }
+
+    // 所有分片串流皆已連線
+    fn grpc_connected(&self) -> bool {
+        let streams = self.grpc_streams.load(std::sync::atomic::Ordering::Relaxed);
+        streams > 0 && self.grpc_connected_streams.load(std::sync::atomic::Ordering::Relaxed) >= streams
+    }
+
+    fn mark_grpc_update(&self) {
+        self.last_grpc_update_ms.store(Utc::now().timestamp_millis(), std::sync::atomic::Ordering::Relaxed);
+    }
+
+    fn mark_slot(&self, slot: u64) {
+        self.current_slot.fetch_max(slot, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    fn current_slot(&self) -> u64 {
+        self.current_slot.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn record_grpc_stale_reconnect(&self) {
+        self.grpc_stale_reconnects.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    // 距離上次 Geyser 更新的秒數；尚未收到更新時以啟動時間計算
+    fn seconds_since_grpc_update(&self) -> u64 {
+        match self.last_grpc_update_ms.load(std::sync::atomic::Ordering::Relaxed) {
+            0 => self.started_at.elapsed().as_secs(),
+            ms => (Utc::now().timestamp_millis() - ms).max(0) as u64 / 1000,
+        }
+    }
+}
+
+// 應用狀態結構
+#[derive(Clone)]
+struct AppState {
+    wallets: SharedWallets,
+    database: SharedDatabase,
+    database_writer: DatabaseWriter,
+    grpc_resubscribe: GrpcResubscribeSignal,
+    grpc_restart: GrpcRestartSignal,
+    lst_tokens: SharedLstTokens,
+    lst_rates: SharedLstRates,
+    events: WalletEvents,
+    health: SharedHealth,
+    rpc: SharedRpc,
+    websocket_slots: Arc<tokio::sync::Semaphore>,
+    shutdown: ShutdownSignal,
+    backfills: Arc<Mutex<HashMap<String, BackfillStatus>>>, // 各錢包最近一次回補任務的狀態
+    notifier: Notifier,
+    unsaved_wallets: Arc<Mutex<std::collections::HashSet<String>>>, // 透過 API 新增但寫入配置文件失敗的錢包，重新載入時保留
+    history_flush_started: Arc<std::sync::atomic::AtomicBool>,
+    price: SharedPrice,
+    validators: SharedValidators,
+    config: Config,
+}
+
+type CsvSender = tokio::sync::mpsc::Sender<Result<String, std::io::Error>>;
+
+// 歷史資料儲存後端 (預設 redb，啟用 sqlite feature 後可選 SQLite)
+trait HistoryStore: Send + Sync {
+    fn write_batch(&self, history: &[WalletHistoryRecord], transactions: &[WalletTransactionRecord]) -> Result<(), Box<dyn std::error::Error>>;
+    fn load_history(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>, limit: usize) -> Result<Vec<WalletHistoryRecord>, Box<dyn std::error::Error>>;
+    fn load_history_series(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<BalanceHistory>, Box<dyn std::error::Error>>;
+    fn load_recent_history(&self, address: &str, limit: usize) -> Result<Vec<WalletHistoryRecord>, Box<dyn std::error::Error>>;
+    fn load_latest(&self, address: &str) -> Result<Option<WalletHistoryRecord>, Box<dyn std::error::Error>>;
+    fn earliest_history(&self, address: &str) -> Result<Option<DateTime<Utc>>, Box<dyn std::error::Error>>;
+    fn load_transactions(&self, address: &str, page: usize, page_size: usize) -> Result<(usize, Vec<WalletTransactionRecord>), Box<dyn std::error::Error>>;
+    fn load_daily_fees(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<DailyFees>, Box<dyn std::error::Error>>;
+    fn save_block_rewards(&self, rewards: &[BlockRewardRecord]) -> Result<(), Box<dyn std::error::Error>>;
+    fn load_daily_income(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<DailyIncome>, Box<dyn std::error::Error>>;
+    fn delete_wallet(&self, address: &str) -> Result<(), Box<dyn std::error::Error>>;
+    fn delete_history(&self, address: &str, after: Option<DateTime<Utc>>, before: Option<DateTime<Utc>>) -> Result<usize, Box<dyn std::error::Error>>;
+    fn stream_history_csv(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>, sender: &CsvSender) -> Result<(), Box<dyn std::error::Error>>;
+    fn compact(&self, retention: &RetentionConfig) -> Result<usize, Box<dyn std::error::Error>>;
+    fn prune(&self, config: &DatabaseConfig) -> Result<usize, Box<dyn std::error::Error>>;
+    fn backup(&self, config: &BackupConfig) -> Result<BackupInfo, Box<dyn std::error::Error>>;
+    fn restore_backup(&self, path: &std::path::Path) -> Result<(RestoreResponse, Vec<String>), Box<dyn std::error::Error>>;
+    fn import_records(&self, records: &[WalletHistoryRecord]) -> Result<(RestoreResponse, Vec<String>), Box<dyn std::error::Error>>;
+    fn stats(&self) -> Result<DatabaseStats, Box<dyn std::error::Error>>;
+    fn compact_file(&self) -> Result<CompactResponse, Box<dyn std::error::Error>>;
+    fn check_writable(&self) -> Result<(), Box<dyn std::error::Error>>;
+    fn append_audit(&self, entry: &AuditEntry) -> Result<(), Box<dyn std::error::Error>>;
+    fn load_audit(&self, address: Option<&str>, before: Option<u64>, limit: usize) -> Result<Vec<AuditEntry>, Box<dyn std::error::Error>>;
+    fn append_token_event(&self, event: &TokenAccountEvent) -> Result<(), Box<dyn std::error::Error>>;
+    fn load_token_events(&self, address: &str, limit: usize) -> Result<Vec<TokenAccountEvent>, Box<dyn std::error::Error>>;
+    fn save_seen_mints(&self, address: &str, mints: &[String]) -> Result<(), Box<dyn std::error::Error>>;
+    fn load_seen_mints(&self, address: &str) -> Result<std::collections::HashSet<String>, Box<dyn std::error::Error>>;
+    fn append_token_amount(&self, record: &TokenAmountRecord) -> Result<(), Box<dyn std::error::Error>>;
+    fn load_token_amounts(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<TokenAmountRecord>, Box<dyn std::error::Error>>;
+    fn insert_alert(&self, record: &AlertRecord) -> Result<(), Box<dyn std::error::Error>>;
+    fn set_alert_state(&self, id: u64, state: AlertState) -> Result<Option<AlertRecord>, Box<dyn std::error::Error>>;
+    fn save_alert_snooze(&self, rule: &str, address: &str, until: DateTime<Utc>) -> Result<(), Box<dyn std::error::Error>>;
+    fn load_alert_snoozes(&self) -> Result<Vec<(String, String, DateTime<Utc>)>, Box<dyn std::error::Error>>;
+    fn load_alerts(&self, state: Option<AlertState>, before: Option<u64>, limit: usize) -> Result<Vec<AlertRecord>, Box<dyn std::error::Error>>;
+    fn save_daily_report(&self, report: &DailyReport) -> Result<(), Box<dyn std::error::Error>>;
+    fn load_daily_report(&self, date: chrono::NaiveDate) -> Result<Option<DailyReport>, Box<dyn std::error::Error>>;
+    fn append_price(&self, point: &PricePoint) -> Result<(), Box<dyn std::error::Error>>;
+    fn load_prices(&self, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<PricePoint>, Box<dyn std::error::Error>>;
+    fn latest_price(&self) -> Result<Option<PricePoint>, Box<dyn std::error::Error>>;
+    fn prune_prices(&self, before: DateTime<Utc>) -> Result<usize, Box<dyn std::error::Error>>;
+    fn append_fx_rates(&self, timestamp: DateTime<Utc>, rates: &[(String, f64)]) -> Result<(), Box<dyn std::error::Error>>;
+    fn load_fx_rates(&self, currency: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<FxRate>, Box<dyn std::error::Error>>;
+    fn save_cost_basis(&self, address: &str, basis: Option<&CostBasis>) -> Result<(), Box<dyn std::error::Error>>;
+    fn load_cost_basis(&self, address: &str) -> Result<Option<CostBasis>, Box<dyn std::error::Error>>;
+    fn save_epoch(&self, boundary: &EpochBoundary) -> Result<(), Box<dyn std::error::Error>>;
+    fn load_epoch(&self, epoch: u64) -> Result<Option<EpochBoundary>, Box<dyn std::error::Error>>;
+    fn load_epochs(&self, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<EpochBoundary>, Box<dyn std::error::Error>>;
+    fn save_stake_reward(&self, address: &str, reward: &StakeReward) -> Result<(), Box<dyn std::error::Error>>;
+    fn load_stake_rewards(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<StakeReward>, Box<dyn std::error::Error>>;
+}
+
+// redb 後端；壓縮檔案需要 &mut Database，因此以讀寫鎖包裝
+struct RedbStore {
+    db: std::sync::RwLock<Database>,
+    path: String,
+}
+
+impl RedbStore {
+    fn db(&self) -> std::sync::RwLockReadGuard<'_, Database> {
+        self.db.read().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl HistoryStore for RedbStore {
+    fn write_batch(&self, history: &[WalletHistoryRecord], transactions: &[WalletTransactionRecord]) -> Result<(), Box<dyn std::error::Error>> {
+        write_batch(&self.db(), history, transactions)
+    }
+
+    fn load_history(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>, limit: usize) -> Result<Vec<WalletHistoryRecord>, Box<dyn std::error::Error>> {
+        load_wallet_history(&self.db(), address, from, to, limit)
+    }
+
+    fn load_history_series(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<BalanceHistory>, Box<dyn std::error::Error>> {
+        load_history_series(&self.db(), address, from, to)
+    }
+
+    fn load_recent_history(&self, address: &str, limit: usize) -> Result<Vec<WalletHistoryRecord>, Box<dyn std::error::Error>> {
+        load_recent_wallet_history(&self.db(), address, limit)
+    }
+
+    fn load_latest(&self, address: &str) -> Result<Option<WalletHistoryRecord>, Box<dyn std::error::Error>> {
+        load_latest_wallet_record(&self.db(), address)
+    }
+
+    fn earliest_history(&self, address: &str) -> Result<Option<DateTime<Utc>>, Box<dyn std::error::Error>> {
+        earliest_wallet_history(&self.db(), address)
+    }
+
+    fn load_transactions(&self, address: &str, page: usize, page_size: usize) -> Result<(usize, Vec<WalletTransactionRecord>), Box<dyn std::error::Error>> {
+        load_wallet_transactions(&self.db(), address, page, page_size)
+    }
+
+    fn load_daily_fees(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<DailyFees>, Box<dyn std::error::Error>> {
+        load_daily_fees(&self.db(), address, from, to)
+    }
+
+    fn save_block_rewards(&self, rewards: &[BlockRewardRecord]) -> Result<(), Box<dyn std::error::Error>> {
+        save_block_rewards(&self.db(), rewards)
+    }
+
+    fn load_daily_income(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<DailyIncome>, Box<dyn std::error::Error>> {
+        load_daily_income(&self.db(), address, from, to)
+    }
+
+    fn delete_wallet(&self, address: &str) -> Result<(), Box<dyn std::error::Error>> {
+        delete_wallet_history(&self.db(), address)?;
+        delete_wallet_transactions(&self.db(), address)?;
+        delete_wallet_fees(&self.db(), address)?;
+        delete_wallet_income(&self.db(), address)?;
+        save_cost_basis(&self.db(), address, None)?;
+        delete_stake_rewards(&self.db(), address)?;
+        delete_token_amounts(&self.db(), address)?;
+        delete_wallet_token_events(&self.db(), address)?;
+        delete_seen_mints(&self.db(), address)
+    }
+
+    fn delete_history(&self, address: &str, after: Option<DateTime<Utc>>, before: Option<DateTime<Utc>>) -> Result<usize, Box<dyn std::error::Error>> {
+        delete_history_range(&self.db(), address, after, before)
+    }
+
+    fn stream_history_csv(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>, sender: &CsvSender) -> Result<(), Box<dyn std::error::Error>> {
+        // 每頁各自取得讀鎖與讀取交易，送出期間不持有鎖，compact_file 不會被慢速下載阻擋
+        send_history_csv(from, |from| load_wallet_history(&self.db(), address, from, to, CSV_PAGE_SIZE), sender)
+    }
+
+    fn compact(&self, retention: &RetentionConfig) -> Result<usize, Box<dyn std::error::Error>> {
+        compact_history(&self.db(), retention)
+    }
+
+    fn prune(&self, config: &DatabaseConfig) -> Result<usize, Box<dyn std::error::Error>> {
+        prune_history(&self.db(), config)
+    }
+
+    fn backup(&self, config: &BackupConfig) -> Result<BackupInfo, Box<dyn std::error::Error>> {
+        backup_database(&self.db(), config)
+    }
+
+    fn restore_backup(&self, path: &std::path::Path) -> Result<(RestoreResponse, Vec<String>), Box<dyn std::error::Error>> {
+        restore_from_backup(&self.db(), path)
+    }
+
+    fn import_records(&self, records: &[WalletHistoryRecord]) -> Result<(RestoreResponse, Vec<String>), Box<dyn std::error::Error>> {
+        import_history_records(&self.db(), records)
+    }
+
+    fn stats(&self) -> Result<DatabaseStats, Box<dyn std::error::Error>> {
+        database_stats(&self.db(), &self.path)
+    }
+
+    fn compact_file(&self) -> Result<CompactResponse, Box<dyn std::error::Error>> {
+        // compact 需要獨占資料庫，寫鎖會等待進行中的讀寫完成
+        let mut db = self.db.write().unwrap_or_else(|e| e.into_inner());
+        let size_before = fs::metadata(&self.path)?.len();
+        let compacted = db.compact()?;
+        let size_after = fs::metadata(&self.path)?.len();
+        info!("🗜️ 資料庫壓縮完成: {} → {} bytes", size_before, size_after);
+        Ok(CompactResponse { compacted, size_before, size_after })
+    }
+
+    fn check_writable(&self) -> Result<(), Box<dyn std::error::Error>> {
+        // 提交一個空的寫入交易，確認檔案仍可寫入
+        self.db().begin_write()?.commit()?;
+        Ok(())
+    }
+
+    fn append_audit(&self, entry: &AuditEntry) -> Result<(), Box<dyn std::error::Error>> {
+        append_audit_entry(&self.db(), entry)
+    }
+
+    fn load_audit(&self, address: Option<&str>, before: Option<u64>, limit: usize) -> Result<Vec<AuditEntry>, Box<dyn std::error::Error>> {
+        load_audit_entries(&self.db(), address, before, limit)
+    }
+
+    fn append_token_event(&self, event: &TokenAccountEvent) -> Result<(), Box<dyn std::error::Error>> {
+        append_token_event(&self.db(), event)
+    }
+
+    fn load_token_events(&self, address: &str, limit: usize) -> Result<Vec<TokenAccountEvent>, Box<dyn std::error::Error>> {
+        load_token_events(&self.db(), address, limit)
+    }
+
+    fn save_seen_mints(&self, address: &str, mints: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        save_seen_mints(&self.db(), address, mints)
+    }
+
+    fn load_seen_mints(&self, address: &str) -> Result<std::collections::HashSet<String>, Box<dyn std::error::Error>> {
+        load_seen_mints(&self.db(), address)
+    }
+
+    fn insert_alert(&self, record: &AlertRecord) -> Result<(), Box<dyn std::error::Error>> {
+        insert_alert(&self.db(), record)
+    }
+
+    fn set_alert_state(&self, id: u64, state: AlertState) -> Result<Option<AlertRecord>, Box<dyn std::error::Error>> {
+        set_alert_state(&self.db(), id, state)
+    }
+
+    fn save_alert_snooze(&self, rule: &str, address: &str, until: DateTime<Utc>) -> Result<(), Box<dyn std::error::Error>> {
+        save_alert_snooze(&self.db(), rule, address, until)
+    }
+
+    fn load_alert_snoozes(&self) -> Result<Vec<(String, String, DateTime<Utc>)>, Box<dyn std::error::Error>> {
+        load_alert_snoozes(&self.db())
+    }
+
+    fn load_alerts(&self, state: Option<AlertState>, before: Option<u64>, limit: usize) -> Result<Vec<AlertRecord>, Box<dyn std::error::Error>> {
+        load_alerts(&self.db(), state, before, limit)
+    }
+
+    fn save_daily_report(&self, report: &DailyReport) -> Result<(), Box<dyn std::error::Error>> {
+        save_daily_report(&self.db(), report)
+    }
+
+    fn load_daily_report(&self, date: chrono::NaiveDate) -> Result<Option<DailyReport>, Box<dyn std::error::Error>> {
+        load_daily_report(&self.db(), date)
+    }
+
+    fn append_price(&self, point: &PricePoint) -> Result<(), Box<dyn std::error::Error>> {
+        append_price(&self.db(), point)
+    }
+
+    fn load_prices(&self, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<PricePoint>, Box<dyn std::error::Error>> {
+        load_prices(&self.db(), from, to)
+    }
+
+    fn latest_price(&self) -> Result<Option<PricePoint>, Box<dyn std::error::Error>> {
+        latest_price(&self.db())
+    }
+
+    fn prune_prices(&self, before: DateTime<Utc>) -> Result<usize, Box<dyn std::error::Error>> {
+        prune_prices(&self.db(), before)
+    }
+
+    fn append_fx_rates(&self, timestamp: DateTime<Utc>, rates: &[(String, f64)]) -> Result<(), Box<dyn std::error::Error>> {
+        append_fx_rates(&self.db(), timestamp, rates)
+    }
+
+    fn load_fx_rates(&self, currency: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<FxRate>, Box<dyn std::error::Error>> {
+        load_fx_rates(&self.db(), currency, from, to)
+    }
+
+    fn save_cost_basis(&self, address: &str, basis: Option<&CostBasis>) -> Result<(), Box<dyn std::error::Error>> {
+        save_cost_basis(&self.db(), address, basis)
+    }
+
+    fn load_cost_basis(&self, address: &str) -> Result<Option<CostBasis>, Box<dyn std::error::Error>> {
+        load_cost_basis(&self.db(), address)
+    }
+
+    fn save_epoch(&self, boundary: &EpochBoundary) -> Result<(), Box<dyn std::error::Error>> {
+        save_epoch(&self.db(), boundary)
+    }
+
+    fn load_epoch(&self, epoch: u64) -> Result<Option<EpochBoundary>, Box<dyn std::error::Error>> {
+        Ok(load_epochs(&self.db(), epoch..=epoch)?.pop())
+    }
+
+    fn load_epochs(&self, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<EpochBoundary>, Box<dyn std::error::Error>> {
+        let epochs = load_epochs(&self.db(), 0..=u64::MAX)?;
+        Ok(epochs.into_iter().filter(|e| from.is_none_or(|from| e.started_at >= from) && to.is_none_or(|to| e.started_at <= to)).collect())
+    }
+
+    fn save_stake_reward(&self, address: &str, reward: &StakeReward) -> Result<(), Box<dyn std::error::Error>> {
+        save_stake_reward(&self.db(), address, reward)
+    }
+
+    fn load_stake_rewards(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<StakeReward>, Box<dyn std::error::Error>> {
+        load_stake_rewards(&self.db(), address, from, to)
+    }
+
+    fn append_token_amount(&self, record: &TokenAmountRecord) -> Result<(), Box<dyn std::error::Error>> {
+        append_token_amount(&self.db(), record)
+    }
+
+    fn load_token_amounts(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<TokenAmountRecord>, Box<dyn std::error::Error>> {
+        load_token_amounts(&self.db(), address, from, to)
+    }
+}
+
+// 依設定開啟儲存後端
+fn open_history_store(config: &DatabaseConfig) -> Result<SharedDatabase, Box<dyn std::error::Error>> {
+    let path = config.path();
+    if let Some(parent) = std::path::Path::new(&path).parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    match config.backend.as_str() {
+        "redb" => Ok(Arc::new(RedbStore {
+            db: std::sync::RwLock::new(initialize_database(&path)?),
+            path,
+        })),
+        #[cfg(feature = "sqlite")]
+        "sqlite" => Ok(Arc::new(sqlite_store::SqliteStore::open(&path)?)),
+        #[cfg(not(feature = "sqlite"))]
+        "sqlite" => Err("SQLite 後端需以 --features sqlite 編譯".into()),
+        other => Err(format!("不支援的資料庫後端: {}", other).into()),
+    }
+}
+
+// 資料庫操作函數
+fn initialize_database(path: &str) -> Result<Database, Box<dyn std::error::Error>> {
+    let db = Database::create(path)?;
+    // 先建立歷史表格，讓讀取端在尚無任何記錄時也能正常開啟
+    let write_txn = db.begin_write()?;
+    for (table, _) in HISTORY_TIERS {
+        write_txn.open_table(table)?;
+    }
+    write_txn.open_table(WALLET_LATEST_TABLE)?;
+    write_txn.commit()?;
+    migrate_legacy_history(&db)?;
+    info!("📊 資料庫已初始化: {}", path);
+    Ok(db)
+}
+
+// 將舊版 JSON 格式的歷史表格轉換為 bincode 編碼的新表格
+fn migrate_legacy_history(db: &Database) -> Result<(), Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let existing: Vec<String> = read_txn.list_tables()?.map(|table| table.name().to_string()).collect();
+    drop(read_txn);
+
+    let write_txn = db.begin_write()?;
+    let mut migrated = 0usize;
+    if existing.iter().any(|name| name == LEGACY_WALLET_HISTORY_TABLE.name()) {
+        {
+            let legacy = write_txn.open_table(LEGACY_WALLET_HISTORY_TABLE)?;
+            let mut table = write_txn.open_table(WALLET_HISTORY_TABLE)?;
+            for entry in legacy.iter()? {
+                let (_, value) = entry?;
+                let record: WalletHistoryRecord = serde_json::from_str(value.value())?;
+                let encoded = bincode::serialize(&record)?;
+                table.insert((record.address.as_str(), history_key_millis(record.timestamp)), encoded.as_slice())?;
+                migrated += 1;
+            }
+        }
+        write_txn.delete_table(LEGACY_WALLET_HISTORY_TABLE)?;
+    }
+
+    for (json_definition, definition, aggregated) in JSON_HISTORY_TIERS {
+        if !existing.iter().any(|name| name == json_definition.name()) {
+            continue;
+        }
+        {
+            let json_table = write_txn.open_table(json_definition)?;
+            let mut table = write_txn.open_table(definition)?;
+            for entry in json_table.iter()? {
+                let (key, value) = entry?;
+                let encoded = if aggregated {
+                    bincode::serialize(&serde_json::from_str::<HistoryAggregate>(value.value())?)?
+                } else {
+                    bincode::serialize(&serde_json::from_str::<WalletHistoryRecord>(value.value())?)?
+                };
+                table.insert(key.value(), encoded.as_slice())?;
+                migrated += 1;
+            }
+        }
+        write_txn.delete_table(json_definition)?;
+    }
+    write_txn.commit()?;
+
+    if migrated > 0 {
+        info!("📦 已將 {} 條歷史記錄遷移到新的資料庫格式", migrated);
+    }
+    Ok(())
+}
+
+fn history_key_millis(timestamp: DateTime<Utc>) -> u64 {
+    timestamp.timestamp_millis().max(0) as u64
+}
+
+// 將單一表格的所有記錄複製到備份資料庫
+fn copy_table<K: redb::Key + 'static, V: redb::Value + 'static>(
+    read_txn: &redb::ReadTransaction,
+    write_txn: &redb::WriteTransaction,
+    definition: TableDefinition<K, V>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let source = match read_txn.open_table(definition) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut target = write_txn.open_table(definition)?;
+    for entry in source.iter()? {
+        let (key, value) = entry?;
+        target.insert(key.value(), value.value())?;
+    }
+    Ok(())
+}
+
+// 在單一讀取交易中將所有表格複製到新的資料庫檔案，得到一致的快照
+fn backup_database(db: &Database, config: &BackupConfig) -> Result<BackupInfo, Box<dyn std::error::Error>> {
+    fs::create_dir_all(&config.dir)?;
+    let created_at = Utc::now();
+    let file = format!("wallet_history-{}.redb", created_at.format("%Y%m%d-%H%M%S"));
+    let path = std::path::Path::new(&config.dir).join(&file);
+    let temp_path = path.with_extension("redb.tmp");
+
+    {
+        let backup = Database::create(&temp_path)?;
+        let read_txn = db.begin_read()?;
+        let write_txn = backup.begin_write()?;
+        for (definition, _) in HISTORY_TIERS {
+            copy_table(&read_txn, &write_txn, definition)?;
+        }
+        copy_table(&read_txn, &write_txn, WALLET_LATEST_TABLE)?;
+        copy_table(&read_txn, &write_txn, WALLET_TRANSACTIONS_TABLE)?;
+        copy_table(&read_txn, &write_txn, WALLET_FEES_TABLE)?;
+        copy_table(&read_txn, &write_txn, WALLET_INCOME_TABLE)?;
+        copy_table(&read_txn, &write_txn, AUDIT_LOG_TABLE)?;
+        copy_table(&read_txn, &write_txn, TOKEN_EVENTS_TABLE)?;
+        copy_table(&read_txn, &write_txn, SEEN_MINTS_TABLE)?;
+        copy_table(&read_txn, &write_txn, ALERTS_TABLE)?;
+        copy_table(&read_txn, &write_txn, ALERT_SNOOZES_TABLE)?;
+        copy_table(&read_txn, &write_txn, DAILY_REPORTS_TABLE)?;
+        copy_table(&read_txn, &write_txn, SOL_PRICES_TABLE)?;
+        copy_table(&read_txn, &write_txn, FX_RATES_TABLE)?;
+        copy_table(&read_txn, &write_txn, EPOCHS_TABLE)?;
+        copy_table(&read_txn, &write_txn, STAKE_REWARDS_TABLE)?;
+        copy_table(&read_txn, &write_txn, TOKEN_AMOUNTS_TABLE)?;
+        copy_table(&read_txn, &write_txn, COST_BASIS_TABLE)?;
+        write_txn.commit()?;
+    }
+    // 寫完才改名，避免列出未完成的備份
+    fs::rename(&temp_path, &path)?;
+    finish_backup(config, file, created_at)
+}
+
+// 備份完成後清理超出保留數量的舊備份
+fn finish_backup(config: &BackupConfig, file: String, created_at: DateTime<Utc>) -> Result<BackupInfo, Box<dyn std::error::Error>> {
+    if config.keep > 0 {
+        for old in list_backups(&config.dir)?.into_iter().skip(config.keep) {
+            if let Err(e) = fs::remove_file(std::path::Path::new(&config.dir).join(&old.file)) {
+                warn!("⚠️ 刪除舊備份 {} 失敗: {}", old.file, e);
+            }
+        }
+    }
+
+    let path = std::path::Path::new(&config.dir).join(&file);
+    let size = fs::metadata(&path)?.len();
+    info!("💾 資料庫已備份到 {} ({} bytes)", path.display(), size);
+    Ok(BackupInfo { file, size, created_at })
+}
+
+// 將來源表格中目標尚不存在的鍵合併進目標表格，返回 (匯入, 略過) 數量
+fn merge_table<K: redb::Key + 'static, V: redb::Value + 'static>(
+    read_txn: &redb::ReadTransaction,
+    write_txn: &redb::WriteTransaction,
+    definition: TableDefinition<K, V>,
+) -> Result<(usize, usize), Box<dyn std::error::Error>> {
+    let source = match read_txn.open_table(definition) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok((0, 0)),
+        Err(e) => return Err(e.into()),
+    };
+    let mut target = write_txn.open_table(definition)?;
+    let (mut imported, mut skipped) = (0, 0);
+    for entry in source.iter()? {
+        let (key, value) = entry?;
+        if target.get(key.value())?.is_some() {
+            skipped += 1;
+        } else {
+            target.insert(key.value(), value.value())?;
+            imported += 1;
+        }
+    }
+    Ok((imported, skipped))
+}
+
+// 合併原始歷史表格，並記錄有新增記錄的地址
+fn merge_history_table(
+    read_txn: &redb::ReadTransaction,
+    write_txn: &redb::WriteTransaction,
+    touched: &mut BTreeSet<String>,
+) -> Result<(usize, usize), Box<dyn std::error::Error>> {
+    let source = match read_txn.open_table(WALLET_HISTORY_TABLE) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok((0, 0)),
+        Err(e) => return Err(e.into()),
+    };
+    let mut target = write_txn.open_table(WALLET_HISTORY_TABLE)?;
+    let (mut imported, mut skipped) = (0, 0);
+    for entry in source.iter()? {
+        let (key, value) = entry?;
+        if target.get(key.value())?.is_some() {
+            skipped += 1;
+        } else {
+            target.insert(key.value(), value.value())?;
+            touched.insert(key.value().0.to_string());
+            imported += 1;
+        }
+    }
+    Ok((imported, skipped))
+}
+
+// 更新有匯入記錄的錢包的最新快照；匯入的資料比現有快照舊時保留現有快照
+fn refresh_latest_records(write_txn: &redb::WriteTransaction, addresses: &BTreeSet<String>) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let history = write_txn.open_table(WALLET_HISTORY_TABLE)?;
+    let mut latest_table = write_txn.open_table(WALLET_LATEST_TABLE)?;
+    for address in addresses {
+        let Some(entry) = history.range((address.as_str(), 0)..=(address.as_str(), u64::MAX))?.next_back() else {
+            continue;
+        };
+        let (key, value) = entry?;
+        let newer = match latest_table.get(address.as_str())? {
+            Some(current) => bincode::deserialize::<WalletHistoryRecord>(current.value())
+                .map_or(true, |current| key.value().1 > history_key_millis(current.timestamp)),
+            None => true,
+        };
+        if newer {
+            latest_table.insert(address.as_str(), value.value())?;
+        }
+    }
+    Ok(addresses.iter().cloned().collect())
+}
+
+// 從備份檔合併歷史與交易記錄 (以鍵去重，保留現有資料)
+fn restore_from_backup(db: &Database, path: &std::path::Path) -> Result<(RestoreResponse, Vec<String>), Box<dyn std::error::Error>> {
+    let backup = Database::open(path)?;
+    let read_txn = backup.begin_read()?;
+    let write_txn = db.begin_write()?;
+    let mut response = RestoreResponse::default();
+    let mut touched = BTreeSet::new();
+    for (definition, aggregated) in HISTORY_TIERS {
+        let (imported, skipped) = if aggregated {
+            merge_table(&read_txn, &write_txn, definition)?
+        } else {
+            merge_history_table(&read_txn, &write_txn, &mut touched)?
+        };
+        response.imported += imported;
+        response.skipped += skipped;
+    }
+    merge_table(&read_txn, &write_txn, WALLET_TRANSACTIONS_TABLE)?;
+    merge_table(&read_txn, &write_txn, WALLET_FEES_TABLE)?;
+    merge_table(&read_txn, &write_txn, WALLET_INCOME_TABLE)?;
+    merge_table(&read_txn, &write_txn, TOKEN_EVENTS_TABLE)?;
+    merge_table(&read_txn, &write_txn, SEEN_MINTS_TABLE)?;
+    merge_table(&read_txn, &write_txn, DAILY_REPORTS_TABLE)?;
+    merge_table(&read_txn, &write_txn, SOL_PRICES_TABLE)?;
+    merge_table(&read_txn, &write_txn, FX_RATES_TABLE)?;
+    merge_table(&read_txn, &write_txn, EPOCHS_TABLE)?;
+    merge_table(&read_txn, &write_txn, STAKE_REWARDS_TABLE)?;
+    merge_table(&read_txn, &write_txn, TOKEN_AMOUNTS_TABLE)?;
+    merge_table(&read_txn, &write_txn, COST_BASIS_TABLE)?;
+    let addresses = refresh_latest_records(&write_txn, &touched)?;
+    write_txn.commit()?;
+    Ok((response, addresses))
+}
+
+// 匯入歷史記錄，依 (地址, 時間戳) 合併
+fn import_history_records(db: &Database, records: &[WalletHistoryRecord]) -> Result<(RestoreResponse, Vec<String>), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    let mut response = RestoreResponse::default();
+    let mut touched = BTreeSet::new();
+    {
+        let mut table = write_txn.open_table(WALLET_HISTORY_TABLE)?;
+        for record in records {
+            let key = (record.address.as_str(), history_key_millis(record.timestamp));
+            if table.get(key)?.is_some() {
+                response.skipped += 1;
+                continue;
+            }
+            let value = bincode::serialize(record)?;
+            table.insert(key, value.as_slice())?;
+            touched.insert(record.address.clone());
+            response.imported += 1;
+        }
+    }
+    let addresses = refresh_latest_records(&write_txn, &touched)?;
+    write_txn.commit()?;
+    Ok((response, addresses))
+}
+
+// 列出備份目錄中的備份檔案 (最新的在前)
+fn list_backups(dir: &str) -> Result<Vec<BackupInfo>, Box<dyn std::error::Error>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut backups = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let file = entry.file_name().to_string_lossy().to_string();
+        if !file.starts_with("wallet_history-") || !(file.ends_with(".redb") || file.ends_with(".sqlite")) {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        let created_at = metadata.modified().map(DateTime::<Utc>::from).unwrap_or_else(|_| Utc::now());
+        backups.push(BackupInfo { file, size: metadata.len(), created_at });
+    }
+    // 檔名包含時間戳，依檔名排序即為時間順序
+    backups.sort_by(|a, b| b.file.cmp(&a.file));
+    Ok(backups)
+}
+
+// 定期備份資料庫
+async fn backup_task(db: SharedDatabase, config: BackupConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_hours * 3600));
+    interval.tick().await; // 啟動時不立即備份
+
+    loop {
+        interval.tick().await;
+        let db = db.clone();
+        let config = config.clone();
+        match tokio::task::spawn_blocking(move || db.backup(&config).map_err(|e| e.to_string())).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => error!("❌ 定期備份資料庫失敗: {}", e),
+            Err(e) => error!("❌ 定期備份任務異常: {}", e),
+        }
+    }
+}
+
+// 依資料庫設定刪除過舊或超出數量上限的歷史記錄，返回刪除的記錄數
+fn prune_history(db: &Database, config: &DatabaseConfig) -> Result<usize, Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    let mut removed = 0usize;
+
+    if config.retention_days > 0 {
+        let cutoff = history_key_millis(Utc::now()).saturating_sub(config.retention_days * DAY_MS);
+        for (definition, _) in HISTORY_TIERS {
+            let mut table = write_txn.open_table(definition)?;
+            let before = table.len()?;
+            table.retain(|(_, time), _| time >= cutoff)?;
+            removed += (before - table.len()?) as usize;
+        }
+    }
+
+    if config.max_history_per_wallet > 0 {
+        let mut table = write_txn.open_table(WALLET_HISTORY_TABLE)?;
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for entry in table.iter()? {
+            let (key, _) = entry?;
+            *counts.entry(key.value().0.to_string()).or_default() += 1;
+        }
+
+        for (address, count) in counts {
+            let excess = count.saturating_sub(config.max_history_per_wallet);
+            if excess == 0 {
+                continue;
+            }
+            // 第 excess 筆之後的記錄保留，之前的刪除
+            let keep_from = match table.range((address.as_str(), 0)..=(address.as_str(), u64::MAX))?.nth(excess) {
+                Some(entry) => entry?.0.value().1,
+                None => continue,
+            };
+            table.retain_in((address.as_str(), 0)..(address.as_str(), keep_from), |_, _| false)?;
+            removed += excess;
+        }
+    }
+
+    write_txn.commit()?;
+    Ok(removed)
+}
+
+// 定期依資料庫設定清理歷史記錄
+async fn history_pruning_task(db: SharedDatabase, config: DatabaseConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.prune_interval_secs.max(60)));
+
+    loop {
+        interval.tick().await;
+        let db = db.clone();
+        let config = config.clone();
+        let result = tokio::task::spawn_blocking(move || db.prune(&config).map_err(|e| e.to_string())).await;
+        match result {
+            Ok(Ok(0)) => debug!("🧹 沒有需要清理的歷史記錄"),
+            Ok(Ok(removed)) => info!("🧹 已清理 {} 條過舊的歷史記錄", removed),
+            Ok(Err(e)) => warn!("⚠️ 清理歷史記錄失敗: {}", e),
+            Err(e) => warn!("⚠️ 清理歷史記錄任務異常: {}", e),
+        }
+    }
+}
+
+// 在同一個寫入交易中保存多筆歷史與交易記錄
+fn write_batch(
+    db: &Database,
+    history: &[WalletHistoryRecord],
+    transactions: &[WalletTransactionRecord],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    if !history.is_empty() {
+        let mut table = write_txn.open_table(WALLET_HISTORY_TABLE)?;
+        let mut latest = write_txn.open_table(WALLET_LATEST_TABLE)?;
+        for record in history {
+            let value = bincode::serialize(record)?;
+            table.insert((record.address.as_str(), history_key_millis(record.timestamp)), value.as_slice())?;
+            latest.insert(record.address.as_str(), value.as_slice())?;
+        }
+    }
+    if !transactions.is_empty() {
+        let mut table = write_txn.open_table(WALLET_TRANSACTIONS_TABLE)?;
+        let mut fees = write_txn.open_table(WALLET_FEES_TABLE)?;
+        let mut income = write_txn.open_table(WALLET_INCOME_TABLE)?;
+        for record in transactions {
+            // slot 補零確保同一錢包的交易按時間排序
+            let key = format!("{}_{:020}_{}", record.address, record.slot, record.signature);
+            let value = serde_json::to_string(record)?;
+            // 重新連線後重複收到的交易不重複累計手續費與收入
+            let existed = table.insert(key.as_str(), value.as_str())?.is_some();
+            if !existed && record.fee > 0.0 {
+                let fee_key = (record.address.as_str(), fee_day_millis(record.timestamp));
+                let (base, priority, count) = fees.get(fee_key)?.map(|v| v.value()).unwrap_or_default();
+                let (fee_base, fee_priority) = fee_lamports(record);
+                fees.insert(fee_key, (base + fee_base, priority + fee_priority, count + 1))?;
+            }
+            if let Some(tips) = jito_tip_lamports(record).filter(|_| !existed) {
+                let income_key = (record.address.as_str(), fee_day_millis(record.timestamp));
+                let (jito, claims, block_rewards, blocks) = income.get(income_key)?.map(|v| v.value()).unwrap_or_default();
+                income.insert(income_key, (jito + tips, claims + 1, block_rewards, blocks))?;
+            }
+        }
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+enum DatabaseWriteCommand {
+    History(WalletHistoryRecord),
+    Transaction(WalletTransactionRecord),
+    BlockReward(BlockRewardRecord),
+    TokenEvent(TokenAccountEvent),
+    SeenMints(String, Vec<String>),
+    Alert(AlertRecord),
+    AlertState(u64, AlertState),
+    AlertSnooze(String, String, DateTime<Utc>),
+    TokenAmount(TokenAmountRecord),
+    Flush(tokio::sync::oneshot::Sender<()>),
+    Shutdown,
+}
+
+// 背景批次寫入：gRPC 更新只把記錄送進佇列，由專用執行緒合併成單一交易提交
+#[derive(Clone)]
+struct DatabaseWriter {
+    sender: std::sync::mpsc::Sender<DatabaseWriteCommand>,
+    exporter: Option<HistoryExporter>,
+    thread: Arc<Mutex<Option<std::thread::JoinHandle<()>>>>,
+}
+
+impl DatabaseWriter {
+    fn start(db: SharedDatabase, exporter: Option<HistoryExporter>) -> Self {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("db-writer".to_string())
+            .spawn(move || database_writer_loop(db.as_ref(), receiver))
+            .expect("無法啟動資料庫寫入執行緒");
+        Self { sender, exporter, thread: Arc::new(Mutex::new(Some(thread))) }
+    }
+
+    fn save_history(&self, record: WalletHistoryRecord) {
+        if let Some(exporter) = &self.exporter {
+            exporter.export(&record);
+        }
+        if self.sender.send(DatabaseWriteCommand::History(record)).is_err() {
+            warn!("⚠️ 資料庫寫入執行緒已停止，歷史記錄未保存");
+        }
+    }
+
+    fn save_transaction(&self, record: WalletTransactionRecord) {
+        if self.sender.send(DatabaseWriteCommand::Transaction(record)).is_err() {
+            warn!("⚠️ 資料庫寫入執行緒已停止，交易記錄未保存");
+        }
+    }
+
+    fn save_block_reward(&self, record: BlockRewardRecord) {
+        if self.sender.send(DatabaseWriteCommand::BlockReward(record)).is_err() {
+            warn!("⚠️ 資料庫寫入執行緒已停止，出塊獎勵未保存");
+        }
+    }
+
+    fn save_token_event(&self, event: TokenAccountEvent) {
+        if self.sender.send(DatabaseWriteCommand::TokenEvent(event)).is_err() {
+            warn!("⚠️ 資料庫寫入執行緒已停止，token account 事件未保存");
+        }
+    }
+
+    fn save_seen_mints(&self, address: &str, mints: Vec<String>) {
+        if self.sender.send(DatabaseWriteCommand::SeenMints(address.to_string(), mints)).is_err() {
+            warn!("⚠️ 資料庫寫入執行緒已停止，已見過的 mint 未保存");
+        }
+    }
+
+    fn save_alert(&self, record: AlertRecord) {
+        if self.sender.send(DatabaseWriteCommand::Alert(record)).is_err() {
+            warn!("⚠️ 資料庫寫入執行緒已停止，告警記錄未保存");
+        }
+    }
+
+    fn set_alert_state(&self, id: u64, state: AlertState) {
+        if self.sender.send(DatabaseWriteCommand::AlertState(id, state)).is_err() {
+            warn!("⚠️ 資料庫寫入執行緒已停止，告警 {} 狀態未更新", id);
+        }
+    }
+
+    fn save_alert_snooze(&self, rule: &str, address: &str, until: DateTime<Utc>) {
+        if self.sender.send(DatabaseWriteCommand::AlertSnooze(rule.to_string(), address.to_string(), until)).is_err() {
+            warn!("⚠️ 資料庫寫入執行緒已停止，告警靜音未保存");
+        }
+    }
+
+    fn save_token_amount(&self, record: TokenAmountRecord) {
+        if self.sender.send(DatabaseWriteCommand::TokenAmount(record)).is_err() {
+            warn!("⚠️ 資料庫寫入執行緒已停止，代幣數量未保存");
+        }
+    }
+
+    // 等待目前佇列中的記錄全部寫入
+    async fn flush(&self) {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        if self.sender.send(DatabaseWriteCommand::Flush(tx)).is_ok() {
+            let _ = rx.await;
+        }
+        if let Some(exporter) = &self.exporter {
+            exporter.flush().await;
+        }
+    }
+
+    // 關閉時寫入剩餘記錄並等待寫入執行緒結束，避免程式退出時中斷進行中的交易
+    async fn shutdown(&self) {
+        self.flush().await;
+        let _ = self.sender.send(DatabaseWriteCommand::Shutdown);
+        let thread = self.thread.lock().unwrap().take();
+        if let Some(thread) = thread {
+            let _ = tokio::task::spawn_blocking(move || thread.join()).await;
+        }
+    }
+}
+
+fn database_writer_loop(db: &dyn HistoryStore, receiver: std::sync::mpsc::Receiver<DatabaseWriteCommand>) {
+    let mut history = Vec::new();
+    let mut transactions = Vec::new();
+    let mut block_rewards = Vec::new();
+    let mut waiters = Vec::new();
+    let mut deadline: Option<std::time::Instant> = None;
+    let mut shutdown_requested = false; // 收到 Shutdown 後寫入剩餘記錄再結束
+
+    loop {
+        let timeout = deadline
+            .map(|d| d.saturating_duration_since(std::time::Instant::now()))
+            .unwrap_or(Duration::from_secs(3600));
+        let stopping = match receiver.recv_timeout(timeout) {
+            Ok(command) => {
+                deadline.get_or_insert_with(|| std::time::Instant::now() + Duration::from_millis(WRITE_BATCH_INTERVAL_MS));
+                match command {
+                    DatabaseWriteCommand::History(record) => history.push(record),
+                    DatabaseWriteCommand::Transaction(record) => transactions.push(record),
+                    DatabaseWriteCommand::BlockReward(record) => block_rewards.push(record),
+                    // token account 事件很少發生，直接寫入不參與批次
+                    DatabaseWriteCommand::TokenEvent(event) => {
+                        if let Err(e) = db.append_token_event(&event) {
+                            error!("❌ 寫入 token account 事件失敗: {}", e);
+                        }
+                    }
+                    DatabaseWriteCommand::SeenMints(address, mints) => {
+                        if let Err(e) = db.save_seen_mints(&address, &mints) {
+                            error!("❌ 寫入錢包 {} 已見過的 mint 失敗: {}", address, e);
+                        }
+                    }
+                    // 告警在持有錢包鎖時觸發，交由寫入執行緒保存，同一佇列保證記錄先於狀態更新寫入
+                    DatabaseWriteCommand::Alert(record) => {
+                        if let Err(e) = db.insert_alert(&record) {
+                            error!("❌ 保存告警記錄失敗: {}", e);
+                        }
+                    }
+                    DatabaseWriteCommand::AlertState(id, state) => {
+                        if let Err(e) = db.set_alert_state(id, state) {
+                            error!("❌ 更新告警 {} 狀態失敗: {}", id, e);
+                        }
+                    }
+                    DatabaseWriteCommand::AlertSnooze(rule, address, until) => {
+                        if let Err(e) = db.save_alert_snooze(&rule, &address, until) {
+                            error!("❌ 保存告警靜音失敗: {}", e);
+                        }
+                    }
+                    DatabaseWriteCommand::TokenAmount(record) => {
+                        if let Err(e) = db.append_token_amount(&record) {
+                            error!("❌ 寫入代幣數量失敗: {}", e);
+                        }
+                    }
+                    DatabaseWriteCommand::Flush(waiter) => waiters.push(waiter),
+                    DatabaseWriteCommand::Shutdown => shutdown_requested = true,
+                }
+                shutdown_requested
+            }
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => false,
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => true,
+        };
+
+        let pending = history.len() + transactions.len();
+        let due = deadline.is_some_and(|d| std::time::Instant::now() >= d);
+        if stopping || pending >= WRITE_BATCH_SIZE || due || !waiters.is_empty() {
+            // 出塊獎勵為累加寫入，與歷史分開提交，避免歷史重試時重複累計
+            let mut retained = false;
+            if !block_rewards.is_empty() {
+                if retry_write("出塊獎勵", block_rewards.len(), || db.save_block_rewards(&block_rewards)) || stopping {
+                    block_rewards.clear();
+                } else {
+                    retained = true;
+                }
+            }
+            if pending > 0 {
+                if retry_write("歷史與交易記錄", pending, || db.write_batch(&history, &transactions)) {
+                    debug!("💾 批次寫入 {} 條歷史記錄、{} 筆交易", history.len(), transactions.len());
+                    history.clear();
+                    transactions.clear();
+                } else if !stopping {
+                    // 保留在記憶體中併入下一批再試，超過上限時捨棄最舊的記錄
+                    for records in [history.len(), transactions.len()] {
+                        if records > WRITE_RETRY_BUFFER_LIMIT {
+                            error!("❌ 待寫入的記錄超過 {} 筆，捨棄最舊的 {} 筆", WRITE_RETRY_BUFFER_LIMIT, records - WRITE_RETRY_BUFFER_LIMIT);
+                        }
+                    }
+                    history.drain(..history.len().saturating_sub(WRITE_RETRY_BUFFER_LIMIT));
+                    transactions.drain(..transactions.len().saturating_sub(WRITE_RETRY_BUFFER_LIMIT));
+                    retained = true;
+                }
+            }
+            deadline = retained.then(|| std::time::Instant::now() + Duration::from_millis(WRITE_BATCH_INTERVAL_MS));
+            for waiter in waiters.drain(..) {
+                let _ = waiter.send(());
+            }
+        }
+
+        if stopping {
+            break;
+        }
+    }
+}
+
+// 寫入失敗 (例如磁碟暫時已滿或檔案被鎖定) 時以加倍的間隔重試，等待期間新的記錄留在佇列中；
+// 每次寫入都是單一交易，失敗時整批回滾，重試不會重複寫入。全部失敗時返回 false
+fn retry_write(what: &str, count: usize, mut write: impl FnMut() -> Result<(), Box<dyn std::error::Error>>) -> bool {
+    let mut delay = Duration::from_millis(WRITE_RETRY_DELAY_MS);
+    for attempt in 1..=WRITE_BATCH_MAX_ATTEMPTS {
+        match write() {
+            Ok(()) => return true,
+            Err(e) if attempt < WRITE_BATCH_MAX_ATTEMPTS => {
+                warn!("⚠️ 寫入{}失敗 (第 {} 次)，{} 毫秒後重試: {}", what, attempt, delay.as_millis(), e);
+                std::thread::sleep(delay);
+                delay *= 2;
+            }
+            Err(e) => error!("❌ 寫入{}失敗 {} 次 ({} 筆記錄): {}", what, attempt, count, e),
+        }
+    }
+    false
+}
+
+// 讀取錢包在指定時間範圍內的歷史記錄
+fn load_wallet_history(
+    db: &Database,
+    address: &str,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    limit: usize,
+) -> Result<Vec<WalletHistoryRecord>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let table = read_txn.open_table(WALLET_HISTORY_TABLE)?;
+    let mut records = Vec::new();
+    
+    let start = from.map(history_key_millis).unwrap_or(0);
+    let end = to.map(history_key_millis).unwrap_or(u64::MAX);
+    // 只讀取需要的筆數，避免大範圍查詢將整段歷史載入記憶體
+    for entry in table.range((address, start)..=(address, end))?.take(limit) {
+        let (_, value) = entry?;
+        records.push(bincode::deserialize(value.value())?);
+    }
+    
+    Ok(records)
+}
+
+fn parse_history_value(value: &[u8], aggregated: bool) -> Result<BalanceHistory, bincode::Error> {
+    if aggregated {
+        bincode::deserialize::<HistoryAggregate>(value).map(BalanceHistory::from)
+    } else {
+        bincode::deserialize::<WalletHistoryRecord>(value).map(BalanceHistory::from)
+    }
+}
+
+// 讀取圖表用的歷史序列 (包含已降採樣的彙總表格)：除了範圍內的記錄，另外以 from
+// 之前最後一筆作為起點，避免範圍內沒有變化的錢包在圖表或合計中顯示為 0
+fn load_history_series(
+    db: &Database,
+    address: &str,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Vec<BalanceHistory>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let start = from.map(history_key_millis).unwrap_or(0);
+    let end = to.map(history_key_millis).unwrap_or(u64::MAX);
+
+    let mut series = Vec::new();
+    let mut before: Option<(u64, BalanceHistory)> = None;
+    for (definition, aggregated) in HISTORY_TIERS {
+        let table = read_txn.open_table(definition)?;
+        if from.is_some() {
+            if let Some(entry) = table.range((address, 0)..(address, start))?.next_back() {
+                let (key, value) = entry?;
+                let time = key.value().1;
+                if before.as_ref().is_none_or(|(t, _)| time > *t) {
+                    before = Some((time, parse_history_value(value.value(), aggregated)?));
+                }
+            }
+        }
+        for entry in table.range((address, start)..=(address, end))? {
+            let (_, value) = entry?;
+            series.push(parse_history_value(value.value(), aggregated)?);
+        }
+    }
+
+    // 起點放在最前面，排序後 from 當下的記錄仍排在起點之後
+    if let (Some(from), Some((_, mut first))) = (from, before) {
+        first.timestamp = from;
+        series.insert(0, first);
+    }
+    series.sort_by_key(|h| h.timestamp);
+    Ok(series)
+}
+
+// 讀取錢包最新一筆記錄；舊資料庫尚無快照時改從歷史表格取最後一筆
+fn load_latest_wallet_record(db: &Database, address: &str) -> Result<Option<WalletHistoryRecord>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let table = read_txn.open_table(WALLET_LATEST_TABLE)?;
+    if let Some(value) = table.get(address)? {
+        return Ok(Some(bincode::deserialize(value.value())?));
+    }
+    drop(table);
+    drop(read_txn);
+    Ok(load_recent_wallet_history(db, address, 1)?.pop())
+}
+
+// 錢包最早一筆歷史的時間，包含已降採樣到彙總表格的舊資料
+fn earliest_wallet_history(db: &Database, address: &str) -> Result<Option<DateTime<Utc>>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let mut earliest: Option<u64> = None;
+    for (definition, _) in HISTORY_TIERS {
+        let table = read_txn.open_table(definition)?;
+        if let Some(entry) = table.range((address, 0)..=(address, u64::MAX))?.next() {
+            let time = entry?.0.value().1;
+            earliest = Some(earliest.map_or(time, |t| t.min(time)));
+        }
+    }
+    Ok(earliest.and_then(|time| DateTime::from_timestamp_millis(time as i64)))
+}
+
+// 讀取錢包最近的 limit 筆歷史記錄 (按時間排序)
+fn load_recent_wallet_history(db: &Database, address: &str, limit: usize) -> Result<Vec<WalletHistoryRecord>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let table = read_txn.open_table(WALLET_HISTORY_TABLE)?;
+    let mut records = Vec::new();
+    for entry in table.range((address, 0)..=(address, u64::MAX))?.rev().take(limit) {
+        let (_, value) = entry?;
+        records.push(bincode::deserialize(value.value())?);
+    }
+    records.reverse();
+    Ok(records)
+}
+
+fn delete_wallet_history(db: &Database, address: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    {
+        for (definition, _) in HISTORY_TIERS {
+            let mut table = write_txn.open_table(definition)?;
+            table.retain_in((address, 0)..=(address, u64::MAX), |_, _| false)?;
+        }
+        write_txn.open_table(WALLET_LATEST_TABLE)?.remove(address)?;
+    }
+    write_txn.commit()?;
+    info!("🗑️ 已刪除錢包 {} 的歷史數據", address);
+    Ok(())
+}
+
+// 刪除錢包在 [after, before) 區間內的歷史記錄 (含彙總)，保留錢包與交易記錄
+fn delete_history_range(
+    db: &Database,
+    address: &str,
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let start = after.map(history_key_millis).unwrap_or(0);
+    let end = before.map(history_key_millis).unwrap_or(u64::MAX);
+    let write_txn = db.begin_write()?;
+    let mut removed = 0usize;
+    {
+        for (definition, _) in HISTORY_TIERS {
+            let mut table = write_txn.open_table(definition)?;
+            table.retain_in((address, start)..(address, end), |_, _| {
+                removed += 1;
+                false
+            })?;
+        }
+
+        // 最新快照可能已被刪除，改用剩下的最後一筆
+        let history = write_txn.open_table(WALLET_HISTORY_TABLE)?;
+        let last = history.range((address, 0)..=(address, u64::MAX))?.next_back().transpose()?.map(|(_, value)| value.value().to_vec());
+        let mut latest = write_txn.open_table(WALLET_LATEST_TABLE)?;
+        match last {
+            Some(value) => latest.insert(address, value.as_slice())?,
+            None => latest.remove(address)?,
+        };
+    }
+    write_txn.commit()?;
+    info!("🗑️ 已刪除錢包 {} 的 {} 條歷史記錄", address, removed);
+    Ok(removed)
+}
+
+// 將 source 表格中早於 cutoff 的記錄彙總到 target 表格的 bucket_ms 區間，並刪除原記錄
+fn roll_up_history(
+    db: &Database,
+    source: HistoryTable,
+    source_aggregated: bool,
+    target: HistoryTable,
+    bucket_ms: u64,
+    cutoff: u64,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut rolled = 0usize;
+    // 分批提交，每批從上一批最後處理的鍵之後繼續掃描
+    let mut resume: Option<(String, u64)> = None;
+    loop {
+        let write_txn = db.begin_write()?;
+        let mut processed: Vec<(String, u64)> = Vec::new();
+        {
+            let mut source_table = write_txn.open_table(source)?;
+            // 鍵依 (地址, 時間) 排序，同一區間內的記錄按時間先後合併
+            let mut buckets: BTreeMap<(String, u64), HistoryAggregate> = BTreeMap::new();
+            let lower = resume.as_ref().map(|(address, time)| (address.as_str(), *time)).unwrap_or(("", 0));
+            for entry in source_table.range(lower..)? {
+                let (key, value) = entry?;
+                let (address, time) = key.value();
+                if time >= cutoff {
+                    continue;
+                }
+                let mut aggregate: HistoryAggregate = if source_aggregated {
+                    bincode::deserialize(value.value())?
+                } else {
+                    bincode::deserialize::<WalletHistoryRecord>(value.value())?.into()
+                };
+                let bucket = time - time % bucket_ms;
+                aggregate.timestamp = DateTime::from_timestamp_millis(bucket as i64).unwrap_or(aggregate.timestamp);
+                match buckets.get_mut(&(address.to_string(), bucket)) {
+                    Some(existing) => existing.merge(&aggregate),
+                    None => {
+                        buckets.insert((address.to_string(), bucket), aggregate);
+                    }
+                }
+                processed.push((address.to_string(), time));
+                if processed.len() >= COMPACTION_CHUNK_SIZE {
+                    break;
+                }
+            }
+            if processed.is_empty() {
+                return Ok(rolled);
+            }
+
+            let mut target_table = write_txn.open_table(target)?;
+            for ((address, bucket), aggregate) in buckets {
+                // 目標區間已有較早的彙總時 (包含上一批)，接續合併
+                let existing: Option<HistoryAggregate> = match target_table.get((address.as_str(), bucket))? {
+                    Some(value) => Some(bincode::deserialize(value.value())?),
+                    None => None,
+                };
+                let merged = match existing {
+                    Some(mut existing) => {
+                        existing.merge(&aggregate);
+                        existing
+                    }
+                    None => aggregate,
+                };
+                let value = bincode::serialize(&merged)?;
+                target_table.insert((address.as_str(), bucket), value.as_slice())?;
+            }
+            for (address, time) in &processed {
+                source_table.remove((address.as_str(), *time))?;
+            }
+        }
+        write_txn.commit()?;
+        rolled += processed.len();
+        if processed.len() < COMPACTION_CHUNK_SIZE {
+            return Ok(rolled);
+        }
+        resume = processed.pop();
+    }
+}
+
+// 計算保留期限，並對齊到下一層的區間起點，避免區間被拆成兩次彙總
+fn retention_cutoff(now_ms: u64, days: u64, align_ms: u64) -> u64 {
+    let cutoff = now_ms.saturating_sub(days * DAY_MS);
+    cutoff - cutoff % align_ms
+}
+
+// 依保留策略降採樣歷史資料，返回處理的記錄數
+fn compact_history(db: &Database, retention: &RetentionConfig) -> Result<usize, Box<dyn std::error::Error>> {
+    let now = history_key_millis(Utc::now());
+    let mut processed = 0;
+
+    if retention.raw_days > 0 {
+        let cutoff = retention_cutoff(now, retention.raw_days, MINUTE_MS);
+        processed += roll_up_history(db, WALLET_HISTORY_TABLE, false, WALLET_HISTORY_1M_TABLE, MINUTE_MS, cutoff)?;
+    }
+    if retention.minute_days > 0 {
+        let cutoff = retention_cutoff(now, retention.minute_days, HOUR_MS);
+        processed += roll_up_history(db, WALLET_HISTORY_1M_TABLE, true, WALLET_HISTORY_1H_TABLE, HOUR_MS, cutoff)?;
+    }
+    if retention.hour_days > 0 {
+        let cutoff = retention_cutoff(now, retention.hour_days, DAY_MS);
+        processed += roll_up_history(db, WALLET_HISTORY_1H_TABLE, true, WALLET_HISTORY_1D_TABLE, DAY_MS, cutoff)?;
+    }
+    if retention.day_days > 0 {
+        let cutoff = retention_cutoff(now, retention.day_days, DAY_MS);
+        // 同樣分批刪除過期的日彙總
+        loop {
+            let write_txn = db.begin_write()?;
+            let expired = {
+                let mut table = write_txn.open_table(WALLET_HISTORY_1D_TABLE)?;
+                let mut expired: Vec<(String, u64)> = Vec::new();
+                for entry in table.iter()? {
+                    let (key, _) = entry?;
+                    let (address, time) = key.value();
+                    if time < cutoff {
+                        expired.push((address.to_string(), time));
+                        if expired.len() >= COMPACTION_CHUNK_SIZE {
+                            break;
+                        }
+                    }
+                }
+                for (address, time) in &expired {
+                    table.remove((address.as_str(), *time))?;
+                }
+                expired.len()
+            };
+            write_txn.commit()?;
+            processed += expired;
+            if expired < COMPACTION_CHUNK_SIZE {
+                break;
+            }
+        }
+    }
+
+    Ok(processed)
+}
+
+// 定期執行歷史資料降採樣
+async fn history_compaction_task(db: SharedDatabase, retention: RetentionConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(retention.interval_secs.max(60)));
+
+    loop {
+        interval.tick().await;
+        let db = db.clone();
+        let retention = retention.clone();
+        let result = tokio::task::spawn_blocking(move || db.compact(&retention).map_err(|e| e.to_string())).await;
+        match result {
+            Ok(Ok(0)) => debug!("🗜️ 歷史資料無需壓縮"),
+            Ok(Ok(processed)) => info!("🗜️ 歷史資料壓縮完成，處理了 {} 條記錄", processed),
+            Ok(Err(e)) => warn!("⚠️ 歷史資料壓縮失敗: {}", e),
+            Err(e) => warn!("⚠️ 歷史資料壓縮任務異常: {}", e),
+        }
+    }
+}
+
+// 全域或任一錢包設定了 min_interval_ms 時需要 history_flush_task
+fn history_coalescing(config: &Config) -> bool {
+    std::iter::once(config.history)
+        .chain(config.wallets.iter().filter_map(|w| w.history))
+        .any(|h| h.min_interval_ms > 0)
+}
+
+// 補記因 [history].min_interval_ms 合併而暫存的最後狀態，避免變化停止後遺漏最終餘額
+async fn history_flush_task(wallets: SharedWallets, writer: DatabaseWriter) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+
+    loop {
+        interval.tick().await;
+        let mut wallets_guard = wallets.write().await;
+        for wallet in wallets_guard.values_mut().filter(|w| w.history_pending) {
+            if wallet.flush_pending_history() {
+                writer.save_history(wallet.history_record());
+            }
+        }
+    }
+}
+
+// 分頁讀取錢包交易 (最新的在前)，返回 (總數, 當頁記錄)
+fn load_wallet_transactions(
+    db: &Database,
+    address: &str,
+    page: usize,
+    page_size: usize,
+) -> Result<(usize, Vec<WalletTransactionRecord>), Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let table = match read_txn.open_table(WALLET_TRANSACTIONS_TABLE) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok((0, Vec::new())),
+        Err(e) => return Err(e.into()),
+    };
+
+    // '_' 的下一個字元是 '`'，作為前綴範圍的結束
+    let start = format!("{}_", address);
+    let end = format!("{}`", address);
+    let total = table.range(start.as_str()..end.as_str())?.count();
+
+    let mut records = Vec::new();
+    for entry in table.range(start.as_str()..end.as_str())?.rev().skip(page.saturating_sub(1) * page_size).take(page_size) {
+        let (_, value) = entry?;
+        records.push(serde_json::from_str(value.value())?);
+    }
+
+    Ok((total, records))
+}
+
+fn delete_wallet_transactions(db: &Database, address: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(WALLET_TRANSACTIONS_TABLE)?;
+        let start = format!("{}_", address);
+        let end = format!("{}`", address);
+        table.retain_in(start.as_str()..end.as_str(), |_, _| false)?;
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+// 新增一筆稽核記錄，id 接續目前最大值
+fn append_audit_entry(db: &Database, entry: &AuditEntry) -> Result<(), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(AUDIT_LOG_TABLE)?;
+        let id = table.last()?.map(|(key, _)| key.value() + 1).unwrap_or(1);
+        let value = serde_json::to_string(&AuditEntry { id, ..entry.clone() })?;
+        table.insert(id, value.as_str())?;
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+fn append_token_event(db: &Database, event: &TokenAccountEvent) -> Result<(), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(TOKEN_EVENTS_TABLE)?;
+        let key = format!("{}_{:020}_{}", event.address, history_key_millis(event.timestamp), event.token_account);
+        let value = serde_json::to_string(event)?;
+        table.insert(key.as_str(), value.as_str())?;
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+// 讀取錢包最近的 token account 事件 (最新的在前)
+fn load_token_events(db: &Database, address: &str, limit: usize) -> Result<Vec<TokenAccountEvent>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let table = match read_txn.open_table(TOKEN_EVENTS_TABLE) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let start = format!("{}_", address);
+    let end = format!("{}`", address);
+    let mut events = Vec::new();
+    for entry in table.range(start.as_str()..end.as_str())?.rev().take(limit) {
+        let (_, value) = entry?;
+        events.push(serde_json::from_str(value.value())?);
+    }
+    Ok(events)
+}
+
+// 記錄錢包持有過的 mint，已存在的保留首次看到的時間
+fn save_seen_mints(db: &Database, address: &str, mints: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let now = Utc::now().timestamp_millis() as u64;
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(SEEN_MINTS_TABLE)?;
+        for mint in mints {
+            let key = format!("{}_{}", address, mint);
+            if table.get(key.as_str())?.is_none() {
+                table.insert(key.as_str(), now)?;
+            }
+        }
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+fn load_seen_mints(db: &Database, address: &str) -> Result<std::collections::HashSet<String>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let table = match read_txn.open_table(SEEN_MINTS_TABLE) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(std::collections::HashSet::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let start = format!("{}_", address);
+    let end = format!("{}`", address);
+    let mut mints = std::collections::HashSet::new();
+    for entry in table.range(start.as_str()..end.as_str())? {
+        let (key, _) = entry?;
+        mints.insert(key.value()[start.len()..].to_string());
+    }
+    Ok(mints)
+}
+
+fn delete_seen_mints(db: &Database, address: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(SEEN_MINTS_TABLE)?;
+        let start = format!("{}_", address);
+        let end = format!("{}`", address);
+        table.retain_in(start.as_str()..end.as_str(), |_, _| false)?;
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+// 寫入一筆告警記錄，id 由 Notifier 在觸發時分配
+fn insert_alert(db: &Database, record: &AlertRecord) -> Result<(), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(ALERTS_TABLE)?;
+        let value = serde_json::to_string(record)?;
+        table.insert(record.id, value.as_str())?;
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+// 記錄告警靜音結束時間，並順便清除已過期的靜音
+fn save_alert_snooze(db: &Database, rule: &str, address: &str, until: DateTime<Utc>) -> Result<(), Box<dyn std::error::Error>> {
+    let now = Utc::now().timestamp_millis() as u64;
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(ALERT_SNOOZES_TABLE)?;
+        table.retain(|_, until| until > now)?;
+        table.insert((rule, address), until.timestamp_millis() as u64)?;
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+// 讀取尚未結束的告警靜音
+fn load_alert_snoozes(db: &Database) -> Result<Vec<(String, String, DateTime<Utc>)>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let table = match read_txn.open_table(ALERT_SNOOZES_TABLE) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let now = Utc::now();
+    let mut snoozes = Vec::new();
+    for entry in table.iter()? {
+        let (key, value) = entry?;
+        let (rule, address) = key.value();
+        let until = DateTime::from_timestamp_millis(value.value() as i64).unwrap_or_default();
+        if until > now {
+            snoozes.push((rule.to_string(), address.to_string(), until));
+        }
+    }
+    Ok(snoozes)
+}
+
+fn set_alert_state(db: &Database, id: u64, state: AlertState) -> Result<Option<AlertRecord>, Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    let record = {
+        let mut table = write_txn.open_table(ALERTS_TABLE)?;
+        let record = match table.get(id)? {
+            Some(value) => Some(serde_json::from_str::<AlertRecord>(value.value())?),
+            None => None,
+        };
+        let Some(mut record) = record else {
+            return Ok(None);
+        };
+        record.transition(state);
+        let value = serde_json::to_string(&record)?;
+        table.insert(id, value.as_str())?;
+        record
+    };
+    write_txn.commit()?;
+    Ok(Some(record))
+}
+
+fn save_daily_report(db: &Database, report: &DailyReport) -> Result<(), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(DAILY_REPORTS_TABLE)?;
+        let key = report.date.to_string();
+        let value = serde_json::to_string(report)?;
+        table.insert(key.as_str(), value.as_str())?;
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+fn append_price(db: &Database, point: &PricePoint) -> Result<(), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(SOL_PRICES_TABLE)?;
+        table.insert(history_key_millis(point.timestamp), point.usd)?;
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+// 讀取區間內的價格，與歷史序列一致以 from 之前的最後一筆作為起點
+fn load_prices(db: &Database, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<PricePoint>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let table = match read_txn.open_table(SOL_PRICES_TABLE) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let start = from.map(history_key_millis).unwrap_or(0);
+    let end = to.map(history_key_millis).unwrap_or(u64::MAX);
+    let mut prices = Vec::new();
+    if start > 0 {
+        if let Some(entry) = table.range(..start)?.next_back() {
+            let (_, value) = entry?;
+            prices.push(PricePoint { timestamp: from.unwrap_or_default(), usd: value.value() });
+        }
+    }
+    for entry in table.range(start..=end)? {
+        let (key, value) = entry?;
+        prices.push(PricePoint { timestamp: DateTime::from_timestamp_millis(key.value() as i64).unwrap_or_default(), usd: value.value() });
+    }
+    Ok(prices)
+}
+
+// 最後一筆價格，保留實際的取得時間
+fn latest_price(db: &Database) -> Result<Option<PricePoint>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let table = match read_txn.open_table(SOL_PRICES_TABLE) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(table.last()?.map(|(key, value)| PricePoint {
+        timestamp: DateTime::from_timestamp_millis(key.value() as i64).unwrap_or_default(),
+        usd: value.value(),
+    }))
+}
+
+// before 之前的價格每小時只保留第一筆，返回刪除的筆數
+fn prune_prices(db: &Database, before: DateTime<Utc>) -> Result<usize, Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    let removed = {
+        let mut table = write_txn.open_table(SOL_PRICES_TABLE)?;
+        let mut stale = Vec::new();
+        let mut hour = None;
+        for entry in table.range(..history_key_millis(before))? {
+            let (key, _) = entry?;
+            let key = key.value();
+            if hour == Some(key / HOUR_MS) {
+                stale.push(key);
+            }
+            hour = Some(key / HOUR_MS);
+        }
+        for key in &stale {
+            table.remove(*key)?;
+        }
+        stale.len()
+    };
+    write_txn.commit()?;
+    Ok(removed)
+}
+
+fn append_fx_rates(db: &Database, timestamp: DateTime<Utc>, rates: &[(String, f64)]) -> Result<(), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(FX_RATES_TABLE)?;
+        for (currency, rate) in rates {
+            table.insert((currency.as_str(), history_key_millis(timestamp)), *rate)?;
+        }
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+// 與 load_prices 相同，以 from 之前的最後一筆匯率作為起點
+fn load_fx_rates(
+    db: &Database,
+    currency: &str,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Vec<FxRate>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let table = match read_txn.open_table(FX_RATES_TABLE) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let start = from.map(history_key_millis).unwrap_or(0);
+    let end = to.map(history_key_millis).unwrap_or(u64::MAX);
+    let mut rates = Vec::new();
+    if start > 0 {
+        if let Some(entry) = table.range((currency, 0)..(currency, start))?.next_back() {
+            let (_, value) = entry?;
+            rates.push(FxRate { timestamp: from.unwrap_or_default(), rate: value.value() });
+        }
+    }
+    for entry in table.range((currency, start)..=(currency, end))? {
+        let (key, value) = entry?;
+        rates.push(FxRate { timestamp: DateTime::from_timestamp_millis(key.value().1 as i64).unwrap_or_default(), rate: value.value() });
+    }
+    Ok(rates)
+}
+
+fn save_cost_basis(db: &Database, address: &str, basis: Option<&CostBasis>) -> Result<(), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(COST_BASIS_TABLE)?;
+        match basis {
+            Some(basis) => {
+                let value = serde_json::to_string(basis)?;
+                table.insert(address, value.as_str())?;
+            }
+            None => {
+                table.remove(address)?;
+            }
+        }
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+fn load_cost_basis(db: &Database, address: &str) -> Result<Option<CostBasis>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let table = match read_txn.open_table(COST_BASIS_TABLE) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    match table.get(address)? {
+        Some(value) => Ok(Some(serde_json::from_str(value.value())?)),
+        None => Ok(None),
+    }
+}
+
+fn save_epoch(db: &Database, boundary: &EpochBoundary) -> Result<(), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(EPOCHS_TABLE)?;
+        table.insert(boundary.epoch, (boundary.start_slot, history_key_millis(boundary.started_at)))?;
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+fn load_epochs(db: &Database, epochs: std::ops::RangeInclusive<u64>) -> Result<Vec<EpochBoundary>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let table = match read_txn.open_table(EPOCHS_TABLE) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut boundaries = Vec::new();
+    for entry in table.range(epochs)? {
+        let (key, value) = entry?;
+        let (start_slot, started_ms) = value.value();
+        boundaries.push(EpochBoundary {
+            epoch: key.value(),
+            start_slot,
+            started_at: DateTime::from_timestamp_millis(started_ms as i64).unwrap_or_default(),
+        });
+    }
+    Ok(boundaries)
+}
+
+fn save_stake_reward(db: &Database, address: &str, reward: &StakeReward) -> Result<(), Box<dyn std::error::Error>> {
+    let value = serde_json::to_string(reward)?;
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(STAKE_REWARDS_TABLE)?;
+        table.insert((address, reward.epoch), value.as_str())?;
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+// 依入帳時間篩選，每年約 180 個 epoch，直接讀取錢包的全部記錄
+fn load_stake_rewards(
+    db: &Database,
+    address: &str,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Vec<StakeReward>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let table = match read_txn.open_table(STAKE_REWARDS_TABLE) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut rewards = Vec::new();
+    for entry in table.range((address, 0)..=(address, u64::MAX))? {
+        let (_, value) = entry?;
+        let reward: StakeReward = serde_json::from_str(value.value())?;
+        if from.is_none_or(|from| reward.credited_at >= from) && to.is_none_or(|to| reward.credited_at <= to) {
+            rewards.push(reward);
+        }
+    }
+    Ok(rewards)
+}
+
+fn delete_stake_rewards(db: &Database, address: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(STAKE_REWARDS_TABLE)?;
+        table.retain_in((address, 0)..=(address, u64::MAX), |_, _| false)?;
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+fn append_token_amount(db: &Database, record: &TokenAmountRecord) -> Result<(), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(TOKEN_AMOUNTS_TABLE)?;
+        table.insert((record.address.as_str(), history_key_millis(record.timestamp)), record.amount)?;
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+fn load_token_amounts(
+    db: &Database,
+    address: &str,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Vec<TokenAmountRecord>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let table = match read_txn.open_table(TOKEN_AMOUNTS_TABLE) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let start = from.map(history_key_millis).unwrap_or(0);
+    let end = to.map(history_key_millis).unwrap_or(u64::MAX);
+    let mut records = Vec::new();
+    for entry in table.range((address, start)..=(address, end))? {
+        let (key, value) = entry?;
+        records.push(TokenAmountRecord {
+            address: address.to_string(),
+            timestamp: DateTime::from_timestamp_millis(key.value().1 as i64).unwrap_or_default(),
+            amount: value.value(),
+        });
+    }
+    Ok(records)
+}
+
+fn delete_token_amounts(db: &Database, address: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(TOKEN_AMOUNTS_TABLE)?;
+        table.retain_in((address, 0)..=(address, u64::MAX), |_, _| false)?;
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+fn load_daily_report(db: &Database, date: chrono::NaiveDate) -> Result<Option<DailyReport>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let table = match read_txn.open_table(DAILY_REPORTS_TABLE) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let key = date.to_string();
+    match table.get(key.as_str())? {
+        Some(value) => Ok(Some(serde_json::from_str(value.value())?)),
+        None => Ok(None),
+    }
+}
+
+// 由新到舊讀取告警記錄，可依狀態篩選
+fn load_alerts(db: &Database, state: Option<AlertState>, before: Option<u64>, limit: usize) -> Result<Vec<AlertRecord>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let table = match read_txn.open_table(ALERTS_TABLE) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut alerts = Vec::new();
+    for entry in table.range(..before.unwrap_or(u64::MAX))?.rev() {
+        let (_, value) = entry?;
+        let alert: AlertRecord = serde_json::from_str(value.value())?;
+        if state.is_some_and(|state| state != alert.state) {
+            continue;
+        }
+        alerts.push(alert);
+        if alerts.len() >= limit {
+            break;
+        }
+    }
+    Ok(alerts)
+}
+
+// 交易記錄的手續費拆成 (基本費, 優先費)，單位 lamports
+fn fee_lamports(record: &WalletTransactionRecord) -> (u64, u64) {
+    let total = (record.fee * 1_000_000_000.0).round() as u64;
+    let priority = ((record.priority_fee * 1_000_000_000.0).round() as u64).min(total);
+    (total - priority, priority)
+}
+
+// 領取 Jito tips 的金額 (錢包本身的轉入加上入帳到 vote / stake account 的部分)，單位 lamports
+fn jito_tip_lamports(record: &WalletTransactionRecord) -> Option<u64> {
+    if record.income != Some(IncomeKind::JitoTip) {
+        return None;
+    }
+    let own = if record.direction == "incoming" { record.amount } else { 0.0 };
+    Some(((own + record.account_tips) * 1_000_000_000.0).round() as u64)
+}
+
+// 手續費以 UTC 日為單位累計
+fn fee_day_millis(timestamp: DateTime<Utc>) -> u64 {
+    history_key_millis(timestamp) / DAY_MS * DAY_MS
+}
+
+fn load_daily_fees(
+    db: &Database,
+    address: &str,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Vec<DailyFees>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let table = match read_txn.open_table(WALLET_FEES_TABLE) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let start = from.map(fee_day_millis).unwrap_or(0);
+    let end = to.map(history_key_millis).unwrap_or(u64::MAX);
+    let mut days = Vec::new();
+    for entry in table.range((address, start)..=(address, end))? {
+        let (key, value) = entry?;
+        let (base_lamports, priority_lamports, transactions) = value.value();
+        days.push(DailyFees { day_ms: key.value().1, base_lamports, priority_lamports, transactions });
+    }
+    Ok(days)
+}
+
+fn delete_wallet_fees(db: &Database, address: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(WALLET_FEES_TABLE)?;
+        table.retain_in((address, 0)..=(address, u64::MAX), |_, _| false)?;
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+// 出塊獎勵與手續費相同以 UTC 日為單位累計
+fn save_block_rewards(db: &Database, rewards: &[BlockRewardRecord]) -> Result<(), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(WALLET_INCOME_TABLE)?;
+        for reward in rewards {
+            let key = (reward.address.as_str(), fee_day_millis(reward.timestamp));
+            let (jito, claims, block_rewards, blocks) = table.get(key)?.map(|v| v.value()).unwrap_or_default();
+            table.insert(key, (jito, claims, block_rewards + reward.lamports, blocks + 1))?;
+        }
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+fn load_daily_income(
+    db: &Database,
+    address: &str,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Vec<DailyIncome>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let table = match read_txn.open_table(WALLET_INCOME_TABLE) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let start = from.map(fee_day_millis).unwrap_or(0);
+    let end = to.map(history_key_millis).unwrap_or(u64::MAX);
+    let mut days = Vec::new();
+    for entry in table.range((address, start)..=(address, end))? {
+        let (key, value) = entry?;
+        let (jito_lamports, tip_claims, block_reward_lamports, blocks) = value.value();
+        days.push(DailyIncome { day_ms: key.value().1, jito_lamports, tip_claims, block_reward_lamports, blocks });
+    }
+    Ok(days)
+}
+
+fn delete_wallet_income(db: &Database, address: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(WALLET_INCOME_TABLE)?;
+        table.retain_in((address, 0)..=(address, u64::MAX), |_, _| false)?;
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+fn delete_wallet_token_events(db: &Database, address: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(TOKEN_EVENTS_TABLE)?;
+        let start = format!("{}_", address);
+        let end = format!("{}`", address);
+        table.retain_in(start.as_str()..end.as_str(), |_, _| false)?;
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+// 由新到舊讀取稽核記錄，可依錢包地址篩選
+fn load_audit_entries(
+    db: &Database,
+    address: Option<&str>,
+    before: Option<u64>,
+    limit: usize,
+) -> Result<Vec<AuditEntry>, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let table = match read_txn.open_table(AUDIT_LOG_TABLE) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut entries = Vec::new();
+    for entry in table.range(..before.unwrap_or(u64::MAX))?.rev() {
+        let (_, value) = entry?;
+        let entry: AuditEntry = serde_json::from_str(value.value())?;
+        if address.is_some_and(|address| address != entry.address) {
+            continue;
+        }
+        entries.push(entry);
+        if entries.len() >= limit {
+            break;
+        }
+    }
+    Ok(entries)
+}
+
+// 統計資料庫大小與各錢包的記錄數量、時間範圍
+fn database_stats(db: &Database, path: &str) -> Result<DatabaseStats, Box<dyn std::error::Error>> {
+    let read_txn = db.begin_read()?;
+    let mut wallets: BTreeMap<String, WalletDatabaseStats> = BTreeMap::new();
+
+    for (definition, aggregated) in HISTORY_TIERS {
+        let table = read_txn.open_table(definition)?;
+        for entry in table.iter()? {
+            let (key, _) = entry?;
+            let (address, time) = key.value();
+            let stats = wallets.entry(address.to_string()).or_insert_with(|| WalletDatabaseStats {
+                address: address.to_string(),
+                ..Default::default()
+            });
+            if aggregated {
+                stats.aggregated_records += 1;
+            } else {
+                stats.history_records += 1;
+            }
+            let time = DateTime::from_timestamp_millis(time as i64).unwrap_or_default();
+            stats.oldest = Some(stats.oldest.map_or(time, |t| t.min(time)));
+            stats.newest = Some(stats.newest.map_or(time, |t| t.max(time)));
+        }
+    }
+
+    // 尚未記錄過任何交易時表格不存在
+    match read_txn.open_table(WALLET_TRANSACTIONS_TABLE) {
+        Ok(table) => {
+            for entry in table.iter()? {
+                let (key, _) = entry?;
+                // 交易鍵格式為 {address}_{slot}_{signature}
+                let Some((address, _)) = key.value().split_once('_') else {
+                    continue;
+                };
+                wallets
+                    .entry(address.to_string())
+                    .or_insert_with(|| WalletDatabaseStats {
+                        address: address.to_string(),
+                        ..Default::default()
+                    })
+                    .transactions += 1;
+            }
+        }
+        Err(redb::TableError::TableDoesNotExist(_)) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let wallets: Vec<WalletDatabaseStats> = wallets.into_values().collect();
+    Ok(DatabaseStats {
+        backend: "redb",
+        path: path.to_string(),
+        file_size: fs::metadata(path)?.len(),
+        history_records: wallets.iter().map(|w| w.history_records).sum(),
+        transactions: wallets.iter().map(|w| w.transactions).sum(),
+        wallets,
+    })
+}
+
+// 第一列為期初餘額 (opening)，之後每次總餘額變化為 deposit 或 withdrawal；
+// 只有組成變化 (例如 SOL 包裝為 WSOL) 而總額不變的記錄不列出。沒有價格歷史的時間點 USD 欄位留空
+fn send_ledger_csv(
+    db: &dyn HistoryStore,
+    address: &str,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    sender: &CsvSender,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let history = db.load_history_series(address, from, to)?;
+    let prices = db.load_prices(from, to)?;
+    let price_at = |timestamp: DateTime<Utc>| prices[..prices.partition_point(|p| p.timestamp <= timestamp)].last().map(|p| p.usd);
+
+    let mut chunk = String::from("date,type,asset,amount,balance,price_usd,value_usd,balance_value_usd,components\n");
+    let mut previous: Option<&BalanceHistory> = None;
+    for point in &history {
+        let (kind, amount, components) = match previous {
+            None => ("opening", point.total_balance, String::new()),
+            Some(previous) => {
+                let delta = point.total_balance - previous.total_balance;
+                if delta.abs() < 1e-9 {
+                    continue;
+                }
+                (if delta > 0.0 { "deposit" } else { "withdrawal" }, delta, ledger_components(previous, point))
+            }
+        };
+        let price = price_at(point.timestamp);
+        let usd = |value: f64| price.map(|price| format!("{:.2}", value * price)).unwrap_or_default();
+        chunk.push_str(&format!(
+            "{},{},SOL,{:.9},{:.9},{},{},{},{}\n",
+            point.timestamp.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            kind,
+            amount,
+            point.total_balance,
+            price.map(|price| format!("{:.4}", price)).unwrap_or_default(),
+            usd(amount),
+            usd(point.total_balance),
+            components,
+        ));
+        previous = Some(point);
+
+        // 每累積約 64KB 送出一次，送出失敗代表客戶端已斷線
+        if chunk.len() >= 64 * 1024 && sender.blocking_send(Ok(std::mem::take(&mut chunk))).is_err() {
+            return Ok(());
+        }
+    }
+
+    if !chunk.is_empty() {
+        let _ = sender.blocking_send(Ok(chunk));
+    }
+    Ok(())
+}
+
+// 列出變化的餘額類型，例如 "sol:+1.500000000 wsol:-0.500000000"
+fn ledger_components(previous: &BalanceHistory, current: &BalanceHistory) -> String {
+    DATA_TYPES
+        .iter()
+        .filter(|&&data_type| data_type != "total")
+        .filter_map(|&data_type| {
+            let delta = history_value(current, data_type) - history_value(previous, data_type);
+            (delta.abs() >= 1e-9).then(|| format!("{}:{:+.9}", data_type, delta))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// 分頁讀取歷史並送出 CSV；load_page 返回 from 起 (含) 最多 CSV_PAGE_SIZE 筆，
+// 每頁讀完即釋放資料庫的鎖，等待慢速客戶端接收時不會阻擋其他讀寫
+fn send_history_csv(
+    from: Option<DateTime<Utc>>,
+    mut load_page: impl FnMut(Option<DateTime<Utc>>) -> Result<Vec<WalletHistoryRecord>, Box<dyn std::error::Error>>,
+    sender: &CsvSender,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut from = from;
+    let mut header = Some(String::from("timestamp,sol_balance,wsol_balance,staked_balance,lst_balance,total_balance\n"));
+    loop {
+        let page = load_page(from)?;
+        let mut chunk = header.take().unwrap_or_default();
+        for record in &page {
+            chunk.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                record.timestamp.to_rfc3339(),
+                record.sol_balance,
+                record.wsol_balance,
+                record.staked_balance,
+                record.lst_balance,
+                record.total_balance,
+            ));
+        }
+        // 送出失敗代表客戶端已斷線
+        if !chunk.is_empty() && sender.blocking_send(Ok(chunk)).is_err() {
+            return Ok(());
+        }
+        match page.last() {
+            Some(last) if page.len() >= CSV_PAGE_SIZE => from = Some(last.timestamp + chrono::Duration::milliseconds(1)),
+            _ => return Ok(()),
+        }
+    }
+}
+
+// 通知系統
+#[derive(Clone)]
+struct NotificationSink {
+    name: &'static str,
+    sender: tokio::sync::mpsc::Sender<BalanceChangeEvent>,
+    min_delta: f64,
+    wallets: Vec<String>,         // 為空時通知所有錢包 (地址或名稱)
+    exclude_wallets: Vec<String>, // 不通知的錢包 (地址或名稱)
+    new_token_alerts: bool,
+}
+
+impl NotificationSink {
+    fn accepts(&self, event: &BalanceChangeEvent) -> bool {
+        let matches = |list: &Vec<String>| list.iter().any(|w| *w == event.address || *w == event.name);
+        if event.account_event == Some(AccountEvent::NewTokenReceived) && !self.new_token_alerts {
+            return false;
+        }
+        (event.delta.abs() >= self.min_delta || event.account_event.is_some() || event.alert.is_some())
+            && (self.wallets.is_empty() || matches(&self.wallets))
+            && !matches(&self.exclude_wallets)
+    }
+}
+
+// 通知目標可在配置文件變更時整組替換，舊的傳送任務在佇列清空後自行結束
+#[derive(Clone, Default)]
+struct Notifier {
+    config: Arc<std::sync::RwLock<NotificationsConfig>>,
+    sinks: Arc<std::sync::RwLock<Vec<NotificationSink>>>,
+    rules: Arc<std::sync::RwLock<Vec<AlertRule>>>,
+    database: Option<SharedDatabase>, // 讀取未解除的告警
+    writer: Option<DatabaseWriter>, // 保存觸發的告警、狀態與靜音
+    last_alert_id: Arc<std::sync::atomic::AtomicU64>, // 已分配的最大告警 id
+    snoozed: Arc<Mutex<HashMap<(String, String), DateTime<Utc>>>>, // (規則名稱, 錢包地址) -> 靜音結束時間
+    routes: Arc<std::sync::RwLock<Vec<NotificationRoute>>>,
+    stream: Arc<std::sync::RwLock<Option<tokio::sync::mpsc::Sender<BalanceChangeEvent>>>>,
+    incident_sinks: Arc<std::sync::RwLock<Vec<IncidentSink>>>,
+    open_incidents: Arc<Mutex<std::collections::HashSet<String>>>, // 已開立、尚未關閉的事件 dedup key
+    balance_alerts: Arc<Mutex<HashMap<(String, String), Option<u64>>>>, // 總餘額低於門檻中的 (規則名稱, 錢包地址) -> 告警 id
+    #[cfg(feature = "rhai")]
+    script_engine: Arc<script::ScriptEngine>,
+    #[cfg(feature = "rhai")]
+    scripts: Arc<std::sync::RwLock<Vec<(AlertRule, script::ScriptCondition)>>>, // 已編譯的 script 規則
+    #[cfg(feature = "rhai")]
+    script_matches: Arc<Mutex<HashMap<(String, String), Option<u64>>>>, // 條件目前成立的 (規則名稱, 錢包地址) -> 告警 id
+    #[cfg(feature = "rhai")]
+    script_history: Arc<Mutex<HashMap<String, Vec<BalanceHistory>>>>, // 錢包地址 -> script 規則最大區間內的歷史
+}
+
+impl Notifier {
+    fn start(config: &NotificationsConfig, database: SharedDatabase, writer: DatabaseWriter) -> Self {
+        let last_alert_id = match database.load_alerts(None, None, 1) {
+            Ok(alerts) => alerts.first().map_or(0, |alert| alert.id),
+            Err(e) => {
+                warn!("⚠️ 讀取告警記錄失敗: {}", e);
+                0
+            }
+        };
+        // 重啟後沿用尚未結束的靜音
+        let snoozed = match database.load_alert_snoozes() {
+            Ok(snoozes) => snoozes.into_iter().map(|(rule, address, until)| ((rule, address), until)).collect(),
+            Err(e) => {
+                warn!("⚠️ 讀取告警靜音失敗: {}", e);
+                HashMap::new()
+            }
+        };
+        let notifier = Notifier {
+            database: Some(database),
+            writer: Some(writer),
+            last_alert_id: Arc::new(std::sync::atomic::AtomicU64::new(last_alert_id)),
+            snoozed: Arc::new(Mutex::new(snoozed)),
+            ..Notifier::default()
+        };
+        notifier.reload(config);
+        notifier.restore_alert_state(config);
+        notifier
+    }
+
+    // 尚未解除 (開啟或已確認) 的告警；重啟後用來恢復告警狀態，避免條件仍成立的告警重複觸發或永遠不會解除
+    fn unresolved_alerts(&self, kind: AlertKind) -> Vec<AlertRecord> {
+        let Some(database) = &self.database else {
+            return Vec::new();
+        };
+        match database.load_alerts(None, None, usize::MAX) {
+            Ok(alerts) => alerts.into_iter().filter(|alert| alert.kind == kind && alert.state != AlertState::Resolved).collect(),
+            Err(e) => {
+                warn!("⚠️ 讀取未解除的告警失敗: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    // 啟動時依資料庫中未解除的告警重建已開立的事件與條件成立中的告警；規則已移除的告警直接解除
+    fn restore_alert_state(&self, config: &NotificationsConfig) {
+        let mut restored = 0;
+        for kind in [AlertKind::StaleWallet, AlertKind::ValidatorRunway, AlertKind::Script, AlertKind::BalanceBelow] {
+            for alert in self.unresolved_alerts(kind) {
+                let Some(rule) = config.alerts.iter().find(|rule| rule.name == alert.rule && rule.kind == kind) else {
+                    // stale_wallet 與 validator_runway 由各自的檢查任務解除
+                    if matches!(kind, AlertKind::Script | AlertKind::BalanceBelow) {
+                        self.resolve_alert(&alert.rule, &alert.address, Some(alert.id));
+                    }
+                    continue;
+                };
+                if rule.severity == AlertSeverity::Incident {
+                    self.open_incidents.lock().unwrap().insert(incident_dedup_key(&alert.rule, &alert.address));
+                    restored += 1;
+                }
+                let key = (alert.rule, alert.address);
+                match kind {
+                    AlertKind::BalanceBelow => {
+                        self.balance_alerts.lock().unwrap().insert(key, Some(alert.id));
+                    }
+                    #[cfg(feature = "rhai")]
+                    AlertKind::Script => {
+                        self.script_matches.lock().unwrap().insert(key, Some(alert.id));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if restored > 0 {
+            info!("🚨 已恢復 {} 個尚未關閉的事件", restored);
+        }
+    }
+
+    // 依 balance_below 規則檢查錢包更新：總餘額低於 min_balance 時告警，回到門檻以上時解除
+    fn check_balance(&self, wallet: &WalletBalance, previous: &BalanceHistory) {
+        let total = wallet.total_balance();
+        for rule in self.rules().iter().filter(|rule| rule.kind == AlertKind::BalanceBelow && rule.applies_to(wallet)) {
+            let key = (rule.name.clone(), wallet.address.clone());
+            let mut alerts = self.balance_alerts.lock().unwrap();
+            match (total < rule.min_balance, alerts.contains_key(&key)) {
+                (true, false) => {
+                    warn!("🪫 告警 {}: 錢包 {} ({}) 總餘額 {:.9} SOL 低於 {} SOL", rule.name, wallet.name, &wallet.address[..8], total, rule.min_balance);
+                    let detail = format!("總餘額 {:.9} SOL 低於 {} SOL", total, rule.min_balance);
+                    let id = self.fire_alert(rule, BalanceChangeEvent::balance_below(wallet, rule, previous), detail);
+                    alerts.insert(key, id);
+                }
+                (false, true) => {
+                    if let Some(id) = alerts.remove(&key) {
+                        info!("✅ 告警 {}: 錢包 {} 總餘額已回到 {} SOL 以上", rule.name, wallet.name, rule.min_balance);
+                        self.resolve_alert(&rule.name, &wallet.address, id);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn reload(&self, config: &NotificationsConfig) {
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = config.clone();
+        let sinks = Self::start_sinks(config);
+        *self.sinks.write().unwrap_or_else(|e| e.into_inner()) = sinks;
+        *self.incident_sinks.write().unwrap_or_else(|e| e.into_inner()) = Self::start_incident_sinks(config);
+        *self.stream.write().unwrap_or_else(|e| e.into_inner()) = config.stream.as_ref().and_then(start_event_stream);
+        *self.rules.write().unwrap_or_else(|e| e.into_inner()) = config.alerts.clone();
+        // 規則已移除的低餘額告警視為解除
+        let removed: Vec<((String, String), Option<u64>)> = {
+            let mut alerts = self.balance_alerts.lock().unwrap();
+            let (kept, removed) = std::mem::take(&mut *alerts)
+                .into_iter()
+                .partition(|((rule, _), _)| config.alerts.iter().any(|r| r.name == *rule && r.kind == AlertKind::BalanceBelow));
+            *alerts = kept;
+            removed.into_iter().collect()
+        };
+        for ((rule, address), id) in removed {
+            self.resolve_alert(&rule, &address, id);
+        }
+        for route in &config.routes {
+            if route.quiet_hours.as_deref().is_some_and(|q| parse_quiet_hours(q).is_none()) {
+                warn!("⚠️ 通知路由 {} 的 quiet_hours 格式錯誤，應為 HH:MM-HH:MM", route.name);
+            }
+        }
+        *self.routes.write().unwrap_or_else(|e| e.into_inner()) = config.routes.clone();
+        self.compile_scripts(&config.alerts);
+    }
+
+    // 編譯 script 規則的條件，無法編譯的規則記錄錯誤後略過
+    fn compile_scripts(&self, rules: &[AlertRule]) {
+        let rules = rules.iter().filter(|rule| rule.kind == AlertKind::Script);
+        #[cfg(feature = "rhai")]
+        {
+            let mut scripts = Vec::new();
+            for rule in rules {
+                let Some(condition) = rule.condition.as_deref() else {
+                    error!("❌ 告警規則 {} 缺少 condition", rule.name);
+                    continue;
+                };
+                match self.script_engine.compile(condition) {
+                    Ok(compiled) => scripts.push((rule.clone(), compiled)),
+                    Err(e) => error!("❌ 告警規則 {} 的條件無法編譯: {}", rule.name, e),
+                }
+            }
+            if !scripts.is_empty() {
+                info!("📜 已載入 {} 條 script 告警規則", scripts.len());
+            }
+            *self.scripts.write().unwrap_or_else(|e| e.into_inner()) = scripts;
+            // 區間可能改變，下次評估時重新從資料庫載入
+            self.script_history.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        }
+        #[cfg(not(feature = "rhai"))]
+        for rule in rules {
+            warn!("⚠️ 告警規則 {} 的條件 {:?} 需以 --features rhai 編譯才會生效", rule.name, rule.condition.as_deref().unwrap_or(""));
+        }
+    }
+
+    // 依 script 規則檢查錢包更新：條件由不成立變為成立時告警，恢復不成立時解除告警
+    fn check_scripts(&self, wallet: &WalletBalance, previous: &BalanceHistory) {
+        #[cfg(feature = "rhai")]
+        {
+            let scripts = self.scripts.read().unwrap_or_else(|e| e.into_inner());
+            let applicable: Vec<_> = scripts.iter().filter(|(rule, _)| rule.applies_to(wallet)).collect();
+            let Some(window) = applicable.iter().map(|(_, condition)| condition.window()).max() else {
+                return;
+            };
+            let history = Arc::new(self.script_history(wallet, script::window_start(window)));
+            for (rule, condition) in applicable {
+                let matched = match self.script_engine.evaluate(condition, wallet, previous, history.clone()) {
+                    Ok(matched) => matched,
+                    Err(e) => {
+                        warn!("⚠️ 告警規則 {} 對錢包 {} 執行失敗: {}", rule.name, wallet.name, e);
+                        continue;
+                    }
+                };
+                let key = (rule.name.clone(), wallet.address.clone());
+                let mut matches = self.script_matches.lock().unwrap();
+                match (matched, matches.contains_key(&key)) {
+                    (true, false) => {
+                        info!("📜 告警 {}: 錢包 {} ({}) 符合條件", rule.name, wallet.name, &wallet.address[..8]);
+                        let detail = format!("{}, 總餘額 {:.9} → {:.9} SOL", rule.condition.as_deref().unwrap_or(""), previous.total_balance, wallet.total_balance());
+                        let id = self.fire_alert(rule, BalanceChangeEvent::script(wallet, rule, previous), detail);
+                        matches.insert(key, id);
+                    }
+                    (false, true) => {
+                        if let Some(id) = matches.remove(&key) {
+                            info!("✅ 告警 {}: 錢包 {} 已不符合條件", rule.name, wallet.name);
+                            self.resolve_alert(&rule.name, &wallet.address, id);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        #[cfg(not(feature = "rhai"))]
+        let _ = (wallet, previous);
+    }
+
+    // script 規則統計用的區間歷史：重啟後記憶體只有最新快照，每個錢包第一次評估時從資料庫載入
+    // 區間內的記錄 (含區間開始前的最後一筆作為起點)，之後只追加記憶體中較新的記錄並丟棄區間外的舊記錄
+    #[cfg(feature = "rhai")]
+    fn script_history(&self, wallet: &WalletBalance, since: DateTime<Utc>) -> Vec<BalanceHistory> {
+        let mut cache = self.script_history.lock().unwrap_or_else(|e| e.into_inner());
+        let history = cache.entry(wallet.address.clone()).or_insert_with(|| match &self.database {
+            Some(database) => database.load_history_series(&wallet.address, Some(since), None).unwrap_or_else(|e| {
+                warn!("⚠️ 載入錢包 {} 的 script 區間歷史失敗: {}", wallet.name, e);
+                Vec::new()
+            }),
+            None => Vec::new(),
+        });
+        let last = history.last().map(|point| point.timestamp);
+        history.extend(wallet.history.iter().filter(|point| last.is_none_or(|t| point.timestamp > t)).cloned());
+        let current = wallet.balance_point();
+        if history.last().is_none_or(|point| point.timestamp < current.timestamp) {
+            history.push(current);
+        }
+        let start = history.partition_point(|point| point.timestamp < since).saturating_sub(1);
+        history.drain(..start);
+        if let Some(first) = history.first_mut().filter(|first| first.timestamp < since) {
+            first.timestamp = since;
+        }
+        history.clone()
+    }
+
+    fn start_sinks(config: &NotificationsConfig) -> Vec<NotificationSink> {
+        let mut sinks = Vec::new();
+
+        if let Some(telegram_config) = &config.telegram {
+            let (tx, rx) = tokio::sync::mpsc::channel(telegram_config.queue_size.max(1));
+            tokio::spawn(telegram_sender_task(telegram_config.clone(), rx));
+            sinks.push(NotificationSink {
+                name: "Telegram",
+                sender: tx,
+                min_delta: telegram_config.min_delta,
+                wallets: Vec::new(),
+                exclude_wallets: Vec::new(),
+                new_token_alerts: config.new_token_alerts,
+            });
+            info!("📨 Telegram 通知已啟用 (chat_id: {})", telegram_config.chat_id);
+        }
+
+        if let Some(webhook_config) = &config.webhook {
+            let (tx, rx) = tokio::sync::mpsc::channel(webhook_config.queue_size.max(1));
+            tokio::spawn(webhook_sender_task(webhook_config.clone(), rx));
+            sinks.push(NotificationSink {
+                name: "Webhook",
+                sender: tx,
+                min_delta: webhook_config.min_delta,
+                wallets: Vec::new(),
+                exclude_wallets: Vec::new(),
+                new_token_alerts: config.new_token_alerts,
+            });
+            info!("📨 Webhook 通知已啟用 ({} 個 URL)", webhook_config.urls.len());
+        }
+
+        if let Some(discord_config) = &config.discord {
+            let (tx, rx) = tokio::sync::mpsc::channel(discord_config.queue_size.max(1));
+            tokio::spawn(discord_sender_task(discord_config.clone(), rx));
+            sinks.push(NotificationSink {
+                name: "Discord",
+                sender: tx,
+                min_delta: discord_config.min_delta,
+                wallets: discord_config.wallets.clone(),
+                exclude_wallets: discord_config.exclude_wallets.clone(),
+                new_token_alerts: config.new_token_alerts,
+            });
+            info!("📨 Discord 通知已啟用");
+        }
+
+        if let Some(mqtt_config) = &config.mqtt {
+            let (tx, rx) = tokio::sync::mpsc::channel(mqtt_config.queue_size.max(1));
+            tokio::spawn(mqtt_sender_task(mqtt_config.clone(), rx));
+            sinks.push(NotificationSink {
+                name: "MQTT",
+                sender: tx,
+                min_delta: mqtt_config.min_delta,
+                wallets: mqtt_config.wallets.clone(),
+                exclude_wallets: mqtt_config.exclude_wallets.clone(),
+                new_token_alerts: config.new_token_alerts,
+            });
+            info!("📨 MQTT 通知已啟用 ({}:{}，topic {}/#)", mqtt_config.host, mqtt_config.port, mqtt_config.topic_prefix);
+        }
+
+        if let Some(email_config) = &config.email {
+            match EmailMailer::new(email_config) {
+                Ok(mailer) => {
+                    let (tx, rx) = tokio::sync::mpsc::channel(email_config.queue_size.max(1));
+                    tokio::spawn(email_sender_task(email_config.clone(), mailer, rx));
+                    sinks.push(NotificationSink {
+                        name: "Email",
+                        sender: tx,
+                        min_delta: email_config.min_delta,
+                        wallets: email_config.wallets.clone(),
+                        exclude_wallets: email_config.exclude_wallets.clone(),
+                        new_token_alerts: config.new_token_alerts,
+                    });
+                    if email_config.digest {
+                        info!("📨 Email 通知已啟用 ({} 位收件人，每日 {:02}:00 UTC 寄出摘要)", email_config.to.len(), email_config.digest_hour % 24);
+                    } else {
+                        info!("📨 Email 通知已啟用 ({} 位收件人)", email_config.to.len());
+                    }
+                }
+                Err(e) => error!("❌ Email 通知設定錯誤: {}", e),
+            }
+        }
+
+        sinks
+    }
+
+    fn start_incident_sinks(config: &NotificationsConfig) -> Vec<IncidentSink> {
+        let mut sinks = Vec::new();
+
+        if let Some(pagerduty_config) = &config.pagerduty {
+            let (tx, rx) = tokio::sync::mpsc::channel(pagerduty_config.queue_size.max(1));
+            tokio::spawn(pagerduty_sender_task(pagerduty_config.clone(), rx));
+            sinks.push(IncidentSink { name: "PagerDuty", sender: tx });
+            info!("🚨 PagerDuty 事件通知已啟用");
+        }
+
+        if let Some(opsgenie_config) = &config.opsgenie {
+            let (tx, rx) = tokio::sync::mpsc::channel(opsgenie_config.queue_size.max(1));
+            tokio::spawn(opsgenie_sender_task(opsgenie_config.clone(), rx));
+            sinks.push(IncidentSink { name: "Opsgenie", sender: tx });
+            info!("🚨 Opsgenie 事件通知已啟用");
+        }
+
+        if sinks.is_empty() && config.alerts.iter().any(|rule| rule.severity == AlertSeverity::Incident) {
+            warn!("⚠️ 有 incident 等級的告警規則，但未設定 [notifications.pagerduty] 或 [notifications.opsgenie]");
+        }
+        sinks
+    }
+
+    fn notify_incident(&self, incident: IncidentEvent) {
+        for sink in self.incident_sinks.read().unwrap_or_else(|e| e.into_inner()).iter() {
+            if let Err(e) = sink.sender.try_send(incident.clone()) {
+                warn!("⚠️ {} 事件佇列已滿或已關閉，丟棄事件: {}", sink.name, e);
+            }
+        }
+    }
+
+    fn notify(&self, event: &BalanceChangeEvent) {
+        if let Some(stream) = self.stream.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            if let Err(e) = stream.try_send(event.clone()) {
+                warn!("⚠️ 事件串流佇列已滿或已關閉，丟棄事件: {}", e);
+            }
+        }
+        let routes = self.routes.read().unwrap_or_else(|e| e.into_inner());
+        let now = Utc::now();
+        // 有設定路由時，事件只依第一條符合錢包、群組與等級的路由投遞，其他路由不再比對
+        let route = match routes.iter().find(|route| route.matches(event)) {
+            Some(route) => Some(route),
+            None if routes.is_empty() => None,
+            None => return,
+        };
+        for sink in self.sinks.read().unwrap_or_else(|e| e.into_inner()).iter().filter(|sink| sink.accepts(event)) {
+            let mut event = event.clone();
+            if let Some(route) = route {
+                if !route.delivers(&event, sink.name, now) {
+                    continue;
+                }
+                if let Some(template) = &route.template {
+                    event.message = Some(render_notification_template(template, &event));
+                }
+            }
+            if let Err(e) = sink.sender.try_send(event) {
+                warn!("⚠️ {} 通知佇列已滿或已關閉，丟棄事件: {}", sink.name, e);
+            }
+        }
+    }
+
+    fn config(&self) -> NotificationsConfig {
+        self.config.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn rules(&self) -> Vec<AlertRule> {
+        self.rules.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    // 依 large_transfer 規則檢查一筆交易，符合時附上對手方與簽名發送告警
+    fn check_transfer(&self, wallet: &WalletBalance, record: &WalletTransactionRecord, old_balance: f64, new_balance: f64) {
+        for rule in self.rules().iter().filter(|rule| rule.applies_to(wallet) && rule.matches_transfer(record)) {
+            info!("🐋 告警 {}: 錢包 {} {} {:.9} SOL (交易 {})", rule.name, wallet.name, record.direction, record.amount, &record.signature[..8]);
+            let detail = format!(
+                "{} {:.9} SOL, 對手方 {}, 交易 {}",
+                record.direction,
+                record.amount,
+                record.counterparty.as_deref().unwrap_or("-"),
+                record.signature
+            );
+            self.fire_alert(rule, BalanceChangeEvent::transfer(wallet, rule, record, old_balance, new_balance), detail);
+        }
+    }
+
+    // 觸發告警：規則的靜音期間內略過，否則保存告警記錄並發送通知，返回告警 id
+    fn fire_alert(&self, rule: &AlertRule, mut event: BalanceChangeEvent, detail: String) -> Option<u64> {
+        let now = Utc::now();
+        {
+            let mut snoozed = self.snoozed.lock().unwrap();
+            snoozed.retain(|_, until| *until > now);
+            let key = (rule.name.clone(), event.address.clone());
+            if snoozed.contains_key(&key) {
+                debug!("🔕 告警 {} 對錢包 {} 靜音中，略過", rule.name, &event.address[..8]);
+                return None;
+            }
+            if rule.snooze_secs > 0 {
+                let until = now + chrono::Duration::seconds(rule.snooze_secs as i64);
+                if let Some(writer) = &self.writer {
+                    writer.save_alert_snooze(&rule.name, &event.address, until);
+                }
+                snoozed.insert(key, until);
+            }
+        }
+
+        event.severity = Some(rule.severity);
+        let record = AlertRecord {
+            id: self.last_alert_id.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1,
+            rule: rule.name.clone(),
+            kind: rule.kind,
+            address: event.address.clone(),
+            name: event.name.clone(),
+            state: AlertState::Open,
+            detail: Some(detail.clone()),
+            fired_at: now,
+            acknowledged_at: None,
+            resolved_at: None,
+        };
+        // 在錢包鎖內觸發，不直接寫入資料庫
+        event.alert_id = self.writer.as_ref().map(|writer| {
+            let id = record.id;
+            writer.save_alert(record);
+            id
+        });
+        self.notify(&event);
+        if rule.severity == AlertSeverity::Incident {
+            let dedup_key = incident_dedup_key(&rule.name, &event.address);
+            warn!("🚨 開立事件 {}", dedup_key);
+            self.open_incidents.lock().unwrap().insert(dedup_key.clone());
+            self.notify_incident(IncidentEvent {
+                action: IncidentAction::Trigger,
+                dedup_key,
+                summary: format!("{}: {} ({})", rule.name, event.name, &event.address[..8]),
+                detail,
+                event: Some(event.clone()),
+            });
+        }
+        event.alert_id
+    }
+
+    // 告警條件解除 (例如無活動的錢包恢復更新)；已開立的事件一併關閉
+    fn resolve_alert(&self, rule: &str, address: &str, id: Option<u64>) {
+        if let (Some(writer), Some(id)) = (&self.writer, id) {
+            writer.set_alert_state(id, AlertState::Resolved);
+        }
+        let dedup_key = incident_dedup_key(rule, address);
+        if self.open_incidents.lock().unwrap().remove(&dedup_key) {
+            info!("✅ 關閉事件 {}", dedup_key);
+            self.notify_incident(IncidentEvent {
+                action: IncidentAction::Resolve,
+                summary: format!("{}: {} 已恢復", rule, &address[..8]),
+                detail: String::new(),
+                dedup_key,
+                event: None,
+            });
+        }
+    }
+}
+
+// 以指數退避重試 POST JSON 請求，429/5xx/網路錯誤視為暫時性失敗
+async fn post_json_with_retry(
+    client: &reqwest::Client,
+    url: &str,
+    body: &serde_json::Value,
+    max_retries: u32,
+    sink_name: &str,
+) -> bool {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        // 預設指數退避: 1s, 2s, 4s ...
+        let mut retry_delay = Duration::from_secs(1 << (attempt - 1).min(6));
+
+        match client.post(url).json(body).send().await {
+            Ok(response) if response.status().is_success() => return true,
+            Ok(response) => {
+                let status = response.status();
+                let transient = status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+                let retry_after_header = response
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.parse::<u64>().ok());
+                let text = response.text().await.unwrap_or_default();
+
+                if !transient {
+                    error!("❌ {} 通知發送失敗 ({}): {}", sink_name, status, text);
+                    return false;
+                }
+
+                // 429 時依照伺服器回傳的 Retry-After (或 Telegram 的 retry_after) 等待
+                let retry_after_body = serde_json::from_str::<serde_json::Value>(&text)
+                    .ok()
+                    .and_then(|v| v["parameters"]["retry_after"].as_u64());
+                if let Some(retry_after) = retry_after_header.or(retry_after_body) {
+                    retry_delay = Duration::from_secs(retry_after);
+                }
+                warn!("⚠️ {} 通知暫時失敗 ({})，第 {} 次重試", sink_name, status, attempt);
+            }
+            Err(e) => {
+                warn!("⚠️ {} 通知請求錯誤: {}，第 {} 次重試", sink_name, e, attempt);
+            }
+        }
+
+        if attempt > max_retries {
+            error!("❌ {} 通知重試 {} 次後放棄: {}", sink_name, max_retries, url);
+            return false;
+        }
+        tokio::time::sleep(retry_delay).await;
+    }
+}
+
+fn format_notification_text(event: &BalanceChangeEvent) -> String {
+    let direction = if event.delta >= 0.0 { "📈" } else { "📉" };
+    let account_event = event
+        .account_event
+        .map(|e| match &event.mint {
+            Some(mint) => format!("{} {}\n", e.description(), mint),
+            None => format!("{}\n", e.description()),
+        })
+        .unwrap_or_default();
+    let transfer = match (&event.alert, &event.transfer) {
+        (Some(alert), Some(transfer)) => format!(
+            "🐋 {}: {} {:.6} SOL\n對手方: {}\n交易: https://solscan.io/tx/{}\n",
+            alert,
+            transfer.direction,
+            transfer.amount,
+            transfer.counterparty.as_deref().unwrap_or("-"),
+            transfer.signature,
+        ),
+        (Some(alert), None) => match event.idle_secs {
+            Some(idle_secs) => format!("⏰ {}: {} 分鐘沒有任何更新\n", alert, idle_secs / 60),
+            None => match &event.condition {
+                Some(condition) => format!("📜 {}: {}\n", alert, condition),
+                None => format!("🔔 {}\n", alert),
+            },
+        },
+        _ => String::new(),
+    };
+    format!(
+        "{}{}{} {} ({})\n{} 餘額變化: {:+.6}\n{:.6} → {:.6}\n總計: {:.6}\nSlot: {}\n時間: {}",
+        transfer,
+        account_event,
+        direction,
+        event.name,
+        &event.address[..8],
+        event.balance_type.to_uppercase(),
+        event.delta,
+        event.old_balance,
+        event.new_balance,
+        event.total_balance,
+        event.slot,
+        event.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+    )
+}
+
+async fn telegram_sender_task(config: TelegramConfig, mut receiver: tokio::sync::mpsc::Receiver<BalanceChangeEvent>) {
+    let client = reqwest::Client::builder().timeout(Duration::from_secs(NOTIFY_HTTP_TIMEOUT_SECS)).build().unwrap_or_default();
+    let url = format!("https://api.telegram.org/bot{}/sendMessage", config.bot_token);
+
+    while let Some(event) = receiver.recv().await {
+        let body = serde_json::json!({
+            "chat_id": config.chat_id,
+            "text": notification_text(&event),
+            "disable_web_page_preview": true,
+        });
+
+        if post_json_with_retry(&client, &url, &body, config.max_retries, "Telegram").await {
+            debug!("📨 Telegram 通知已發送: {} ({})", event.name, &event.address[..8]);
+        }
+    }
+}
+
+fn format_discord_embed(event: &BalanceChangeEvent) -> serde_json::Value {
+    let (arrow, color) = if event.delta >= 0.0 { ("⬆️", 0x2ecc71) } else { ("⬇️", 0xe74c3c) };
+    let title = match (&event.alert, event.account_event) {
+        (Some(alert), _) if event.idle_secs.is_some() => format!("⏰ {} {}", alert, event.name),
+        (Some(alert), _) if event.condition.is_some() => format!("📜 {} {}", alert, event.name),
+        (Some(alert), _) => format!("🐋 {} {}", alert, event.name),
+        (None, Some(account_event)) => format!("{} {}", account_event.description(), event.name),
+        (None, None) => format!("{} {} {} 餘額變化", arrow, event.name, event.balance_type.to_uppercase()),
+    };
+    let mut embed = serde_json::json!({
+        "title": title,
+        "url": format!("https://solscan.io/account/{}", event.address),
+        "color": color,
+        "fields": [
+            { "name": "變化", "value": format!("{:+.6} SOL", event.delta), "inline": true },
+            { "name": "餘額", "value": format!("{:.6} → {:.6}", event.old_balance, event.new_balance), "inline": true },
+            { "name": "總計", "value": format!("{:.6} SOL", event.total_balance), "inline": true },
+            { "name": "錢包", "value": format!("[{}](https://solscan.io/account/{})", event.address, event.address), "inline": false },
+            { "name": "Slot", "value": event.slot.to_string(), "inline": true },
+        ],
+        "timestamp": event.timestamp.to_rfc3339(),
+    });
+    if let (Some(transfer), Some(fields)) = (&event.transfer, embed["fields"].as_array_mut()) {
+        fields.push(serde_json::json!({
+            "name": "對手方",
+            "value": transfer.counterparty.as_deref().map(|c| format!("[{}](https://solscan.io/account/{})", c, c)).unwrap_or_else(|| "-".to_string()),
+            "inline": false,
+        }));
+        fields.push(serde_json::json!({
+            "name": "交易",
+            "value": format!("[{}](https://solscan.io/tx/{})", transfer.signature, transfer.signature),
+            "inline": false,
+        }));
+    }
+    if let Some(message) = &event.message {
+        embed["description"] = serde_json::Value::String(message.clone());
+    }
+    if let (Some(condition), Some(fields)) = (&event.condition, embed["fields"].as_array_mut()) {
+        fields.push(serde_json::json!({ "name": "條件", "value": format!("`{}`", condition), "inline": false }));
+    }
+    embed
+}
+
+async fn discord_sender_task(config: DiscordConfig, mut receiver: tokio::sync::mpsc::Receiver<BalanceChangeEvent>) {
+    let client = reqwest::Client::builder().timeout(Duration::from_secs(NOTIFY_HTTP_TIMEOUT_SECS)).build().unwrap_or_default();
+
+    while let Some(event) = receiver.recv().await {
+        let mut body = serde_json::json!({ "embeds": [format_discord_embed(&event)] });
+        if let Some(username) = &config.username {
+            body["username"] = serde_json::Value::String(username.clone());
+        }
+
+        if post_json_with_retry(&client, &config.webhook_url, &body, config.max_retries, "Discord").await {
+            debug!("📨 Discord 通知已發送: {} ({})", event.name, &event.address[..8]);
+        }
+    }
+}
+
+async fn webhook_sender_task(config: WebhookConfig, mut receiver: tokio::sync::mpsc::Receiver<BalanceChangeEvent>) {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.timeout_secs))
+        .build()
+        .unwrap_or_default();
+
+    while let Some(event) = receiver.recv().await {
+        let body = match serde_json::to_value(&event) {
+            Ok(body) => body,
+            Err(e) => {
+                error!("❌ 序列化 Webhook 事件失敗: {}", e);
+                continue;
+            }
+        };
+
+        for url in &config.urls {
+            if post_json_with_retry(&client, url, &body, config.max_retries, "Webhook").await {
+                debug!("📨 Webhook 通知已發送: {} -> {}", event.name, url);
+            }
+        }
+    }
+}
+
+// 發布到 MQTT broker：每個事件送到 {prefix}/wallets/{address}/event，告警另送到 {prefix}/alerts，
+// 最新餘額以 {prefix}/wallets/{address}/state 發布，方便 Home Assistant 等直接訂閱
+async fn mqtt_sender_task(config: MqttConfig, mut receiver: tokio::sync::mpsc::Receiver<BalanceChangeEvent>) {
+    let mut options = rumqttc::MqttOptions::new(&config.client_id, &config.host, config.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if let (Some(username), Some(password)) = (&config.username, &config.password) {
+        options.set_credentials(username, password);
+    }
+    let (client, mut eventloop) = rumqttc::AsyncClient::new(options, config.queue_size.max(1));
+    // 事件迴圈負責連線與重連，必須持續 poll
+    let connection = tokio::spawn(async move {
+        loop {
+            if let Err(e) = eventloop.poll().await {
+                warn!("⚠️ MQTT 連線錯誤: {}，5 秒後重連", e);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+        }
+    });
+    // qos 已在載入配置時檢查為 0 到 2
+    let qos = match config.qos {
+        0 => rumqttc::QoS::AtMostOnce,
+        1 => rumqttc::QoS::AtLeastOnce,
+        _ => rumqttc::QoS::ExactlyOnce,
+    };
+    let prefix = config.topic_prefix.trim_end_matches('/');
+
+    while let Some(event) = receiver.recv().await {
+        let payload = match serde_json::to_vec(&event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("❌ 序列化 MQTT 事件失敗: {}", e);
+                continue;
+            }
+        };
+        let state = serde_json::json!({
+            "name": event.name,
+            "total_balance": event.total_balance,
+            "timestamp": event.timestamp,
+            "slot": event.slot,
+        });
+
+        let mut messages = vec![
+            (format!("{}/wallets/{}/event", prefix, event.address), false, payload.clone()),
+            (format!("{}/wallets/{}/state", prefix, event.address), config.retain_state, state.to_string().into_bytes()),
+        ];
+        if event.alert.is_some() {
+            messages.push((format!("{}/alerts", prefix), false, payload));
+        }
+        for (topic, retain, payload) in messages {
+            if let Err(e) = client.publish(topic, qos, retain, payload).await {
+                warn!("⚠️ MQTT 發布失敗: {}", e);
+            }
+        }
+        debug!("📨 MQTT 事件已發布: {} ({})", event.name, &event.address[..8]);
+    }
+
+    let _ = client.disconnect().await;
+    connection.abort();
+}
+
+// 事件管理平台 (PagerDuty / Opsgenie) 的事件，同一規則與錢包使用相同的 dedup key
+#[derive(Debug, Clone)]
+struct IncidentEvent {
+    action: IncidentAction,
+    dedup_key: String,
+    summary: String,
+    detail: String,
+    event: Option<BalanceChangeEvent>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum IncidentAction {
+    Trigger,
+    Resolve,
+}
+
+struct IncidentSink {
+    name: &'static str,
+    sender: tokio::sync::mpsc::Sender<IncidentEvent>,
+}
+
+fn incident_dedup_key(rule: &str, address: &str) -> String {
+    format!("sol-wallet-monitor:{}:{}", rule, address)
+}
+
+async fn pagerduty_sender_task(config: PagerDutyConfig, mut receiver: tokio::sync::mpsc::Receiver<IncidentEvent>) {
+    let client = reqwest::Client::builder().timeout(Duration::from_secs(NOTIFY_HTTP_TIMEOUT_SECS)).build().unwrap_or_default();
+
+    while let Some(incident) = receiver.recv().await {
+        let body = match incident.action {
+            IncidentAction::Trigger => serde_json::json!({
+                "routing_key": config.routing_key,
+                "event_action": "trigger",
+                "dedup_key": incident.dedup_key,
+                "payload": {
+                    "summary": incident.summary,
+                    "source": "sol-wallet-monitor",
+                    "severity": "critical",
+                    "timestamp": incident.event.as_ref().map(|e| e.timestamp).unwrap_or_else(Utc::now).to_rfc3339(),
+                    "custom_details": { "detail": incident.detail, "event": incident.event },
+                },
+            }),
+            IncidentAction::Resolve => serde_json::json!({
+                "routing_key": config.routing_key,
+                "event_action": "resolve",
+                "dedup_key": incident.dedup_key,
+            }),
+        };
+
+        if post_json_with_retry(&client, &config.url, &body, config.max_retries, "PagerDuty").await {
+            debug!("🚨 PagerDuty 事件已送出: {:?} {}", incident.action, incident.dedup_key);
+        }
+    }
+}
+
+async fn opsgenie_sender_task(config: OpsgenieConfig, mut receiver: tokio::sync::mpsc::Receiver<IncidentEvent>) {
+    let mut headers = reqwest::header::HeaderMap::new();
+    match reqwest::header::HeaderValue::from_str(&format!("GenieKey {}", config.api_key)) {
+        Ok(value) => headers.insert(reqwest::header::AUTHORIZATION, value),
+        Err(e) => {
+            error!("❌ Opsgenie api_key 格式錯誤: {}", e);
+            return;
+        }
+    };
+    let client = reqwest::Client::builder()
+        .default_headers(headers)
+        .timeout(Duration::from_secs(NOTIFY_HTTP_TIMEOUT_SECS))
+        .build()
+        .unwrap_or_default();
+
+    while let Some(incident) = receiver.recv().await {
+        let (url, body) = match incident.action {
+            IncidentAction::Trigger => {
+                let mut details = serde_json::Map::new();
+                if let Some(event) = &incident.event {
+                    details.insert("address".to_string(), event.address.clone().into());
+                    details.insert("wallet".to_string(), event.name.clone().into());
+                    details.insert("total_balance".to_string(), format!("{:.9}", event.total_balance).into());
+                    if let Some(alert_id) = event.alert_id {
+                        details.insert("alert_id".to_string(), alert_id.to_string().into());
+                    }
+                }
+                let body = serde_json::json!({
+                    "message": incident.summary.chars().take(130).collect::<String>(),
+                    "alias": incident.dedup_key,
+                    "description": incident.detail,
+                    "priority": config.priority,
+                    "tags": config.tags,
+                    "details": details,
+                    "source": "sol-wallet-monitor",
+                });
+                (config.url.clone(), body)
+            }
+            IncidentAction::Resolve => {
+                // 以 alias 指定要關閉的告警
+                let Ok(mut url) = reqwest::Url::parse(&config.url) else {
+                    error!("❌ Opsgenie url 格式錯誤: {}", config.url);
+                    continue;
+                };
+                if let Ok(mut segments) = url.path_segments_mut() {
+                    segments.pop_if_empty().push(&incident.dedup_key).push("close");
+                }
+                url.set_query(Some("identifierType=alias"));
+                (url.to_string(), serde_json::json!({ "source": "sol-wallet-monitor" }))
+            }
+        };
+
+        if post_json_with_retry(&client, &url, &body, config.max_retries, "Opsgenie").await {
+            debug!("🚨 Opsgenie 事件已送出: {:?} {}", incident.action, incident.dedup_key);
+        }
+    }
+}
+
+// 以 {{wallet.name}}、{{delta}}、{{tx}} 與事件欄位 (例如 {{balance_type}}) 替換佔位符，{{text}} 為預設的通知內容
+fn render_notification_template(template: &str, event: &BalanceChangeEvent) -> String {
+    let mut values = vec![
+        ("wallet.name".to_string(), event.name.clone()),
+        ("wallet.address".to_string(), event.address.clone()),
+        ("wallet.group".to_string(), event.group.clone().unwrap_or_default()),
+        ("delta".to_string(), format!("{:+.6}", event.delta)),
+        ("tx".to_string(), event.transfer.as_ref().map(|t| format!("https://solscan.io/tx/{}", t.signature)).unwrap_or_default()),
+    ];
+    if let Ok(serde_json::Value::Object(fields)) = serde_json::to_value(event) {
+        for (key, value) in fields {
+            let value = match value {
+                serde_json::Value::String(s) => s,
+                serde_json::Value::Number(n) if n.is_f64() => format!("{:.6}", n.as_f64().unwrap_or_default()),
+                other => other.to_string(),
+            };
+            values.push((key, value));
+        }
+    }
+    let mut rendered = template.to_string();
+    for (key, value) in values {
+        rendered = rendered.replace(&format!("{{{{{}}}}}", key), &value);
+    }
+    rendered.replace("{{text}}", &notification_text(event))
+}
+
+// 路由模板產生的內容優先，否則為預設格式
+fn notification_text(event: &BalanceChangeEvent) -> String {
+    event.message.clone().unwrap_or_else(|| format_notification_text(event))
+}
+
+// 每日摘要最多保留的事件數，超過的只計數
+const MAX_DIGEST_EVENTS: usize = 10_000;
+
+fn format_email_digest(events: &[BalanceChangeEvent], dropped: usize) -> String {
+    let wallets: std::collections::HashSet<&str> = events.iter().map(|e| e.address.as_str()).collect();
+    let mut body = format!("共 {} 筆事件，{} 個錢包\n", events.len() + dropped, wallets.len());
+    if dropped > 0 {
+        body.push_str(&format!("(另有 {} 筆超過摘要上限未列出)\n", dropped));
+    }
+    body.push('\n');
+    for event in events {
+        let label = event.alert.as_deref().map(|alert| format!("[{}] ", alert)).unwrap_or_default();
+        body.push_str(&format!(
+            "{} {}{} ({}) {} {:+.6} → 總計 {:.6}\n",
+            event.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            label,
+            event.name,
+            &event.address[..8],
+            event.balance_type.to_uppercase(),
+            event.delta,
+            event.total_balance,
+        ));
+    }
+    body
+}
+
+// 距離下一次寄出每日摘要的時間
+fn until_next_digest(hour: u32) -> Duration {
+    let now = Utc::now();
+    let today = now.date_naive().and_hms_opt(hour % 24, 0, 0).unwrap_or_default().and_utc();
+    let next = if today > now { today } else { today + chrono::Duration::days(1) };
+    (next - now).to_std().unwrap_or_default()
+}
+
+struct EmailMailer {
+    transport: lettre::AsyncSmtpTransport<lettre::Tokio1Executor>,
+    from: lettre::message::Mailbox,
+    to: Vec<lettre::message::Mailbox>,
+}
+
+impl EmailMailer {
+    fn new(config: &EmailConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        type Transport = lettre::AsyncSmtpTransport<lettre::Tokio1Executor>;
+        if config.to.is_empty() {
+            return Err("未設定收件人 (to)".into());
+        }
+        let mut builder = match config.tls {
+            SmtpTls::Starttls => Transport::starttls_relay(&config.smtp_host)?,
+            SmtpTls::Tls => Transport::relay(&config.smtp_host)?,
+            SmtpTls::None => Transport::builder_dangerous(&config.smtp_host),
+        };
+        if let Some(port) = config.smtp_port {
+            builder = builder.port(port);
+        }
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(lettre::transport::smtp::authentication::Credentials::new(username.clone(), password.clone()));
+        }
+        Ok(Self {
+            transport: builder.timeout(Some(Duration::from_secs(config.timeout_secs))).build(),
+            from: config.from.parse()?,
+            to: config.to.iter().map(|to| to.parse()).collect::<Result<_, _>>()?,
+        })
+    }
+
+    // 連線錯誤與 4xx 暫時性錯誤以指數退避重試
+    async fn send(&self, subject: &str, body: String, max_retries: u32) -> bool {
+        use lettre::AsyncTransport;
+
+        let mut builder = lettre::Message::builder()
+            .from(self.from.clone())
+            .subject(subject)
+            .header(lettre::message::header::ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            builder = builder.to(to.clone());
+        }
+        let message = match builder.body(body) {
+            Ok(message) => message,
+            Err(e) => {
+                error!("❌ 建立 Email 失敗: {}", e);
+                return false;
+            }
+        };
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match self.transport.send(message.clone()).await {
+                Ok(_) => return true,
+                Err(e) if e.is_permanent() => {
+                    error!("❌ Email 通知發送失敗: {}", e);
+                    return false;
+                }
+                Err(e) => warn!("⚠️ Email 通知暫時失敗: {}，第 {} 次重試", e, attempt),
+            }
+            if attempt > max_retries {
+                error!("❌ Email 通知重試 {} 次後放棄: {}", max_retries, subject);
+                return false;
+            }
+            tokio::time::sleep(Duration::from_secs(1 << (attempt - 1).min(6))).await;
+        }
+    }
+}
+
+// digest 模式下一般餘額變化累積到每日固定時間合併寄出，告警仍立即寄出
+async fn email_sender_task(config: EmailConfig, mailer: EmailMailer, mut receiver: tokio::sync::mpsc::Receiver<BalanceChangeEvent>) {
+    let mut digest = Vec::new();
+    let mut dropped = 0;
+    let digest_timer = tokio::time::sleep(until_next_digest(config.digest_hour));
+    tokio::pin!(digest_timer);
+
+    loop {
+        tokio::select! {
+            event = receiver.recv() => match event {
+                Some(event) if config.digest && event.alert.is_none() => {
+                    if digest.len() < MAX_DIGEST_EVENTS {
+                        digest.push(event);
+                    } else {
+                        dropped += 1;
+                    }
+                }
+                Some(event) => {
+                    let subject = render_notification_template(&config.subject, &event);
+                    let body = render_notification_template(&config.body, &event);
+                    if mailer.send(&subject, body, config.max_retries).await {
+                        debug!("📨 Email 通知已發送: {} ({})", event.name, &event.address[..8]);
+                    }
+                }
+                None => break,
+            },
+            _ = &mut digest_timer, if config.digest => {
+                send_email_digest(&config, &mailer, &mut digest, &mut dropped).await;
+                digest_timer.as_mut().reset(tokio::time::Instant::now() + until_next_digest(config.digest_hour));
+            }
+        }
+    }
+    // 設定重新載入或程式關閉時寄出尚未寄出的摘要
+    send_email_digest(&config, &mailer, &mut digest, &mut dropped).await;
+}
+
+async fn send_email_digest(config: &EmailConfig, mailer: &EmailMailer, digest: &mut Vec<BalanceChangeEvent>, dropped: &mut usize) {
+    if digest.is_empty() && *dropped == 0 {
+        return;
+    }
+    let count = digest.len() + *dropped;
+    let subject = config
+        .digest_subject
+        .replace("{{date}}", &Utc::now().format("%Y-%m-%d").to_string())
+        .replace("{{count}}", &count.to_string());
+    if mailer.send(&subject, format_email_digest(digest, *dropped), config.max_retries).await {
+        info!("📨 Email 每日摘要已寄出 ({} 筆事件)", count);
+    }
+    digest.clear();
+    *dropped = 0;
+}
+
+#[derive(Debug, Clone, Copy)]
+enum StreamFormat {
+    Json,
+    Avro,
+}
+
+impl StreamFormat {
+    fn encode(self, event: &BalanceChangeEvent) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            StreamFormat::Json => Ok(serde_json::to_vec(event)?),
+            #[cfg(any(feature = "kafka", feature = "nats"))]
+            StreamFormat::Avro => avro_event_datum(event),
+            #[cfg(not(any(feature = "kafka", feature = "nats")))]
+            StreamFormat::Avro => Err("Avro 格式需以 --features kafka 或 nats 編譯".into()),
+        }
+    }
+}
+
+fn start_event_stream(config: &StreamConfig) -> Option<tokio::sync::mpsc::Sender<BalanceChangeEvent>> {
+    let format = match config.format.as_str() {
+        "json" => StreamFormat::Json,
+        "avro" => StreamFormat::Avro,
+        other => {
+            error!("❌ 不支援的事件串流格式: {}", other);
+            return None;
+        }
+    };
+    let (tx, rx) = tokio::sync::mpsc::channel(config.queue_size.max(1));
+    tokio::spawn(event_stream_task(config.clone(), format, rx));
+    info!("📡 事件串流已啟用 ({} {} → {}，{:?})", config.kind, config.servers, config.topic, format);
+    Some(tx)
+}
+
+type StreamResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// 事件串流的傳送端：Kafka 以錢包地址作為 key，同一錢包的事件落在同一個 partition；NATS 發布到 {topic}.{address}
+// send_event 依呼叫順序將事件排入傳送佇列，返回的 future 等待送達確認，不需要逐筆等待
+trait EventPublisher: Send + Sync {
+    fn send_event<'a>(&'a self, topic: &'a str, key: &'a str, payload: Vec<u8>) -> futures::future::BoxFuture<'a, StreamResult<futures::future::BoxFuture<'static, StreamResult<()>>>>;
+}
+
+#[cfg(feature = "kafka")]
+impl EventPublisher for rdkafka::producer::FutureProducer {
+    fn send_event<'a>(&'a self, topic: &'a str, key: &'a str, payload: Vec<u8>) -> futures::future::BoxFuture<'a, StreamResult<futures::future::BoxFuture<'static, StreamResult<()>>>> {
+        use rdkafka::{error::KafkaError, types::RDKafkaErrorCode};
+        Box::pin(async move {
+            loop {
+                match self.send_result(rdkafka::producer::FutureRecord::to(topic).key(key).payload(&payload)) {
+                    Ok(delivery) => {
+                        let delivery: futures::future::BoxFuture<'static, StreamResult<()>> = Box::pin(async move {
+                            match delivery.await {
+                                Ok(Ok(_)) => Ok(()),
+                                Ok(Err((e, _))) => Err(e.into()),
+                                Err(_) => Err("Kafka producer 已關閉".into()),
+                            }
+                        });
+                        return Ok(delivery);
+                    }
+                    // 本地佇列已滿時等待 librdkafka 送出一部分後再排入
+                    Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), _)) => tokio::time::sleep(Duration::from_millis(100)).await,
+                    Err((e, _)) => return Err(e.into()),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(feature = "nats")]
+impl EventPublisher for async_nats::Client {
+    fn send_event<'a>(&'a self, topic: &'a str, key: &'a str, payload: Vec<u8>) -> futures::future::BoxFuture<'a, StreamResult<futures::future::BoxFuture<'static, StreamResult<()>>>> {
+        Box::pin(async move {
+            // core NATS 沒有送達確認，排入客戶端的傳送佇列即完成
+            self.publish(format!("{}.{}", topic, key), payload.into()).await?;
+            let delivered: futures::future::BoxFuture<'static, StreamResult<()>> = Box::pin(futures::future::ready(Ok(())));
+            Ok(delivered)
+        })
+    }
+}
+
+#[cfg(feature = "nats")]
+const STREAM_MAX_RETRY_DELAY_SECS: u64 = 60; // NATS 連線重試的最長間隔
+
+async fn connect_event_publisher(config: &StreamConfig) -> StreamResult<Box<dyn EventPublisher>> {
+    match config.kind.as_str() {
+        #[cfg(feature = "kafka")]
+        "kafka" => {
+            let producer: rdkafka::producer::FutureProducer = rdkafka::ClientConfig::new()
+                .set("bootstrap.servers", &config.servers)
+                .set("message.timeout.ms", "30000")
+                .create()?;
+            Ok(Box::new(producer))
+        }
+        #[cfg(not(feature = "kafka"))]
+        "kafka" => Err("Kafka 串流需以 --features kafka 編譯".into()),
+        #[cfg(feature = "nats")]
+        "nats" => {
+            // 啟動時 NATS 伺服器可能尚未就緒，以指數退避重試；連線後由客戶端自行重連
+            let mut delay = Duration::from_secs(1);
+            loop {
+                match async_nats::connect(&config.servers).await {
+                    Ok(client) => return Ok(Box::new(client)),
+                    Err(e) => {
+                        warn!("⚠️ NATS 連線失敗: {}，{} 秒後重試", e, delay.as_secs());
+                        tokio::time::sleep(delay).await;
+                        delay = (delay * 2).min(Duration::from_secs(STREAM_MAX_RETRY_DELAY_SECS));
+                    }
+                }
+            }
+        }
+        #[cfg(not(feature = "nats"))]
+        "nats" => Err("NATS 串流需以 --features nats 編譯".into()),
+        other => Err(format!("不支援的事件串流類型: {}", other).into()),
+    }
+}
+
+async fn event_stream_task(config: StreamConfig, format: StreamFormat, mut receiver: tokio::sync::mpsc::Receiver<BalanceChangeEvent>) {
+    let publisher = match connect_event_publisher(&config).await {
+        Ok(publisher) => publisher,
+        Err(e) => {
+            error!("❌ 事件串流連線失敗: {}", e);
+            return;
+        }
+    };
+
+    // 送達確認在背景等待，事件依收到的順序排入傳送佇列
+    let mut deliveries = futures::stream::FuturesUnordered::new();
+    loop {
+        tokio::select! {
+            event = receiver.recv() => {
+                let Some(event) = event else {
+                    break;
+                };
+                let payload = match format.encode(&event) {
+                    Ok(payload) => payload,
+                    Err(e) => {
+                        error!("❌ 編碼串流事件失敗: {}", e);
+                        continue;
+                    }
+                };
+                let topic = match (&event.alert, &config.alerts_topic) {
+                    (Some(_), Some(alerts_topic)) => alerts_topic,
+                    _ => &config.topic,
+                };
+                match publisher.send_event(topic, &event.address, payload).await {
+                    Ok(delivery) => deliveries.push(delivery),
+                    Err(e) => warn!("⚠️ 事件串流送出失敗: {}", e),
+                }
+            }
+            Some(result) = deliveries.next(), if !deliveries.is_empty() => {
+                if let Err(e) = result {
+                    warn!("⚠️ 事件串流送出失敗: {}", e);
+                }
+            }
+        }
+    }
+    while let Some(result) = deliveries.next().await {
+        if let Err(e) = result {
+            warn!("⚠️ 事件串流送出失敗: {}", e);
+        }
+    }
+}
+
+// Avro 訊息為不含 header 的 datum，讀取端使用下列 schema 解碼
+#[cfg(any(feature = "kafka", feature = "nats"))]
+const BALANCE_EVENT_AVRO_SCHEMA: &str = r#"{
+  "type": "record",
+  "name": "BalanceChangeEvent",
+  "namespace": "sol_wallet_monitor",
+  "fields": [
+    {"name": "address", "type": "string"},
+    {"name": "name", "type": "string"},
+    {"name": "group", "type": ["null", "string"], "default": null},
+    {"name": "balance_type", "type": "string"},
+    {"name": "old_balance", "type": "double"},
+    {"name": "new_balance", "type": "double"},
+    {"name": "delta", "type": "double"},
+    {"name": "total_balance", "type": "double"},
+    {"name": "timestamp", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+    {"name": "slot", "type": "long"},
+    {"name": "account_event", "type": ["null", "string"], "default": null},
+    {"name": "mint", "type": ["null", "string"], "default": null},
+    {"name": "alert", "type": ["null", "string"], "default": null},
+    {"name": "severity", "type": ["null", "string"], "default": null},
+    {"name": "alert_id", "type": ["null", "long"], "default": null},
+    {"name": "signature", "type": ["null", "string"], "default": null},
+    {"name": "counterparty", "type": ["null", "string"], "default": null}
+  ]
+}"#;
+
+#[cfg(any(feature = "kafka", feature = "nats"))]
+fn avro_event_datum(event: &BalanceChangeEvent) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    use apache_avro::types::Value;
+    static SCHEMA: std::sync::OnceLock<apache_avro::Schema> = std::sync::OnceLock::new();
+    let schema = SCHEMA.get_or_init(|| apache_avro::Schema::parse_str(BALANCE_EVENT_AVRO_SCHEMA).expect("Avro schema 格式錯誤"));
+
+    let optional = |value: Option<Value>| match value {
+        Some(value) => Value::Union(1, Box::new(value)),
+        None => Value::Union(0, Box::new(Value::Null)),
+    };
+    let optional_string = |value: Option<String>| optional(value.map(Value::String));
+    let enum_name = |value: Option<serde_json::Value>| value.and_then(|v| v.as_str().map(str::to_string));
+    let record = Value::Record(vec![
+        ("address".to_string(), Value::String(event.address.clone())),
+        ("name".to_string(), Value::String(event.name.clone())),
+        ("group".to_string(), optional_string(event.group.clone())),
+        ("balance_type".to_string(), Value::String(event.balance_type.clone())),
+        ("old_balance".to_string(), Value::Double(event.old_balance)),
+        ("new_balance".to_string(), Value::Double(event.new_balance)),
+        ("delta".to_string(), Value::Double(event.delta)),
+        ("total_balance".to_string(), Value::Double(event.total_balance)),
+        ("timestamp".to_string(), Value::TimestampMillis(event.timestamp.timestamp_millis())),
+        ("slot".to_string(), Value::Long(event.slot as i64)),
+        ("account_event".to_string(), optional_string(enum_name(event.account_event.and_then(|e| serde_json::to_value(e).ok())))),
+        ("mint".to_string(), optional_string(event.mint.clone())),
+        ("alert".to_string(), optional_string(event.alert.clone())),
+        ("severity".to_string(), optional_string(enum_name(event.severity.and_then(|s| serde_json::to_value(s).ok())))),
+        ("alert_id".to_string(), optional(event.alert_id.map(|id| Value::Long(id as i64)))),
+        ("signature".to_string(), optional_string(event.transfer.as_ref().map(|t| t.signature.clone()))),
+        ("counterparty".to_string(), optional_string(event.transfer.as_ref().and_then(|t| t.counterparty.clone()))),
+    ]);
+    Ok(apache_avro::to_avro_datum(schema, record)?)
+}
+
+// 歷史記錄匯出到外部分析資料庫 (ClickHouse / Postgres)
+enum ExportCommand {
+    Record(WalletHistoryRecord),
+    Flush(tokio::sync::oneshot::Sender<()>),
+}
+
+#[derive(Clone)]
+struct HistoryExporter {
+    sender: tokio::sync::mpsc::Sender<ExportCommand>,
+}
+
+impl HistoryExporter {
+    fn start(config: &ExportConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let sink = ExportSink::new(config)?;
+        let (sender, receiver) = tokio::sync::mpsc::channel(config.queue_size.max(1));
+        tokio::spawn(history_export_task(config.clone(), sink, receiver));
+        info!("📤 歷史記錄匯出已啟用 ({} → {})", config.kind, config.table);
+        Ok(Self { sender })
+    }
+
+    fn export(&self, record: &WalletHistoryRecord) {
+        if let Err(e) = self.sender.try_send(ExportCommand::Record(record.clone())) {
+            warn!("⚠️ 匯出佇列已滿或已關閉，丟棄記錄: {}", e);
+        }
+    }
+
+    // 等待佇列中的記錄全部匯出 (或重試失敗後放棄)
+    async fn flush(&self) {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        if self.sender.send(ExportCommand::Flush(tx)).await.is_ok() {
+            let _ = rx.await;
+        }
+    }
+}
+
+enum ExportSink {
+    ClickHouse {
+        client: reqwest::Client,
+        url: reqwest::Url,
+        auth: Option<(String, Option<String>)>,
+        table: String,
+        schema_ready: bool,
+    },
+    #[cfg(feature = "postgres")]
+    Postgres {
+        dsn: String,
+        table: String,
+        client: Option<tokio_postgres::Client>,
+    },
+}
+
+impl ExportSink {
+    fn new(config: &ExportConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        match config.kind.as_str() {
+            "clickhouse" => {
+                // 帳號密碼從 URL 取出改用 Basic Auth，避免出現在查詢字串中
+                let mut url = reqwest::Url::parse(&config.dsn)?;
+                let auth = (!url.username().is_empty())
+                    .then(|| (url.username().to_string(), url.password().map(str::to_string)));
+                let _ = url.set_username("");
+                let _ = url.set_password(None);
+                Ok(ExportSink::ClickHouse {
+                    client: reqwest::Client::builder().timeout(Duration::from_secs(30)).build()?,
+                    url,
+                    auth,
+                    table: config.table.clone(),
+                    schema_ready: false,
+                })
+            }
+            #[cfg(feature = "postgres")]
+            "postgres" => Ok(ExportSink::Postgres {
+                dsn: config.dsn.clone(),
+                table: config.table.clone(),
+                client: None,
+            }),
+            #[cfg(not(feature = "postgres"))]
+            "postgres" => Err("Postgres 匯出需以 --features postgres 編譯".into()),
+            other => Err(format!("不支援的匯出類型: {}", other).into()),
+        }
+    }
+
+    async fn send(&mut self, records: &[WalletHistoryRecord]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            ExportSink::ClickHouse { client, url, auth, table, schema_ready } => {
+                if !*schema_ready {
+                    let create = format!(
+                        "CREATE TABLE IF NOT EXISTS {} (address String, timestamp DateTime64(3, 'UTC'), \
+                         sol_balance Float64, wsol_balance Float64, staked_balance Float64, lst_balance Float64, \
+                         total_balance Float64) ENGINE = ReplacingMergeTree ORDER BY (address, timestamp)",
+                        table
+                    );
+                    clickhouse_query(client, url, auth, &create, String::new()).await?;
+                    *schema_ready = true;
+                }
+
+                let mut body = String::new();
+                for record in records {
+                    let row = serde_json::json!({
+                        "address": record.address,
+                        "timestamp": record.timestamp.format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+                        "sol_balance": record.sol_balance,
+                        "wsol_balance": record.wsol_balance,
+                        "staked_balance": record.staked_balance,
+                        "lst_balance": record.lst_balance,
+                        "total_balance": record.total_balance,
+                    });
+                    body.push_str(&row.to_string());
+                    body.push('\n');
+                }
+                let insert = format!("INSERT INTO {} FORMAT JSONEachRow", table);
+                clickhouse_query(client, url, auth, &insert, body).await
+            }
+            #[cfg(feature = "postgres")]
+            ExportSink::Postgres { dsn, table, client } => {
+                if client.as_ref().is_none_or(|c| c.is_closed()) {
+                    *client = Some(connect_postgres(dsn, table).await?);
+                }
+                let Some(client) = client.as_mut() else {
+                    return Ok(());
+                };
+                postgres_insert(client, table, records).await
+            }
+        }
+    }
+}
+
+async fn clickhouse_query(
+    client: &reqwest::Client,
+    url: &reqwest::Url,
+    auth: &Option<(String, Option<String>)>,
+    query: &str,
+    body: String,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut url = url.clone();
+    url.query_pairs_mut().append_pair("query", query);
+    let mut request = client.post(url).body(body);
+    if let Some((user, password)) = auth {
+        request = request.basic_auth(user, password.as_ref());
+    }
+
+    let response = request.send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        return Err(format!("ClickHouse 回應 {}: {}", status, text.trim()).into());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "postgres")]
+async fn connect_postgres(dsn: &str, table: &str) -> Result<tokio_postgres::Client, Box<dyn std::error::Error + Send + Sync>> {
+    let (client, connection) = tokio_postgres::connect(dsn, tokio_postgres::NoTls).await?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            warn!("⚠️ Postgres 匯出連線中斷: {}", e);
+        }
+    });
+
+    client
+        .batch_execute(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                address TEXT NOT NULL,
+                timestamp TIMESTAMPTZ NOT NULL,
+                sol_balance DOUBLE PRECISION NOT NULL,
+                wsol_balance DOUBLE PRECISION NOT NULL,
+                staked_balance DOUBLE PRECISION NOT NULL,
+                lst_balance DOUBLE PRECISION NOT NULL,
+                total_balance DOUBLE PRECISION NOT NULL,
+                PRIMARY KEY (address, timestamp)
+            )",
+            table
+        ))
+        .await?;
+    info!("📤 已連線到 Postgres 匯出目標");
+    Ok(client)
+}
+
+#[cfg(feature = "postgres")]
+async fn postgres_insert(
+    client: &mut tokio_postgres::Client,
+    table: &str,
+    records: &[WalletHistoryRecord],
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    const COLUMNS: usize = 7;
+    // Postgres 單一語句最多 65535 個參數
+    const ROWS_PER_STATEMENT: usize = 1000;
+
+    let transaction = client.transaction().await?;
+    for chunk in records.chunks(ROWS_PER_STATEMENT) {
+        let mut query = format!(
+            "INSERT INTO {} (address, timestamp, sol_balance, wsol_balance, staked_balance, lst_balance, total_balance) VALUES ",
+            table
+        );
+        let mut params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = Vec::with_capacity(chunk.len() * COLUMNS);
+        for (i, record) in chunk.iter().enumerate() {
+            if i > 0 {
+                query.push(',');
+            }
+            let base = i * COLUMNS;
+            query.push_str(&format!(
+                "(${},${},${},${},${},${},${})",
+                base + 1, base + 2, base + 3, base + 4, base + 5, base + 6, base + 7
+            ));
+            params.extend_from_slice(&[
+                &record.address,
+                &record.timestamp,
+                &record.sol_balance,
+                &record.wsol_balance,
+                &record.staked_balance,
+                &record.lst_balance,
+                &record.total_balance,
+            ]);
+        }
+        query.push_str(" ON CONFLICT (address, timestamp) DO NOTHING");
+        transaction.execute(query.as_str(), &params).await?;
+    }
+    transaction.commit().await?;
+    Ok(())
+}
+
+// 以指數退避重試匯出一個批次，超過重試次數後放棄
+async fn export_batch_with_retry(sink: &mut ExportSink, records: &[WalletHistoryRecord], max_retries: u32) {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let error = match sink.send(records).await {
+            Ok(()) => {
+                debug!("📤 已匯出 {} 條歷史記錄", records.len());
+                return;
+            }
+            Err(e) => e.to_string(),
+        };
+
+        if attempt > max_retries {
+            error!("❌ 匯出重試 {} 次後放棄，遺失 {} 條記錄: {}", max_retries, records.len(), error);
+            return;
+        }
+        warn!("⚠️ 匯出歷史記錄失敗: {}，第 {} 次重試", error, attempt);
+        tokio::time::sleep(Duration::from_secs(1 << (attempt - 1).min(6))).await;
+    }
+}
+
+async fn history_export_task(config: ExportConfig, mut sink: ExportSink, mut receiver: tokio::sync::mpsc::Receiver<ExportCommand>) {
+    let mut batch = Vec::new();
+    let mut waiters = Vec::new();
+    let mut interval = tokio::time::interval(Duration::from_secs(config.flush_interval_secs.max(1)));
+
+    loop {
+        let (flush, closed) = tokio::select! {
+            command = receiver.recv() => match command {
+                Some(ExportCommand::Record(record)) => {
+                    batch.push(record);
+                    (batch.len() >= config.batch_size.max(1), false)
+                }
+                Some(ExportCommand::Flush(waiter)) => {
+                    waiters.push(waiter);
+                    (true, false)
+                }
+                None => (true, true),
+            },
+            _ = interval.tick() => (true, false),
+        };
+
+        if flush && !batch.is_empty() {
+            export_batch_with_retry(&mut sink, &batch, config.max_retries).await;
+            batch.clear();
+        }
+        for waiter in waiters.drain(..) {
+            let _ = waiter.send(());
+        }
+        if closed {
+            break;
+        }
+    }
+}
+
+// Web API handlers
+async fn get_wallets(
+    Query(params): Query<WalletsQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<([(header::HeaderName, String); 1], Json<Vec<WalletSummary>>), (StatusCode, Json<ErrorResponse>)> {
+    let sort = params.sort.as_deref().unwrap_or("name");
+    if !matches!(sort, "name" | "total_balance" | "last_update") {
+        return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse::with_detail(ErrorCode::UnsupportedSortField, sort))));
+    }
+    let descending = match params.order.as_deref() {
+        None => sort != "name",
+        Some("asc") => false,
+        Some("desc") => true,
+        Some(other) => {
+            return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse::with_detail(ErrorCode::UnsupportedSortOrder, other))));
+        }
+    };
+    let query = params.q.as_deref().map(|q| q.trim().to_lowercase()).filter(|q| !q.is_empty());
+
+    let wallets_guard = state.wallets.read().await;
+    let mut wallets: Vec<&WalletBalance> = wallets_guard
+        .values()
+        .filter(|w| w.archived == params.archived)
+        .filter(|w| params.tag.as_ref().is_none_or(|tag| w.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))))
+        .filter(|w| query.as_ref().is_none_or(|q| w.name.to_lowercase().contains(q) || w.address.to_lowercase().contains(q)))
+        .collect();
+
+    match sort {
+        "total_balance" => wallets.sort_by(|a, b| a.total_balance().total_cmp(&b.total_balance())),
+        "last_update" => wallets.sort_by_key(|w| w.last_update),
+        _ => wallets.sort_by(|a, b| a.name.cmp(&b.name)),
+    }
+    if descending {
+        wallets.reverse();
+    }
+
+    // 總數放在標頭，維持回應為陣列
+    let total = wallets.len();
+    let page_size = params.page_size.map(|size| size.clamp(1, 1000)).unwrap_or(total.max(1));
+    let summaries: Vec<WalletSummary> = wallets
+        .into_iter()
+        .skip(params.page.saturating_sub(1).saturating_mul(page_size))
+        .take(page_size)
+        .map(|w| w.to_summary(params.include_history, state.health.current_slot()))
+        .collect();
+    Ok(([(header::HeaderName::from_static("x-total-count"), total.to_string())], Json(summaries)))
+}
+
+// 顏色只接受 #RGB 或 #RRGGBB
+fn is_valid_color(color: &str) -> bool {
+    color
+        .strip_prefix('#')
+        .is_some_and(|hex| matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+// CSV 欄位跳脫
+fn csv_escape(field: &str) -> String {
+    if field.contains(',') || field.contains('"') || field.contains('\n') || field.contains('\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+async fn export_wallets(
+    Query(params): Query<ExportQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let mut entries: Vec<WalletExportEntry> = {
+        let wallets_guard = state.wallets.read().await;
+        wallets_guard
+            .values()
+            .map(|w| WalletExportEntry {
+                address: w.address.clone(),
+                name: w.name.clone(),
+                group: w.group.clone(),
+                tags: w.tags.clone(),
+                note: w.note.clone(),
+                color: w.color.clone(),
+            })
+            .collect()
+    };
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let (body, content_type, filename) = match params.format.as_str() {
+        "json" => {
+            let body = serde_json::to_string_pretty(&entries).map_err(|e| {
+                (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::with_detail(ErrorCode::SerializeFailed, e)))
+            })?;
+            (body, "application/json", "wallets.json")
+        }
+        "csv" => {
+            let mut body = String::from("address,name,group,tags,note,color\n");
+            for entry in &entries {
+                body.push_str(&format!(
+                    "{},{},{},{},{},{}\n",
+                    csv_escape(&entry.address),
+                    csv_escape(&entry.name),
+                    csv_escape(entry.group.as_deref().unwrap_or("")),
+                    csv_escape(&entry.tags.join(";")),
+                    csv_escape(entry.note.as_deref().unwrap_or("")),
+                    csv_escape(entry.color.as_deref().unwrap_or("")),
+                ));
+            }
+            (body, "text/csv; charset=utf-8", "wallets.csv")
+        }
+        other => {
+            return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse::with_detail(ErrorCode::UnsupportedExportFormat, other))));
+        }
+    };
+
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename))
+        .body(Body::from(body))
+        .unwrap())
+}
+
+async fn export_wallet_history_csv(
+    Path(address): Path<String>,
+    Query(params): Query<HistoryCsvQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    if !state.wallets.read().await.contains_key(&address) {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+    }
+
+    let from = params.from.and_then(|t| DateTime::from_timestamp(t, 0));
+    let to = params.to.and_then(|t| DateTime::from_timestamp(t, 0));
+    let filename = format!("{}_history.csv", address);
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<String, std::io::Error>>(16);
+    let database = state.database.clone();
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = database.stream_history_csv(&address, from, to, &tx) {
+            error!("❌ 匯出歷史 CSV 失敗: {}", e);
+            let _ = tx.blocking_send(Err(std::io::Error::other(e.to_string())));
+        }
+    });
+
+    let stream = futures::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|chunk| (chunk, rx)) });
+
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "text/csv; charset=utf-8")
+        .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename))
+        .body(Body::from_stream(stream))
+        .unwrap())
+}
+
+// 帳本式 CSV：每筆總餘額變化一列，附當時的 SOL 價格與 USD 金額，供匯入會計軟體
+async fn export_wallet_ledger_csv(
+    Path(address): Path<String>,
+    Query(params): Query<HistoryCsvQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    if !state.wallets.read().await.contains_key(&address) {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+    }
+
+    let from = params.from.and_then(|t| DateTime::from_timestamp(t, 0));
+    let to = params.to.and_then(|t| DateTime::from_timestamp(t, 0));
+    let filename = format!("{}_ledger.csv", address);
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<String, std::io::Error>>(16);
+    let database = state.database.clone();
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = send_ledger_csv(database.as_ref(), &address, from, to, &tx) {
+            error!("❌ 匯出帳本 CSV 失敗: {}", e);
+            let _ = tx.blocking_send(Err(std::io::Error::other(e.to_string())));
+        }
+    });
+
+    let stream = futures::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|chunk| (chunk, rx)) });
+
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "text/csv; charset=utf-8")
+        .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename))
+        .body(Body::from_stream(stream))
+        .unwrap())
+}
+
+async fn get_wallet_detail(
+    Path(address): Path<String>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<WalletSummary>, StatusCode> {
+    let history_loaded = match state.wallets.read().await.get(&address) {
+        Some(wallet) => wallet.history_loaded,
+        None => return Err(StatusCode::NOT_FOUND),
+    };
+
+    // 第一次查詢時才從資料庫載入最近的歷史記錄
+    if !history_loaded {
+        match state.database.load_recent_history(&address, MAX_HISTORY_SIZE) {
+            Ok(records) => {
+                if let Some(wallet) = state.wallets.write().await.get_mut(&address) {
+                    info!("📚 為錢包 {} 載入 {} 條歷史記錄", wallet.name, records.len());
+                    wallet.merge_history_from_db(records);
+                }
+            }
+            Err(e) => warn!("⚠️ 載入錢包 {} 歷史資料失敗: {}", address, e),
+        }
+    }
+
+    let wallets_guard = state.wallets.read().await;
+    match wallets_guard.get(&address) {
+        Some(wallet) => Ok(Json(wallet.to_summary(true, state.health.current_slot()))),
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+async fn get_wallet_transactions(
+    Path(address): Path<String>,
+    Query(params): Query<TransactionsQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<TransactionsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if !state.wallets.read().await.contains_key(&address) {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+    }
+
+    let page = params.page.max(1);
+    let page_size = params.page_size.clamp(1, 500);
+    match state.database.load_transactions(&address, page, page_size) {
+        Ok((total, transactions)) => Ok(Json(TransactionsResponse {
+            address,
+            page,
+            page_size,
+            total,
+            transactions,
+        })),
+        Err(e) => {
+            error!("❌ 讀取錢包交易記錄失敗: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::TransactionsReadFailed))))
+        }
+    }
+}
+
+async fn get_wallet_token_events(
+    Path(address): Path<String>,
+    Query(params): Query<TokenEventsQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<Vec<TokenAccountEvent>>, (StatusCode, Json<ErrorResponse>)> {
+    if !state.wallets.read().await.contains_key(&address) {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+    }
+
+    match state.database.load_token_events(&address, params.limit.clamp(1, 1000)) {
+        Ok(events) => Ok(Json(events)),
+        Err(e) => {
+            error!("❌ 讀取 token account 事件失敗: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::TokenEventsReadFailed))))
+        }
+    }
+}
+
+async fn get_chart_data(
+    Query(params): Query<ChartQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<Vec<ChartDataPoint>>, StatusCode> {
+    let addresses: Vec<String> = {
+        let wallets_guard = state.wallets.read().await;
+        if params.wallet == ALL_WALLETS {
+            wallets_guard.values().filter(|w| !w.archived).map(|w| w.address.clone()).collect()
+        } else if wallets_guard.contains_key(&params.wallet) {
+            vec![params.wallet.clone()]
+        } else {
+            return Err(StatusCode::NOT_FOUND);
+        }
+    };
+
+    let (from, to, interval) = chart_range(&params.interval, params.from, params.to);
+    let converter = load_chart_converter(state.database.as_ref(), state.config.price.as_ref(), params.currency.as_deref(), from, to)?;
+    if params.data_type == FEES_DATA_TYPE {
+        return build_fee_chart(state.database.as_ref(), &addresses, from, to, converter.as_ref(), params.ma, params.ema).map(Json);
+    }
+    let income_kind = match params.data_type.as_str() {
+        JITO_TIPS_DATA_TYPE => Some(IncomeKind::JitoTip),
+        BLOCK_REWARDS_DATA_TYPE => Some(IncomeKind::BlockReward),
+        _ => None,
+    };
+    if let Some(kind) = income_kind {
+        return build_income_chart(state.database.as_ref(), &addresses, kind, from, to, converter.as_ref(), params.ma, params.ema).map(Json);
+    }
+    if params.data_type == PNL_DATA_TYPE {
+        // 成本設定屬於單一錢包，不支援合計曲線
+        if params.wallet == ALL_WALLETS {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        let currency = converter.as_ref().map(|converter| converter.currency.as_str());
+        return build_pnl_chart(state.database.as_ref(), &params.wallet, from, to, currency, &params.sampling, params.ma, params.ema).map(Json);
+    }
+    let series = load_chart_series(state.database.as_ref(), &addresses, from, to)?;
+    // __all__ 為所有錢包的合計曲線
+    let mut history = if params.wallet == ALL_WALLETS {
+        aggregate_history(series.iter())
+    } else {
+        series.into_iter().next().unwrap_or_default()
+    };
+    if let Some(converter) = &converter {
+        history = converter.convert_history(&history).map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    }
+
+    Ok(Json(build_chart_data(history.iter().collect(), &params.data_type, interval, &params.sampling, params.ma, params.ema)))
+}
+
+// 圖表指定 currency 時載入時間範圍內的價格與匯率
+fn load_chart_converter(
+    db: &dyn HistoryStore,
+    config: Option<&PriceConfig>,
+    currency: Option<&str>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Option<FiatConverter>, StatusCode> {
+    let Some(currency) = fiat_currency(config, currency).map_err(|_| StatusCode::BAD_REQUEST)? else {
+        return Ok(None);
+    };
+    FiatConverter::load(db, &currency, from, to).map(Some).map_err(|e| {
+        error!("❌ 讀取 {} 價格與匯率失敗: {}", currency, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+// 指定 from/to 時使用明確的時間範圍，否則依 interval 往前推算；返回的 interval 用於取樣過濾
+fn chart_range(interval: &str, from: Option<i64>, to: Option<i64>) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>, &str) {
+    if from.is_some() || to.is_some() {
+        (
+            from.and_then(|t| DateTime::from_timestamp(t, 0)),
+            to.and_then(|t| DateTime::from_timestamp(t, 0)),
+            "ALL",
+        )
+    } else {
+        (interval_to_duration(interval).map(|d| Utc::now() - d), None, interval)
+    }
+}
+
+// USD 損益圖表：在每個餘額記錄與價格記錄的時間點，以當時的持倉成本、餘額與價格計算總損益；指定法幣時價格以當時匯率、成本以每筆成交當時的匯率換算
+#[allow(clippy::too_many_arguments)]
+fn build_pnl_chart(
+    db: &dyn HistoryStore,
+    address: &str,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    currency: Option<&str>,
+    sampling: &str,
+    ma: Option<usize>,
+    ema: Option<usize>,
+) -> Result<Vec<ChartDataPoint>, StatusCode> {
+    let read_error = |e: Box<dyn std::error::Error>| {
+        error!("❌ 讀取損益數據失敗: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    let basis = db.load_cost_basis(address).map_err(read_error)?.ok_or(StatusCode::NOT_FOUND)?;
+    let history = db.load_history_series(address, from, to).map_err(read_error)?;
+    let prices = db.load_prices(from, to).map_err(read_error)?;
+    // 成交可能早於圖表範圍，載入完整的匯率歷史
+    let rates = currency.map(|currency| FiatConverter::load_rates(db, currency)).transpose().map_err(read_error)?;
+    let rate_at = |timestamp: Option<DateTime<Utc>>| rates.as_ref().map_or(Ok(1.0), |rates| rates.usd_rate_near(timestamp));
+
+    // 依時間合併餘額與價格兩條序列，任一方變化時都產生一個點
+    let mut events: Vec<(DateTime<Utc>, Option<f64>, Option<f64>)> = history
+        .iter()
+        .map(|h| (h.timestamp, Some(h.total_balance), None))
+        .chain(prices.iter().map(|p| (p.timestamp, None, Some(p.usd))))
+        .collect();
+    events.sort_by_key(|(timestamp, _, _)| *timestamp);
+
+    let (mut balance, mut price) = (None, None);
+    let mut chart_data = Vec::new();
+    for (timestamp, new_balance, new_price) in events {
+        balance = new_balance.or(balance);
+        price = new_price.or(price);
+        let (Some(balance), Some(price)) = (balance, price) else {
+            continue;
+        };
+        let rate = rate_at(Some(timestamp)).map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+        let position = basis.position_at(timestamp, rate_at).map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+        chart_data.push(ChartDataPoint {
+            time: timestamp.timestamp(),
+            value: position.realized + position.unrealized(balance, price * rate),
+            ma: None,
+            ema: None,
+        });
+    }
+
+    // 同一秒內保留最後一個點
+    chart_data.reverse();
+    chart_data.dedup_by_key(|point| point.time);
+    chart_data.reverse();
+    apply_moving_averages(&mut chart_data, ma, ema);
+    Ok(sample_chart_data(chart_data, sampling))
+}
+
+// 每日手續費圖表，多個錢包時加總
+fn build_fee_chart(
+    db: &dyn HistoryStore,
+    addresses: &[String],
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    converter: Option<&FiatConverter>,
+    ma: Option<usize>,
+    ema: Option<usize>,
+) -> Result<Vec<ChartDataPoint>, StatusCode> {
+    let mut totals: BTreeMap<u64, f64> = BTreeMap::new();
+    for address in addresses {
+        let days = db.load_daily_fees(address, from, to).map_err(|e| {
+            error!("❌ 讀取手續費記錄失敗: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        for day in days {
+            *totals.entry(day.day_ms).or_default() += day.total_sol();
+        }
+    }
+    Ok(daily_chart(&totals, from, to, converter, ma, ema))
+}
+
+// 每日 Jito tips 或出塊獎勵圖表，多個錢包時加總
+#[allow(clippy::too_many_arguments)]
+fn build_income_chart(
+    db: &dyn HistoryStore,
+    addresses: &[String],
+    kind: IncomeKind,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    converter: Option<&FiatConverter>,
+    ma: Option<usize>,
+    ema: Option<usize>,
+) -> Result<Vec<ChartDataPoint>, StatusCode> {
+    let mut totals: BTreeMap<u64, f64> = BTreeMap::new();
+    for address in addresses {
+        let days = db.load_daily_income(address, from, to).map_err(|e| {
+            error!("❌ 讀取驗證者收入失敗: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        for day in days {
+            *totals.entry(day.day_ms).or_default() += day.sol(kind);
+        }
+    }
+    Ok(daily_chart(&totals, from, to, converter, ma, ema))
+}
+
+// 每天一個點 (當天 0 點 UTC)，期間內沒有記錄的日子補 0
+// 指定法幣時以當天結束 (或目前) 的價格換算，沒有價格的日子略過
+fn daily_chart(
+    totals: &BTreeMap<u64, f64>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    converter: Option<&FiatConverter>,
+    ma: Option<usize>,
+    ema: Option<usize>,
+) -> Vec<ChartDataPoint> {
+    let (Some(&first), Some(&last)) = (totals.keys().next(), totals.keys().next_back()) else {
+        return Vec::new();
+    };
+    let first = from.map(fee_day_millis).unwrap_or(first).min(first);
+    let last = fee_day_millis(to.unwrap_or_else(Utc::now)).max(last);
+    let now = Utc::now();
+    let mut chart_data: Vec<ChartDataPoint> = (first..=last)
+        .step_by(DAY_MS as usize)
+        .filter_map(|day_ms| {
+            let amount = totals.get(&day_ms).copied().unwrap_or(0.0);
+            let value = match converter {
+                Some(converter) => {
+                    let day_end = DateTime::from_timestamp_millis((day_ms + DAY_MS - 1) as i64).unwrap_or(now).min(now);
+                    amount * converter.sol_price_at(day_end).ok()?
+                }
+                None => amount,
+            };
+            Some(ChartDataPoint { time: (day_ms / 1000) as i64, value, ma: None, ema: None })
+        })
+        .collect();
+    apply_moving_averages(&mut chart_data, ma, ema);
+    chart_data
+}
+
+// 從資料庫讀取多個錢包在時間範圍內的歷史序列
+fn load_chart_series(
+    db: &dyn HistoryStore,
+    addresses: &[String],
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Vec<Vec<BalanceHistory>>, StatusCode> {
+    addresses
+        .iter()
+        .map(|address| {
+            db.load_history_series(address, from, to).map_err(|e| {
+                error!("❌ 讀取歷史數據失敗: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })
+        })
+        .collect()
+}
+
+async fn get_candles(
+    Query(params): Query<CandlesQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<Vec<Candle>>, (StatusCode, Json<ErrorResponse>)> {
+    let bucket_secs = interval_to_duration(&params.bucket.to_uppercase())
+        .map(|d| d.num_seconds())
+        .ok_or_else(|| {
+            (StatusCode::BAD_REQUEST, Json(ErrorResponse::with_detail(ErrorCode::UnsupportedBucket, &params.bucket)))
+        })?;
+
+    let addresses: Vec<String> = {
+        let wallets_guard = state.wallets.read().await;
+        if params.wallet == ALL_WALLETS {
+            wallets_guard.values().filter(|w| !w.archived).map(|w| w.address.clone()).collect()
+        } else if wallets_guard.contains_key(&params.wallet) {
+            vec![params.wallet.clone()]
+        } else {
+            return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+        }
+    };
+
+    // 區間對齊到 bucket 長度，未指定 from 時往前取 limit 個區間；
+    // from/to 來自查詢參數，超出可表示的時間範圍或運算溢位都視為範圍過大
+    let range_too_large = || (StatusCode::BAD_REQUEST, Json(ErrorResponse::new(ErrorCode::RangeTooLarge)));
+    let to = params.to.unwrap_or_else(|| Utc::now().timestamp());
+    let to_time = DateTime::from_timestamp(to, 0).ok_or_else(range_too_large)?;
+    let first_start = match params.from {
+        Some(from) => {
+            DateTime::from_timestamp(from, 0).ok_or_else(range_too_large)?;
+            from.checked_sub(from.rem_euclid(bucket_secs))
+        }
+        None => (params.limit.clamp(1, 1000) as i64 - 1)
+            .checked_mul(bucket_secs)
+            .and_then(|span| to.checked_sub(to.rem_euclid(bucket_secs))?.checked_sub(span)),
+    }
+    .ok_or_else(range_too_large)?;
+    let from_time = DateTime::from_timestamp(first_start, 0).ok_or_else(range_too_large)?;
+    match to.checked_sub(first_start) {
+        Some(span) if span / bucket_secs <= 10000 => {}
+        _ => return Err(range_too_large()),
+    }
+
+    let series = load_chart_series(state.database.as_ref(), &addresses, Some(from_time), Some(to_time))
+        .map_err(|status| (status, Json(ErrorResponse::new(ErrorCode::HistoryReadFailed))))?;
+    let history = if params.wallet == ALL_WALLETS {
+        aggregate_history(series.iter())
+    } else {
+        series.into_iter().next().unwrap_or_default()
+    };
+
+    Ok(Json(build_candles(&history, &params.data_type, first_start, to, bucket_secs)))
+}
+
+// 將歷史數據切成固定長度的 K 線；餘額為階梯函數，開盤價取區間開始時的餘額，
+// 沒有變化的區間以前一根收盤價補成平盤
+fn build_candles(history: &[BalanceHistory], data_type: &str, first_start: i64, end: i64, bucket_secs: i64) -> Vec<Candle> {
+    let mut candles = Vec::new();
+    let mut points = history
+        .iter()
+        .map(|h| (h.timestamp.timestamp(), history_value(h, data_type)))
+        .filter(|(_, value)| value.is_finite())
+        .peekable();
+    // first_start 當下 (含之前) 的餘額作為第一根 K 線的開盤價，第一次變化之前的區間也以此補成平盤；
+    // load_history_series 會把 from 之前的最後一筆以 from 為時間放在最前面
+    let mut last_close: Option<f64> = None;
+    while let Some((_, value)) = points.next_if(|(time, _)| *time <= first_start) {
+        last_close = Some(value);
+    }
+
+    // 先算好區間數再逐一產生，避免 start 累加到 end 附近時溢位
+    let buckets = match end.checked_sub(first_start) {
+        Some(span) if span >= 0 => span / bucket_secs + 1,
+        _ => 0,
+    };
+    for index in 0..buckets {
+        let start = first_start + index * bucket_secs;
+        let bucket_end = start.saturating_add(bucket_secs);
+        let mut candle = last_close.map(|close| Candle { time: start, open: close, high: close, low: close, close });
+
+        while let Some((_, value)) = points.next_if(|(time, _)| *time < bucket_end) {
+            match candle.as_mut() {
+                Some(candle) => {
+                    candle.high = candle.high.max(value);
+                    candle.low = candle.low.min(value);
+                    candle.close = value;
+                }
+                None => {
+                    candle = Some(Candle { time: start, open: value, high: value, low: value, close: value });
+                }
+            }
+        }
+
+        if let Some(candle) = candle {
+            last_close = Some(candle.close);
+            candles.push(candle);
+        }
+    }
+
+    candles
+}
+
+// 依時間範圍過濾並採樣歷史數據為圖表點
+fn build_chart_data(
+    mut history: Vec<&BalanceHistory>,
+    data_type: &str,
+    interval: &str,
+    sampling: &str,
+    ma: Option<usize>,
+    ema: Option<usize>,
+) -> Vec<ChartDataPoint> {
+    // 排序歷史數據以確保時間順序
+    history.sort_by_key(|h| h.timestamp);
+    
+    // 根據時間範圍過濾數據
+    let now = Utc::now();
+    let filtered_history: Vec<_> = match interval {
+        "5M" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_minutes() <= 5).collect(),
+        "10M" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_minutes() <= 10).collect(),
+        "30M" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_minutes() <= 30).collect(),
+        "1H" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_hours() <= 1).collect(),
+        "2H" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_hours() <= 2).collect(),
+        "4H" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_hours() <= 4).collect(),
+        "8H" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_hours() <= 8).collect(),
+        "12H" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_hours() <= 12).collect(),
+        "1D" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_days() <= 1).collect(),
+        "1W" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_weeks() <= 1).collect(),
+        _ => history,
+    };
+    
+    let mut chart_data: Vec<ChartDataPoint> = filtered_history
+        .iter()
+        .filter_map(|h| {
+            let value = history_value(h, data_type);
+            
+            // 過濾掉無效數值
+            if value.is_finite() && !value.is_nan() {
+                Some(ChartDataPoint {
+                    time: h.timestamp.timestamp(),
+                    value,
+                    ma: None,
+                    ema: None,
+                })
+            } else {
+                None
+            }
+        })
+        .collect();
+    
+    // 去除重複時間戳（保留最新的）
+    chart_data.dedup_by_key(|point| point.time);
+    
+    // 在採樣前以完整數據計算移動平均
+    apply_moving_averages(&mut chart_data, ma, ema);
+    
+    let sampled_data = sample_chart_data(chart_data, sampling);
+
+    info!("📊 圖表數據準備完成: {} 點 (時間範圍: {})", sampled_data.len(), interval);
+    
+    sampled_data
+}
+
+// 採樣到 1000 筆數據 (預設 LTTB，保留尖峰與低谷)
+fn sample_chart_data(chart_data: Vec<ChartDataPoint>, sampling: &str) -> Vec<ChartDataPoint> {
+    if chart_data.len() > 1000 {
+        let sampled = match sampling {
+            "uniform" => sample_uniform(&chart_data, 1000),
+            _ => sample_lttb(&chart_data, 1000),
+        };
+        info!("📊 圖表數據採樣 ({}): 原始 {} 點 -> 採樣 {} 點", sampling, chart_data.len(), sampled.len());
+        sampled
+    } else {
+        info!("📊 圖表數據無需採樣: {} 點 (上限: 1000 點)", chart_data.len());
+        chart_data
+    }
+}
+
+// 計算簡單移動平均 (資料點不足 period 時不輸出) 與指數移動平均
+fn apply_moving_averages(chart_data: &mut [ChartDataPoint], ma: Option<usize>, ema: Option<usize>) {
+    if let Some(period) = ma.filter(|p| *p > 0) {
+        let mut sum = 0.0;
+        for i in 0..chart_data.len() {
+            sum += chart_data[i].value;
+            if i >= period {
+                sum -= chart_data[i - period].value;
+            }
+            if i + 1 >= period {
+                chart_data[i].ma = Some(sum / period as f64);
+            }
+        }
+    }
+
+    if let Some(period) = ema.filter(|p| *p > 0) {
+        let alpha = 2.0 / (period as f64 + 1.0);
+        let mut current: Option<f64> = None;
+        for point in chart_data.iter_mut() {
+            let next = match current {
+                Some(prev) => alpha * point.value + (1.0 - alpha) * prev,
+                None => point.value,
+            };
+            point.ema = Some(next);
+            current = Some(next);
+        }
+    }
+}
+
+// 基於時間的均勻採樣：取最接近每個目標時間的數據點
+fn sample_uniform(chart_data: &[ChartDataPoint], threshold: usize) -> Vec<ChartDataPoint> {
+    let start_time = chart_data.first().unwrap().time;
+    let end_time = chart_data.last().unwrap().time;
+    let time_span = end_time - start_time;
+    
+    if time_span <= 0 {
+        // 如果時間跨度為0，直接返回原數據
+        return chart_data.to_vec();
+    }
+    
+    let mut sampled = Vec::new();
+    let sample_interval = time_span as f64 / (threshold - 1) as f64;
+    
+    for i in 0..threshold {
+        let target_time = start_time + (i as f64 * sample_interval) as i64;
+        
+        // 找到最接近目標時間的數據點
+        let closest_point = chart_data.iter()
+            .min_by_key(|point| (point.time - target_time).abs())
+            .unwrap();
+        
+        sampled.push(closest_point.clone());
+    }
+    
+    // 去除重複的時間點，保持時間順序
+    sampled.sort_by_key(|point| point.time);
+    sampled.dedup_by_key(|point| point.time);
+    sampled
+}
+
+// Largest-Triangle-Three-Buckets 降採樣：保留首尾兩點，中間每個區間選出
+// 與前一個選取點及下一區間平均點構成最大三角形面積的點
+fn sample_lttb(chart_data: &[ChartDataPoint], threshold: usize) -> Vec<ChartDataPoint> {
+    if threshold < 3 || chart_data.len() <= threshold {
+        return chart_data.to_vec();
+    }
+
+    let mut sampled = Vec::with_capacity(threshold);
+    let bucket_size = (chart_data.len() - 2) as f64 / (threshold - 2) as f64;
+    let mut selected = 0;
+    sampled.push(chart_data[0].clone());
+
+    for i in 0..threshold - 2 {
+        let bucket_start = (i as f64 * bucket_size) as usize + 1;
+        let bucket_end = ((i + 1) as f64 * bucket_size) as usize + 1;
+
+        // 下一區間的平均點 (最後一個區間使用最後一點)
+        let next_start = bucket_end;
+        let next_end = (((i + 2) as f64 * bucket_size) as usize + 1).min(chart_data.len());
+        let next = &chart_data[next_start..next_end.max(next_start + 1)];
+        let avg_time = next.iter().map(|p| p.time as f64).sum::<f64>() / next.len() as f64;
+        let avg_value = next.iter().map(|p| p.value).sum::<f64>() / next.len() as f64;
+
+        let a = &chart_data[selected];
+        let (a_time, a_value) = (a.time as f64, a.value);
+        let mut max_area = -1.0;
+        for (index, point) in chart_data.iter().enumerate().take(bucket_end).skip(bucket_start) {
+            let area = ((a_time - avg_time) * (point.value - a_value)
+                - (a_time - point.time as f64) * (avg_value - a_value))
+                .abs();
+            if area > max_area {
+                max_area = area;
+                selected = index;
+            }
+        }
+        sampled.push(chart_data[selected].clone());
+    }
+
+    sampled.push(chart_data[chart_data.len() - 1].clone());
+    sampled
+}
+
+async fn get_wallet_flows(
+    Path(address): Path<String>,
+    Query(params): Query<FlowsQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<FlowsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let bucket_size = interval_to_duration(&params.interval).ok_or_else(|| {
+        (StatusCode::BAD_REQUEST, Json(ErrorResponse::with_detail(ErrorCode::UnsupportedInterval, &params.interval)))
+    })?;
+    let bucket_secs = bucket_size.num_seconds();
+    let bucket_count = params.buckets.clamp(1, 1000) as i64;
+
+    if !state.wallets.read().await.contains_key(&address) {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+    }
+
+    // 以區間長度對齊，最後一個區間包含現在
+    let now = Utc::now().timestamp();
+    let last_start = now - now.rem_euclid(bucket_secs);
+    let first_start = last_start - (bucket_count - 1) * bucket_secs;
+
+    let mut buckets: Vec<FlowBucket> = (0..bucket_count)
+        .map(|i| FlowBucket {
+            start: first_start + i * bucket_secs,
+            end: first_start + (i + 1) * bucket_secs,
+            inflow: 0.0,
+            outflow: 0.0,
+            net: 0.0,
+        })
+        .collect();
+
+    let history = state.database.load_history_series(&address, DateTime::from_timestamp(first_start, 0), None).map_err(|e| {
+        error!("❌ 讀取歷史數據失敗: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::HistoryReadFailed)))
+    })?;
+
+    // 相鄰兩點的變化量計入後一點所在的區間
+    for pair in history.windows(2) {
+        let time = pair[1].timestamp.timestamp();
+        if time < first_start {
+            continue;
+        }
+        let delta = history_value(&pair[1], &params.data_type) - history_value(&pair[0], &params.data_type);
+        if !delta.is_finite() {
+            continue;
+        }
+        let index = ((time - first_start) / bucket_secs) as usize;
+        if let Some(bucket) = buckets.get_mut(index) {
+            if delta > 0.0 {
+                bucket.inflow += delta;
+            } else {
+                bucket.outflow += -delta;
+            }
+            bucket.net += delta;
+        }
+    }
+
+    let total_inflow: f64 = buckets.iter().map(|b| b.inflow).sum();
+    let total_outflow: f64 = buckets.iter().map(|b| b.outflow).sum();
+
+    Ok(Json(FlowsResponse {
+        address,
+        interval: params.interval,
+        data_type: params.data_type,
+        total_inflow,
+        total_outflow,
+        net: total_inflow - total_outflow,
+        buckets,
+    }))
+}
+
+async fn get_wallet_stats(
+    Path(address): Path<String>,
+    Query(params): Query<StatsQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<WalletStatsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if !state.wallets.read().await.contains_key(&address) {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+    }
+
+    let from = interval_to_duration(&params.interval).map(|d| Utc::now() - d);
+    let history = state.database.load_history_series(&address, from, None).map_err(|e| {
+        error!("❌ 讀取歷史數據失敗: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::HistoryReadFailed)))
+    })?;
+
+    let fees = state.database.load_daily_fees(&address, from, None).map_err(|e| {
+        error!("❌ 讀取手續費記錄失敗: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::TransactionsReadFailed)))
+    })?;
+    let rewards = state.database.load_stake_rewards(&address, from, None).map_err(|e| {
+        error!("❌ 讀取質押獎勵失敗: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::RewardsReadFailed)))
+    })?;
+    let income = state.database.load_daily_income(&address, from, None).map_err(|e| {
+        error!("❌ 讀取驗證者收入失敗: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::IncomeReadFailed)))
+    })?;
+
+    let stats: BTreeMap<String, BalanceStats> = DATA_TYPES
+        .iter()
+        .filter_map(|&data_type| compute_balance_stats(&history, data_type).map(|s| (data_type.to_string(), s)))
+        .collect();
+    let staked_change = stats.get("staked").map(|s| s.change).unwrap_or(0.0);
+
+    Ok(Json(WalletStatsResponse {
+        address,
+        interval: params.interval,
+        samples: history.len(),
+        stats,
+        fees: FeeStats::from_days(&fees),
+        rewards: RewardStats::new(&rewards, staked_change),
+        income: IncomeStats::from_days(&income),
+    }))
+}
+
+async fn get_portfolio(
+    Query(params): Query<CurrencyQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<PortfolioResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let currency = fiat_currency(state.config.price.as_ref(), params.currency.as_deref()).map_err(|e| (StatusCode::BAD_REQUEST, Json(e)))?;
+    let summary = portfolio_summary(&state.wallets.read().await);
+    let Some(currency) = currency else {
+        return Ok(Json(PortfolioResponse { summary, fiat: None }));
+    };
+
+    let Some(price) = *state.price.read().unwrap_or_else(|e| e.into_inner()) else {
+        return Err((StatusCode::SERVICE_UNAVAILABLE, Json(ErrorResponse::new(ErrorCode::PriceUnavailable))));
+    };
+    let price = price.usd * current_usd_rate(state.database.as_ref(), &currency)?;
+    let fiat = FiatValue {
+        currency,
+        price,
+        sol_value: summary.sol_balance * price,
+        wsol_value: summary.wsol_balance * price,
+        staked_value: summary.staked_balance * price,
+        lst_value: summary.lst_balance * price,
+        total_value: summary.total_balance * price,
+    };
+    Ok(Json(PortfolioResponse { summary, fiat: Some(fiat) }))
+}
+
+async fn get_portfolio_breakdown(
+    Query(params): Query<BreakdownQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<PortfolioBreakdown>, (StatusCode, Json<ErrorResponse>)> {
+    let step_secs = interval_to_duration(&params.interval)
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, Json(ErrorResponse::with_detail(ErrorCode::UnsupportedInterval, &params.interval))))?
+        .num_seconds();
+    if !DATA_TYPES.contains(&params.data_type.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse::with_detail(ErrorCode::UnsupportedDataType, &params.data_type))));
+    }
+    let point_count = params.buckets.clamp(1, 1000) as i64;
+
+    // (地址, 名稱, 群組, 目前餘額)，不含已封存的錢包
+    let members: Vec<(String, String, Option<String>, f64)> = state
+        .wallets
+        .read()
+        .await
+        .values()
+        .filter(|w| !w.archived)
+        .map(|w| (w.address.clone(), w.name.clone(), w.group.clone(), history_value(&w.balance_point(), &params.data_type)))
+        .collect();
+
+    let total: f64 = members.iter().map(|m| m.3).sum();
+    let mut wallets: Vec<WalletShare> = members
+        .iter()
+        .map(|(address, name, group, balance)| WalletShare {
+            address: address.clone(),
+            name: name.clone(),
+            group: group.clone(),
+            balance: *balance,
+            share: share_of(*balance, total),
+        })
+        .collect();
+    wallets.sort_by(|a, b| b.balance.total_cmp(&a.balance).then_with(|| a.name.cmp(&b.name)));
+
+    let mut group_balances: BTreeMap<String, (usize, f64)> = BTreeMap::new();
+    for wallet in &wallets {
+        let entry = group_balances.entry(share_group(&wallet.group)).or_default();
+        entry.0 += 1;
+        entry.1 += wallet.balance;
+    }
+    let groups = group_balances
+        .into_iter()
+        .map(|(name, (count, balance))| GroupShare { name, wallets: count, balance, share: share_of(balance, total) })
+        .collect();
+
+    // 歷史點以區間長度對齊 (UTC)，最後一個點為現在並使用目前餘額
+    let now = Utc::now().timestamp();
+    let last_start = now - now.rem_euclid(step_secs);
+    let mut times: Vec<i64> = (1..point_count).rev().map(|i| last_start - (i - 1) * step_secs).collect();
+    times.push(now);
+
+    let from = DateTime::from_timestamp(times[0], 0);
+    let database = state.database.clone();
+    let addresses: Vec<String> = members.iter().map(|m| m.0.clone()).collect();
+    let history_times = times[..times.len() - 1].to_vec();
+    let data_type = params.data_type.clone();
+    let read_error = |e: String| {
+        error!("❌ 讀取歷史數據失敗: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::HistoryReadFailed)))
+    };
+    let series_values = tokio::task::spawn_blocking(move || -> Result<Vec<Vec<f64>>, String> {
+        addresses
+            .iter()
+            .map(|address| {
+                let series = database.load_history_series(address, from, None).map_err(|e| e.to_string())?;
+                Ok(series_values_at(&series, &history_times, &data_type))
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| read_error(e.to_string()))?
+    .map_err(read_error)?;
+    let mut values = Vec::with_capacity(members.len());
+    for (mut wallet_values, (_, _, _, balance)) in series_values.into_iter().zip(&members) {
+        wallet_values.push(*balance);
+        values.push(wallet_values);
+    }
+
+    let history = times
+        .iter()
+        .enumerate()
+        .map(|(i, &time)| {
+            let total: f64 = values.iter().map(|v| v[i]).sum();
+            let mut group_totals: BTreeMap<String, f64> = BTreeMap::new();
+            let wallets = members
+                .iter()
+                .zip(&values)
+                .map(|((address, _, group, _), v)| {
+                    *group_totals.entry(share_group(group)).or_default() += v[i];
+                    (address.clone(), share_of(v[i], total))
+                })
+                .collect();
+            let groups = group_totals.into_iter().map(|(group, balance)| (group, share_of(balance, total))).collect();
+            ShareSnapshot { time, total, wallets, groups }
+        })
+        .collect();
+
+    Ok(Json(PortfolioBreakdown { interval: params.interval, data_type: params.data_type, total, wallets, groups, history }))
+}
+
+fn share_of(balance: f64, total: f64) -> f64 {
+    if total > 0.0 {
+        balance / total
+    } else {
+        0.0
+    }
+}
+
+fn share_group(group: &Option<String>) -> String {
+    group.clone().unwrap_or_else(|| UNGROUPED.to_string())
+}
+
+// 每個時間點取該時間 (含) 之前的最後一筆記錄，在第一筆記錄之前為 0
+fn series_values_at(series: &[BalanceHistory], times: &[i64], data_type: &str) -> Vec<f64> {
+    let mut index = 0;
+    let mut value = 0.0;
+    times
+        .iter()
+        .map(|&time| {
+            while index < series.len() && series[index].timestamp.timestamp() <= time {
+                value = history_value(&series[index], data_type);
+                index += 1;
+            }
+            value
+        })
+        .collect()
+}
+
+// 所有未封存錢包的餘額合計
+fn portfolio_summary(wallets: &HashMap<String, WalletBalance>) -> PortfolioSummary {
+    let mut portfolio = PortfolioSummary {
+        wallet_count: wallets.values().filter(|w| !w.archived).count(),
+        sol_balance: 0.0,
+        wsol_balance: 0.0,
+        staked_balance: 0.0,
+        lst_balance: 0.0,
+        total_balance: 0.0,
+        last_update: None,
+    };
+
+    for wallet in wallets.values().filter(|w| !w.archived) {
+        portfolio.sol_balance += wallet.sol_balance;
+        portfolio.wsol_balance += if wallet.wsol_initialized { wallet.wsol_balance } else { 0.0 };
+        portfolio.staked_balance += wallet.staked_balance();
+        portfolio.lst_balance += wallet.lst_balance();
+        portfolio.total_balance += wallet.total_balance();
+        portfolio.last_update = portfolio.last_update.max(Some(wallet.last_update));
+    }
+
+    portfolio
+}
+
+async fn get_history(
+    Query(params): Query<HistoryQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<([(header::HeaderName, String); 1], Json<Vec<WalletHistoryRecord>>), (StatusCode, Json<ErrorResponse>)> {
+    if !state.wallets.read().await.contains_key(&params.wallet) {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+    }
+
+    let from = match params.cursor {
+        Some(cursor) => DateTime::from_timestamp_millis(cursor as i64),
+        None => params.from.and_then(|t| DateTime::from_timestamp(t, 0)),
+    };
+    let to = params.to.and_then(|t| DateTime::from_timestamp(t, 0));
+    let limit = params.limit.clamp(1, 100_000);
+    // 多讀一筆判斷是否還有下一頁，下一頁從該筆開始
+    match state.database.load_history(&params.wallet, from, to, limit + 1) {
+        Ok(mut records) => {
+            let next_cursor = if records.len() > limit {
+                records.pop().map(|r| history_key_millis(r.timestamp).to_string()).unwrap_or_default()
+            } else {
+                String::new()
+            };
+            Ok(([(header::HeaderName::from_static("x-next-cursor"), next_cursor)], Json(records)))
+        }
+        Err(e) => {
+            error!("❌ 讀取歷史數據失敗: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::HistoryReadFailed))))
+        }
+    }
+}
+
+async fn create_backup(
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<BackupInfo>, (StatusCode, Json<ErrorResponse>)> {
+    // 備份前先寫入佇列中的記錄
+    state.database_writer.flush().await;
+
+    let database = state.database.clone();
+    let config = state.config.backup.clone();
+    match tokio::task::spawn_blocking(move || database.backup(&config).map_err(|e| e.to_string())).await {
+        Ok(Ok(info)) => Ok(Json(info)),
+        Ok(Err(e)) => {
+            error!("❌ 備份資料庫失敗: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::with_detail(ErrorCode::BackupFailed, e))))
+        }
+        Err(e) => {
+            error!("❌ 備份任務異常: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::BackupFailed))))
+        }
+    }
+}
+
+async fn restore_backup(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Json(request): Json<RestoreRequest>,
+) -> Result<Json<RestoreResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let backup_path = match &request.backup {
+        Some(file) => {
+            // 只允許備份目錄中的檔案
+            if !list_backups(&state.config.backup.dir).unwrap_or_default().iter().any(|b| &b.file == file) {
+                return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::with_detail(ErrorCode::BackupNotFound, file))));
+            }
+            Some(std::path::Path::new(&state.config.backup.dir).join(file))
+        }
+        None if request.records.is_empty() => {
+            return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse::new(ErrorCode::RestoreSourceRequired))));
+        }
+        None => None,
+    };
+
+    state.database_writer.flush().await;
+    let database = state.database.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        match backup_path {
+            Some(path) => database.restore_backup(&path),
+            None => database.import_records(&request.records),
+        }
+        .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|result| result);
+
+    match result {
+        Ok((response, addresses)) => {
+            // 受影響的錢包下次查詢詳情時重新從資料庫載入歷史
+            let mut wallets_guard = state.wallets.write().await;
+            for address in &addresses {
+                if let Some(wallet) = wallets_guard.get_mut(address) {
+                    wallet.history_loaded = false;
+                }
+            }
+            info!("📥 歷史資料匯入完成: 匯入 {} 條，略過 {} 條", response.imported, response.skipped);
+            Ok(Json(response))
+        }
+        Err(e) => {
+            error!("❌ 匯入歷史資料失敗: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::with_detail(ErrorCode::ImportFailed, e))))
+        }
+    }
+}
+
+async fn get_backups(
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<Vec<BackupInfo>>, (StatusCode, Json<ErrorResponse>)> {
+    list_backups(&state.config.backup.dir).map(Json).map_err(|e| {
+        error!("❌ 讀取備份列表失敗: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::BackupListFailed)))
+    })
+}
+
+async fn get_database_stats(
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<DatabaseStats>, (StatusCode, Json<ErrorResponse>)> {
+    let database = state.database.clone();
+    match tokio::task::spawn_blocking(move || database.stats().map_err(|e| e.to_string())).await {
+        Ok(Ok(stats)) => Ok(Json(stats)),
+        Ok(Err(e)) => {
+            error!("❌ 讀取資料庫統計失敗: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::with_detail(ErrorCode::DatabaseStatsFailed, e))))
+        }
+        Err(e) => {
+            error!("❌ 資料庫統計任務異常: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::DatabaseStatsFailed))))
+        }
+    }
+}
+
+async fn compact_database(
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<CompactResponse>, (StatusCode, Json<ErrorResponse>)> {
+    // 壓縮前先寫入佇列中的記錄
+    state.database_writer.flush().await;
+
+    let database = state.database.clone();
+    match tokio::task::spawn_blocking(move || database.compact_file().map_err(|e| e.to_string())).await {
+        Ok(Ok(response)) => Ok(Json(response)),
+        Ok(Err(e)) => {
+            error!("❌ 壓縮資料庫失敗: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::with_detail(ErrorCode::CompactFailed, e))))
+        }
+        Err(e) => {
+            error!("❌ 壓縮任務異常: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::CompactFailed))))
+        }
+    }
+}
+
+async fn get_wallets_profits(
+    Query(params): Query<ProfitsQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<ProfitsResponse>, StatusCode> {
+    let addresses: Vec<String> = state.wallets.read().await
+        .values()
+        .filter(|w| !w.archived)
+        .map(|w| w.address.clone())
+        .collect();
+    let mut profits = Vec::new();
+    
+    info!("📊 批次計算獲利 - 時間間隔: {}, 數據類型: {}, 錢包數: {}", 
+          params.interval, params.data_type, addresses.len());
+    
+    // 根據時間範圍從資料庫讀取數據
+    let from = interval_to_duration(&params.interval).map(|d| Utc::now() - d);
+    for address in &addresses {
+        // 只需區間內第一筆與最新一筆，不載入整段歷史
+        let read_error = |e: Box<dyn std::error::Error>| {
+            error!("❌ 讀取歷史數據失敗: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        };
+        let earliest = state.database.load_history(address, from, None, 1).map_err(read_error)?.pop().map(BalanceHistory::from);
+        let latest = state.database.load_latest(address).map_err(read_error)?.map(BalanceHistory::from);
+        
+        // 計算獲利
+        let profit_info = if let Some((earliest, latest)) = earliest.zip(latest).filter(|(e, l)| l.timestamp > e.timestamp) {
+            let earliest_value = history_value(earliest, &params.data_type);
+            let latest_value = history_value(latest, &params.data_type);
+            
+            let profit = latest_value - earliest_value;
+            
+            // 判斷獲利的顏色類別
+            let profit_class = if profit > 0.000001 {
+                "positive"
+            } else if profit < -0.000001 {
+                "negative"
+            } else {
+                "neutral"
+            };
+            
+            WalletProfitInfo {
+                address: address.clone(),
+                profit,
+                profit_class: profit_class.to_string(),
+            }
+        } else {
+            // 沒有足夠的數據
+            WalletProfitInfo {
+                address: address.clone(),
+                profit: 0.0,
+                profit_class: "neutral".to_string(),
+            }
+        };
+        
+        profits.push(profit_info);
+    }
+    
+    info!("✅ 批次獲利計算完成，處理了 {} 個錢包", profits.len());
+    
+    Ok(Json(ProfitsResponse {
+        interval: params.interval,
+        data_type: params.data_type,
+        profits,
+    }))
+}
+
+async fn add_wallet(
+    actor: AuditActor,
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Json(request): Json<AddWalletRequest>,
+) -> Result<(StatusCode, Json<ApiResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let result = create_wallet(state.clone(), &request).await;
+    record_audit(&state, actor, AuditAction::Add, request.address.trim(), Some(request.name.trim().to_string()), &result);
+    result
+}
+
+async fn create_wallet(
+    state: AppState,
+    request: &AddWalletRequest,
+) -> Result<(StatusCode, Json<ApiResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let name = request.name.trim();
+    let input = request.address.trim();
+    
+    // 驗證輸入
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse::new(ErrorCode::WalletNameRequired))));
+    }
+    
+    if input.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse::new(ErrorCode::AddressRequired))));
+    }
+
+    // .sol 網域先解析為擁有者地址，網域一併保存供顯示
+    let (address, domain) = if is_sol_domain(input) {
+        let domain = input.to_ascii_lowercase();
+        match resolve_sol_domain(&domain, &state.rpc).await {
+            Ok(Some(owner)) => {
+                info!("🔗 網域 {} 解析為 {}", domain, owner);
+                (owner, Some(domain))
+            }
+            Ok(None) => return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::with_detail(ErrorCode::DomainNotFound, domain)))),
+            Err(e) => return Err((StatusCode::BAD_GATEWAY, Json(ErrorResponse::with_detail(ErrorCode::DomainResolveFailed, e)))),
+        }
+    } else {
+        (input.to_string(), None)
+    };
+    let address = address.as_str();
+    
+    if let Err(code) = validate_wallet_address(address, request.allow_off_curve || request.account_type.allows_off_curve()) {
+        return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse::new(code))));
+    }
+    let vote_account = request.vote_account.as_deref().map(str::trim).filter(|v| !v.is_empty());
+    if let Some(vote_account) = vote_account {
+        if request.account_type != AccountType::Validator {
+            return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse::with_detail(ErrorCode::InvalidVoteAccount, "account_type 必須為 validator"))));
+        }
+        if let Err(code) = validate_wallet_address(vote_account, false) {
+            return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse::with_detail(ErrorCode::InvalidVoteAccount, code.message(Language::Zh)))));
+        }
+    }
+    
+    // 檢查錢包是否已存在
+    {
+        let wallets_guard = state.wallets.read().await;
+        if wallets_guard.contains_key(address) {
+            return Err((StatusCode::CONFLICT, Json(ErrorResponse::new(ErrorCode::WalletExists))));
+        }
+        
+        // 檢查名稱是否已存在
+        for wallet in wallets_guard.values() {
+            if wallet.name == name {
+                return Err((StatusCode::CONFLICT, Json(ErrorResponse::new(ErrorCode::WalletNameExists))));
+            }
+        }
+    }
+    
+    // 先加入錢包並立即回應，餘額在背景從 RPC 取得；完成前 initializing 為 true
+    let mut new_wallet = WalletBalance::new(address.to_string(), name.to_string());
+    new_wallet.history_config = state.config.history;
+    new_wallet.domain = domain.clone();
+    new_wallet.account_type = request.account_type;
+    new_wallet.vote_account = vote_account.map(str::to_string);
+    {
+        let mut wallets_guard = state.wallets.write().await;
+        state.events.publish_added(&new_wallet);
+        wallets_guard.insert(address.to_string(), new_wallet.clone());
+    }
+
+    // 更新配置文件；失敗時錢包仍會監聽，重新載入配置文件時不移除，但重新啟動後不會保留
+    if let Err(e) = update_config_file(address, name).await {
+        warn!("⚠️ 更新配置文件失敗，錢包 {} 重新啟動後不會保留: {}", &address[..8], e);
+        state.unsaved_wallets.lock().unwrap_or_else(|e| e.into_inner()).insert(address.to_string());
+    } else {
+        if request.allow_off_curve {
+            if let Err(e) = set_wallet_config_field(address, "allow_off_curve", Some(toml::Value::Boolean(true))).await {
+                warn!("⚠️ 更新配置文件失敗: {}", e);
+            }
+        }
+        if let Some(domain) = &domain {
+            if let Err(e) = set_wallet_config_field(address, "domain", Some(toml::Value::String(domain.clone()))).await {
+                warn!("⚠️ 更新配置文件失敗: {}", e);
+            }
+        }
+        if request.account_type != AccountType::Wallet {
+            let value = toml::Value::String(request.account_type.as_str().to_string());
+            if let Err(e) = set_wallet_config_field(address, "account_type", Some(value)).await {
+                warn!("⚠️ 更新配置文件失敗: {}", e);
+            }
+        }
+        if let Some(vote_account) = vote_account {
+            if let Err(e) = set_wallet_config_field(address, "vote_account", Some(toml::Value::String(vote_account.to_string()))).await {
+                warn!("⚠️ 更新配置文件失敗: {}", e);
+            }
+        }
+    }
+
+    // 通知 gRPC 串流更新訂閱以監聽新錢包
+    state.grpc_resubscribe.send_replace(());
+
+    info!("✅ 成功新增錢包: {} ({}) - 正在背景初始化餘額並更新gRPC訂閱", name, &address[..8]);
+
+    spawn_wallet_initialization(state, new_wallet);
+
+    Ok((StatusCode::ACCEPTED, Json(ApiResponse {
+        success: true,
+        message: format!("已新增錢包 {}，正在初始化餘額", name),
+    })))
+}
+
+async fn update_wallet(
+    Path(address): Path<String>,
+    actor: AuditActor,
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Json(request): Json<UpdateWalletRequest>,
+) -> Result<Json<ApiResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let old_name = state.wallets.read().await.get(&address).map(|w| w.name.clone());
+    let result = apply_wallet_update(address.clone(), &state, &request).await;
+
+    // 同時修改多個欄位時以封存狀態為主要動作，改名前後另記在 detail
+    let new_name = request.name.as_deref().map(str::trim).filter(|name| old_name.as_deref() != Some(*name));
+    let action = match request.archived {
+        Some(true) => AuditAction::Archive,
+        Some(false) => AuditAction::Unarchive,
+        None if new_name.is_some() => AuditAction::Rename,
+        None => AuditAction::Update,
+    };
+    let detail = match new_name {
+        Some(name) => Some(format!("{} → {}", old_name.unwrap_or_default(), name)),
+        None => {
+            let fields: Vec<&str> = [
+                ("group", request.group.is_some()),
+                ("tags", request.tags.is_some()),
+                ("note", request.note.is_some()),
+                ("color", request.color.is_some()),
+            ]
+            .into_iter()
+            .filter_map(|(field, changed)| changed.then_some(field))
+            .collect();
+            (!fields.is_empty()).then(|| fields.join(", "))
+        }
+    };
+    record_audit(&state, actor, action, &address, detail, &result);
+    result
+}
+
+async fn apply_wallet_update(
+    address: String,
+    state: &AppState,
+    request: &UpdateWalletRequest,
+) -> Result<Json<ApiResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let name = request.name.as_deref().map(str::trim);
+    let group = request.group.as_deref().map(str::trim);
+    let note = request.note.as_deref().map(str::trim);
+    let color = request.color.as_deref().map(str::trim);
+    let tags: Option<Vec<String>> = request.tags.as_ref().map(|tags| {
+        tags.iter().map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect()
+    });
+
+    // 驗證輸入
+    if name == Some("") {
+        return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse::new(ErrorCode::WalletNameRequired))));
+    }
+    if color.is_some_and(|c| !c.is_empty() && !is_valid_color(c)) {
+        return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse::new(ErrorCode::InvalidColor))));
+    }
+
+    let (wallet_name, archive_changed) = {
+        let mut wallets_guard = state.wallets.write().await;
+        if !wallets_guard.contains_key(&address) {
+            return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+        }
+
+        // 檢查名稱是否已被其他錢包使用
+        if let Some(name) = name {
+            if wallets_guard.values().any(|w| w.name == name && w.address != address) {
+                return Err((StatusCode::CONFLICT, Json(ErrorResponse::new(ErrorCode::WalletNameExists))));
+            }
+        }
+
+        let wallet = wallets_guard.get_mut(&address).unwrap();
+        if let Some(name) = name.filter(|name| *name != wallet.name) {
+            state.events.publish_renamed(&address, &wallet.name, name);
+            wallet.name = name.to_string();
+        }
+        if let Some(group) = group {
+            wallet.group = if group.is_empty() { None } else { Some(group.to_string()) };
+        }
+        if let Some(tags) = &tags {
+            wallet.tags = tags.clone();
+        }
+        if let Some(note) = note {
+            wallet.note = if note.is_empty() { None } else { Some(note.to_string()) };
+        }
+        if let Some(color) = color {
+            wallet.color = if color.is_empty() { None } else { Some(color.to_string()) };
+        }
+        let archive_changed = request.archived.is_some_and(|archived| archived != wallet.archived);
+        if let Some(archived) = request.archived {
+            wallet.archived = archived;
+        }
+        state.events.publish_update(wallet);
+        (wallet.name.clone(), archive_changed)
+    };
+
+    // 更新配置文件 (封存狀態以外的變更無需重啟 gRPC 訂閱)
+    let mut config_updates: Vec<(&str, Option<toml::Value>)> = Vec::new();
+    if let Some(name) = name {
+        config_updates.push(("name", Some(toml::Value::String(name.to_string()))));
+    }
+    if let Some(group) = group {
+        config_updates.push(("group", (!group.is_empty()).then(|| toml::Value::String(group.to_string()))));
+    }
+    if let Some(tags) = tags {
+        let value = toml::Value::Array(tags.into_iter().map(toml::Value::String).collect());
+        config_updates.push(("tags", Some(value)));
+    }
+    if let Some(note) = note {
+        config_updates.push(("note", (!note.is_empty()).then(|| toml::Value::String(note.to_string()))));
+    }
+    if let Some(color) = color {
+        config_updates.push(("color", (!color.is_empty()).then(|| toml::Value::String(color.to_string()))));
+    }
+    if archive_changed {
+        config_updates.push(("archived", (request.archived == Some(true)).then_some(toml::Value::Boolean(true))));
+    }
+    for (key, value) in config_updates {
+        if let Err(e) = set_wallet_config_field(&address, key, value).await {
+            warn!("⚠️ 更新配置文件失敗: {}", e);
+        }
+    }
+
+    if archive_changed {
+        // 取消封存時先從 RPC 取得最新餘額，再重新訂閱
+        if request.archived == Some(false) {
+            let wallet = state.wallets.read().await.get(&address).cloned();
+            if let Some(wallet) = wallet {
+                let mut refreshed = HashMap::from([(address.clone(), wallet)]);
+                let lst_rates = state.lst_rates.lock().unwrap().clone();
+                initialize_wallets_from_rpc(
+                    &mut refreshed,
+                    &state.database_writer,
+                    &state.rpc,
+                    &state.lst_tokens,
+                    &lst_rates,
+                    state.config.owner_token_accounts(),
+                )
+                .await;
+                if let Some(wallet) = refreshed.remove(&address) {
+                    state.events.publish_update(&wallet);
+                    state.wallets.write().await.insert(address.clone(), wallet);
+                }
+            }
+        }
+
+        state.grpc_resubscribe.send_replace(());
+        info!("📦 錢包 {} 已{}封存 - 正在更新gRPC訂閱", wallet_name, if request.archived == Some(true) { "" } else { "取消" });
+    }
+
+    info!("✅ 成功更新錢包: {} ({})", wallet_name, &address[..8]);
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("成功更新錢包 {}", wallet_name),
+    }))
+}
+
+// 從 RPC 重新讀取單一錢包的餘額與 stake account，修正串流遺漏更新造成的錯誤狀態
+async fn refresh_wallet(
+    Path(address): Path<String>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<ApiResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let Some(wallet) = state.wallets.read().await.get(&address).cloned() else {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+    };
+    if wallet.archived {
+        return Err((StatusCode::CONFLICT, Json(ErrorResponse::new(ErrorCode::WalletArchived))));
+    }
+    let before = wallet.total_balance();
+
+    // 與餘額核對相同：以 RPC 查詢錢包的所有帳戶，透過串流的處理流程套用並記錄；串流已套用更新資料的帳戶不覆寫
+    let shard = GrpcShard { index: 0, count: 1 };
+    let subscription = AccountSubscription::new(&state.wallets, &state.lst_tokens, CommitmentLevel::Confirmed, shard, state.config.owner_token_accounts()).await;
+    let addresses: Vec<String> = subscription
+        .addresses()
+        .into_iter()
+        .filter(|account| subscription.wallet_for(account).as_deref() == Some(address.as_str()))
+        .collect();
+    let accounts = fetch_accounts(&state.rpc, addresses).await.map_err(|e| {
+        warn!("⚠️ 重新整理錢包 {} 查詢 RPC 失敗: {}", &address[..8], e);
+        (StatusCode::BAD_GATEWAY, Json(ErrorResponse::with_detail(ErrorCode::WalletRefreshFailed, e)))
+    })?;
+
+    let mut wallets_guard = state.wallets.write().await;
+    for (pubkey, slot, account) in accounts {
+        // 查詢期間錢包可能已被刪除；串流已套用比此次查詢更新的資料時不覆寫
+        if !wallets_guard.get(&address).is_some_and(|wallet| wallet.last_slot <= slot) {
+            continue;
+        }
+        // 帳戶不存在視為餘額 0 (例如 ATA 或 stake account 已關閉)
+        let (lamports, data) = match account {
+            Some(account) => (account.lamports, account.data),
+            None => (0, vec![0; TOKEN_AMOUNT_LEN]),
+        };
+        let account = AccountUpdate { pubkey, lamports, data, slot };
+        apply_account_update(&account, &mut wallets_guard, &subscription, &state.lst_tokens, &state.lst_rates, &state.database_writer, &state.notifier, &state.events);
+    }
+    let Some(current) = wallets_guard.get(&address) else {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+    };
+    let after = current.total_balance();
+    let name = current.name.clone();
+    drop(wallets_guard);
+
+    info!("🔄 已從 RPC 重新整理錢包 {} ({}) 總餘額: {:.9} → {:.9}", name, &address[..8], before, after);
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("已從 RPC 重新整理錢包 {}", name),
+    }))
+}
+
+// 驗證 currency 參數並轉為大寫：USD 需設定 [price]，其他幣別需列在 [price.fx] 的 currencies
+fn fiat_currency(config: Option<&PriceConfig>, currency: Option<&str>) -> Result<Option<String>, ErrorResponse> {
+    let Some(currency) = currency.map(|c| c.trim().to_uppercase()).filter(|c| !c.is_empty()) else {
+        return Ok(None);
+    };
+    let supported = config.is_some_and(|price| {
+        currency == USD_CURRENCY || price.fx.as_ref().is_some_and(|fx| fx.currencies.iter().any(|c| c.eq_ignore_ascii_case(&currency)))
+    });
+    if supported {
+        Ok(Some(currency))
+    } else {
+        Err(ErrorResponse::with_detail(ErrorCode::UnsupportedCurrency, currency))
+    }
+}
+
+// 目前 1 USD 對目標幣別的匯率
+fn current_usd_rate(db: &dyn HistoryStore, currency: &str) -> Result<f64, (StatusCode, Json<ErrorResponse>)> {
+    let now = Utc::now();
+    let converter = FiatConverter::load(db, currency, Some(now), Some(now)).map_err(|e| {
+        error!("❌ 讀取 {} 匯率失敗: {}", currency, e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::FxRateReadFailed)))
+    })?;
+    converter.usd_rate_at(now).map_err(|code| (StatusCode::SERVICE_UNAVAILABLE, Json(ErrorResponse::with_detail(code, currency))))
+}
+
+async fn get_price(
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<PricePoint>, (StatusCode, Json<ErrorResponse>)> {
+    let price = *state.price.read().unwrap_or_else(|e| e.into_inner());
+    price.map(Json).ok_or((StatusCode::SERVICE_UNAVAILABLE, Json(ErrorResponse::new(ErrorCode::PriceUnavailable))))
+}
+
+async fn get_cost_basis(
+    Path(address): Path<String>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<CostBasis>, (StatusCode, Json<ErrorResponse>)> {
+    if !state.wallets.read().await.contains_key(&address) {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+    }
+    let database = state.database.clone();
+    let db_address = address.clone();
+    match tokio::task::spawn_blocking(move || database.load_cost_basis(&db_address).map_err(|e| e.to_string())).await {
+        Ok(Ok(Some(basis))) => Ok(Json(basis)),
+        Ok(Ok(None)) => Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::CostBasisNotSet)))),
+        Ok(Err(e)) => {
+            error!("❌ 讀取錢包 {} 的成本設定失敗: {}", &address[..8], e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::CostBasisReadFailed))))
+        }
+        Err(e) => {
+            error!("❌ 讀取成本設定任務異常: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::CostBasisReadFailed))))
+        }
+    }
+}
+
+// 在 blocking 執行緒保存或刪除錢包的成本設定
+async fn save_cost_basis(state: &AppState, address: &str, basis: Option<CostBasis>) -> Result<(), String> {
+    let database = state.database.clone();
+    let address = address.to_string();
+    tokio::task::spawn_blocking(move || database.save_cost_basis(&address, basis.as_ref()).map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+// 設定錢包的成本，整份取代既有設定；成交記錄依時間排序後保存
+async fn set_cost_basis(
+    Path(address): Path<String>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Json(mut basis): Json<CostBasis>,
+) -> Result<Json<ApiResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let Some(name) = state.wallets.read().await.get(&address).map(|w| w.name.clone()) else {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+    };
+    if let Err(detail) = basis.validate() {
+        return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse::with_detail(ErrorCode::InvalidCostBasis, detail))));
+    }
+    basis.fills.sort_by_key(|fill| fill.timestamp);
+
+    if let Err(e) = save_cost_basis(&state, &address, Some(basis.clone())).await {
+        error!("❌ 保存錢包 {} 的成本設定失敗: {}", &address[..8], e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::CostBasisUpdateFailed))));
+    }
+    info!("💰 已更新錢包 {} 的成本設定 (初始 {:.4} SOL @ {:.4} USD，{} 筆成交)", name, basis.amount, basis.price, basis.fills.len());
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("已更新錢包 {} 的成本設定", name),
+    }))
+}
+
+async fn delete_cost_basis(
+    Path(address): Path<String>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<ApiResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let Some(name) = state.wallets.read().await.get(&address).map(|w| w.name.clone()) else {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+    };
+    if let Err(e) = save_cost_basis(&state, &address, None).await {
+        error!("❌ 刪除錢包 {} 的成本設定失敗: {}", &address[..8], e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::CostBasisUpdateFailed))));
+    }
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("已刪除錢包 {} 的成本設定", name),
+    }))
+}
+
+async fn get_wallet_pnl(
+    Path(address): Path<String>,
+    Query(params): Query<CurrencyQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<PnlResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let currency = fiat_currency(state.config.price.as_ref(), params.currency.as_deref())
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(e)))?
+        .unwrap_or_else(|| USD_CURRENCY.to_string());
+    let Some(balance) = state.wallets.read().await.get(&address).map(|w| w.total_balance()) else {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+    };
+    let Some(price) = *state.price.read().unwrap_or_else(|e| e.into_inner()) else {
+        return Err((StatusCode::SERVICE_UNAVAILABLE, Json(ErrorResponse::new(ErrorCode::PriceUnavailable))));
+    };
+
+    let database = state.database.clone();
+    let db_address = address.clone();
+    let db_currency = currency.clone();
+    let (basis, rates) = tokio::task::spawn_blocking(move || {
+        let basis = match database.load_cost_basis(&db_address) {
+            Ok(Some(basis)) => basis,
+            Ok(None) => return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::CostBasisNotSet)))),
+            Err(e) => {
+                error!("❌ 讀取錢包 {} 的成本設定失敗: {}", &db_address[..8], e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::CostBasisReadFailed))));
+            }
+        };
+        let rates = FiatConverter::load_rates(database.as_ref(), &db_currency).map_err(|e| {
+            error!("❌ 讀取 {} 匯率失敗: {}", db_currency, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::FxRateReadFailed)))
+        })?;
+        Ok((basis, rates))
+    })
+    .await
+    .map_err(|e| {
+        error!("❌ 讀取成本設定任務異常: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::CostBasisReadFailed)))
+    })??;
+
+    // 價格以目前匯率換算，成本依每筆成交當時的匯率換算
+    let now = Utc::now();
+    let unavailable = |code| (StatusCode::SERVICE_UNAVAILABLE, Json(ErrorResponse::with_detail(code, &currency)));
+    let rate = rates.usd_rate_at(now).map_err(unavailable)?;
+    let position = basis.position_at(now, |timestamp| rates.usd_rate_near(timestamp)).map_err(unavailable)?;
+    let price_value = price.usd * rate;
+    let unrealized = position.unrealized(balance, price_value);
+    Ok(Json(PnlResponse {
+        address,
+        currency,
+        balance,
+        price: price_value,
+        price_updated_at: price.timestamp,
+        position: position.amount,
+        average_cost: position.average_cost(),
+        cost_basis: position.cost,
+        market_value: balance * price_value,
+        realized_pnl: position.realized,
+        unrealized_pnl: unrealized,
+        total_pnl: position.realized + unrealized,
+    }))
+}
+
+// 錢包每個 epoch 的質押獎勵，依入帳時間由舊到新
+async fn get_wallet_rewards(
+    Path(address): Path<String>,
+    Query(params): Query<HistoryCsvQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<Vec<StakeReward>>, (StatusCode, Json<ErrorResponse>)> {
+    if !state.wallets.read().await.contains_key(&address) {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+    }
+    let from = params.from.and_then(|t| DateTime::from_timestamp(t, 0));
+    let to = params.to.and_then(|t| DateTime::from_timestamp(t, 0));
+    match state.database.load_stake_rewards(&address, from, to) {
+        Ok(rewards) => Ok(Json(rewards)),
+        Err(e) => {
+            error!("❌ 讀取錢包 {} 的質押獎勵失敗: {}", &address[..8], e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::RewardsReadFailed))))
+        }
+    }
+}
+
+// token_account 類型非 WSOL 代幣的數量變化，依時間由舊到新
+async fn get_wallet_token_history(
+    Path(address): Path<String>,
+    Query(params): Query<HistoryCsvQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<Vec<TokenAmountRecord>>, (StatusCode, Json<ErrorResponse>)> {
+    if !state.wallets.read().await.contains_key(&address) {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+    }
+    let from = params.from.and_then(|t| DateTime::from_timestamp(t, 0));
+    let to = params.to.and_then(|t| DateTime::from_timestamp(t, 0));
+    match state.database.load_token_amounts(&address, from, to) {
+        Ok(records) => Ok(Json(records)),
+        Err(e) => {
+            error!("❌ 讀取帳戶 {} 的代幣數量歷史失敗: {}", &address[..8], e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::TokenHistoryReadFailed))))
+        }
+    }
+}
+
+async fn get_validator_status(
+    Path(address): Path<String>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<ValidatorStatus>, (StatusCode, Json<ErrorResponse>)> {
+    match state.wallets.read().await.get(&address).map(|w| w.account_type) {
+        Some(AccountType::Validator) => {}
+        Some(_) => return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse::new(ErrorCode::NotValidator)))),
+        None => return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound)))),
+    }
+    let status = state.validators.read().await.get(&address).cloned();
+    status.map(Json).ok_or((StatusCode::SERVICE_UNAVAILABLE, Json(ErrorResponse::new(ErrorCode::ValidatorStatusUnavailable))))
+}
+
+async fn get_epochs(
+    Query(params): Query<HistoryCsvQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<Vec<EpochBoundary>>, (StatusCode, Json<ErrorResponse>)> {
+    let from = params.from.and_then(|t| DateTime::from_timestamp(t, 0));
+    let to = params.to.and_then(|t| DateTime::from_timestamp(t, 0));
+    match state.database.load_epochs(from, to) {
+        Ok(epochs) => Ok(Json(epochs)),
+        Err(e) => {
+            error!("❌ 讀取 epoch 記錄失敗: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::EpochsReadFailed))))
+        }
+    }
+}
+
+// 從鏈上交易記錄回補錢包加入監控前的餘額歷史，只填補資料庫最早一筆記錄之前的區間；
+// 回補需要大量 RPC 請求，在背景執行並立即返回 202，進度由 GET 同一路徑查詢
+async fn backfill_wallet(
+    Path(address): Path<String>,
+    Query(params): Query<BackfillQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<(StatusCode, Json<BackfillStatus>), (StatusCode, Json<ErrorResponse>)> {
+    let days = params.days.unwrap_or(30);
+    if days == 0 || days > MAX_BACKFILL_DAYS {
+        return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse::new(ErrorCode::InvalidBackfillDays))));
+    }
+    let Some(wallet) = state.wallets.read().await.get(&address).cloned() else {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+    };
+    if wallet.archived {
+        return Err((StatusCode::CONFLICT, Json(ErrorResponse::new(ErrorCode::WalletArchived))));
+    }
+    let status = BackfillStatus {
+        state: BackfillState::Running,
+        days,
+        started_at: Utc::now(),
+        finished_at: None,
+        result: None,
+        error: None,
+    };
+    {
+        let mut backfills = state.backfills.lock().unwrap_or_else(|e| e.into_inner());
+        if backfills.get(&address).is_some_and(|status| status.state == BackfillState::Running) {
+            return Err((StatusCode::CONFLICT, Json(ErrorResponse::new(ErrorCode::BackfillInProgress))));
+        }
+        backfills.insert(address.clone(), status.clone());
+    }
+
+    let guard = BackfillGuard { backfills: state.backfills.clone(), address: address.clone() };
+    tokio::spawn(async move {
+        let result = backfill_wallet_history(&state, &wallet, days).await;
+        match &result {
+            Ok(response) => {
+                if response.imported > 0 {
+                    // 下次查詢詳情時重新從資料庫載入歷史
+                    if let Some(wallet) = state.wallets.write().await.get_mut(&address) {
+                        wallet.history_loaded = false;
+                    }
+                }
+                info!("⏪ 已回補錢包 {} 最近 {} 天的歷史: 讀取 {} 筆交易，匯入 {} 條{}",
+                      &address[..8], days, response.transactions, response.imported, if response.truncated { " (已達交易數上限)" } else { "" });
+            }
+            Err(e) => error!("❌ 回補錢包 {} 的歷史失敗: {}", &address[..8], e),
+        }
+        guard.finish(result);
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(status)))
+}
+
+async fn get_backfill_status(
+    Path(address): Path<String>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<BackfillStatus>, (StatusCode, Json<ErrorResponse>)> {
+    match state.backfills.lock().unwrap_or_else(|e| e.into_inner()).get(&address) {
+        Some(status) => Ok(Json(status.clone())),
+        None => Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::BackfillNotFound)))),
+    }
+}
+
+// 由目前餘額往回走訪交易：SOL 取每筆交易後的 post balance，WSOL 在交易有動到時取 post token balance，
+// 否則沿用較新交易的 pre 值；質押與 LST 無法從交易重建，以目前數值近似
+async fn backfill_wallet_history(state: &AppState, wallet: &WalletBalance, days: u32) -> Result<BackfillResponse, String> {
+    state.database_writer.flush().await;
+    let database = state.database.clone();
+    let address = wallet.address.clone();
+    let earliest = tokio::task::spawn_blocking(move || database.earliest_history(&address).map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let since = Utc::now() - chrono::Duration::days(days as i64);
+    let until = earliest.unwrap_or_else(Utc::now);
+    if until <= since {
+        return Ok(BackfillResponse::default());
+    }
+
+    let pubkey = Pubkey::from_str(&wallet.address).map_err(|e| e.to_string())?;
+    let mut signatures = Vec::new();
+    let mut before: Option<Signature> = None;
+    let mut truncated = false;
+    'pages: loop {
+        let page = state
+            .rpc
+            .call(|client| async move {
+                let config = GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until: None,
+                    limit: Some(BACKFILL_SIGNATURES_PAGE),
+                    commitment: Some(solana_sdk::commitment_config::CommitmentConfig::confirmed()),
+                };
+                client.get_signatures_for_address_with_config(&pubkey, config).await
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        before = Some(Signature::from_str(&last.signature).map_err(|e| e.to_string())?);
+        let full_page = page.len() == BACKFILL_SIGNATURES_PAGE;
+
+        for status in page {
+            let Some(block_time) = status.block_time.and_then(|t| DateTime::from_timestamp(t, 0)) else {
+                continue;
+            };
+            if block_time < since {
+                break 'pages;
+            }
+            if block_time >= until {
+                continue;
+            }
+            if signatures.len() >= MAX_BACKFILL_TRANSACTIONS {
+                truncated = true;
+                break 'pages;
+            }
+            signatures.push(status.signature);
+        }
+        if !full_page {
+            break;
+        }
+    }
+
+    let track_wsol = wallet.account_type == AccountType::Wallet;
+    let mut wsol = if track_wsol && wallet.wsol_initialized { wallet.wsol_balance } else { 0.0 };
+    let (staked, lst) = (wallet.staked_balance(), wallet.lst_balance());
+    let mut records: Vec<WalletHistoryRecord> = Vec::new();
+    for signature in &signatures {
+        let parsed = Signature::from_str(signature).map_err(|e| e.to_string())?;
+        let transaction = match state
+            .rpc
+            .call(|client| async move {
+                let config = RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Json),
+                    commitment: Some(solana_sdk::commitment_config::CommitmentConfig::confirmed()),
+                    max_supported_transaction_version: Some(0),
+                };
+                client.get_transaction_with_config(&parsed, config).await
+            })
+            .await
+        {
+            Ok(transaction) => transaction,
+            Err(e) => {
+                warn!("⚠️ 讀取交易 {} 失敗，略過: {}", &signature[..8], e);
+                continue;
+            }
+        };
+        let Some((timestamp, sol_balance, wsol_change)) = transaction_balances(&transaction, &wallet.address) else {
+            continue;
+        };
+        if let (true, Some((_, post))) = (track_wsol, wsol_change) {
+            wsol = post;
+        }
+        // 同一秒內的多筆交易只保留最新的狀態
+        if records.last().is_none_or(|newer| newer.timestamp != timestamp) {
+            records.push(WalletHistoryRecord {
+                timestamp,
+                address: wallet.address.clone(),
+                sol_balance,
+                wsol_balance: wsol,
+                staked_balance: staked,
+                lst_balance: lst,
+                total_balance: sol_balance + wsol + staked + lst,
+            });
+        }
+        if let (true, Some((pre, _))) = (track_wsol, wsol_change) {
+            wsol = pre;
+        }
+    }
+
+    let oldest = records.last().map(|record| record.timestamp);
+    let database = state.database.clone();
+    let (result, _) = tokio::task::spawn_blocking(move || database.import_records(&records).map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())??;
+    Ok(BackfillResponse { transactions: signatures.len(), imported: result.imported, skipped: result.skipped, truncated, oldest })
+}
+
+// 取出交易時間、錢包在交易後的 SOL 餘額，以及交易有動到時錢包持有的 WSOL 在交易前後的數量
+fn transaction_balances(transaction: &EncodedConfirmedTransactionWithStatusMeta, address: &str) -> Option<(DateTime<Utc>, f64, Option<(f64, f64)>)> {
+    let timestamp = DateTime::from_timestamp(transaction.block_time?, 0)?;
+    let meta = transaction.transaction.meta.as_ref()?;
+    let EncodedTransaction::Json(ui_transaction) = &transaction.transaction.transaction else {
+        return None;
+    };
+    let UiMessage::Raw(message) = &ui_transaction.message else {
+        return None;
+    };
+
+    // 完整帳戶列表 = 靜態帳戶 + ALT 載入的可寫帳戶 + ALT 載入的唯讀帳戶
+    let loaded: Vec<&String> = match &meta.loaded_addresses {
+        OptionSerializer::Some(loaded) => loaded.writable.iter().chain(loaded.readonly.iter()).collect(),
+        _ => Vec::new(),
+    };
+    let index = message.account_keys.iter().chain(loaded).position(|key| key == address)?;
+    let sol_balance = *meta.post_balances.get(index)? as f64 / 1_000_000_000.0;
+
+    let wsol = |balances: &OptionSerializer<Vec<UiTransactionTokenBalance>>| -> Option<f64> {
+        let OptionSerializer::Some(balances) = balances else {
+            return None;
+        };
+        let amounts: Vec<f64> = balances
+            .iter()
+            .filter(|balance| balance.mint == WSOL_MINT && matches!(&balance.owner, OptionSerializer::Some(owner) if owner == address))
+            .filter_map(|balance| balance.ui_token_amount.amount.parse::<u64>().ok())
+            .map(|amount| amount as f64 / 1_000_000_000.0)
+            .collect();
+        (!amounts.is_empty()).then(|| amounts.iter().sum())
+    };
+    let wsol_change = match (wsol(&meta.pre_token_balances), wsol(&meta.post_token_balances)) {
+        (None, None) => None,
+        (pre, post) => Some((pre.unwrap_or(0.0), post.unwrap_or(0.0))),
+    };
+    Some((timestamp, sol_balance, wsol_change))
+}
+
+// 中斷所有串流並立即重連，用於懷疑串流停滯但未觸發自動重連時
+async fn restart_grpc_stream(
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<ApiResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let streaming = state.config.transport == Transport::WsRpc || state.config.grpc.is_some();
+    if !streaming {
+        return Err((StatusCode::CONFLICT, Json(ErrorResponse::new(ErrorCode::StreamingDisabled))));
+    }
+    state.grpc_restart.send_replace(());
+    info!("🔁 管理員要求重新連接串流");
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: "已要求重新連接串流".to_string(),
+    }))
+}
+
+async fn get_groups(axum::extract::State(state): axum::extract::State<AppState>) -> Json<Vec<GroupSummary>> {
+    Json(group_summaries(&state.wallets.read().await))
+}
+
+// 依群組加總未封存錢包的餘額，依名稱排序
+fn group_summaries(wallets: &HashMap<String, WalletBalance>) -> Vec<GroupSummary> {
+    let mut groups: HashMap<String, GroupSummary> = HashMap::new();
+
+    for wallet in wallets.values().filter(|w| !w.archived) {
+        let Some(group_name) = &wallet.group else {
+            continue;
+        };
+        let group = groups.entry(group_name.clone()).or_insert_with(|| GroupSummary {
+            name: group_name.clone(),
+            wallets: Vec::new(),
+            sol_balance: 0.0,
+            wsol_balance: 0.0,
+            staked_balance: 0.0,
+            lst_balance: 0.0,
+            total_balance: 0.0,
+        });
+        group.wallets.push(wallet.address.clone());
+        group.sol_balance += wallet.sol_balance;
+        group.wsol_balance += if wallet.wsol_initialized { wallet.wsol_balance } else { 0.0 };
+        group.staked_balance += wallet.staked_balance();
+        group.lst_balance += wallet.lst_balance();
+        group.total_balance += wallet.total_balance();
+    }
+
+    let mut groups: Vec<GroupSummary> = groups.into_values().collect();
+    groups.sort_by(|a, b| a.name.cmp(&b.name));
+    groups
+}
+
+async fn get_group_chart_data(
+    Path(name): Path<String>,
+    Query(params): Query<GroupChartQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<Vec<ChartDataPoint>>, StatusCode> {
+    let members: Vec<String> = state.wallets.read().await
+        .values()
+        .filter(|w| !w.archived && w.group.as_deref() == Some(name.as_str()))
+        .map(|w| w.address.clone())
+        .collect();
+    if members.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let from = interval_to_duration(&params.interval).map(|d| Utc::now() - d);
+    let converter = load_chart_converter(state.database.as_ref(), state.config.price.as_ref(), params.currency.as_deref(), from, None)?;
+    let series = load_chart_series(state.database.as_ref(), &members, from, None)?;
+    let mut aggregated = aggregate_history(series.iter());
+    if let Some(converter) = &converter {
+        aggregated = converter.convert_history(&aggregated).map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    }
+    Ok(Json(build_chart_data(aggregated.iter().collect(), &params.data_type, &params.interval, &params.sampling, params.ma, params.ema)))
+}
+
+async fn delete_wallet_history_handler(
+    Path(address): Path<String>,
+    Query(params): Query<DeleteHistoryQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<DeleteHistoryResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if !state.wallets.read().await.contains_key(&address) {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+    }
+
+    let after = params.after.and_then(|t| DateTime::from_timestamp(t, 0));
+    let before = params.before.and_then(|t| DateTime::from_timestamp(t, 0));
+    if let (Some(after), Some(before)) = (after, before) {
+        if after >= before {
+            return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse::new(ErrorCode::InvalidTimeRange))));
+        }
+    }
+    // 沒有上限的刪除會清掉到目前為止的所有記錄，避免漏帶參數時誤刪
+    if before.is_none() && !params.all {
+        return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse::new(ErrorCode::HistoryRangeRequired))));
+    }
+
+    // 先寫入佇列中的記錄，確保區間內的資料一併刪除
+    state.database_writer.flush().await;
+
+    let database = state.database.clone();
+    let db_address = address.clone();
+    let deleted = match tokio::task::spawn_blocking(move || database.delete_history(&db_address, after, before).map_err(|e| e.to_string())).await {
+        Ok(Ok(deleted)) => deleted,
+        Ok(Err(e)) => {
+            error!("❌ 刪除歷史記錄失敗: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::with_detail(ErrorCode::HistoryDeleteFailed, e))));
+        }
+        Err(e) => {
+            error!("❌ 刪除歷史記錄任務異常: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::HistoryDeleteFailed))));
+        }
+    };
+
+    // 同步移除記憶體中的歷史
+    if let Some(wallet) = state.wallets.write().await.get_mut(&address) {
+        wallet.history.retain(|h| after.is_some_and(|t| h.timestamp < t) || before.is_some_and(|t| h.timestamp >= t));
+    }
+
+    Ok(Json(DeleteHistoryResponse { deleted }))
+}
+
+async fn delete_wallet(
+    Path(address): Path<String>,
+    actor: AuditActor,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<ApiResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let name = state.wallets.read().await.get(&address).map(|w| w.name.clone());
+    let result = remove_wallet(address.clone(), &state).await;
+    record_audit(&state, actor, AuditAction::Delete, &address, name, &result);
+    result
+}
+
+async fn remove_wallet(address: String, state: &AppState) -> Result<Json<ApiResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let wallet_name = {
+        let mut wallets_guard = state.wallets.write().await;
+        if let Some(wallet) = wallets_guard.remove(&address) {
+            state.events.publish_removed(&address, &wallet.name);
+            wallet.name.clone()
+        } else {
+            return Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::WalletNotFound))));
+        }
+    };
+    
+    state.unsaved_wallets.lock().unwrap_or_else(|e| e.into_inner()).remove(&address);
+
+    // 先寫入佇列中的記錄，避免刪除後又寫回該錢包的資料
+    state.database_writer.flush().await;
+
+    // 刪除資料庫中的歷史記錄
+    if let Err(e) = state.database.delete_wallet(&address) {
+        warn!("⚠️ 刪除錢包歷史與交易記錄失敗: {}", e);
+    }
+    
+    // 更新配置文件
+    if let Err(e) = remove_from_config_file(&address).await {
+        warn!("⚠️ 更新配置文件失敗: {}", e);
+    }
+    
+    // 通知 gRPC 串流更新訂閱以停止監聽已刪除的錢包
+    state.grpc_resubscribe.send_replace(());
+    
+    info!("✅ 成功刪除錢包: {} ({}) - 正在更新gRPC訂閱", wallet_name, &address[..8]);
+    
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("成功刪除錢包 {}", wallet_name),
+    }))
+}
+
+// 寫入稽核記錄；寫入失敗只記錄警告，不影響原本操作的回應
+fn record_audit<T>(
+    state: &AppState,
+    actor: AuditActor,
+    action: AuditAction,
+    address: &str,
+    detail: Option<String>,
+    result: &Result<T, (StatusCode, Json<ErrorResponse>)>,
+) {
+    let entry = AuditEntry {
+        id: 0,
+        timestamp: Utc::now(),
+        action,
+        address: address.to_string(),
+        detail,
+        actor,
+        success: result.is_ok(),
+        error: result.as_ref().err().map(|(_, error)| error.code),
+    };
+    if let Err(e) = state.database.append_audit(&entry) {
+        warn!("⚠️ 寫入稽核記錄失敗: {}", e);
+    }
+}
+
+async fn get_audit_log(
+    Query(params): Query<AuditQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<Vec<AuditEntry>>, (StatusCode, Json<ErrorResponse>)> {
+    let limit = params.limit.clamp(1, 1000);
+    match state.database.load_audit(params.address.as_deref(), params.before, limit) {
+        Ok(entries) => Ok(Json(entries)),
+        Err(e) => {
+            error!("❌ 讀取稽核記錄失敗: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::AuditReadFailed))))
+        }
+    }
+}
+
+async fn get_alert_history(
+    Query(params): Query<AlertsQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<Vec<AlertRecord>>, (StatusCode, Json<ErrorResponse>)> {
+    let limit = params.limit.clamp(1, 1000);
+    match state.database.load_alerts(params.state, params.before, limit) {
+        Ok(alerts) => Ok(Json(alerts)),
+        Err(e) => {
+            error!("❌ 讀取告警記錄失敗: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::AlertsReadFailed))))
+        }
+    }
+}
+
+async fn get_daily_report(
+    Query(params): Query<DailyReportQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<DailyReport>, (StatusCode, Json<ErrorResponse>)> {
+    let today = Utc::now().date_naive();
+    let date = params.date.unwrap_or(today - chrono::Duration::days(1));
+    if date > today {
+        return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse::new(ErrorCode::InvalidReportDate))));
+    }
+    let currency = fiat_currency(state.config.price.as_ref(), params.currency.as_deref()).map_err(|e| (StatusCode::BAD_REQUEST, Json(e)))?;
+    // 尚未寫入資料庫的記錄也要計入，過去的日期產生後即保存，不能遺漏
+    state.database_writer.flush().await;
+
+    // 已保存的報表直接返回；過去的日期尚未產生時即時產生並保存，當天的報表只計算到目前為止且不保存
+    let members = report_members(&state.wallets).await;
+    let database = state.database.clone();
+    let result = tokio::task::spawn_blocking(move || -> Result<(DailyReport, Option<FiatConverter>), String> {
+        let report = match database.load_daily_report(date).map_err(|e| e.to_string())? {
+            Some(report) => report,
+            None => {
+                let report = generate_daily_report(database.as_ref(), &members, date).map_err(|e| e.to_string())?;
+                if date < today {
+                    database.save_daily_report(&report).map_err(|e| e.to_string())?;
+                }
+                report
+            }
+        };
+        let (from, to) = report_day_range(date);
+        let converter = currency.map(|currency| FiatConverter::load(database.as_ref(), &currency, Some(from), Some(to))).transpose();
+        Ok((report, converter.map_err(|e| e.to_string())?))
+    })
+    .await
+    .unwrap_or_else(|e| Err(e.to_string()));
+    match result {
+        Ok((mut report, converter)) => {
+            if let Some(converter) = converter {
+                report.apply_fiat(&converter).map_err(|code| {
+                    (StatusCode::SERVICE_UNAVAILABLE, Json(ErrorResponse::with_detail(code, format!("{} {}", converter.currency, date))))
+                })?;
+            }
+            Ok(Json(report))
+        }
+        Err(e) => {
+            error!("❌ 讀取 {} 的每日報表失敗: {}", date, e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::ReportReadFailed))))
+        }
+    }
+}
+
+async fn acknowledge_alert(
+    Path(id): Path<u64>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<AlertRecord>, (StatusCode, Json<ErrorResponse>)> {
+    // 告警經由寫入佇列儲存，先寫入佇列中的記錄，避免剛觸發的告警找不到
+    state.database_writer.flush().await;
+
+    let database = state.database.clone();
+    match tokio::task::spawn_blocking(move || database.set_alert_state(id, AlertState::Acknowledged).map_err(|e| e.to_string())).await {
+        Ok(Ok(Some(alert))) => {
+            info!("✅ 告警 {} ({}) 已確認", id, alert.rule);
+            Ok(Json(alert))
+        }
+        Ok(Ok(None)) => Err((StatusCode::NOT_FOUND, Json(ErrorResponse::new(ErrorCode::AlertNotFound)))),
+        Ok(Err(e)) => {
+            error!("❌ 更新告警 {} 狀態失敗: {}", id, e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::AlertUpdateFailed))))
+        }
+        Err(e) => {
+            error!("❌ 更新告警 {} 狀態任務異常: {}", id, e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse::new(ErrorCode::AlertUpdateFailed))))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct WebSocketQueryParams {
+    format: Option<String>, // "json" (預設) 或 "msgpack"
+}
+
+async fn websocket_handler(
+    ws: WebSocketUpgrade,
+    headers: axum::http::HeaderMap,
+    Query(params): Query<WebSocketQueryParams>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Response {
+    // 可用 ?format=msgpack 或 Sec-WebSocket-Protocol: msgpack 要求二進位格式
+    let msgpack_protocol = headers
+        .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|p| p.trim() == "msgpack");
+    let format = match params.format.as_deref() {
+        Some("msgpack") => WsFormat::MsgPack,
+        Some("json") => WsFormat::Json,
+        None if msgpack_protocol => WsFormat::MsgPack,
+        None => WsFormat::Json,
+        Some(other) => {
+            return (StatusCode::BAD_REQUEST, Json(ErrorResponse::with_detail(ErrorCode::UnsupportedFormat, other))).into_response();
+        }
+    };
+    // 超過連線上限時拒絕新的連線
+    let Ok(slot) = state.websocket_slots.clone().try_acquire_owned() else {
+        warn!("⚠️ WebSocket 連線數已達上限 {}，拒絕新連線", state.config.websocket.max_connections);
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(ErrorResponse::new(ErrorCode::TooManyConnections))).into_response();
+    };
+    ws.protocols(["msgpack"]).on_upgrade(move |socket| {
+        websocket_connection(socket, format, state.wallets, state.events, state.health, state.config.websocket, state.shutdown, slot)
+    })
+}
+
+// WebSocket 訊息編碼：JSON 文字或 MessagePack 二進位
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WsFormat {
+    Json,
+    MsgPack,
+}
+
+impl WsFormat {
+    fn encode(self, message: &serde_json::Value) -> axum::extract::ws::Message {
+        match self {
+            WsFormat::Json => axum::extract::ws::Message::Text(message.to_string()),
+            WsFormat::MsgPack => match rmp_serde::to_vec_named(message) {
+                Ok(bytes) => axum::extract::ws::Message::Binary(bytes),
+                Err(e) => {
+                    warn!("⚠️ MessagePack 編碼失敗，改用 JSON: {}", e);
+                    axum::extract::ws::Message::Text(message.to_string())
+                }
+            },
+        }
+    }
+}
+
+// 錢包推送用的 JSON，只含最新一筆歷史數據
+fn wallet_update_json(wallet: &WalletBalance) -> serde_json::Value {
+    let latest_history = wallet.history.back();
+    serde_json::json!({
+        "address": wallet.address,
+        "name": wallet.name,
+        "group": wallet.group,
+        "tags": wallet.tags,
+        "note": wallet.note,
+        "color": wallet.color,
+        "domain": wallet.domain,
+        "account_type": wallet.account_type,
+        "token": wallet.token,
+        "sol_balance": wallet.sol_balance,
+        "wsol_balance": if wallet.wsol_initialized { wallet.wsol_balance } else { 0.0 },
+        "wsol_account_open": wallet.wsol_account_open,
+        "staked_balance": wallet.staked_balance(),
+        "lst_balance": wallet.lst_balance(),
+        "lst_holdings": wallet.lst_holdings.values().collect::<Vec<_>>(),
+        "total_balance": wallet.total_balance(),
+        "last_update": wallet.last_update,
+        "last_slot": wallet.last_slot,
+        "initializing": !wallet.wsol_initialized,
+        "latest_data": latest_history.map(|h| serde_json::json!({
+            "time": h.timestamp.timestamp(),
+            "sol_balance": h.sol_balance,
+            "wsol_balance": h.wsol_balance,
+            "staked_balance": h.staked_balance,
+            "lst_balance": h.lst_balance,
+            "total_balance": h.total_balance
+        }))
+    })
+}
+
+// 所有未封存錢包的完整狀態，連線、變更訂閱與事件落後時發送
+async fn wallet_snapshot_events(wallets: &SharedWallets) -> Vec<WalletEvent> {
+    let wallets_guard = wallets.read().await;
+    wallets_guard
+        .values()
+        .filter(|wallet| !wallet.archived)
+        .map(|wallet| WalletEvent::Update {
+            address: wallet.address.clone(),
+            wallet: Arc::new(wallet_update_json(wallet)),
+        })
+        .collect()
+}
+
+// 推送 JSON 中可訂閱的欄位，address 一律保留
+const WALLET_UPDATE_FIELDS: &[&str] = &[
+    "address", "name", "group", "tags", "note", "color", "sol_balance", "wsol_balance", "staked_balance",
+    "lst_balance", "lst_holdings", "total_balance", "last_update", "last_slot", "initializing", "latest_data",
+];
+
+// WebSocket 客戶端訊息，例如 {"action":"subscribe","wallets":["addr"],"fields":["total_balance"]}
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum WsClientMessage {
+    Subscribe {
+        #[serde(default)]
+        wallets: Option<Vec<String>>,
+        #[serde(default)]
+        fields: Option<Vec<String>>,
+    },
+}
+
+// 客戶端的訂閱範圍，None 表示全部
+#[derive(Debug, Default)]
+struct WsSubscription {
+    wallets: Option<std::collections::HashSet<String>>,
+    fields: Option<Vec<String>>,
+}
+
+impl WsSubscription {
+    fn new(wallets: Option<Vec<String>>, fields: Option<Vec<String>>) -> Result<Self, String> {
+        if let Some(fields) = &fields {
+            let unknown: Vec<&str> = fields
+                .iter()
+                .map(String::as_str)
+                .filter(|field| !WALLET_UPDATE_FIELDS.contains(field))
+                .collect();
+            if !unknown.is_empty() {
+                return Err(format!("未知的欄位: {}，可用欄位: {}", unknown.join(", "), WALLET_UPDATE_FIELDS.join(", ")));
+            }
+        }
+        Ok(Self {
+            wallets: wallets.map(|wallets| wallets.into_iter().collect()),
+            fields,
+        })
+    }
+
+    // 轉成要發送的訊息；不在訂閱範圍內時返回 None
+    fn message(&self, event: &WalletEvent) -> Option<serde_json::Value> {
+        if self.wallets.as_ref().is_some_and(|wallets| !wallets.contains(event.address())) {
+            return None;
+        }
+        Some(match event {
+            WalletEvent::Update { wallet, .. } => serde_json::json!({ "type": "update", "wallet": self.filter_fields(wallet) }),
+            WalletEvent::Delete { address } => serde_json::json!({ "type": "delete", "address": address }),
+            WalletEvent::Added { wallet, .. } => serde_json::json!({ "type": "wallet_added", "wallet": self.filter_fields(wallet) }),
+            WalletEvent::Removed { address, name } => serde_json::json!({ "type": "wallet_removed", "address": address, "name": name }),
+            WalletEvent::Renamed { address, old_name, name } => serde_json::json!({
+                "type": "wallet_renamed",
+                "address": address,
+                "old_name": old_name,
+                "name": name
+            }),
+        })
+    }
+
+    fn filter_fields(&self, wallet: &serde_json::Value) -> serde_json::Value {
+        let Some(fields) = &self.fields else {
+            return wallet.clone();
+        };
+        let mut filtered = serde_json::Map::new();
+        filtered.insert("address".to_string(), wallet["address"].clone());
+        for field in fields {
+            if let Some(value) = wallet.get(field) {
+                filtered.insert(field.clone(), value.clone());
+            }
+        }
+        serde_json::Value::Object(filtered)
+    }
+
+    fn messages(&self, events: &[WalletEvent]) -> Vec<serde_json::Value> {
+        events.iter().filter_map(|event| self.message(event)).collect()
+    }
+}
+
+// 每個 WebSocket 客戶端的有界發送佇列，由獨立任務寫入 socket；
+// 佇列滿時丟棄後續更新並標記需要重新同步，避免慢速客戶端拖住事件處理
+struct WsOutbox {
+    sender: tokio::sync::mpsc::Sender<axum::extract::ws::Message>,
+    format: WsFormat,
+    queue_size: usize,
+    resync_needed: bool,
+}
+
+impl WsOutbox {
+    // 客戶端已斷線時返回 Err
+    fn send(&mut self, message: &serde_json::Value) -> Result<(), ()> {
+        self.send_raw(self.format.encode(message))
+    }
+
+    fn send_raw(&mut self, message: axum::extract::ws::Message) -> Result<(), ()> {
+        match self.sender.try_send(message) {
+            Ok(()) => Ok(()),
+            Err(tokio::sync::mpsc::error::TrySendError::Full(_)) => {
+                if !self.resync_needed {
+                    warn!("⚠️ WebSocket 客戶端發送佇列已滿，暫停推送直到可重新同步");
+                    self.resync_needed = true;
+                }
+                Ok(())
+            }
+            Err(tokio::sync::mpsc::error::TrySendError::Closed(_)) => Err(()),
+        }
+    }
+
+    fn send_batch_update(&mut self, updates: Vec<serde_json::Value>) -> Result<(), ()> {
+        if updates.is_empty() {
+            return Ok(());
+        }
+        let count = updates.len();
+        self.send(&serde_json::json!({
+            "type": "batch_update",
+            "updates": updates
+        }))?;
+        debug!("📡 WebSocket 發送 {} 個錢包更新", count);
+        Ok(())
+    }
+
+    // 佇列空出一半後，先通知客戶端再發送訂閱範圍內的完整狀態
+    async fn try_resync(&mut self, subscription: &WsSubscription, wallets: &SharedWallets) -> Result<(), ()> {
+        if !self.resync_needed || self.sender.capacity() < self.queue_size / 2 {
+            return Ok(());
+        }
+        self.resync_needed = false;
+        self.send(&serde_json::json!({ "type": "lagged", "message": "更新過多，已略過部分事件，以下為完整狀態" }))?;
+        self.send_batch_update(subscription.messages(&wallet_snapshot_events(wallets).await))
+    }
+}
+
+async fn websocket_writer(
+    mut sink: futures::stream::SplitSink<WebSocket, axum::extract::ws::Message>,
+    mut receiver: tokio::sync::mpsc::Receiver<axum::extract::ws::Message>,
+) {
+    while let Some(message) = receiver.recv().await {
+        let closing = matches!(message, axum::extract::ws::Message::Close(_));
+        if sink.send(message).await.is_err() || closing {
+            break;
+        }
+    }
+    let _ = sink.close().await;
+}
+
+// 處理客戶端訊息 (JSON 文字或 MessagePack 二進位)；訂閱變更後重新發送訂閱範圍內的完整狀態
+async fn handle_ws_client_message(
+    outbox: &mut WsOutbox,
+    message: Result<WsClientMessage, String>,
+    subscription: &mut WsSubscription,
+    wallets: &SharedWallets,
+) -> Result<(), ()> {
+    let result = message
+        .map_err(|e| format!("無效的訊息: {}", e))
+        .and_then(|message| match message {
+            WsClientMessage::Subscribe { wallets, fields } => WsSubscription::new(wallets, fields),
+        });
+    match result {
+        Ok(new_subscription) => {
+            *subscription = new_subscription;
+            outbox.send(&serde_json::json!({
+                "type": "subscribed",
+                "wallets": subscription.wallets.as_ref().map(|wallets| wallets.iter().collect::<Vec<_>>()),
+                "fields": subscription.fields,
+            }))?;
+            outbox.send_batch_update(subscription.messages(&wallet_snapshot_events(wallets).await))
+        }
+        Err(message) => outbox.send(&serde_json::json!({ "type": "error", "message": message })),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn websocket_connection(
+    socket: WebSocket,
+    format: WsFormat,
+    wallets: SharedWallets,
+    events: WalletEvents,
+    health: SharedHealth,
+    config: WebSocketConfig,
+    mut shutdown: ShutdownSignal,
+    _slot: tokio::sync::OwnedSemaphorePermit, // 連線結束時釋放連線名額
+) {
+    health.websocket_clients.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    // 先訂閱再取完整狀態，避免遺漏兩者之間的事件
+    let mut receiver = events.subscribe();
+    let mut subscription = WsSubscription::default();
+
+    let (sink, mut stream) = socket.split();
+    let queue_size = config.send_queue_size.max(2);
+    let (sender, outgoing) = tokio::sync::mpsc::channel(queue_size);
+    let writer = tokio::spawn(websocket_writer(sink, outgoing));
+    let mut outbox = WsOutbox { sender, format, queue_size, resync_needed: false };
+
+    // 定期 ping，並關閉長時間沒有回應的連線 (例如代理後方已斷線的客戶端)
+    let ping_enabled = config.ping_interval_secs > 0;
+    let mut ping_interval = tokio::time::interval(Duration::from_secs(config.ping_interval_secs.max(1)));
+    ping_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    ping_interval.tick().await;
+    let idle_timeout = Duration::from_secs(config.idle_timeout_secs);
+    let mut last_seen = std::time::Instant::now();
+
+    if outbox.send_batch_update(subscription.messages(&wallet_snapshot_events(&wallets).await)).is_ok() {
+        loop {
+            tokio::select! {
+                event = receiver.recv() => {
+                    let mut pending = match event {
+                        Ok(event) => vec![event],
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                            warn!("⚠️ WebSocket 客戶端落後 {} 個事件，需要重新同步", skipped);
+                            outbox.resync_needed = true;
+                            Vec::new()
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    };
+                    // 合併已在佇列中的事件，一次發送
+                    loop {
+                        match receiver.try_recv() {
+                            Ok(event) => pending.push(event),
+                            Err(tokio::sync::broadcast::error::TryRecvError::Lagged(skipped)) => {
+                                warn!("⚠️ WebSocket 客戶端落後 {} 個事件，需要重新同步", skipped);
+                                outbox.resync_needed = true;
+                            }
+                            Err(_) => break,
+                        }
+                    }
+                    // 等待重新同步期間的事件會包含在完整狀態中，直接略過
+                    let result = if outbox.resync_needed {
+                        outbox.try_resync(&subscription, &wallets).await
+                    } else {
+                        outbox.send_batch_update(subscription.messages(&pending))
+                    };
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                _ = ping_interval.tick(), if ping_enabled => {
+                    if config.idle_timeout_secs > 0 && last_seen.elapsed() >= idle_timeout {
+                        info!("🔌 WebSocket 客戶端 {} 秒未回應，關閉連線", last_seen.elapsed().as_secs());
+                        let _ = outbox.send_raw(axum::extract::ws::Message::Close(None));
+                        break;
+                    }
+                    if outbox.send_raw(axum::extract::ws::Message::Ping(Vec::new())).is_err()
+                        || outbox.try_resync(&subscription, &wallets).await.is_err()
+                    {
+                        break;
+                    }
+                }
+                msg = stream.next() => {
+                    if matches!(msg, Some(Ok(_))) {
+                        last_seen = std::time::Instant::now();
+                    }
+                    let message = match msg {
+                        Some(Ok(axum::extract::ws::Message::Text(text))) => serde_json::from_str(&text).map_err(|e| e.to_string()),
+                        Some(Ok(axum::extract::ws::Message::Binary(bytes))) => rmp_serde::from_slice(&bytes).map_err(|e| e.to_string()),
+                        Some(Ok(_)) => continue,
+                        Some(Err(_)) | None => break,
+                    };
+                    if handle_ws_client_message(&mut outbox, message, &mut subscription, &wallets).await.is_err() {
+                        break;
+                    }
+                }
+                _ = shutdown.wait_for(|stopping| *stopping) => {
+                    // 服務關閉時以 1001 (going away) 關閉連線，客戶端可稍後重新連線
+                    let _ = outbox.send_raw(axum::extract::ws::Message::Close(Some(axum::extract::ws::CloseFrame {
+                        code: axum::extract::ws::close_code::AWAY,
+                        reason: "伺服器關閉中".into(),
+                    })));
+                    break;
+                }
+            }
+        }
+    }
+
+    // 關閉發送佇列，讓寫入任務送出剩餘訊息後結束；客戶端不再讀取時強制中止
+    drop(outbox);
+    let abort = writer.abort_handle();
+    if tokio::time::timeout(Duration::from_secs(5), writer).await.is_err() {
+        abort.abort();
+    }
+    health.websocket_clients.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+}
+
+// 對外的 gRPC 串流服務，推送與 WebSocket 相同的錢包事件
+struct WalletMonitorService {
+    wallets: SharedWallets,
+    events: WalletEvents,
+}
+
+type WalletEventStream = std::pin::Pin<Box<dyn futures::Stream<Item = Result<wallet_monitor_proto::WalletEvent, tonic::Status>> + Send>>;
+
+#[tonic::async_trait]
+impl wallet_monitor_proto::wallet_monitor_server::WalletMonitor for WalletMonitorService {
+    type SubscribeStream = WalletEventStream;
+
+    async fn subscribe(
+        &self,
+        request: tonic::Request<wallet_monitor_proto::SubscribeRequest>,
+    ) -> Result<tonic::Response<Self::SubscribeStream>, tonic::Status> {
+        let filter: std::collections::HashSet<String> = request.into_inner().wallets.into_iter().collect();
+        // 先訂閱再取完整狀態，避免遺漏兩者之間的事件
+        let mut receiver = self.events.subscribe();
+        let wallets = self.wallets.clone();
+        let (sender, outgoing) = tokio::sync::mpsc::channel(64);
+        if filter.is_empty() {
+            info!("🛰️ gRPC 客戶端訂閱所有錢包");
+        } else {
+            info!("🛰️ gRPC 客戶端訂閱 {} 個錢包", filter.len());
+        }
+
+        tokio::spawn(async move {
+            let mut pending = wallet_snapshot_events(&wallets).await;
+            loop {
+                for event in &pending {
+                    if !filter.is_empty() && !filter.contains(event.address()) {
+                        continue;
+                    }
+                    if sender.send(Ok(grpc_wallet_event(event))).await.is_err() {
+                        return;
+                    }
+                }
+                pending = tokio::select! {
+                    event = receiver.recv() => match event {
+                        Ok(event) => vec![event],
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                            warn!("⚠️ gRPC 客戶端落後 {} 個事件，重新送出完整狀態", skipped);
+                            let lagged = wallet_monitor_proto::WalletEvent {
+                                event: Some(wallet_monitor_proto::wallet_event::Event::Lagged(true)),
+                            };
+                            if sender.send(Ok(lagged)).await.is_err() {
+                                return;
+                            }
+                            wallet_snapshot_events(&wallets).await
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+                    },
+                    _ = sender.closed() => return,
+                };
+            }
+        });
+
+        let stream = futures::stream::unfold(outgoing, |mut outgoing| async move {
+            outgoing.recv().await.map(|event| (event, outgoing))
+        });
+        Ok(tonic::Response::new(Box::pin(stream)))
+    }
+}
+
+// 由推送用的錢包 JSON 轉成 proto 訊息
+fn grpc_wallet_update(wallet: &serde_json::Value) -> wallet_monitor_proto::WalletUpdate {
+    let balance = |key: &str| wallet[key].as_f64().unwrap_or(0.0);
+    wallet_monitor_proto::WalletUpdate {
+        address: wallet["address"].as_str().unwrap_or_default().to_string(),
+        name: wallet["name"].as_str().unwrap_or_default().to_string(),
+        group: wallet["group"].as_str().map(str::to_string),
+        tags: wallet["tags"]
+            .as_array()
+            .map(|tags| tags.iter().filter_map(|t| t.as_str().map(str::to_string)).collect())
+            .unwrap_or_default(),
+        sol_balance: balance("sol_balance"),
+        wsol_balance: balance("wsol_balance"),
+        staked_balance: balance("staked_balance"),
+        lst_balance: balance("lst_balance"),
+        total_balance: balance("total_balance"),
+        last_update_ms: wallet["last_update"]
+            .as_str()
+            .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+            .map(|t| t.timestamp_millis())
+            .unwrap_or_default(),
+    }
+}
+
+fn grpc_wallet_event(event: &WalletEvent) -> wallet_monitor_proto::WalletEvent {
+    use wallet_monitor_proto::wallet_event::Event;
+    let event = match event {
+        WalletEvent::Update { wallet, .. } => Event::Update(grpc_wallet_update(wallet)),
+        WalletEvent::Added { wallet, .. } => Event::Added(grpc_wallet_update(wallet)),
+        WalletEvent::Delete { address } | WalletEvent::Removed { address, .. } => Event::Removed(address.clone()),
+        WalletEvent::Renamed { address, old_name, name } => Event::Renamed(wallet_monitor_proto::WalletRenamed {
+            address: address.clone(),
+            old_name: old_name.clone(),
+            name: name.clone(),
+        }),
+    };
+    wallet_monitor_proto::WalletEvent { event: Some(event) }
+}
+
+// 啟動 gRPC 服務；設定了讀取權杖時要求 authorization: Bearer <token>
+async fn serve_grpc(config: GrpcServerConfig, auth: AuthConfig, language: Language, service: WalletMonitorService, shutdown: ShutdownSignal) {
+    let addr = match tokio::net::lookup_host(&config.listen).await.ok().and_then(|mut addrs| addrs.next()) {
+        Some(addr) => addr,
+        None => {
+            error!("❌ 無法解析 gRPC 服務位址: {}", config.listen);
+            return;
+        }
+    };
+    // tonic 的攔截器介面固定返回 Result<Request, Status>
+    #[allow(clippy::result_large_err)]
+    let check_token = move |request: tonic::Request<()>| {
+        if !auth.enabled() || !auth.protect_reads {
+            return Ok(request);
+        }
+        let metadata = request.metadata();
+        let token = metadata
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(str::trim);
+        let code = match token {
+            Some(token) if token_matches(token, &auth.tokens) || token_matches(token, &auth.read_tokens) => return Ok(request),
+            Some(_) => ErrorCode::InvalidToken,
+            None => ErrorCode::TokenRequired,
+        };
+        // 訊息依 accept-language metadata 選擇語言，error-code 為機器可讀代碼
+        let language = metadata
+            .get("accept-language")
+            .and_then(|v| v.to_str().ok())
+            .and_then(Language::from_accept_language)
+            .unwrap_or(language);
+        let mut error_metadata = tonic::metadata::MetadataMap::new();
+        if let Ok(value) = code.name().parse() {
+            error_metadata.insert("error-code", value);
+        }
+        Err(tonic::Status::with_metadata(tonic::Code::Unauthenticated, code.message(language), error_metadata))
+    };
+
+    info!("🛰️ gRPC 服務啟動於 {}", addr);
+    let result = tonic::transport::Server::builder()
+        .add_service(wallet_monitor_proto::wallet_monitor_server::WalletMonitorServer::with_interceptor(service, check_token))
+        .serve_with_shutdown(addr, wait_for_shutdown(shutdown))
+        .await;
+    if let Err(e) = result {
+        error!("❌ gRPC 服務失敗: {}", e);
+    }
+}
+
+// 健康檢查回應
+#[derive(Debug, Serialize)]
+struct HealthResponse {
+    status: &'static str, // "ok" 或 "unavailable"
+    uptime_secs: u64,
+    grpc: GrpcHealth,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rpc: Option<RpcHealth>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    database: Option<DatabaseHealth>, // 寫入檢查只在 /readyz 進行
+    websocket_clients: usize,
+    reconciliation: ReconciliationHealth,
+    rpc_endpoints: Vec<RpcEndpointHealth>,
+}
+
+#[derive(Debug, Serialize)]
+struct ReconciliationHealth {
+    runs: u64,
+    corrections: u64,
+    seconds_since_last_run: Option<u64>,
+    recent: Vec<ReconciliationEvent>, // 最近的修正記錄，新的在後
+}
+
+fn reconciliation_health(state: &AppState) -> ReconciliationHealth {
+    let last_run_ms = state.health.last_reconciliation_ms.load(std::sync::atomic::Ordering::Relaxed);
+    ReconciliationHealth {
+        runs: state.health.reconciliation_runs.load(std::sync::atomic::Ordering::Relaxed),
+        corrections: state.health.reconciliation_corrections.load(std::sync::atomic::Ordering::Relaxed),
+        seconds_since_last_run: (last_run_ms > 0).then(|| (Utc::now().timestamp_millis() - last_run_ms).max(0) as u64 / 1000),
+        recent: state.health.reconciliation_events.lock().unwrap().iter().cloned().collect(),
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct GrpcHealth {
+    connected: bool,
+    streams: usize,
+    connected_streams: usize,
+    seconds_since_last_update: u64,
+    stale: bool,
+    stale_reconnects: u64,
+    current_slot: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    slot_lag: Option<u64>, // RPC 節點 slot - 串流 slot，只在 /readyz 計算
+}
+
+#[derive(Debug, Serialize)]
+struct RpcHealth {
+    reachable: bool,
+    latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    slot: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct DatabaseHealth {
+    writable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+fn grpc_health(state: &AppState) -> GrpcHealth {
+    let seconds_since_last_update = state.health.seconds_since_grpc_update();
+    GrpcHealth {
+        connected: state.health.grpc_connected(),
+        streams: state.health.grpc_streams.load(std::sync::atomic::Ordering::Relaxed),
+        connected_streams: state.health.grpc_connected_streams.load(std::sync::atomic::Ordering::Relaxed),
+        seconds_since_last_update,
+        stale: seconds_since_last_update > state.config.health.stale_after_secs,
+        stale_reconnects: state.health.grpc_stale_reconnects.load(std::sync::atomic::Ordering::Relaxed),
+        current_slot: state.health.current_slot(),
+        slot_lag: None,
+    }
+}
+
+async fn database_health(state: &AppState) -> DatabaseHealth {
+    let db = state.database.clone();
+    let timeout = Duration::from_secs(state.config.health.rpc_timeout_secs);
+    let result = match tokio::time::timeout(timeout, tokio::task::spawn_blocking(move || db.check_writable().map_err(|e| e.to_string()))).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("寫入檢查逾時".to_string()),
+    };
+    DatabaseHealth { writable: result.is_ok(), error: result.err() }
+}
+
+async fn rpc_health(state: &AppState) -> RpcHealth {
+    let timeout = Duration::from_secs(state.config.health.rpc_timeout_secs);
+    let started = std::time::Instant::now();
+    let result = match tokio::time::timeout(timeout, state.rpc.call(|client| async move { client.get_slot().await })).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(_) => Err("RPC 查詢逾時".to_string()),
+    };
+    RpcHealth {
+        reachable: result.is_ok(),
+        latency_ms: started.elapsed().as_millis() as u64,
+        slot: result.as_ref().ok().copied(),
+        error: result.err(),
+    }
+}
+
+fn health_response(status_ok: bool, body: HealthResponse) -> (StatusCode, Json<HealthResponse>) {
+    let status = if status_ok { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(body))
+}
+
+// 存活檢查：Geyser 串流長時間無更新時返回 503，讓 watchdog 重啟服務；只讀取記憶體中的狀態，不寫入資料庫
+async fn healthz(axum::extract::State(state): axum::extract::State<AppState>) -> (StatusCode, Json<HealthResponse>) {
+    let grpc = grpc_health(&state);
+    let ok = !grpc.stale;
+    health_response(ok, HealthResponse {
+        status: if ok { "ok" } else { "unavailable" },
+        uptime_secs: state.health.started_at.elapsed().as_secs(),
+        grpc,
+        rpc: None,
+        database: None,
+        websocket_clients: state.health.websocket_clients.load(std::sync::atomic::Ordering::Relaxed),
+        reconciliation: reconciliation_health(&state),
+        rpc_endpoints: state.rpc.health(),
+    })
+}
+
+// 就緒檢查：另外確認 gRPC 已連線、RPC 節點可連線且資料庫可寫入
+async fn readyz(axum::extract::State(state): axum::extract::State<AppState>) -> (StatusCode, Json<HealthResponse>) {
+    let mut grpc = grpc_health(&state);
+    let (database, rpc) = tokio::join!(database_health(&state), rpc_health(&state));
+    grpc.slot_lag = rpc.slot.filter(|_| grpc.current_slot > 0).map(|slot| slot.saturating_sub(grpc.current_slot));
+    let ok = grpc.connected && !grpc.stale && database.writable && rpc.reachable;
+    health_response(ok, HealthResponse {
+        status: if ok { "ok" } else { "unavailable" },
+        uptime_secs: state.health.started_at.elapsed().as_secs(),
+        grpc,
+        rpc: Some(rpc),
+        database: Some(database),
+        websocket_clients: state.health.websocket_clients.load(std::sync::atomic::Ordering::Relaxed),
+        reconciliation: reconciliation_health(&state),
+        rpc_endpoints: state.rpc.health(),
+    })
+}
+
+// /api/v1 的統一回應格式：{"data", "error", "meta"}
+#[derive(Debug, Serialize)]
+struct ApiEnvelope {
+    data: Option<serde_json::Value>,
+    error: Option<ApiError>,
+    meta: ApiMeta,
+}
+
+#[derive(Debug, Serialize)]
+struct ApiError {
+    code: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<String>, // ErrorResponse 的機器可讀代碼
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ApiMeta {
+    version: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total: Option<usize>, // 分頁查詢時符合條件的總數
+}
+
+async fn api_v1_envelope(request: axum::extract::Request, next: axum::middleware::Next) -> Response {
+    let response = next.run(request).await;
+    let status = response.status();
+    let headers = response.headers();
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+
+    // CSV 等檔案下載維持原樣
+    if status.is_success() && (!is_json || headers.contains_key(header::CONTENT_DISPOSITION)) {
+        return response;
+    }
+
+    let total = headers
+        .get("x-total-count")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("❌ 讀取 API 回應失敗: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let value = serde_json::from_slice::<serde_json::Value>(&bytes).ok();
+    let envelope = if status.is_success() {
+        ApiEnvelope { data: value, error: None, meta: ApiMeta { version: "v1", total } }
+    } else {
+        // 錯誤訊息取自 ErrorResponse，沒有內容時使用狀態碼說明
+        let message = value
+            .as_ref()
+            .and_then(|v| v["error"].as_str())
+            .map(str::to_string)
+            .or_else(|| Some(String::from_utf8_lossy(&bytes).trim().to_string()).filter(|m| !m.is_empty()))
+            .unwrap_or_else(|| status.canonical_reason().unwrap_or("error").to_string());
+        let error_code = value.as_ref().and_then(|v| v["code"].as_str()).map(str::to_string);
+        ApiEnvelope {
+            data: None,
+            error: Some(ApiError { code: status.as_u16(), error_code, message }),
+            meta: ApiMeta { version: "v1", total: None },
+        }
+    };
+
+    let body = serde_json::to_vec(&envelope).unwrap_or_default();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(header::CONTENT_TYPE, header::HeaderValue::from_static("application/json"));
+    Response::from_parts(parts, Body::from(body))
+}
+
+// 錯誤回應以中文建立；請求偏好英文 (或 logging.language = "en") 時依錯誤代碼改寫訊息
+async fn localize_errors(
+    axum::extract::State(default_language): axum::extract::State<Language>,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Response {
+    let language = Language::from_headers(request.headers()).unwrap_or(default_language);
+    let response = next.run(request).await;
+    let status = response.status();
+    if language == Language::Zh || !(status.is_client_error() || status.is_server_error()) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("❌ 讀取 API 回應失敗: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    // 非 ErrorResponse 的內容 (例如框架產生的純文字錯誤) 原樣回傳
+    let Ok(original) = serde_json::from_slice::<ErrorResponse>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    let localized = ErrorResponse::localized(original.code, original.detail, language);
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(header::CONTENT_LANGUAGE, header::HeaderValue::from_static(language.tag()));
+    Response::from_parts(parts, Body::from(serde_json::to_vec(&localized).unwrap_or_default()))
+}
+
+fn token_matches(candidate: &str, tokens: &[String]) -> bool {
+    matching_token(candidate, tokens).is_some()
+}
+
+// 以固定時間比較權杖，避免透過回應時間猜測；返回符合的權杖序號
+fn matching_token(candidate: &str, tokens: &[String]) -> Option<usize> {
+    tokens.iter().enumerate().fold(None, |found, (index, token)| {
+        let equal = token.len() == candidate.len()
+            && token.bytes().zip(candidate.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0;
+        found.or(equal.then_some(index))
+    })
+}
+
+fn unauthorized(code: ErrorCode) -> Response {
+    let mut response = (StatusCode::UNAUTHORIZED, Json(ErrorResponse::new(code))).into_response();
+    response.headers_mut().insert(header::WWW_AUTHENTICATE, header::HeaderValue::from_static("Bearer"));
+    response
+}
+
+// 檢查 Authorization: Bearer 權杖；WebSocket 無法自訂標頭，另接受 ?token= 參數
+async fn require_api_token(
+    axum::extract::State(auth): axum::extract::State<AuthConfig>,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Response {
+    let mutating = !matches!(*request.method(), axum::http::Method::GET | axum::http::Method::HEAD | axum::http::Method::OPTIONS);
+    check_api_token(&auth, mutating, false, request, next).await
+}
+
+// 管理端點 (審計日誌、備份、告警記錄) 即使是讀取也需要完整權杖，不受 protect_reads 影響
+async fn require_admin_token(
+    axum::extract::State(auth): axum::extract::State<AuthConfig>,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Response {
+    check_api_token(&auth, true, false, request, next).await
+}
+
+// 瀏覽器的 WebSocket 無法設定標頭，只有 /ws 接受 ?token= 查詢參數
+async fn require_ws_token(
+    axum::extract::State(auth): axum::extract::State<AuthConfig>,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Response {
+    check_api_token(&auth, false, true, request, next).await
+}
+
+// GraphQL 查詢以 POST 送出但不會修改資料，唯讀權杖即可存取
+async fn require_read_token(
+    axum::extract::State(auth): axum::extract::State<AuthConfig>,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Response {
+    check_api_token(&auth, false, false, request, next).await
+}
+
+async fn check_api_token(auth: &AuthConfig, mutating: bool, allow_query: bool, mut request: axum::extract::Request, next: axum::middleware::Next) -> Response {
+    if !auth.enabled() || (!mutating && !auth.protect_reads) {
+        return next.run(request).await;
+    }
+
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .map(str::to_string);
+    // 查詢參數需經 URL 解碼，權杖含 + / = 等字元時才能正確比對
+    let query_token = allow_query
+        .then(|| axum::extract::Query::<HashMap<String, String>>::try_from_uri(request.uri()).ok())
+        .flatten()
+        .and_then(|axum::extract::Query(mut query)| query.remove("token"));
+    let Some(token) = bearer.or(query_token) else {
+        return unauthorized(ErrorCode::TokenRequired);
+    };
+
+    if let Some(index) = matching_token(&token, &auth.tokens) {
+        request.extensions_mut().insert(ApiTokenId(format!("tokens[{}]", index)));
+        next.run(request).await
+    } else if token_matches(&token, &auth.read_tokens) {
+        if mutating {
+            (StatusCode::FORBIDDEN, Json(ErrorResponse::new(ErrorCode::ReadOnlyToken))).into_response()
+        } else {
+            next.run(request).await
+        }
+    } else {
+        unauthorized(ErrorCode::InvalidToken)
+    }
+}
+
+// 收到 SIGINT / SIGTERM 後設為 true，Web 服務、WebSocket 連線與 gRPC 服務一同結束
+type ShutdownSignal = tokio::sync::watch::Receiver<bool>;
+
+async fn wait_for_shutdown(mut shutdown: ShutdownSignal) {
+    let _ = shutdown.wait_for(|stopping| *stopping).await;
+}
+
+// 等待 Ctrl+C 或 SIGTERM (docker stop、systemctl stop)
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(terminate) => terminate,
+            Err(e) => {
+                warn!("⚠️ 無法監聽 SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+                return;
+            }
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+// 以 rustls 提供 HTTPS，並可選擇在另一個埠將 HTTP 轉址到 HTTPS
+async fn serve_https(
+    app: Router,
+    server_addr: &str,
+    host: &str,
+    https_port: u16,
+    tls: &TlsConfig,
+    shutdown: ShutdownSignal,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let rustls_config = axum_server::tls_rustls::RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path)
+        .await
+        .map_err(|e| format!("無法載入 TLS 憑證 {} / {}: {}", tls.cert_path, tls.key_path, e))?;
+    let addr = tokio::net::lookup_host(server_addr)
+        .await?
+        .next()
+        .ok_or_else(|| format!("無法解析伺服器位址: {}", server_addr))?;
+
+    if let Some(redirect_port) = tls.redirect_http_port {
+        let redirect_addr = format!("{}:{}", host, redirect_port);
+        let listener = tokio::net::TcpListener::bind(&redirect_addr).await?;
+        info!("↪️ HTTP 轉址服務啟動於 http://{} -> https 埠 {}", redirect_addr, https_port);
+        let redirect = Router::new().fallback(move |headers: axum::http::HeaderMap, uri: axum::http::Uri| async move {
+            redirect_to_https(&headers, &uri, https_port)
+        });
+        let redirect_shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, redirect).with_graceful_shutdown(wait_for_shutdown(redirect_shutdown)).await {
+                error!("❌ HTTP 轉址服務失敗: {}", e);
+            }
+        });
+    }
+
+    let handle = axum_server::Handle::new();
+    let shutdown_handle = handle.clone();
+    tokio::spawn(async move {
+        wait_for_shutdown(shutdown).await;
+        shutdown_handle.graceful_shutdown(Some(Duration::from_secs(10)));
+    });
+
+    info!("🔒 Web服務器啟動於 https://{}", server_addr);
+    axum_server::bind_rustls(addr, rustls_config)
+        .handle(handle)
+        .serve(app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+        .await?;
+    Ok(())
+}
+
+// 監聽 Unix domain socket，供 nginx/caddy 等反向代理轉發
+#[cfg(unix)]
+async fn serve_unix_socket(app: Router, socket_path: &str, shutdown: ShutdownSignal) -> Result<(), Box<dyn std::error::Error>> {
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    // 移除上次未正常關閉留下的 socket 檔；路徑是其他類型的檔案時拒絕啟動，避免設定錯誤刪除資料
+    if let Ok(metadata) = fs::symlink_metadata(socket_path) {
+        if !metadata.file_type().is_socket() {
+            return Err(format!("{} 已存在且不是 Unix socket，拒絕覆蓋", socket_path).into());
+        }
+        fs::remove_file(socket_path)?;
+    }
+    let listener = tokio::net::UnixListener::bind(socket_path)
+        .map_err(|e| format!("無法監聽 Unix socket {}: {}", socket_path, e))?;
+    fs::set_permissions(socket_path, fs::Permissions::from_mode(0o660))?;
+    info!("🌐 Web服務器啟動於 unix:{}", socket_path);
+
+    // Unix socket 沒有來源 IP，改由反向代理的標頭取得，審計日誌與速率限制才有可用的 IP
+    let app = app.layer(axum::middleware::from_fn(forwarded_connect_info));
+    let shutdown = wait_for_shutdown(shutdown);
+    tokio::pin!(shutdown);
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("⚠️ 接受 Unix socket 連線失敗: {}", e);
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+
+        let service = hyper_util::service::TowerToHyperService::new(app.clone());
+        tokio::spawn(async move {
+            if let Err(e) = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("Unix socket 連線結束: {}", e);
+            }
+        });
+    }
+
+    let _ = fs::remove_file(socket_path);
+    Ok(())
+}
+
+// 從 X-Forwarded-For (第一個位址) 或 X-Real-IP 取得客戶端 IP；socket 檔權限限制只有本機代理能連線，因此信任這些標頭
+#[cfg(unix)]
+async fn forwarded_connect_info(mut request: axum::extract::Request, next: axum::middleware::Next) -> Response {
+    let headers = request.headers();
+    let ip = headers
+        .get("x-forwarded-for")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').next())
+        .or_else(|| headers.get("x-real-ip").and_then(|v| v.to_str().ok()))
+        .and_then(|v| v.trim().parse::<std::net::IpAddr>().ok())
+        .unwrap_or(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST));
+    request.extensions_mut().insert(axum::extract::ConnectInfo(std::net::SocketAddr::new(ip, 0)));
+    next.run(request).await
+}
+
+#[cfg(not(unix))]
+async fn serve_unix_socket(_app: Router, socket_path: &str, _shutdown: ShutdownSignal) -> Result<(), Box<dyn std::error::Error>> {
+    Err(format!("此平台不支援 Unix socket: {}", socket_path).into())
+}
+
+fn redirect_to_https(headers: &axum::http::HeaderMap, uri: &axum::http::Uri, https_port: u16) -> Response {
+    let Some(host) = headers.get(header::HOST).and_then(|v| v.to_str().ok()) else {
+        return (StatusCode::BAD_REQUEST, Json(ErrorResponse::new(ErrorCode::MissingHostHeader))).into_response();
+    };
+    // 去掉 HTTP 埠號，IPv6 位址保留方括號
+    let hostname = match host.rsplit_once(':') {
+        Some((name, port)) if !port.contains(']') => name,
+        _ => host,
+    };
+    let authority = if https_port == 443 { hostname.to_string() } else { format!("{}:{}", hostname, https_port) };
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    axum::response::Redirect::permanent(&format!("https://{}{}", authority, path)).into_response()
+}
+
+// 記錄每個請求的方法、路徑、狀態碼、耗時與來源 IP，過慢的請求另以 warn 提示
+async fn log_requests(
+    axum::extract::State(config): axum::extract::State<RequestLogConfig>,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let client = request
+        .extensions()
+        .get::<axum::extract::ConnectInfo<std::net::SocketAddr>>()
+        .map(|info| info.0.ip().to_string())
+        .unwrap_or_else(|| "-".to_string());
+    let started = std::time::Instant::now();
+
+    let response = next.run(request).await;
+
+    let elapsed = started.elapsed();
+    let status = response.status().as_u16();
+    let duration_ms = elapsed.as_secs_f64() * 1000.0;
+    // tracing 的事件等級必須是常數，依設定選擇對應的巨集
+    macro_rules! request_event {
+        ($event:ident, $message:literal) => {
+            tracing::$event!(target: "http", %method, %path, status, duration_ms, %client, $message)
+        };
+    }
+    match config.slow_threshold {
+        Some(threshold) if elapsed >= threshold => request_event!(warn, "🐢 慢請求"),
+        _ => match config.level.into_level() {
+            Some(tracing::Level::ERROR) => request_event!(error, "HTTP 請求"),
+            Some(tracing::Level::WARN) => request_event!(warn, "HTTP 請求"),
+            Some(tracing::Level::INFO) => request_event!(info, "HTTP 請求"),
+            Some(tracing::Level::DEBUG) => request_event!(debug, "HTTP 請求"),
+            Some(tracing::Level::TRACE) => request_event!(trace, "HTTP 請求"),
+            None => {}
+        },
+    }
+    response
+}
+
+// 舊版 /api 路由：回應不變，加上棄用標頭指向 /api/v1
+async fn deprecated_api_alias(request: axum::extract::Request, next: axum::middleware::Next) -> Response {
+    let path = request.uri().path();
+    let successor = format!("</api/v1{}>; rel=\"successor-version\"", path.strip_prefix("/api").unwrap_or(path));
+    let mut response = next.run(request).await;
+    response.headers_mut().insert("deprecation", header::HeaderValue::from_static("true"));
+    if let Ok(link) = header::HeaderValue::from_str(&successor) {
+        response.headers_mut().insert(header::LINK, link);
+    }
+    response
+}
+
+async fn serve_index() -> Html<&'static str> {
+    Html(include_str!("../web/index.html"))
+}
+
+// 讀取配置檔案
+fn config_path() -> &'static str {
+    CONFIG_PATH.get().map(String::as_str).unwrap_or("config.toml")
+}
+
+fn load_config() -> Result<Config, Box<dyn std::error::Error>> {
+    let config_content = fs::read_to_string(config_path())?;
+    let mut config: Config = toml::from_str(&config_content)?;
+    
+    // 如果沒有server配置，使用默認值
+    if config_content.find("[server]").is_none() {
+        config.server = ServerConfig::default();
+    }
+
+    // large_transfer 需要交易資料，只有 Geyser 訂閱提供；WebSocket 與輪詢模式直接拒絕，避免規則永遠不會觸發
+    if config.transport != Transport::Geyser || config.grpc.is_none() {
+        if let Some(rule) = config.notifications.alerts.iter().find(|rule| rule.kind == AlertKind::LargeTransfer) {
+            return Err(format!("告警規則 {} 為 large_transfer，需使用 Geyser 傳輸並設定 [grpc]", rule.name).into());
+        }
+    }
+    let rate = config.rpc.requests_per_second;
+    if rate != 0.0 && rate_period(rate).is_none() {
+        return Err(format!("rpc.requests_per_second 必須為 0 或有效的正數，目前為 {}", rate).into());
+    }
+    let rate = config.rate_limit.requests_per_second;
+    if rate != 0.0 && rate_period(rate).is_none() {
+        return Err(format!("rate_limit.requests_per_second 必須為 0 或有效的正數，目前為 {}", rate).into());
+    }
+    if let Some(mqtt) = config.notifications.mqtt.as_ref().filter(|mqtt| mqtt.qos > 2) {
+        return Err(format!("notifications.mqtt.qos 只能是 0、1 或 2，目前為 {}", mqtt.qos).into());
+    }
+    
+    Ok(config)
+}
+
+// 日誌過濾條件的重新載入控制代碼，配置文件變更時用來套用新的日誌等級
+type LogReloadHandle = tracing_subscriber::reload::Handle<
+    tracing_subscriber::EnvFilter,
+    tracing_subscriber::layer::Layered<Vec<LogLayer>, tracing_subscriber::Registry>,
+>;
+
+// 以 tracing 輸出日誌；依賴套件使用 log crate 的記錄也會一併轉入
+fn setup_logging(logging: &LoggingConfig) -> LogReloadHandle {
+    use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+    // 設定 RUST_LOG 時優先使用，方便臨時開啟除錯日誌
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env().or_else(|_| tracing_subscriber::EnvFilter::try_new(&logging.level)).unwrap_or_else(|e| {
+        eprintln!("⚠️ 無效的日誌等級 \"{}\": {}，改用 info", logging.level, e);
+        tracing_subscriber::EnvFilter::new("info")
+    });
+    let mut layers = vec![log_layer(logging.format, std::io::stdout, true)];
+    if let Some(path) = &logging.file {
+        match RotatingLogFile::open(path, logging.max_size_mb, logging.max_files) {
+            Ok(file) => layers.push(log_layer(logging.format, Mutex::new(file), false)),
+            Err(e) => eprintln!("⚠️ 無法開啟日誌檔 {}: {}，只輸出到 stdout", path, e),
+        }
+    }
+    let (filter, handle) = tracing_subscriber::reload::Layer::new(filter);
+    tracing_subscriber::registry().with(layers).with(filter).init();
+    handle
+}
+
+type LogLayer = Box<dyn tracing_subscriber::Layer<tracing_subscriber::Registry> + Send + Sync>;
+
+fn log_layer<W>(format: LogFormat, writer: W, ansi: bool) -> LogLayer
+where
+    W: for<'w> tracing_subscriber::fmt::MakeWriter<'w> + Send + Sync + 'static,
+{
+    use tracing_subscriber::Layer;
+
+    let layer = tracing_subscriber::fmt::layer().with_writer(writer).with_ansi(ansi);
+    match format {
+        LogFormat::Text => layer.boxed(),
+        // 事件欄位攤平到頂層，並附上目前 span 的欄位 (錢包地址、端點等)
+        LogFormat::Json => layer.json().flatten_event(true).with_current_span(true).with_span_list(false).boxed(),
+    }
+}
+
+// 依大小輪替的日誌檔：超過 max_size_mb 時將 file 改名為 file.1，舊檔依序往後移，最多保留 max_files 個
+struct RotatingLogFile {
+    path: std::path::PathBuf,
+    file: fs::File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl RotatingLogFile {
+    fn open(path: &str, max_size_mb: u64, max_files: usize) -> std::io::Result<Self> {
+        let path = std::path::PathBuf::from(path);
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = fs::OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path, file, size, max_size: max_size_mb.max(1) * 1024 * 1024, max_files })
+    }
+
+    fn rotated_path(&self, index: usize) -> std::path::PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file.flush()?;
+        if self.max_files > 0 {
+            let _ = fs::remove_file(self.rotated_path(self.max_files));
+            for index in (1..self.max_files).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    fs::rename(&from, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        // max_files 為 0 時直接清空目前的檔案
+        self.file = fs::OpenOptions::new().create(true).write(true).truncate(true).open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingLogFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+// 計算錢包的 WSOL ATA 地址
+fn calculate_wsol_ata(wallet_address: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let wallet = ProgramPubkey::from_str(wallet_address)?;
+    let wsol_mint = ProgramPubkey::from_str(WSOL_MINT)?;
+    let associated_program_id = ProgramPubkey::from_str(ASSOCIATED_TOKEN_PROGRAM_ID)?;
+    let token_program_id = ProgramPubkey::from_str(TOKEN_PROGRAM_ID)?;
+    
+    let (ata, _) = ProgramPubkey::find_program_address(
+        &[wallet.as_ref(), token_program_id.as_ref(), wsol_mint.as_ref()],
+        &associated_program_id,
+    );
+    
+    Ok(ata.to_string())
+}
+
+// 計算所有錢包的 WSOL ATA 地址
+fn calculate_all_wsol_atas(wallet_addresses: &[String]) -> Vec<String> {
+    let mut ata_addresses = Vec::new();
+    
+    for wallet_address in wallet_addresses {
+        match calculate_wsol_ata(wallet_address) {
+            Ok(ata) => {
+                info!("💎 錢包 {} 的 WSOL ATA: {}", &wallet_address[..8], &ata[..8]);
+                ata_addresses.push(ata);
+            }
+            Err(e) => {
+                error!("❌ 計算錢包 {} 的 WSOL ATA 失敗: {}", wallet_address, e);
+            }
+        }
+    }
+    
+    ata_addresses
+}
+
+// 單一帳戶的最新狀態，Geyser 串流與 RPC 輪詢共用同一套處理流程
+struct AccountUpdate {
+    pubkey: String,
+    lamports: u64,
+    data: Vec<u8>,
+    slot: u64,
+}
+
+// 依帳戶類型更新錢包餘額 (SOL、WSOL、Stake、LST)，並立即推送給 WebSocket 客戶端
+#[allow(clippy::too_many_arguments)]
+fn apply_account_update(
+    account: &AccountUpdate,
+    wallets: &mut HashMap<String, WalletBalance>,
+    subscription: &AccountSubscription,
+    lst_tokens: &[LstToken],
+    lst_rates: &SharedLstRates,
+    writer: &DatabaseWriter,
+    notifier: &Notifier,
+    events: &WalletEvents,
+) {
+    // 此次更新的日誌都帶上所屬錢包，方便依錢包查詢
+    let wallet = subscription.wallet_for(&account.pubkey).unwrap_or_default();
+    let _span = info_span!("account_update", %wallet, account = %account.pubkey, slot = account.slot).entered();
+    // 更新前的餘額，提供 script 告警的 prev_* 變數
+    let previous = wallets.get(&wallet).map(|w| (w.address.clone(), w.balance_point()));
+    let mut updated = Vec::new();
+    // 處理 SOL 帳戶更新
+    match handle_sol_account_update(account, wallets, &subscription.wallet_addresses, writer, notifier) {
+        Ok(address) => updated.extend(address),
+        Err(e) => warn!("⚠️ 處理SOL帳戶更新時出錯: {}", e),
+    }
+    // 處理 token_account 類型的代幣數量
+    match handle_token_account_update(account, wallets, writer, notifier) {
+        Ok(address) => updated.extend(address),
+        Err(e) => warn!("⚠️ 處理Token帳戶更新時出錯: {}", e),
+    }
+    // 處理 WSOL ATA 帳戶更新
+    match handle_wsol_account_update(account, wallets, &subscription.ata_to_wallet_map, writer, notifier) {
+        Ok(address) => updated.extend(address),
+        Err(e) => warn!("⚠️ 處理WSOL帳戶更新時出錯: {}", e),
+    }
+    // 處理 Stake 帳戶更新
+    match handle_stake_account_update(account, wallets, &subscription.stake_to_wallet_map, writer, notifier) {
+        Ok(address) => updated.extend(address),
+        Err(e) => warn!("⚠️ 處理Stake帳戶更新時出錯: {}", e),
+    }
+    // 處理 LST ATA 帳戶更新
+    match handle_lst_account_update(account, wallets, &subscription.lst_ata_map, lst_tokens, lst_rates, writer, notifier) {
+        Ok(address) => updated.extend(address),
+        Err(e) => warn!("⚠️ 處理LST帳戶更新時出錯: {}", e),
+    }
+    // 處理 owner_token_accounts 模式下的 token account 更新
+    match handle_owned_token_account_update(account, wallets, subscription, lst_tokens, lst_rates, writer, notifier) {
+        Ok(addresses) => updated.extend(addresses),
+        Err(e) => warn!("⚠️ 處理Token帳戶更新時出錯: {}", e),
+    }
+    for address in updated {
+        if let Some(wallet) = wallets.get_mut(&address) {
+            wallet.last_slot = wallet.last_slot.max(account.slot);
+            events.publish_update(wallet);
+            let previous = match &previous {
+                Some((primary, point)) if *primary == address => point.clone(),
+                _ => wallet.balance_point(),
+            };
+            notifier.check_balance(wallet, &previous);
+            notifier.check_scripts(wallet, &previous);
+        }
+    }
+}
+
+// 處理 WSOL Account 更新；帳戶被關閉 (lamports 回收) 時串流送來的 data 為空，視為餘額 0
+fn handle_wsol_account_update(
+    account: &AccountUpdate,
+    wallets: &mut HashMap<String, WalletBalance>,
+    ata_to_wallet_map: &HashMap<String, String>,
+    writer: &DatabaseWriter,
+    notifier: &Notifier,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    // 檢查是否是我們監聽的 ATA 地址
+    let Some(wallet_address) = ata_to_wallet_map.get(&account.pubkey) else {
+        return Ok(None);
+    };
+    let Some(wallet) = wallets.get_mut(wallet_address) else {
+        return Ok(None);
+    };
+
+    let closed = account.lamports == 0;
+    let wsol_balance = if closed {
+        0.0
+    } else {
+        // 解析 token account 數據
+        match token_account_amount(&account.data) {
+            Some(amount) => amount as f64 / 1_000_000_000.0, // WSOL decimals = 9
+            None => {
+                warn!("⚠️ 解析 token account 數據失敗: 資料長度 {}", account.data.len());
+                return Ok(None);
+            }
+        }
+    };
+    let account_event = match (wallet.wsol_account_open, closed) {
+        (true, true) => Some(AccountEvent::WsolAccountClosed),
+        (false, false) => Some(AccountEvent::WsolAccountOpened),
+        _ => None,
+    };
+    wallet.wsol_account_open = !closed;
+
+    let old_balance = wallet.wsol_balance;
+    let recorded = wallet.update_wsol(wsol_balance);
+
+    if let Some(account_event) = account_event {
+        info!("{} 錢包 {} 的 WSOL ATA {} (餘額 {:.9} → {:.9})",
+              account_event.description(),
+              &wallet_address[..8],
+              &account.pubkey[..8],
+              old_balance,
+              wsol_balance);
+        writer.save_token_event(TokenAccountEvent::wsol(wallet, account_event, &account.pubkey, old_balance, wsol_balance, account.slot));
+        notifier.notify(&BalanceChangeEvent::new(wallet, "wsol", old_balance, wsol_balance, account.slot).with_account_event(account_event));
+    } else if (wsol_balance - old_balance).abs() > 0.000001 {
+        info!("💎 錢包 {} WSOL 餘額變化: {:.9} SOL (從 {:.9} 到 {:.9})", 
+              &wallet_address[..8], 
+              wsol_balance - old_balance, 
+              old_balance, 
+              wsol_balance);
+        
+        wallet.print_balance("WSOL帳戶更新");
+        
+        notifier.notify(&BalanceChangeEvent::new(wallet, "wsol", old_balance, wsol_balance, account.slot));
+    }
+    // 保存到資料庫；合併期間內的變化由 history_flush_task 補記
+    if recorded {
+        writer.save_history(wallet.history_record());
+    }
+    Ok(Some(wallet.address.clone()))
+}
+
+// 讀取 token account 的 amount；訂閱使用 accounts_data_slice 時 data 只有這 8 bytes
+fn token_account_amount(data: &[u8]) -> Option<u64> {
+    let bytes = if data.len() == TOKEN_AMOUNT_LEN {
+        data
+    } else {
+        data.get(TOKEN_AMOUNT_OFFSET..TOKEN_AMOUNT_OFFSET + TOKEN_AMOUNT_LEN)?
+    };
+    bytes.try_into().ok().map(u64::from_le_bytes)
+}
+
+// 多個 RPC 端點的連線池：依健康狀態與延遲排序，遇到限流、逾時或節點落後時換下一個端點
+struct RpcPool {
+    endpoints: Vec<RpcEndpoint>,
+    cooldown_ms: i64,
+    max_concurrent_requests: usize,
+    requests_per_second: f64,
+    burst: f64,
+    bucket: Mutex<(f64, std::time::Instant)>, // 權杖數可為負，代表已預約的等待
+    max_retries: u32,
+    retry_backoff: Duration,
+}
+
+type SharedRpc = Arc<RpcPool>;
+
+struct RpcEndpoint {
+    url: String,
+    client: Arc<RpcClient>,
+    requests: std::sync::atomic::AtomicU64,
+    errors: std::sync::atomic::AtomicU64,
+    latency_ms: std::sync::atomic::AtomicU64, // 平滑後的平均延遲，0 表示尚未量測
+    unhealthy_until_ms: std::sync::atomic::AtomicI64,
+    last_error: Mutex<Option<String>>,
+}
+
+#[derive(Debug, Serialize)]
+struct RpcEndpointHealth {
+    url: String,
+    healthy: bool,
+    requests: u64,
+    errors: u64,
+    latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_error: Option<String>,
+}
+
+impl RpcPool {
+    fn new(config: &RpcConfig) -> Self {
+        let mut urls = vec![config.endpoint.clone()];
+        for url in &config.endpoints {
+            if !urls.contains(url) {
+                urls.push(url.clone());
+            }
+        }
+        let timeout = Duration::from_secs(config.timeout_secs.max(1));
+        Self {
+            endpoints: urls
+                .into_iter()
+                .map(|url| RpcEndpoint {
+                    client: Arc::new(RpcClient::new_with_timeout(url.clone(), timeout)),
+                    url,
+                    requests: std::sync::atomic::AtomicU64::new(0),
+                    errors: std::sync::atomic::AtomicU64::new(0),
+                    latency_ms: std::sync::atomic::AtomicU64::new(0),
+                    unhealthy_until_ms: std::sync::atomic::AtomicI64::new(0),
+                    last_error: Mutex::new(None),
+                })
+                .collect(),
+            cooldown_ms: config.failover_cooldown_secs as i64 * 1000,
+            max_concurrent_requests: config.max_concurrent_requests.max(1),
+            requests_per_second: config.requests_per_second,
+            burst: config.burst.max(1) as f64,
+            bucket: Mutex::new((config.burst.max(1) as f64, std::time::Instant::now())),
+            max_retries: config.max_retries,
+            retry_backoff: Duration::from_millis(config.retry_backoff_ms),
+        }
+    }
+
+    // 取得一個權杖，返回發送前需等待的時間
+    fn reserve(&self) -> Duration {
+        let rate = self.requests_per_second;
+        if rate <= 0.0 {
+            return Duration::ZERO;
+        }
+        let now = std::time::Instant::now();
+        let mut bucket = self.bucket.lock().unwrap();
+        let (tokens, last) = &mut *bucket;
+        *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * rate).min(self.burst) - 1.0;
+        *last = now;
+        if *tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            // 欠下的權杖過多時等待時間可能超出 Duration 範圍，以最長等待時間為上限而非 panic
+            Duration::try_from_secs_f64(-*tokens / rate).unwrap_or(Duration::MAX)
+        }
+    }
+
+    // 健康的端點優先，其中延遲較低者優先；全部不健康時仍依序嘗試
+    fn ordered(&self) -> Vec<&RpcEndpoint> {
+        let now = Utc::now().timestamp_millis();
+        let mut endpoints: Vec<&RpcEndpoint> = self.endpoints.iter().collect();
+        endpoints.sort_by_key(|endpoint| (!endpoint.is_healthy(now), endpoint.latency_ms.load(std::sync::atomic::Ordering::Relaxed)));
+        endpoints
+    }
+
+    // 只有可重試的錯誤才換端點，其他錯誤 (例如帳戶不存在) 直接返回。
+    // 所有端點都失敗時以指數退避整輪重試
+    async fn call<T, F, Fut>(&self, request: F) -> ClientResult<T>
+    where
+        F: Fn(Arc<RpcClient>) -> Fut,
+        Fut: std::future::Future<Output = ClientResult<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match self.call_once(&request).await {
+                Err(e) if attempt < self.max_retries && is_retryable_rpc_error(&e) => {
+                    let backoff = self.retry_backoff
+                        .checked_mul(2u32.saturating_pow(attempt))
+                        .map_or(Duration::from_millis(RPC_MAX_RETRY_BACKOFF_MS), |backoff| backoff.min(Duration::from_millis(RPC_MAX_RETRY_BACKOFF_MS)));
+                    attempt += 1;
+                    warn!("⏳ RPC 請求被限流或節點落後，{} 毫秒後第 {} 次重試: {}", backoff.as_millis(), attempt, e);
+                    tokio::time::sleep(backoff).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn call_once<T, F, Fut>(&self, request: &F) -> ClientResult<T>
+    where
+        F: Fn(Arc<RpcClient>) -> Fut,
+        Fut: std::future::Future<Output = ClientResult<T>>,
+    {
+        let mut last_error = None;
+        for endpoint in self.ordered() {
+            let wait = self.reserve();
+            if !wait.is_zero() {
+                tokio::time::sleep(wait).await;
+            }
+            let started = std::time::Instant::now();
+            endpoint.requests.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            match request(endpoint.client.clone()).instrument(info_span!("rpc_request", endpoint = %endpoint.display_url())).await {
+                Ok(value) => {
+                    endpoint.record_latency(started.elapsed().as_millis() as u64);
+                    return Ok(value);
+                }
+                Err(e) if is_retryable_rpc_error(&e) => {
+                    endpoint.errors.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    endpoint.unhealthy_until_ms.store(Utc::now().timestamp_millis() + self.cooldown_ms, std::sync::atomic::Ordering::Relaxed);
+                    *endpoint.last_error.lock().unwrap() = Some(e.to_string());
+                    if self.endpoints.len() > 1 {
+                        warn!("⚠️ RPC 端點 {} 暫時無法使用，切換到下一個端點: {}", endpoint.display_url(), e);
+                    }
+                    last_error = Some(e);
+                }
+                Err(e) => {
+                    endpoint.record_latency(started.elapsed().as_millis() as u64);
+                    return Err(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| ClientError::from(ClientErrorKind::Custom("沒有可用的 RPC 端點".to_string()))))
+    }
+
+    fn health(&self) -> Vec<RpcEndpointHealth> {
+        let now = Utc::now().timestamp_millis();
+        self.endpoints
+            .iter()
+            .map(|endpoint| RpcEndpointHealth {
+                url: endpoint.display_url(),
+                healthy: endpoint.is_healthy(now),
+                requests: endpoint.requests.load(std::sync::atomic::Ordering::Relaxed),
+                errors: endpoint.errors.load(std::sync::atomic::Ordering::Relaxed),
+                latency_ms: endpoint.latency_ms.load(std::sync::atomic::Ordering::Relaxed),
+                last_error: endpoint.last_error.lock().unwrap().clone(),
+            })
+            .collect()
+    }
+}
+
+impl RpcEndpoint {
+    fn is_healthy(&self, now_ms: i64) -> bool {
+        self.unhealthy_until_ms.load(std::sync::atomic::Ordering::Relaxed) <= now_ms
+    }
+
+    fn record_latency(&self, sample: u64) {
+        let previous = self.latency_ms.load(std::sync::atomic::Ordering::Relaxed);
+        let latency = if previous == 0 { sample.max(1) } else { (previous * 4 + sample) / 5 };
+        self.latency_ms.store(latency, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    // 隱藏查詢字串，避免 api-key 出現在日誌與健康檢查中
+    fn display_url(&self) -> String {
+        self.url.split('?').next().unwrap_or_default().to_string()
+    }
+}
+
+fn is_retryable_rpc_error(error: &ClientError) -> bool {
+    match error.kind() {
+        ClientErrorKind::Reqwest(_) | ClientErrorKind::Io(_) => true,
+        _ => {
+            let message = error.to_string();
+            RETRYABLE_RPC_ERRORS.iter().any(|pattern| message.contains(pattern))
+        }
+    }
+}
+
+// 查詢錢包擁有的原生 stake accounts (以 withdrawer 權限判斷擁有者)
+async fn query_stake_accounts(wallet_address: &str, rpc: &RpcPool) -> Result<HashMap<String, u64>, Box<dyn std::error::Error + Send + Sync>> {
+    let owner_pubkey = Pubkey::from_str(wallet_address)?;
+    let stake_program = Pubkey::from_str(STAKE_PROGRAM_ID)?;
+
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            STAKE_WITHDRAWER_OFFSET,
+            &owner_pubkey.to_bytes(),
+        ))]),
+        ..RpcProgramAccountsConfig::default()
+    };
+
+    let accounts = rpc
+        .call(|client| {
+            let config = config.clone();
+            async move { client.get_program_accounts_with_config(&stake_program, config).await }
+        })
+        .await?;
+    Ok(accounts
+        .into_iter()
+        .map(|(pubkey, account)| (pubkey.to_string(), account.lamports))
+        .collect())
+}
+
+// 查詢錢包擁有的所有 SPL token account (含非 ATA)，用於 owner_token_accounts 模式
+async fn query_token_accounts(wallet_address: &str, rpc: &RpcPool) -> Result<HashMap<String, OwnedTokenAccount>, Box<dyn std::error::Error + Send + Sync>> {
+    let owner_pubkey = Pubkey::from_str(wallet_address)?;
+    let token_program = Pubkey::from_str(TOKEN_PROGRAM_ID)?;
+
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::DataSize(TOKEN_ACCOUNT_LEN as u64),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(TOKEN_OWNER_OFFSET, &owner_pubkey.to_bytes())),
+        ]),
+        ..RpcProgramAccountsConfig::default()
+    };
+
+    let accounts = rpc
+        .call(|client| {
+            let config = config.clone();
+            async move { client.get_program_accounts_with_config(&token_program, config).await }
+        })
+        .await?;
+    Ok(accounts
+        .into_iter()
+        .filter_map(|(pubkey, account)| parse_owned_token_account(&account.data).map(|(_, token_account)| (pubkey.to_string(), token_account)))
+        .collect())
+}
+
+// 解析 SPL stake pool 帳戶，返回 (pool mint, 1 pool token 折合 SOL)
+fn parse_stake_pool(data: &[u8]) -> Option<(String, f64)> {
+    if data.len() < STAKE_POOL_TOKEN_SUPPLY_OFFSET + 8 {
+        return None;
+    }
+    let mint = Pubkey::try_from(&data[STAKE_POOL_MINT_OFFSET..STAKE_POOL_MINT_OFFSET + 32]).ok()?;
+    let total_lamports = u64::from_le_bytes(data[STAKE_POOL_TOTAL_LAMPORTS_OFFSET..STAKE_POOL_TOTAL_LAMPORTS_OFFSET + 8].try_into().ok()?);
+    let pool_token_supply = u64::from_le_bytes(data[STAKE_POOL_TOKEN_SUPPLY_OFFSET..STAKE_POOL_TOKEN_SUPPLY_OFFSET + 8].try_into().ok()?);
+    let rate = if pool_token_supply == 0 { 1.0 } else { total_lamports as f64 / pool_token_supply as f64 };
+    Some((mint.to_string(), rate))
+}
+
+// 查詢 LST 匯率 (1 LST = ? SOL)
+async fn fetch_lst_rate(rate_source: LstRateSource, rpc: &RpcPool) -> Result<(String, f64), Box<dyn std::error::Error + Send + Sync>> {
+    match rate_source {
+        LstRateSource::Marinade(mint) => {
+            let rate: f64 = reqwest::get(MARINADE_PRICE_URL).await?.text().await?.trim().parse()?;
+            Ok((mint.to_string(), rate))
+        }
+        LstRateSource::SplStakePool(pool) => {
+            let pool_pubkey = Pubkey::from_str(pool)?;
+            let account = rpc.call(|client| async move { client.get_account(&pool_pubkey).await }).await?;
+            parse_stake_pool(&account.data).ok_or_else(|| format!("無法解析 stake pool 帳戶 {}", pool).into())
+        }
+    }
+}
+
+// 解析內建 LST 清單的 mint 與初始匯率
+async fn resolve_lst_tokens(rpc: &RpcPool) -> (Vec<LstToken>, HashMap<String, f64>) {
+    let mut tokens = Vec::new();
+    let mut rates = HashMap::new();
+
+    for (symbol, rate_source) in KNOWN_LSTS {
+        match fetch_lst_rate(*rate_source, rpc).await {
+            Ok((mint, rate)) => {
+                info!("🌊 LST {} ({}) 匯率: 1 {} = {:.6} SOL", symbol, &mint[..8], symbol, rate);
+                tokens.push(LstToken {
+                    symbol: symbol.to_string(),
+                    mint,
+                    rate_source: *rate_source,
+                });
+                rates.insert(symbol.to_string(), rate);
+            }
+            Err(e) => {
+                warn!("⚠️ 無法取得 LST {} 的匯率，將不監控此代幣: {}", symbol, e);
+            }
+        }
+    }
+
+    (tokens, rates)
+}
+
+// 定期更新 LST 匯率
+async fn lst_rate_refresh_task(
+    wallets: SharedWallets,
+    tokens: SharedLstTokens,
+    rates: SharedLstRates,
+    events: WalletEvents,
+    rpc: SharedRpc,
+    refresh_secs: u64,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(refresh_secs.max(10)));
+    interval.tick().await; // 啟動時已取得初始匯率
+
+    loop {
+        interval.tick().await;
+        for token in tokens.iter() {
+            match fetch_lst_rate(token.rate_source, &rpc).await {
+                Ok((_, rate)) => {
+                    rates.lock().unwrap().insert(token.symbol.clone(), rate);
+                    let mut wallets_guard = wallets.write().await;
+                    for wallet in wallets_guard.values_mut() {
+                        wallet.update_lst_rate(&token.symbol, rate);
+                        if wallet.lst_holdings.contains_key(&token.symbol) {
+                            events.publish_update(wallet);
+                        }
+                    }
+                    debug!("🌊 LST {} 匯率已更新: {:.6} SOL", token.symbol, rate);
+                }
+                Err(e) => {
+                    warn!("⚠️ 更新 LST {} 匯率失敗: {}", token.symbol, e);
+                }
+            }
+        }
+    }
+}
+
+fn is_sol_domain(input: &str) -> bool {
+    input.len() > 4 && input.to_ascii_lowercase().ends_with(".sol")
+}
+
+// 名稱帳戶地址 = PDA([sha256(前綴 + 名稱), class (未使用，全 0), 上層名稱帳戶], 名稱服務程式)
+fn sns_name_account(name: &str, parent: &Pubkey) -> Pubkey {
+    let program_id = Pubkey::from_str(SNS_NAME_PROGRAM_ID).expect("valid name service program id");
+    let hashed_name = solana_program::hash::hashv(&[SNS_HASH_PREFIX.as_bytes(), name.as_bytes()]);
+    let class = Pubkey::default();
+    Pubkey::find_program_address(&[hashed_name.as_ref(), class.as_ref(), parent.as_ref()], &program_id).0
+}
+
+// 解析 .sol 網域 (含一層子網域，例如 sub.toly.sol) 的擁有者地址，網域未註冊時返回 None
+async fn resolve_sol_domain(domain: &str, rpc: &RpcPool) -> ClientResult<Option<String>> {
+    let tld = Pubkey::from_str(SNS_SOL_TLD).expect("valid .sol tld");
+    let labels: Vec<&str> = domain.trim_end_matches(".sol").split('.').collect();
+    let key = match labels.as_slice() {
+        [name] => sns_name_account(name, &tld),
+        // 子網域的名稱前綴為 \0
+        [sub, name] => sns_name_account(&format!("\0{}", sub), &sns_name_account(name, &tld)),
+        _ => return Ok(None),
+    };
+    let Some(owner) = fetch_account_owner_field(rpc, key, SNS_OWNER_OFFSET).await? else {
+        return Ok(None);
+    };
+
+    // 代幣化的網域由 tokenizer 程式的 central state 保管，實際擁有者為持有網域 NFT 的錢包
+    let tokenizer = Pubkey::from_str(SNS_TOKENIZER_PROGRAM_ID).expect("valid name tokenizer program id");
+    let (central_state, _) = Pubkey::find_program_address(&[tokenizer.as_ref()], &tokenizer);
+    if owner != central_state {
+        return Ok(Some(owner.to_string()));
+    }
+    let (mint, _) = Pubkey::find_program_address(&[SNS_TOKENIZER_MINT_PREFIX.as_bytes(), key.as_ref()], &tokenizer);
+    let holders = rpc.call(|client| async move { client.get_token_largest_accounts(&mint).await }).await?;
+    let Some(holder) = holders.iter().find(|holder| holder.amount.amount == "1").and_then(|holder| Pubkey::from_str(&holder.address).ok()) else {
+        return Ok(None);
+    };
+    Ok(fetch_account_owner_field(rpc, holder, TOKEN_OWNER_OFFSET).await?.map(|owner| owner.to_string()))
+}
+
+// 讀取帳戶 data 中 offset 起的 32 bytes 地址欄位，帳戶不存在時返回 None
+async fn fetch_account_owner_field(rpc: &RpcPool, key: Pubkey, offset: usize) -> ClientResult<Option<Pubkey>> {
+    let account = rpc
+        .call(|client| async move { client.get_account_with_commitment(&key, client.commitment()).await })
+        .await?
+        .value;
+    Ok(account.and_then(|account| account.data.get(offset..offset + 32).and_then(|owner| Pubkey::try_from(owner).ok())))
+}
+
+// 定期檢查 stale_wallet 規則：錢包超過 stale_secs 沒有任何更新 (長期不活動或訂閱已靜默失效) 時告警，
+// 同一錢包在恢復更新前只告警一次
+async fn stale_wallet_task(wallets: SharedWallets, notifier: Notifier) {
+    let mut interval = tokio::time::interval(Duration::from_secs(STALE_CHECK_INTERVAL_SECS));
+    // (規則名稱, 錢包地址) -> (告警時錢包的最後更新時間, 告警 id)
+    let mut fired: HashMap<(String, String), (DateTime<Utc>, Option<u64>)> = HashMap::new();
+    // 重啟前尚未解除的告警，第一次檢查時仍無活動者沿用原告警，已恢復者標記為解除
+    let mut restored = notifier.unresolved_alerts(AlertKind::StaleWallet);
+
+    loop {
+        interval.tick().await;
+        let rules: Vec<AlertRule> = notifier.rules().into_iter().filter(|rule| rule.kind == AlertKind::StaleWallet).collect();
+        let now = Utc::now();
+        let wallets_guard = wallets.read().await;
+        for alert in std::mem::take(&mut restored) {
+            let rule = rules.iter().find(|rule| rule.name == alert.rule);
+            match (rule, wallets_guard.get(&alert.address)) {
+                (Some(rule), Some(wallet)) if (now - wallet.last_update).num_seconds().max(0) as u64 >= rule.stale_secs => {
+                    fired.insert((alert.rule, alert.address), (wallet.last_update, Some(alert.id)));
+                }
+                _ => notifier.resolve_alert(&alert.rule, &alert.address, Some(alert.id)),
+            }
+        }
+        // 錢包恢復更新、被刪除或規則已移除時將告警標記為已解除並清除記錄，之後可再次告警
+        fired.retain(|(rule, address), (last_update, alert_id)| {
+            let active = rules.iter().any(|r| r.name == *rule) && wallets_guard.get(address).is_some_and(|w| w.last_update == *last_update);
+            if !active {
+                notifier.resolve_alert(rule, address, *alert_id);
+            }
+            active
+        });
+        for rule in &rules {
+            for wallet in wallets_guard.values().filter(|w| !w.archived && rule.applies_to(w)) {
+                let idle_secs = (now - wallet.last_update).num_seconds().max(0) as u64;
+                if idle_secs < rule.stale_secs || fired.contains_key(&(rule.name.clone(), wallet.address.clone())) {
+                    continue;
+                }
+                warn!("⏰ 告警 {}: 錢包 {} ({}) 已 {} 秒沒有任何更新", rule.name, wallet.name, &wallet.address[..8], idle_secs);
+                let alert_id = notifier.fire_alert(rule, BalanceChangeEvent::stale(wallet, rule, idle_secs), format!("{} 秒沒有任何更新", idle_secs));
+                fired.insert((rule.name.clone(), wallet.address.clone()), (wallet.last_update, alert_id));
+            }
+        }
+    }
+}
+
+// 定期更新驗證者狀態：vote account 餘額與 identity 餘額可支付投票手續費的 epoch 數，
+// 低於 validator_runway 規則的 min_epochs 時告警，餘額補足後自動解除
+async fn validator_task(wallets: SharedWallets, rpc: SharedRpc, notifier: Notifier, statuses: SharedValidators) {
+    let mut interval = tokio::time::interval(Duration::from_secs(VALIDATOR_CHECK_INTERVAL_SECS));
+    // (規則名稱, identity 地址) -> 告警 id；重啟前尚未解除的告警在第一次檢查時依目前狀態保留或解除
+    let mut fired: HashMap<(String, String), Option<u64>> = notifier
+        .unresolved_alerts(AlertKind::ValidatorRunway)
+        .into_iter()
+        .map(|alert| ((alert.rule, alert.address), Some(alert.id)))
+        .collect();
+
+    loop {
+        interval.tick().await;
+        let validators: Vec<(String, f64, Option<String>)> = wallets
+            .read()
+            .await
+            .values()
+            .filter(|w| !w.archived && w.account_type == AccountType::Validator)
+            .map(|w| (w.address.clone(), w.sol_balance, w.vote_account.clone()))
+            .collect();
+        // 沒有驗證者時不查詢 RPC
+        let checked = if validators.is_empty() {
+            HashMap::new()
+        } else {
+            let info = match rpc.call(|client| async move { client.get_epoch_info().await }).await {
+                Ok(info) => info,
+                Err(e) => {
+                    warn!("⚠️ 查詢 epoch 資訊失敗: {}", e);
+                    continue;
+                }
+            };
+            let vote_cost_per_epoch = info.slots_in_epoch as f64 * LAMPORTS_PER_SIGNATURE as f64 / 1_000_000_000.0;
+            let mut checked = HashMap::new();
+            for (identity, identity_balance, vote_account) in validators {
+                let vote_balance = match &vote_account {
+                    Some(vote_account) => match query_sol_balance(&rpc, vote_account).await {
+                        Ok(balance) => Some(balance),
+                        Err(e) => {
+                            warn!("⚠️ 查詢 vote account {} 的餘額失敗: {}", vote_account, e);
+                            None
+                        }
+                    },
+                    None => None,
+                };
+                let status = ValidatorStatus {
+                    identity: identity.clone(),
+                    identity_balance,
+                    vote_account,
+                    vote_balance,
+                    epoch: info.epoch,
+                    slots_in_epoch: info.slots_in_epoch,
+                    vote_cost_per_epoch,
+                    runway_epochs: identity_balance / vote_cost_per_epoch,
+                    checked_at: Utc::now(),
+                };
+                checked.insert(identity, status);
+            }
+            checked
+        };
+
+        let rules: Vec<AlertRule> = notifier.rules().into_iter().filter(|rule| rule.kind == AlertKind::ValidatorRunway).collect();
+        let wallets_guard = wallets.read().await;
+        // identity 餘額回到門檻以上、驗證者被移除或規則已移除時將告警標記為已解除
+        fired.retain(|(rule, identity), alert_id| {
+            let low = rules.iter().find(|r| r.name == *rule).zip(checked.get(identity)).is_some_and(|(r, status)| status.runway_epochs < r.min_epochs);
+            if !low {
+                notifier.resolve_alert(rule, identity, *alert_id);
+            }
+            low
+        });
+        for rule in &rules {
+            for status in checked.values() {
+                let Some(wallet) = wallets_guard.get(&status.identity).filter(|w| rule.applies_to(w)) else {
+                    continue;
+                };
+                let key = (rule.name.clone(), status.identity.clone());
+                if status.runway_epochs >= rule.min_epochs || fired.contains_key(&key) {
+                    continue;
+                }
+                let detail = format!("identity 餘額 {:.4} SOL 只夠支付 {:.1} 個 epoch 的投票手續費", status.identity_balance, status.runway_epochs);
+                warn!("🪫 告警 {}: 驗證者 {} ({}) {}", rule.name, wallet.name, &wallet.address[..8], detail);
+                let alert_id = notifier.fire_alert(rule, BalanceChangeEvent::validator_runway(wallet, rule, status.runway_epochs), detail);
+                fired.insert(key, alert_id);
+            }
+        }
+        drop(wallets_guard);
+        *statuses.write().await = checked;
+    }
+}
+
+async fn query_sol_balance(rpc: &RpcPool, address: &str) -> Result<f64, Box<dyn std::error::Error + Send + Sync>> {
+    let pubkey = Pubkey::from_str(address)?;
+    let lamports = rpc.call(|client| async move { client.get_balance(&pubkey).await }).await?;
+    Ok(lamports as f64 / 1_000_000_000.0)
+}
+
+// 定期取得 SOL/USD 價格並保存，作為 PnL 等 USD 換算的依據
+async fn price_feed_task(database: SharedDatabase, price: SharedPrice, config: PriceConfig) {
+    let client = reqwest::Client::builder().timeout(Duration::from_secs(10)).build().unwrap_or_default();
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_secs.max(10)));
+    let mut last_prune: Option<std::time::Instant> = None;
+
+    loop {
+        interval.tick().await;
+        if config.retention_days > 0 && !last_prune.is_some_and(|at| at.elapsed() < Duration::from_millis(HOUR_MS)) {
+            last_prune = Some(std::time::Instant::now());
+            let before = Utc::now() - chrono::Duration::days(config.retention_days.min(36500) as i64);
+            let db = database.clone();
+            match tokio::task::spawn_blocking(move || db.prune_prices(before).map_err(|e| e.to_string())).await {
+                Ok(Ok(0)) => {}
+                Ok(Ok(removed)) => info!("🧹 已清理 {} 筆過舊的 SOL 價格", removed),
+                Ok(Err(e)) => warn!("⚠️ 清理 SOL 價格失敗: {}", e),
+                Err(e) => warn!("⚠️ 清理 SOL 價格任務異常: {}", e),
+            }
+        }
+        let usd = match fetch_sol_price(&client, &config).await {
+            Ok(usd) => usd,
+            Err(e) => {
+                warn!("⚠️ 取得 SOL 價格失敗: {}", e);
+                continue;
+            }
+        };
+        let point = PricePoint { timestamp: Utc::now(), usd };
+        *price.write().unwrap_or_else(|e| e.into_inner()) = Some(point);
+        let db = database.clone();
+        match tokio::task::spawn_blocking(move || db.append_price(&point).map_err(|e| e.to_string())).await {
+            Ok(Ok(())) => debug!("💲 SOL 價格: {:.4} USD", usd),
+            Ok(Err(e)) => warn!("⚠️ 保存 SOL 價格失敗: {}", e),
+            Err(e) => warn!("⚠️ 保存 SOL 價格任務異常: {}", e),
+        }
+    }
+}
+
+async fn fetch_sol_price(client: &reqwest::Client, config: &PriceConfig) -> Result<f64, Box<dyn std::error::Error + Send + Sync>> {
+    let body: serde_json::Value = client.get(&config.url).send().await?.error_for_status()?.json().await?;
+    let value = body.pointer(&config.pointer).ok_or_else(|| format!("回應中找不到 {}", config.pointer))?;
+    // 部分來源以字串表示價格
+    let usd = value.as_f64().or_else(|| value.as_str().and_then(|s| s.parse().ok())).ok_or("價格不是數字")?;
+    if !usd.is_finite() || usd <= 0.0 {
+        return Err(format!("價格不正確: {}", usd).into());
+    }
+    Ok(usd)
+}
+
+// 定期取得 USD 對其他法幣的匯率並保存
+async fn fx_rates_task(database: SharedDatabase, config: FxConfig) {
+    let client = reqwest::Client::builder().timeout(Duration::from_secs(10)).build().unwrap_or_default();
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_secs.max(60)));
+
+    loop {
+        interval.tick().await;
+        let rates = match fetch_fx_rates(&client, &config).await {
+            Ok(rates) => rates,
+            Err(e) => {
+                warn!("⚠️ 取得匯率失敗: {}", e);
+                continue;
+            }
+        };
+        let db = database.clone();
+        let count = rates.len();
+        match tokio::task::spawn_blocking(move || db.append_fx_rates(Utc::now(), &rates).map_err(|e| e.to_string())).await {
+            Ok(Ok(())) => debug!("💱 已更新 {} 種匯率", count),
+            Ok(Err(e)) => warn!("⚠️ 保存匯率失敗: {}", e),
+            Err(e) => warn!("⚠️ 保存匯率任務異常: {}", e),
+        }
+    }
+}
+
+// 返回 (大寫幣別, 1 USD 可兌換的單位)，回應中缺少的幣別略過
+async fn fetch_fx_rates(client: &reqwest::Client, config: &FxConfig) -> Result<Vec<(String, f64)>, Box<dyn std::error::Error + Send + Sync>> {
+    let body: serde_json::Value = client.get(&config.url).send().await?.error_for_status()?.json().await?;
+    let rates = body.pointer(&config.pointer).ok_or_else(|| format!("回應中找不到 {}", config.pointer))?;
+    let mut result = Vec::new();
+    for currency in &config.currencies {
+        let currency = currency.to_uppercase();
+        let rate = rates.get(&currency).and_then(|value| value.as_f64().or_else(|| value.as_str().and_then(|s| s.parse().ok())));
+        match rate {
+            Some(rate) if rate.is_finite() && rate > 0.0 => result.push((currency, rate)),
+            _ => warn!("⚠️ 匯率回應中沒有 {} 的有效匯率", currency),
+        }
+    }
+    if result.is_empty() {
+        return Err("沒有可用的匯率".into());
+    }
+    Ok(result)
+}
+
+// 追蹤 epoch 邊界，並在每個 epoch 開始後查詢有 stake account 的錢包在上一個 epoch 的質押獎勵
+async fn epoch_rewards_task(wallets: SharedWallets, database: SharedDatabase, rpc: SharedRpc) {
+    let mut interval = tokio::time::interval(Duration::from_secs(EPOCH_CHECK_INTERVAL_SECS));
+    let mut current: Option<EpochBoundary> = None;
+    let mut schedule: Option<EpochSchedule> = None; // 補查較早的 epoch 時才需要，取得一次後沿用
+    let mut processed = std::collections::HashSet::new(); // 已查到上一個 epoch 獎勵的錢包
+
+    loop {
+        interval.tick().await;
+        let info = match rpc.call(|client| async move { client.get_epoch_info().await }).await {
+            Ok(info) => info,
+            Err(e) => {
+                warn!("⚠️ 查詢 epoch 資訊失敗: {}", e);
+                continue;
+            }
+        };
+        if current.is_none_or(|boundary| boundary.epoch != info.epoch) {
+            match epoch_boundary(&database, &rpc, info.epoch, info.absolute_slot - info.slot_index, info.absolute_slot).await {
+                Ok(boundary) => {
+                    info!("🕰️ 目前為 epoch {} (開始於 slot {}，{})", boundary.epoch, boundary.start_slot, boundary.started_at);
+                    current = Some(boundary);
+                    processed.clear();
+                }
+                Err(e) => {
+                    warn!("⚠️ 記錄 epoch {} 失敗: {}", info.epoch, e);
+                    continue;
+                }
+            }
+        }
+        let Some(boundary) = current.filter(|boundary| boundary.epoch > 0) else {
+            continue;
+        };
+
+        // 新加入、上次查詢失敗或獎勵尚未發放的錢包在下次檢查時補上
+        let pending: Vec<(String, Vec<String>)> = wallets
+            .read()
+            .await
+            .values()
+            .filter(|w| !w.archived && !w.stake_accounts.is_empty() && !processed.contains(&w.address))
+            .map(|w| (w.address.clone(), w.stake_accounts.keys().cloned().collect()))
+            .collect();
+        for (address, stake_accounts) in pending {
+            match sync_stake_rewards(&database, &rpc, &mut schedule, &info, &boundary, &address, &stake_accounts).await {
+                Ok(true) => {
+                    processed.insert(address);
+                }
+                Ok(false) => debug!("🥩 錢包 {} 在 epoch {} 的質押獎勵尚未可查詢，稍後重試", &address[..8], boundary.epoch - 1),
+                Err(e) => warn!("⚠️ 查詢錢包 {} 的質押獎勵失敗: {}", &address[..8], e),
+            }
+        }
+    }
+}
+
+// 從錢包最後一筆已記錄獎勵的下一個 epoch 補查到上一個 epoch (最多 MAX_REWARD_BACKFILL_EPOCHS 個)，
+// 從未記錄過的錢包只查上一個 epoch。較早的 epoch 查不到獎勵表示沒有獎勵；上一個 epoch 查不到時
+// 獎勵可能仍在發放，返回 false 讓下次檢查重試，直到查到獎勵或 epoch 再次切換
+async fn sync_stake_rewards(
+    database: &SharedDatabase,
+    rpc: &RpcPool,
+    schedule: &mut Option<EpochSchedule>,
+    info: &EpochInfo,
+    current: &EpochBoundary,
+    address: &str,
+    stake_accounts: &[String],
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    let last_epoch = current.epoch - 1;
+    let db = database.clone();
+    let db_address = address.to_string();
+    let recorded: BTreeSet<u64> = tokio::task::spawn_blocking(move || {
+        db.load_stake_rewards(&db_address, None, None).map(|rewards| rewards.iter().map(|r| r.epoch).collect()).map_err(|e| e.to_string())
+    })
+    .await??;
+    if recorded.contains(&last_epoch) {
+        return Ok(true);
+    }
+
+    let first = recorded
+        .last()
+        .map_or(last_epoch, |epoch| epoch + 1)
+        .max(last_epoch.saturating_sub(MAX_REWARD_BACKFILL_EPOCHS - 1));
+    for epoch in (first..=last_epoch).filter(|epoch| !recorded.contains(epoch)) {
+        // 獎勵在下一個 epoch 開始時入帳
+        let credited_at = if epoch == last_epoch {
+            current.started_at
+        } else {
+            let epoch_schedule = match schedule.clone() {
+                Some(epoch_schedule) => epoch_schedule,
+                None => {
+                    let epoch_schedule = rpc.call(|client| async move { client.get_epoch_schedule().await }).await?;
+                    *schedule = Some(epoch_schedule.clone());
+                    epoch_schedule
+                }
+            };
+            epoch_boundary(database, rpc, epoch + 1, epoch_schedule.get_first_slot_in_epoch(epoch + 1), info.absolute_slot).await?.started_at
+        };
+        match record_stake_reward(database, rpc, address, stake_accounts, epoch, credited_at).await? {
+            Some(reward) => {
+                info!("🥩 錢包 {} 在 epoch {} 獲得 {:.9} SOL 質押獎勵 ({} 個 stake account)", &address[..8], reward.epoch, reward.amount, reward.accounts.len())
+            }
+            None if epoch == last_epoch => return Ok(false),
+            None => debug!("🥩 錢包 {} 在 epoch {} 沒有質押獎勵", &address[..8], epoch),
+        }
+    }
+    Ok(true)
+}
+
+// 讀取已保存的 epoch 邊界，沒有時以 epoch 第一個 slot 的區塊時間建立；該 slot 被跳過時依與目前 slot 的差距推算
+async fn epoch_boundary(
+    database: &SharedDatabase,
+    rpc: &RpcPool,
+    epoch: u64,
+    start_slot: u64,
+    current_slot: u64,
+) -> Result<EpochBoundary, Box<dyn std::error::Error + Send + Sync>> {
+    let db = database.clone();
+    if let Some(boundary) = tokio::task::spawn_blocking(move || db.load_epoch(epoch).map_err(|e| e.to_string())).await?? {
+        return Ok(boundary);
+    }
+
+    let started_at = match rpc.call(|client| async move { client.get_block_time(start_slot).await }).await {
+        Ok(timestamp) => DateTime::from_timestamp(timestamp, 0),
+        Err(_) => None,
+    }
+    .unwrap_or_else(|| Utc::now() - chrono::Duration::milliseconds(current_slot.saturating_sub(start_slot) as i64 * SLOT_DURATION_MS));
+    let boundary = EpochBoundary { epoch, start_slot, started_at };
+    let db = database.clone();
+    tokio::task::spawn_blocking(move || db.save_epoch(&boundary).map_err(|e| e.to_string())).await??;
+    Ok(boundary)
+}
+
+// 查詢並保存錢包在指定 epoch 的獎勵；沒有任何獎勵時返回 None
+async fn record_stake_reward(
+    database: &SharedDatabase,
+    rpc: &RpcPool,
+    address: &str,
+    stake_accounts: &[String],
+    epoch: u64,
+    credited_at: DateTime<Utc>,
+) -> Result<Option<StakeReward>, Box<dyn std::error::Error + Send + Sync>> {
+    let pubkeys = stake_accounts.iter().map(|account| Pubkey::from_str(account)).collect::<Result<Vec<_>, _>>()?;
+    let rewards = rpc
+        .call(|client| {
+            let pubkeys = pubkeys.clone();
+            async move { client.get_inflation_reward(&pubkeys, Some(epoch)).await }
+        })
+        .await?;
+    let accounts: Vec<StakeAccountReward> = stake_accounts
+        .iter()
+        .zip(rewards)
+        .filter_map(|(stake_account, reward)| {
+            reward.map(|reward| StakeAccountReward {
+                stake_account: stake_account.clone(),
+                amount: reward.amount as f64 / 1_000_000_000.0,
+                post_balance: reward.post_balance as f64 / 1_000_000_000.0,
+                commission: reward.commission,
+            })
+        })
+        .collect();
+    if accounts.is_empty() {
+        return Ok(None);
+    }
+
+    let reward = StakeReward { epoch, credited_at, amount: accounts.iter().map(|a| a.amount).sum(), accounts };
+    let db = database.clone();
+    let db_address = address.to_string();
+    let saved = reward.clone();
+    tokio::task::spawn_blocking(move || db.save_stake_reward(&db_address, &saved).map_err(|e| e.to_string())).await??;
+    Ok(Some(reward))
+}
+
+// 報表涵蓋的錢包：(地址, 名稱, 群組)，不含已封存的錢包
+async fn report_members(wallets: &SharedWallets) -> Vec<(String, String, Option<String>)> {
+    wallets
+        .read()
+        .await
+        .values()
+        .filter(|w| !w.archived)
+        .map(|w| (w.address.clone(), w.name.clone(), w.group.clone()))
+        .collect()
+}
+
+fn generate_daily_report(
+    database: &dyn HistoryStore,
+    members: &[(String, String, Option<String>)],
+    date: chrono::NaiveDate,
+) -> Result<DailyReport, Box<dyn std::error::Error>> {
+    let (from, to) = report_day_range(date);
+
+    let mut wallets = Vec::new();
+    let mut group_series: BTreeMap<String, Vec<Vec<BalanceHistory>>> = BTreeMap::new();
+    for (address, name, group) in members {
+        // 序列以當天開始前的最後一筆作為開盤，當天才加入的錢包以第一筆記錄為開盤
+        let series = database.load_history_series(address, Some(from), Some(to))?;
+        let Some(balance) = DailyBalance::from_series(&series) else {
+            continue;
+        };
+        wallets.push(WalletDailySummary { address: address.clone(), name: name.clone(), group: group.clone(), balance, fiat: None });
+        if let Some(group) = group {
+            group_series.entry(group.clone()).or_default().push(series);
         }
     }
+    wallets.sort_by(|a, b| a.name.cmp(&b.name));
 
-    fn print_balance(&self, reason: &str) {
-        if !self.wsol_initialized {
-            info!(
-                "💰 {} | {} ({}) | SOL: {:.6} | WSOL: 未初始化 | 總計: {:.6}",
-                reason,
-                self.name,
-                &self.address[..8],
-                self.sol_balance,
-                self.total_balance()
-            );
+    let groups = group_series
+        .into_iter()
+        .filter_map(|(group, series)| {
+            let aggregated = aggregate_history(series.iter());
+            DailyBalance::from_series(&aggregated).map(|balance| GroupDailySummary { group, wallets: series.len(), balance, fiat: None })
+        })
+        .collect();
+
+    Ok(DailyReport { date, generated_at: Utc::now(), wallets, groups, currency: None, delivered_at: None })
+}
+
+fn format_daily_report_text(report: &DailyReport) -> String {
+    let open: f64 = report.wallets.iter().map(|w| w.balance.open).sum();
+    let close: f64 = report.wallets.iter().map(|w| w.balance.close).sum();
+    let line = |label: &str, b: &DailyBalance| {
+        format!("{}: {:.4} → {:.4} ({:+.4})，高 {:.4} / 低 {:.4}\n", label, b.open, b.close, b.net_change, b.high, b.low)
+    };
+
+    let mut text = format!("📊 每日報表 {} (UTC)\n合計: {:.4} → {:.4} ({:+.4}) SOL\n", report.date, open, close, close - open);
+    if let Some(currency) = &report.currency {
+        let fiat_open: f64 = report.wallets.iter().filter_map(|w| w.fiat).map(|f| f.open).sum();
+        let fiat_close: f64 = report.wallets.iter().filter_map(|w| w.fiat).map(|f| f.close).sum();
+        text.push_str(&format!("價值: {:.2} → {:.2} ({:+.2}) {}\n", fiat_open, fiat_close, fiat_close - fiat_open, currency));
+    }
+    if !report.groups.is_empty() {
+        text.push_str("\n群組\n");
+        for group in &report.groups {
+            text.push_str(&line(&group.group, &group.balance));
+        }
+    }
+    text.push_str("\n錢包\n");
+    for wallet in &report.wallets {
+        text.push_str(&line(&format!("{} ({})", wallet.name, &wallet.address[..8]), &wallet.balance));
+    }
+    text
+}
+
+// 透過已設定的通知目標寄送報表；Telegram 與 Discord 有訊息長度限制，過長時截斷
+async fn deliver_daily_report(config: &NotificationsConfig, channels: &[String], report: &DailyReport) {
+    let enabled = |channel: &str| channels.iter().any(|c| c.eq_ignore_ascii_case(channel));
+    let text = format_daily_report_text(report);
+    let title = format!("📊 每日報表 {}", report.date);
+    let client = reqwest::Client::builder().timeout(Duration::from_secs(NOTIFY_HTTP_TIMEOUT_SECS)).build().unwrap_or_default();
+
+    if let Some(telegram) = config.telegram.as_ref().filter(|_| enabled("telegram")) {
+        let url = format!("https://api.telegram.org/bot{}/sendMessage", telegram.bot_token);
+        let body = serde_json::json!({ "chat_id": telegram.chat_id, "text": text.chars().take(4000).collect::<String>() });
+        post_json_with_retry(&client, &url, &body, telegram.max_retries, "Telegram").await;
+    }
+    if let Some(discord) = config.discord.as_ref().filter(|_| enabled("discord")) {
+        let mut body = serde_json::json!({ "embeds": [{ "title": title, "description": text.chars().take(4000).collect::<String>() }] });
+        if let Some(username) = &discord.username {
+            body["username"] = serde_json::Value::String(username.clone());
+        }
+        post_json_with_retry(&client, &discord.webhook_url, &body, discord.max_retries, "Discord").await;
+    }
+    if let Some(webhook) = config.webhook.as_ref().filter(|_| enabled("webhook")) {
+        match serde_json::to_value(report) {
+            Ok(body) => {
+                for url in &webhook.urls {
+                    post_json_with_retry(&client, url, &body, webhook.max_retries, "Webhook").await;
+                }
+            }
+            Err(e) => error!("❌ 序列化每日報表失敗: {}", e),
+        }
+    }
+    if let Some(email) = config.email.as_ref().filter(|_| enabled("email")) {
+        match EmailMailer::new(email) {
+            Ok(mailer) => {
+                mailer.send(&format!("[SOL 監控] 每日報表 {}", report.date), text.clone(), email.max_retries).await;
+            }
+            Err(e) => error!("❌ Email 通知設定錯誤: {}", e),
+        }
+    }
+}
+
+// 每天 reports.hour (UTC) 之後產生前一天的報表；已保存的日期不重複產生，啟動時補產生前一天的報表
+async fn daily_report_task(wallets: SharedWallets, database: SharedDatabase, notifier: Notifier, config: ReportsConfig, price_config: Option<PriceConfig>) {
+    use chrono::Timelike;
+
+    for channel in config.channels.iter().filter(|c| !matches!(c.to_lowercase().as_str(), "telegram" | "discord" | "webhook" | "email")) {
+        warn!("⚠️ 每日報表不支援通知目標 {}", channel);
+    }
+    let currency = match fiat_currency(price_config.as_ref(), config.currency.as_deref()) {
+        Ok(currency) => currency,
+        Err(e) => {
+            warn!("⚠️ 每日報表{}", e.error);
+            None
+        }
+    };
+    let mut interval = tokio::time::interval(Duration::from_secs(REPORT_CHECK_INTERVAL_SECS));
+
+    loop {
+        interval.tick().await;
+        let now = Utc::now();
+        if now.hour() < config.hour.min(23) {
+            continue;
+        }
+        let date = now.date_naive() - chrono::Duration::days(1);
+        // 已保存但尚未寄送的報表 (例如先透過 API 查詢而產生) 仍需寄送
+        let existing = match database.load_daily_report(date) {
+            Ok(Some(report)) if report.delivered_at.is_some() || config.channels.is_empty() => continue,
+            Ok(report) => report,
+            Err(e) => {
+                warn!("⚠️ 讀取每日報表失敗: {}", e);
+                continue;
+            }
+        };
+
+        let members = report_members(&wallets).await;
+        let db = database.clone();
+        let report_currency = currency.clone();
+        let generated = existing.is_none();
+        let result = tokio::task::spawn_blocking(move || -> Result<(DailyReport, DailyReport), String> {
+            let saved = match existing {
+                Some(report) => report,
+                None => {
+                    let report = generate_daily_report(db.as_ref(), &members, date).map_err(|e| e.to_string())?;
+                    db.save_daily_report(&report).map_err(|e| e.to_string())?;
+                    report
+                }
+            };
+            // 寄送的報表附加法幣價值，缺少價格時仍以 SOL 寄送；保存的報表維持以 SOL 計算
+            let mut report = saved.clone();
+            if let Some(currency) = report_currency {
+                let (from, to) = report_day_range(date);
+                let converter = FiatConverter::load(db.as_ref(), &currency, Some(from), Some(to)).map_err(|e| e.to_string())?;
+                if let Err(code) = report.apply_fiat(&converter) {
+                    warn!("⚠️ 每日報表 {} 無法換算為 {}: {}", date, currency, code.message(Language::Zh));
+                }
+            }
+            Ok((saved, report))
+        })
+        .await;
+        match result {
+            Ok(Ok((mut saved, report))) => {
+                if generated {
+                    info!("📊 已產生 {} 的每日報表 ({} 個錢包，{} 個群組)", date, report.wallets.len(), report.groups.len());
+                }
+                if !config.channels.is_empty() {
+                    deliver_daily_report(&notifier.config(), &config.channels, &report).await;
+                    saved.delivered_at = Some(Utc::now());
+                    let db = database.clone();
+                    match tokio::task::spawn_blocking(move || db.save_daily_report(&saved).map_err(|e| e.to_string())).await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => error!("❌ 保存每日報表寄送狀態失敗: {}", e),
+                        Err(e) => error!("❌ 每日報表任務異常: {}", e),
+                    }
+                }
+            }
+            Ok(Err(e)) => error!("❌ 產生每日報表失敗: {}", e),
+            Err(e) => error!("❌ 每日報表任務異常: {}", e),
+        }
+    }
+}
+
+// 定期重新解析錢包的 .sol 網域；網域已轉移或過期時移除顯示並從配置文件刪除，監控的地址不變
+async fn domain_refresh_task(wallets: SharedWallets, events: WalletEvents, rpc: SharedRpc, refresh_secs: u64) {
+    let mut interval = tokio::time::interval(Duration::from_secs(refresh_secs.max(60)));
+
+    loop {
+        interval.tick().await;
+        let domains: Vec<(String, String)> = wallets
+            .read()
+            .await
+            .values()
+            .filter_map(|w| w.domain.clone().map(|domain| (w.address.clone(), domain)))
+            .collect();
+        for (address, domain) in domains {
+            let owner = match resolve_sol_domain(&domain, &rpc).await {
+                Ok(owner) => owner,
+                Err(e) => {
+                    warn!("⚠️ 重新解析網域 {} 失敗: {}", domain, e);
+                    continue;
+                }
+            };
+            if owner.as_deref() == Some(address.as_str()) {
+                debug!("🔗 網域 {} 仍指向 {}", domain, &address[..8]);
+                continue;
+            }
+            warn!("⚠️ 網域 {} 已不再指向錢包 {} (目前: {})，移除網域顯示", domain, address, owner.as_deref().unwrap_or("未註冊"));
+            if let Some(wallet) = wallets.write().await.get_mut(&address) {
+                wallet.domain = None;
+                events.publish_update(wallet);
+            }
+            if let Err(e) = set_wallet_config_field(&address, "domain", None).await {
+                warn!("⚠️ 更新配置文件失敗: {}", e);
+            }
+        }
+    }
+}
+
+// 計算錢包所有 LST 的 ATA 地址，返回 ATA -> (錢包地址, LST 索引)
+fn calculate_lst_atas(wallet_addresses: &[String], tokens: &[LstToken]) -> HashMap<String, (String, usize)> {
+    let mut ata_map = HashMap::new();
+    for wallet_address in wallet_addresses {
+        let Ok(owner) = Pubkey::from_str(wallet_address) else {
+            continue;
+        };
+        for (index, token) in tokens.iter().enumerate() {
+            if let Ok(mint) = Pubkey::from_str(&token.mint) {
+                let ata = get_associated_token_address(&owner, &mint);
+                ata_map.insert(ata.to_string(), (wallet_address.clone(), index));
+            }
+        }
+    }
+    ata_map
+}
+
+// 從RPC初始化所有錢包餘額：錢包、WSOL 與 LST ATA 以 getMultipleAccounts 批次查詢，
+// stake accounts 逐錢包查詢，所有請求以 max_concurrent_requests 限制並行數
+// 依配置建立錢包，只載入最新快照，完整歷史在查詢錢包詳情時才讀取（不使用WSOL餘額，因為可能過時）
+fn wallet_from_config(wallet_config: &WalletConfig, defaults: HistoryConfig, database: &SharedDatabase) -> WalletBalance {
+    let mut wallet = WalletBalance::new(wallet_config.address.clone(), wallet_config.name.clone());
+    apply_wallet_config(&mut wallet, wallet_config, defaults);
+    match database.load_latest(&wallet_config.address) {
+        Ok(Some(record)) => {
+            wallet.load_history_from_db(vec![record]);
+            wallet.history_loaded = false;
+        }
+        Ok(None) => {}
+        Err(e) => warn!("⚠️ 載入錢包 {} 最新記錄失敗: {}，將從空白開始", wallet.name, e),
+    }
+    // 重啟後仍記得曾持有過的代幣，避免再次收到時被誤判為新代幣
+    match database.load_seen_mints(&wallet_config.address) {
+        Ok(mints) => wallet.seen_mints = mints,
+        Err(e) => warn!("⚠️ 載入錢包 {} 已見過的 mint 失敗: {}", wallet.name, e),
+    }
+    wallet
+}
+
+fn apply_wallet_config(wallet: &mut WalletBalance, wallet_config: &WalletConfig, defaults: HistoryConfig) {
+    wallet.name = wallet_config.name.clone();
+    wallet.group = wallet_config.group.clone();
+    wallet.tags = wallet_config.tags.clone();
+    wallet.note = wallet_config.note.clone();
+    wallet.color = wallet_config.color.clone().filter(|c| is_valid_color(c));
+    wallet.domain = wallet_config.domain.clone();
+    wallet.account_type = wallet_config.account_type;
+    wallet.vote_account = wallet_config.vote_account.clone().filter(|_| wallet_config.account_type == AccountType::Validator);
+    wallet.archived = wallet_config.archived;
+    wallet.history_config = wallet_config.history.unwrap_or(defaults);
+}
+
+// 背景從 RPC 取得新加入 (或取消封存) 錢包的餘額，完成後併入目前狀態
+fn spawn_wallet_initialization(state: AppState, new_wallet: WalletBalance) {
+    let address = new_wallet.address.clone();
+    let span = info_span!("initialize_wallet", wallet = %address);
+    tokio::spawn(async move {
+        let mut initialized = HashMap::from([(address.clone(), new_wallet)]);
+        let lst_rates = state.lst_rates.lock().unwrap().clone();
+        initialize_wallets_from_rpc(&mut initialized, &state.database_writer, &state.rpc, &state.lst_tokens, &lst_rates, state.config.owner_token_accounts()).await;
+        let Some(wallet) = initialized.remove(&address) else {
+            return;
+        };
+        let mut wallets_guard = state.wallets.write().await;
+        // 初始化期間錢包可能已被刪除
+        if let Some(current) = wallets_guard.get_mut(&address) {
+            // 串流已送達更新時保留較新的 SOL 餘額
+            if current.last_slot == 0 {
+                current.sol_balance = wallet.sol_balance;
+            }
+            current.token = wallet.token;
+            current.wsol_account_open = wallet.wsol_account_open;
+            current.token_accounts = wallet.token_accounts;
+            current.seen_mints.extend(wallet.seen_mints);
+            current.initialize_wsol(wallet.wsol_balance);
+            current.initialize_stake_accounts(wallet.stake_accounts);
+            current.initialize_lst_holdings(wallet.lst_holdings);
+            state.events.publish_update(current);
+        }
+    }.instrument(span));
+}
+
+async fn initialize_wallets_from_rpc(
+    wallets: &mut HashMap<String, WalletBalance>,
+    writer: &DatabaseWriter,
+    rpc: &SharedRpc,
+    lst_tokens: &[LstToken],
+    lst_rates: &HashMap<String, f64>,
+    owner_token_accounts: bool,
+) {
+    let started_at = std::time::Instant::now();
+    let active: Vec<String> = wallets.values().filter(|w| !w.archived).map(|w| w.address.clone()).collect();
+    info!("🔄 開始從RPC獲取 {} 個錢包的最新餘額 (略過 {} 個已封存)", active.len(), wallets.len() - active.len());
+    // WSOL ATA、LST ATA 與 stake account 只對 wallet 類型查詢
+    let owners: Vec<String> = active.iter().filter(|address| wallets[*address].account_type == AccountType::Wallet).cloned().collect();
+    // owner_token_accounts 模式改為查詢錢包擁有的所有 token account，不另外計算 ATA
+    let ata_owners: &[String] = if owner_token_accounts { &[] } else { &owners };
+
+    let wsol_atas: HashMap<String, String> = ata_owners
+        .iter()
+        .filter_map(|address| match calculate_wsol_ata(address) {
+            Ok(ata) => Some((address.clone(), ata)),
+            Err(e) => {
+                error!("❌ 計算錢包 {} 的 WSOL ATA 失敗: {}", address, e);
+                None
+            }
+        })
+        .collect();
+    let lst_atas = calculate_lst_atas(ata_owners, lst_tokens);
+    let addresses: Vec<String> = active
+        .iter()
+        .chain(wsol_atas.values())
+        .chain(lst_atas.keys())
+        .cloned()
+        .collect();
+
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(rpc.max_concurrent_requests));
+    let account_batches = futures::future::join_all(addresses.chunks(RPC_MULTIPLE_ACCOUNTS_LIMIT).map(|chunk| {
+        let semaphore = semaphore.clone();
+        async move {
+            let _permit = semaphore.acquire_owned().await;
+            fetch_accounts(rpc, chunk.to_vec()).await
+        }
+    }));
+    let stake_queries = futures::future::join_all(owners.iter().map(|address| {
+        let semaphore = semaphore.clone();
+        let address = address.clone();
+        async move {
+            let _permit = semaphore.acquire_owned().await;
+            let result = query_stake_accounts(&address, rpc).await.map_err(|e| e.to_string());
+            (address, result)
+        }
+    }));
+    let token_queries = futures::future::join_all(owners.iter().filter(|_| owner_token_accounts).map(|address| {
+        let semaphore = semaphore.clone();
+        let address = address.clone();
+        async move {
+            let _permit = semaphore.acquire_owned().await;
+            let result = query_token_accounts(&address, rpc).await.map_err(|e| e.to_string());
+            (address, result)
+        }
+    }));
+    let (account_batches, stake_results, token_results) = tokio::join!(account_batches, stake_queries, token_queries);
+
+    // 查詢失敗的帳戶不會出現在 accounts 中；查詢成功但帳戶不存在時為 None
+    let mut accounts: HashMap<String, Option<solana_sdk::account::Account>> = HashMap::new();
+    for batch in account_batches {
+        match batch {
+            Ok(batch) => accounts.extend(batch.into_iter().map(|(address, _, account)| (address, account))),
+            Err(e) => error!("❌ 批次查詢帳戶失敗: {}", e),
+        }
+    }
+    let mut stake_results: HashMap<String, Result<HashMap<String, u64>, String>> = stake_results.into_iter().collect();
+    let mut token_results: HashMap<String, Result<HashMap<String, OwnedTokenAccount>, String>> = token_results.into_iter().collect();
+
+    // token_account 類型從帳戶資料取得 mint，再查詢 mint 的 decimals 換算數量
+    let token_mints: HashMap<String, String> = active
+        .iter()
+        .filter(|address| wallets[*address].account_type == AccountType::TokenAccount)
+        .filter_map(|address| {
+            let data = &accounts.get(address)?.as_ref()?.data;
+            (data.len() >= TOKEN_AMOUNT_OFFSET + TOKEN_AMOUNT_LEN).then(|| (address.clone(), bs58::encode(&data[..32]).into_string()))
+        })
+        .collect();
+    let mut mint_decimals: HashMap<String, u8> = HashMap::new();
+    if !token_mints.is_empty() {
+        let mints: Vec<String> = token_mints.values().cloned().collect::<std::collections::HashSet<_>>().into_iter().collect();
+        match fetch_accounts(rpc, mints).await {
+            Ok(mints) => mint_decimals.extend(
+                mints.into_iter().filter_map(|(mint, _, account)| Some((mint, *account?.data.get(MINT_DECIMALS_OFFSET)?))),
+            ),
+            Err(e) => error!("❌ 查詢代幣 mint 失敗: {}", e),
+        }
+    }
+    let token_amount = |address: Option<&String>| -> Option<f64> {
+        match accounts.get(address?)? {
+            Some(account) => token_account_amount(&account.data).map(|amount| amount as f64 / 1_000_000_000.0),
+            None => Some(0.0), // ATA 不存在，餘額為 0
+        }
+    };
+
+    for address in &active {
+        let Some(wallet) = wallets.get_mut(address) else {
+            continue;
+        };
+
+        match accounts.get(address) {
+            Some(account) => {
+                wallet.update_sol(account.as_ref().map(|account| account.lamports).unwrap_or(0));
+            }
+            None => error!("❌ 獲取錢包 {} 的SOL餘額失敗", wallet.name),
+        }
+        if wallet.account_type == AccountType::TokenAccount {
+            wallet.token = token_mints.get(address).and_then(|mint| {
+                let decimals = *mint_decimals.get(mint)?;
+                let amount = token_account_amount(&accounts.get(address)?.as_ref()?.data)?;
+                Some(TokenAccountBalance {
+                    mint: mint.clone(),
+                    amount: amount as f64 / 10f64.powi(decimals as i32),
+                    decimals,
+                })
+            });
+            match &wallet.token {
+                Some(token) => info!("   🪙 代幣 {}: {:.6}", &token.mint[..8], token.amount),
+                None => warn!("⚠️ 帳戶 {} 不是有效的 token account 或無法取得 mint，只追蹤 lamports", wallet.name),
+            }
+        }
+
+        match token_results.remove(address) {
+            Some(Ok(token_accounts)) => {
+                info!("   🪙 錢包 {} 擁有 {} 個 token account", &address[..8], token_accounts.len());
+                // 初始化時已持有的代幣不視為新代幣
+                let unseen: Vec<String> = token_accounts
+                    .values()
+                    .map(|account| account.mint.clone())
+                    .filter(|mint| !wallet.seen_mints.contains(mint))
+                    .collect::<std::collections::HashSet<_>>()
+                    .into_iter()
+                    .collect();
+                if !unseen.is_empty() {
+                    wallet.seen_mints.extend(unseen.iter().cloned());
+                    writer.save_seen_mints(address, unseen);
+                }
+                wallet.token_accounts = token_accounts;
+            }
+            Some(Err(e)) => warn!("⚠️ 查詢錢包 {} 的 token accounts 失敗: {}", wallet.name, e),
+            None => {}
+        }
+        let owned_totals = (owner_token_accounts && wallet.account_type == AccountType::Wallet).then(|| wallet.owned_token_totals(lst_tokens));
+
+        // 查詢失敗時設置為0以避免未初始化狀態；WSOL token account 本身的數量即為 WSOL 餘額
+        let wsol_balance = match (&wallet.token, &owned_totals) {
+            (Some(token), _) if token.mint == WSOL_MINT => Some(token.amount),
+            (_, Some((wsol, _))) => Some(*wsol),
+            _ => token_amount(wsol_atas.get(address)),
+        };
+        wallet.initialize_wsol(wsol_balance.unwrap_or(0.0));
+        wallet.wsol_account_open = if owned_totals.is_some() {
+            wallet.token_accounts.values().any(|account| account.mint == WSOL_MINT)
         } else {
-            info!(
-                "💰 {} | {} ({}) | SOL: {:.6} | WSOL: {:.6} | 總計: {:.6}",
-                reason,
-                self.name,
-                &self.address[..8],
-                self.sol_balance,
-                self.wsol_balance,
-                self.total_balance()
+            wsol_atas.get(address).and_then(|ata| accounts.get(ata)).is_some_and(Option::is_some)
+        };
+
+        // 非 wallet 類型沒有 stake account (類型變更時清除舊的)
+        if wallet.account_type != AccountType::Wallet {
+            wallet.initialize_stake_accounts(HashMap::new());
+        }
+        match stake_results.remove(address) {
+            Some(Ok(stake_accounts)) => {
+                if !stake_accounts.is_empty() {
+                    info!("   🥩 錢包 {} 找到 {} 個 stake account", &address[..8], stake_accounts.len());
+                }
+                wallet.initialize_stake_accounts(stake_accounts);
+            }
+            Some(Err(e)) => warn!("⚠️ 查詢錢包 {} 的 stake accounts 失敗: {}", wallet.name, e),
+            None => {}
+        }
+
+        if !lst_tokens.is_empty() {
+            let lst_amounts: Vec<(usize, f64)> = match &owned_totals {
+                Some((_, amounts)) => amounts.iter().copied().enumerate().collect(),
+                None => lst_atas
+                    .iter()
+                    .filter(|(_, (owner, _))| owner == address)
+                    .map(|(ata, (_, index))| (*index, token_amount(Some(ata)).unwrap_or(0.0)))
+                    .collect(),
+            };
+            let mut holdings = HashMap::new();
+            for (index, amount) in lst_amounts {
+                let token = &lst_tokens[index];
+                if amount > 0.0 {
+                    let rate = lst_rates.get(&token.symbol).copied().unwrap_or(1.0);
+                    info!("   🌊 {}: {:.6} (≈ {:.6} SOL)", token.symbol, amount, amount * rate);
+                    holdings.insert(token.symbol.clone(), LstHolding {
+                        symbol: token.symbol.clone(),
+                        mint: token.mint.clone(),
+                        amount,
+                        rate,
+                        sol_value: amount * rate,
+                    });
+                }
+            }
+            wallet.initialize_lst_holdings(holdings);
+        }
+
+        wallet.print_balance("RPC初始化");
+
+        // 保存最新餘額記錄到資料庫
+        if wallet.wsol_initialized {
+            let current_record = WalletHistoryRecord::new(
+                wallet.address.clone(),
+                wallet.sol_balance,
+                wallet.wsol_balance,
+                wallet.staked_balance(),
+                wallet.lst_balance(),
             );
+            writer.save_history(current_record);
         }
     }
+
+    info!("✅ 所有錢包的最新餘額獲取完成！耗時 {:.1} 秒", started_at.elapsed().as_secs_f64());
 }
 
-type SharedWallets = Arc<Mutex<HashMap<String, WalletBalance>>>;
-type SharedDatabase = Arc<Database>;
 
-// gRPC 流重啟信號
-type GrpcRestartSignal = Arc<Mutex<bool>>;
 
-// 應用狀態結構
-#[derive(Clone)]
-struct AppState {
-    wallets: SharedWallets,
-    database: SharedDatabase,
-    grpc_restart_signal: GrpcRestartSignal,
-    config: Config,
+// 處理 SOL Account 更新
+fn handle_sol_account_update(
+    account: &AccountUpdate,
+    wallets: &mut HashMap<String, WalletBalance>,
+    wallet_addresses: &[String],
+    writer: &DatabaseWriter,
+    notifier: &Notifier,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    // 檢查是否是我們監聽的錢包地址
+    if wallet_addresses.contains(&account.pubkey) {
+        if let Some(wallet) = wallets.get_mut(&account.pubkey) {
+            let old_balance = wallet.sol_balance;
+            let recorded = wallet.update_sol(account.lamports);
+            
+            if (wallet.sol_balance - old_balance).abs() > 0.000001 {
+                info!("💰 錢包 {} SOL 餘額變化: {:.6} SOL (從 {:.6} 到 {:.6})", 
+                      &account.pubkey[..8], 
+                      wallet.sol_balance - old_balance, 
+                      old_balance, 
+                      wallet.sol_balance);
+                
+                wallet.print_balance("SOL帳戶更新");
+                
+                notifier.notify(&BalanceChangeEvent::new(wallet, "sol", old_balance, wallet.sol_balance, account.slot));
+            }
+            // 保存到資料庫；合併期間內的變化由 history_flush_task 補記
+            if recorded {
+                writer.save_history(wallet.history_record());
+            }
+            return Ok(Some(wallet.address.clone()));
+        }
+    }
+    Ok(None)
 }
 
-// 資料庫操作函數
-fn initialize_database() -> Result<Database, Box<dyn std::error::Error>> {
-    let db = Database::create(DB_FILE)?;
-    info!("📊 資料庫已初始化: {}", DB_FILE);
-    Ok(db)
+// 處理 token_account 類型帳戶本身的代幣數量更新
+fn handle_token_account_update(
+    account: &AccountUpdate,
+    wallets: &mut HashMap<String, WalletBalance>,
+    writer: &DatabaseWriter,
+    notifier: &Notifier,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let Some(wallet) = wallets.get_mut(&account.pubkey) else {
+        return Ok(None);
+    };
+    // 非 token_account 類型或尚未從 RPC 取得 mint 時略過
+    let Some(token) = wallet.token.clone() else {
+        return Ok(None);
+    };
+    // 帳戶關閉時 data 為空，視為數量 0
+    let amount = match token_account_amount(&account.data) {
+        Some(amount) => amount as f64 / 10f64.powi(token.decimals as i32),
+        None if account.lamports == 0 => 0.0,
+        None => {
+            warn!("⚠️ 解析 token account 數據失敗: 資料長度 {}", account.data.len());
+            return Ok(None);
+        }
+    };
+    let recorded = wallet.update_token(amount);
+
+    if (amount - token.amount).abs() > 0.000001 {
+        info!("🪙 帳戶 {} 代幣 {} 數量變化: {:.6} (從 {:.6} 到 {:.6})",
+              &account.pubkey[..8],
+              &token.mint[..8],
+              amount - token.amount,
+              token.amount,
+              amount);
+
+        wallet.print_balance("Token帳戶更新");
+
+        let balance_type = if token.mint == WSOL_MINT { "wsol" } else { "token" };
+        notifier.notify(&BalanceChangeEvent::new(wallet, balance_type, token.amount, amount, account.slot));
+        // WSOL 已計入餘額歷史，其他代幣的數量另外保存
+        if token.mint != WSOL_MINT {
+            writer.save_token_amount(TokenAmountRecord { address: wallet.address.clone(), timestamp: wallet.last_update, amount });
+        }
+    }
+    // 保存到資料庫；合併期間內的變化由 history_flush_task 補記
+    if recorded {
+        writer.save_history(wallet.history_record());
+    }
+    Ok(Some(wallet.address.clone()))
 }
 
-fn save_wallet_history(db: &Database, record: &WalletHistoryRecord) -> Result<(), Box<dyn std::error::Error>> {
-    let write_txn = db.begin_write()?;
-    {
-        let mut table = write_txn.open_table(WALLET_HISTORY_TABLE)?;
-        let key = format!("{}_{}", record.address, record.timestamp.timestamp_millis());
-        let value = serde_json::to_string(record)?;
-        table.insert(key.as_str(), value.as_str())?;
+// 處理 Stake Account 更新
+fn handle_stake_account_update(
+    account: &AccountUpdate,
+    wallets: &mut HashMap<String, WalletBalance>,
+    stake_to_wallet_map: &HashMap<String, String>,
+    writer: &DatabaseWriter,
+    notifier: &Notifier,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    // 檢查是否是我們監聽的 stake account
+    if let Some(wallet_address) = stake_to_wallet_map.get(&account.pubkey) {
+        if let Some(wallet) = wallets.get_mut(wallet_address) {
+            let old_balance = wallet.staked_balance();
+            let recorded = wallet.update_stake(&account.pubkey, account.lamports);
+            let new_balance = wallet.staked_balance();
+
+            if (new_balance - old_balance).abs() > 0.000001 {
+                info!("🥩 錢包 {} 質押餘額變化: {:.9} SOL (從 {:.9} 到 {:.9})",
+                      &wallet_address[..8],
+                      new_balance - old_balance,
+                      old_balance,
+                      new_balance);
+
+                wallet.print_balance("Stake帳戶更新");
+
+                notifier.notify(&BalanceChangeEvent::new(wallet, "staked", old_balance, new_balance, account.slot));
+            }
+            // 保存到資料庫；合併期間內的變化由 history_flush_task 補記
+            if recorded {
+                writer.save_history(wallet.history_record());
+            }
+            return Ok(Some(wallet.address.clone()));
+        }
     }
-    write_txn.commit()?;
-    Ok(())
+    Ok(None)
+}
+
+// 處理 LST ATA 帳戶更新
+fn handle_lst_account_update(
+    account: &AccountUpdate,
+    wallets: &mut HashMap<String, WalletBalance>,
+    lst_ata_map: &HashMap<String, (String, usize)>,
+    lst_tokens: &[LstToken],
+    lst_rates: &SharedLstRates,
+    writer: &DatabaseWriter,
+    notifier: &Notifier,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    // 檢查是否是我們監聽的 LST ATA 地址
+    if let Some((wallet_address, token_index)) = lst_ata_map.get(&account.pubkey) {
+        let token = &lst_tokens[*token_index];
+        // 帳戶關閉時 data 為空，視為餘額 0
+        let amount = match token_account_amount(&account.data) {
+            Some(amount) => amount as f64 / 1_000_000_000.0, // LST decimals = 9
+            None if account.lamports == 0 => 0.0,
+            None => {
+                warn!("⚠️ 解析 {} token account 數據失敗: 資料長度 {}", token.symbol, account.data.len());
+                return Ok(None);
+            }
+        };
+        let rate = lst_rates.lock().unwrap().get(&token.symbol).copied().unwrap_or(1.0);
+
+        if let Some(wallet) = wallets.get_mut(wallet_address) {
+            let old_balance = wallet.lst_balance();
+            let recorded = wallet.update_lst(token, amount, rate);
+            let new_balance = wallet.lst_balance();
+
+            if (new_balance - old_balance).abs() > 0.000001 {
+                info!("🌊 錢包 {} {} 餘額變化: {:.6} (≈ {:.9} SOL)",
+                      &wallet_address[..8],
+                      token.symbol,
+                      amount,
+                      new_balance - old_balance);
+
+                wallet.print_balance("LST帳戶更新");
+
+                notifier.notify(&BalanceChangeEvent::new(wallet, "lst", old_balance, new_balance, account.slot));
+            }
+            // 保存到資料庫；合併期間內的變化由 history_flush_task 補記
+            if recorded {
+                writer.save_history(wallet.history_record());
+            }
+            return Ok(Some(wallet.address.clone()));
+        }
+    }
+    Ok(None)
+}
+
+// owner_token_accounts 模式：依 token account 的 owner 欄位歸屬錢包，重新彙總 WSOL 與 LST 餘額；
+// 帳戶轉移 owner 時同時更新原本與新的錢包
+fn handle_owned_token_account_update(
+    account: &AccountUpdate,
+    wallets: &mut HashMap<String, WalletBalance>,
+    subscription: &AccountSubscription,
+    lst_tokens: &[LstToken],
+    lst_rates: &SharedLstRates,
+    writer: &DatabaseWriter,
+    notifier: &Notifier,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    if subscription.token_owners.is_empty() {
+        return Ok(vec![]);
+    }
+    // 之前持有此帳戶的錢包；訂閱建立後才出現的帳戶只記錄在錢包狀態中
+    let previous = subscription.token_account_map.get(&account.pubkey).cloned().or_else(|| {
+        wallets.values().find(|w| w.token_accounts.contains_key(&account.pubkey)).map(|w| w.address.clone())
+    });
+    // 帳戶關閉時 data 為空，視為已不屬於任何錢包
+    let parsed = if account.lamports == 0 { None } else { parse_owned_token_account(&account.data) };
+    let current = parsed.filter(|(owner, _)| subscription.token_owners.contains(owner));
+    if previous.is_none() && current.is_none() {
+        return Ok(vec![]);
+    }
+
+    let mut changed = Vec::new();
+    if let Some(address) = &previous {
+        if current.as_ref().is_none_or(|(owner, _)| owner != address) {
+            if let Some(wallet) = wallets.get_mut(address) {
+                wallet.token_accounts.remove(&account.pubkey);
+                changed.push(address.clone());
+            }
+        }
+    }
+    if let Some((owner, token_account)) = current {
+        if let Some(wallet) = wallets.get_mut(&owner) {
+            let (mint, amount) = (token_account.mint.clone(), token_account.amount);
+            if wallet.token_accounts.insert(account.pubkey.clone(), token_account).is_none() {
+                info!("🪙 錢包 {} 新增 token account {}", &owner[..8], &account.pubkey[..8]);
+                subscription.new_token_accounts.store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+            // 首次出現的 mint 記錄為新代幣事件 (空投或粉塵攻擊)
+            if wallet.seen_mints.insert(mint.clone()) {
+                info!("🎁 錢包 {} 收到新代幣 {} (數量 {})", &owner[..8], mint, amount);
+                writer.save_seen_mints(&owner, vec![mint.clone()]);
+                writer.save_token_event(TokenAccountEvent {
+                    event: AccountEvent::NewTokenReceived,
+                    address: owner.clone(),
+                    name: wallet.name.clone(),
+                    mint: mint.clone(),
+                    token_account: account.pubkey.clone(),
+                    amount,
+                    slot: account.slot,
+                    timestamp: Utc::now(),
+                });
+                notifier.notify(
+                    &BalanceChangeEvent::new(wallet, "token", 0.0, amount as f64, account.slot)
+                        .with_account_event(AccountEvent::NewTokenReceived)
+                        .with_mint(&mint),
+                );
+            }
+            changed.push(owner);
+        }
+    }
+
+    for address in &changed {
+        let Some(wallet) = wallets.get_mut(address) else {
+            continue;
+        };
+        let (wsol_balance, lst_amounts) = wallet.owned_token_totals(lst_tokens);
+        let wsol_open = wallet.token_accounts.values().any(|account| account.mint == WSOL_MINT);
+        let account_event = match (wallet.wsol_account_open, wsol_open) {
+            (true, false) => Some(AccountEvent::WsolAccountClosed),
+            (false, true) => Some(AccountEvent::WsolAccountOpened),
+            _ => None,
+        };
+        wallet.wsol_account_open = wsol_open;
+
+        let old_wsol = wallet.wsol_balance;
+        let mut recorded = wallet.update_wsol(wsol_balance);
+        if let Some(account_event) = account_event {
+            info!("{} 錢包 {} 的 WSOL token account {} (餘額 {:.9} → {:.9})",
+                  account_event.description(),
+                  &address[..8],
+                  &account.pubkey[..8],
+                  old_wsol,
+                  wsol_balance);
+            writer.save_token_event(TokenAccountEvent::wsol(wallet, account_event, &account.pubkey, old_wsol, wsol_balance, account.slot));
+            notifier.notify(&BalanceChangeEvent::new(wallet, "wsol", old_wsol, wsol_balance, account.slot).with_account_event(account_event));
+        } else if (wsol_balance - old_wsol).abs() > 0.000001 {
+            info!("💎 錢包 {} WSOL 餘額變化: {:.9} SOL (從 {:.9} 到 {:.9})", &address[..8], wsol_balance - old_wsol, old_wsol, wsol_balance);
+            wallet.print_balance("WSOL帳戶更新");
+            notifier.notify(&BalanceChangeEvent::new(wallet, "wsol", old_wsol, wsol_balance, account.slot));
+        }
+
+        let old_lst = wallet.lst_balance();
+        for (token, amount) in lst_tokens.iter().zip(lst_amounts) {
+            let rate = lst_rates.lock().unwrap().get(&token.symbol).copied().unwrap_or(1.0);
+            recorded |= wallet.update_lst(token, amount, rate);
+        }
+        let new_lst = wallet.lst_balance();
+        if (new_lst - old_lst).abs() > 0.000001 {
+            info!("🌊 錢包 {} LST 餘額變化: {:.9} SOL (從 {:.9} 到 {:.9})", &address[..8], new_lst - old_lst, old_lst, new_lst);
+            wallet.print_balance("LST帳戶更新");
+            notifier.notify(&BalanceChangeEvent::new(wallet, "lst", old_lst, new_lst, account.slot));
+        }
+        // 保存到資料庫；合併期間內的變化由 history_flush_task 補記
+        if recorded {
+            writer.save_history(wallet.history_record());
+        }
+    }
+    Ok(changed)
 }
 
-fn load_wallet_history(db: &Database, address: &str) -> Result<Vec<WalletHistoryRecord>, Box<dyn std::error::Error>> {
-    let read_txn = db.begin_read()?;
-    let table = read_txn.open_table(WALLET_HISTORY_TABLE)?;
-    let mut records = Vec::new();
-    
-    let prefix = format!("{}_", address);
-    let mut iter = table.iter()?;
-    
-    while let Some(entry) = iter.next() {
-        let (key, value) = entry?;
-        let key_str = key.value();
-        if key_str.starts_with(&prefix) {
-            let record: WalletHistoryRecord = serde_json::from_str(value.value())?;
-            records.push(record);
+// 處理交易更新：依據 pre/post balances 分類監控錢包的 SOL 轉帳
+fn handle_transaction_update(
+    update: SubscribeUpdate,
+    subscription: &AccountSubscription,
+    wallets: &HashMap<String, WalletBalance>,
+    writer: &DatabaseWriter,
+    notifier: &Notifier,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(UpdateOneof::Transaction(tx_update)) = update.update_oneof else {
+        return Ok(());
+    };
+    let Some(tx_info) = tx_update.transaction else {
+        return Ok(());
+    };
+    let (Some(transaction), Some(meta)) = (tx_info.transaction, tx_info.meta) else {
+        return Ok(());
+    };
+    let Some(message) = transaction.message else {
+        return Ok(());
+    };
+
+    // 完整帳戶列表 = 靜態帳戶 + ALT 載入的可寫帳戶 + ALT 載入的唯讀帳戶
+    let account_keys: Vec<String> = message
+        .account_keys
+        .iter()
+        .chain(meta.loaded_writable_addresses.iter())
+        .chain(meta.loaded_readonly_addresses.iter())
+        .map(|key| bs58::encode(key).into_string())
+        .collect();
+    let signature = bs58::encode(&tx_info.signature).into_string();
+    // 呼叫 Jito tip distribution 程式的轉入視為領取 MEV tips
+    let jito_claim = account_keys.iter().any(|key| key == JITO_TIP_DISTRIBUTION_PROGRAM_ID);
+    // 手續費 = 每個簽章的基本費 + 優先費
+    let priority_fee = meta.fee.saturating_sub(transaction.signatures.len() as u64 * LAMPORTS_PER_SIGNATURE);
+
+    let deltas: Vec<i128> = meta
+        .pre_balances
+        .iter()
+        .zip(meta.post_balances.iter())
+        .map(|(pre, post)| *post as i128 - *pre as i128)
+        .collect();
+
+    // Jito tips 多半入帳到驗證者的 vote account 或質押者的 stake account，而非錢包本身；依所屬錢包合計
+    let mut account_tips: BTreeMap<&str, i128> = BTreeMap::new();
+    if jito_claim && meta.err.is_none() {
+        for (key, delta) in account_keys.iter().zip(&deltas) {
+            if let Some(wallet) = subscription.tip_accounts.get(key).filter(|_| *delta > 0) {
+                *account_tips.entry(wallet.as_str()).or_default() += delta;
+            }
         }
     }
-    
-    // 按時間排序
-    records.sort_by_key(|r| r.timestamp);
-    Ok(records)
-}
 
-fn delete_wallet_history(db: &Database, address: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let write_txn = db.begin_write()?;
-    {
-        let mut table = write_txn.open_table(WALLET_HISTORY_TABLE)?;
-        let prefix = format!("{}_", address);
-        
-        // 收集需要刪除的鍵
-        let mut keys_to_delete = Vec::new();
-        let mut iter = table.iter()?;
-        while let Some(entry) = iter.next() {
-            let (key, _) = entry?;
-            let key_str = key.value();
-            if key_str.starts_with(&prefix) {
-                keys_to_delete.push(key_str.to_string());
-            }
+    for (index, key) in account_keys.iter().enumerate() {
+        if !subscription.wallet_addresses.contains(key) || index >= deltas.len() {
+            continue;
         }
-        
-        // 刪除找到的鍵
-        for key in keys_to_delete {
-            table.remove(key.as_str())?;
+        let tips = account_tips.remove(key.as_str()).unwrap_or_default();
+
+        let change = deltas[index];
+        let (fee, priority) = if index == 0 { (meta.fee as i128, priority_fee) } else { (0, 0) };
+        let transfer = change + fee; // 扣除手續費後的淨轉帳
+
+        let direction = if transfer > 0 {
+            "incoming"
+        } else if transfer < 0 {
+            "outgoing"
+        } else {
+            "other"
+        };
+
+        // 對手方: 轉出時取收到最多的帳戶，轉入時取付出最多的帳戶
+        let counterparty = deltas
+            .iter()
+            .enumerate()
+            .filter(|(i, d)| *i != index && *i < account_keys.len() && (transfer > 0 && **d < 0 || transfer < 0 && **d > 0))
+            .max_by_key(|(_, d)| d.abs())
+            .map(|(i, _)| account_keys[i].clone());
+
+        let record = WalletTransactionRecord {
+            signature: signature.clone(),
+            slot: tx_update.slot,
+            timestamp: Utc::now(),
+            address: key.clone(),
+            direction: direction.to_string(),
+            amount: transfer.abs() as f64 / 1_000_000_000.0,
+            change: change as f64 / 1_000_000_000.0,
+            fee: fee as f64 / 1_000_000_000.0,
+            priority_fee: priority as f64 / 1_000_000_000.0,
+            counterparty,
+            success: meta.err.is_none(),
+            income: (jito_claim && (transfer > 0 || tips > 0) && meta.err.is_none()).then_some(IncomeKind::JitoTip),
+            account_tips: tips as f64 / 1_000_000_000.0,
+        };
+
+        debug!("🧾 錢包 {} 交易 {} | {} {:.9} SOL | 手續費 {:.9}",
+               &key[..8], &signature[..8], record.direction, record.amount, record.fee);
+        if let Some(tips) = jito_tip_lamports(&record) {
+            info!("🎯 錢包 {} 領取 Jito tips {:.9} SOL (交易 {})", &key[..8], tips as f64 / 1_000_000_000.0, &signature[..8]);
+        }
+
+        if let Some(wallet) = wallets.get(key) {
+            let old_balance = meta.pre_balances[index] as f64 / 1_000_000_000.0;
+            let new_balance = meta.post_balances[index] as f64 / 1_000_000_000.0;
+            notifier.check_transfer(wallet, &record, old_balance, new_balance);
         }
+        writer.save_transaction(record);
     }
-    write_txn.commit()?;
-    info!("🗑️ 已刪除錢包 {} 的歷史數據", address);
+
+    // 錢包本身不在交易中時，另外記錄一筆只有 tips 收入的交易 (錢包的 SOL 餘額不變)
+    for (address, tips) in account_tips {
+        let tips = tips as f64 / 1_000_000_000.0;
+        info!("🎯 錢包 {} 的 vote / stake account 領取 Jito tips {:.9} SOL (交易 {})", &address[..8], tips, &signature[..8]);
+        writer.save_transaction(WalletTransactionRecord {
+            signature: signature.clone(),
+            slot: tx_update.slot,
+            timestamp: Utc::now(),
+            address: address.to_string(),
+            direction: "other".to_string(),
+            amount: 0.0,
+            change: 0.0,
+            fee: 0.0,
+            priority_fee: 0.0,
+            counterparty: None,
+            success: true,
+            income: Some(IncomeKind::JitoTip),
+            account_tips: tips,
+        });
+    }
+
     Ok(())
 }
 
-fn load_all_wallet_history(db: &Database) -> Result<HashMap<String, Vec<WalletHistoryRecord>>, Box<dyn std::error::Error>> {
-    let read_txn = db.begin_read()?;
-    let table = read_txn.open_table(WALLET_HISTORY_TABLE)?;
-    let mut wallet_records: HashMap<String, Vec<WalletHistoryRecord>> = HashMap::new();
-    
-    let mut iter = table.iter()?;
-    while let Some(entry) = iter.next() {
-        let (_, value) = entry?;
-        let record: WalletHistoryRecord = serde_json::from_str(value.value())?;
-        wallet_records.entry(record.address.clone()).or_insert_with(Vec::new).push(record);
-    }
-    
-    // 對每個錢包的記錄按時間排序
-    for records in wallet_records.values_mut() {
-        records.sort_by_key(|r| r.timestamp);
+// 處理區塊 metadata：監控的驗證者 identity 收到的手續費獎勵即為出塊獎勵
+fn handle_block_meta_update(block_meta: &SubscribeUpdateBlockMeta, validator_identities: &[String], writer: &DatabaseWriter) {
+    let Some(rewards) = &block_meta.rewards else {
+        return;
+    };
+    let timestamp = block_meta.block_time.as_ref().and_then(|t| DateTime::from_timestamp(t.timestamp, 0)).unwrap_or_else(Utc::now);
+    for reward in &rewards.rewards {
+        if reward.reward_type != RewardType::Fee as i32 || reward.lamports <= 0 || !validator_identities.contains(&reward.pubkey) {
+            continue;
+        }
+        debug!("🧱 驗證者 {} 在 slot {} 出塊，獎勵 {:.9} SOL", &reward.pubkey[..8], block_meta.slot, reward.lamports as f64 / 1_000_000_000.0);
+        writer.save_block_reward(BlockRewardRecord {
+            address: reward.pubkey.clone(),
+            lamports: reward.lamports as u64,
+            timestamp,
+        });
     }
-    
-    Ok(wallet_records)
 }
 
-// Web API handlers
-async fn get_wallets(axum::extract::State(state): axum::extract::State<AppState>) -> Json<Vec<WalletSummary>> {
-    let wallets_guard = state.wallets.lock().unwrap();
-    let summaries: Vec<WalletSummary> = wallets_guard.values().map(|w| w.to_summary()).collect();
-    Json(summaries)
+// 檢查錢包地址：須為 base58 且解碼後為 32 bytes；一般錢包是 ed25519 公鑰，
+// 不在曲線上的地址 (PDA) 沒有私鑰，多半是輸入錯誤，需明確允許才接受
+fn validate_wallet_address(address: &str, allow_off_curve: bool) -> Result<Pubkey, ErrorCode> {
+    let bytes = bs58::decode(address).into_vec().map_err(|_| ErrorCode::InvalidBase58)?;
+    let bytes: [u8; 32] = bytes.try_into().map_err(|_| ErrorCode::InvalidAddressLength)?;
+    let pubkey = Pubkey::new_from_array(bytes);
+    if !allow_off_curve && !pubkey.is_on_curve() {
+        return Err(ErrorCode::AddressOffCurve);
+    }
+    Ok(pubkey)
 }
 
-async fn get_wallet_detail(
-    Path(address): Path<String>,
-    axum::extract::State(state): axum::extract::State<AppState>,
-) -> Result<Json<WalletSummary>, StatusCode> {
-    let wallets_guard = state.wallets.lock().unwrap();
-    match wallets_guard.get(&address) {
-        Some(wallet) => Ok(Json(wallet.to_summary())),
-        None => Err(StatusCode::NOT_FOUND),
+// 配置文件中的錢包只在地址無法解析時略過；不在曲線上但未設定 allow_off_curve 的地址 (例如升級前已加入的 PDA) 照常監控並警告
+fn check_config_wallet_address(wallet_config: &WalletConfig) -> Result<Pubkey, ErrorCode> {
+    let pubkey = validate_wallet_address(&wallet_config.address, true)?;
+    if !wallet_config.allows_off_curve() && !pubkey.is_on_curve() {
+        warn!(
+            "⚠️ 錢包 {} 的地址 {} 不在 ed25519 曲線上 (PDA)，仍照常監控；請確認地址無誤並在配置文件中設定 allow_off_curve = true",
+            wallet_config.name, wallet_config.address
+        );
     }
+    Ok(pubkey)
 }
 
-async fn get_chart_data(
-    Query(params): Query<ChartQueryParams>,
-    axum::extract::State(state): axum::extract::State<AppState>,
-) -> Result<Json<Vec<ChartDataPoint>>, StatusCode> {
-    let wallets_guard = state.wallets.lock().unwrap();
-    let wallet = wallets_guard.get(&params.wallet).ok_or(StatusCode::NOT_FOUND)?;
-    
-    // 獲取所有歷史數據
-    let mut history: Vec<_> = wallet.history.iter().collect();
+// 配置文件操作函數
+async fn update_config_file(address: &str, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let config_content = fs::read_to_string(config_path())?;
+    let mut lines: Vec<String> = config_content.lines().map(|s| s.to_string()).collect();
     
-    // 排序歷史數據以確保時間順序
-    history.sort_by_key(|h| h.timestamp);
+    // 添加新的錢包配置
+    lines.push(String::new());
+    lines.push("[[wallets]]".to_string());
+    lines.push(format!("address = \"{}\"", address));
+    lines.push(format!("name = \"{}\"", name));
     
-    // 根據時間範圍過濾數據
-    let now = Utc::now();
-    let filtered_history: Vec<_> = match params.interval.as_str() {
-        "5M" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_minutes() <= 5).collect(),
-        "10M" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_minutes() <= 10).collect(),
-        "30M" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_minutes() <= 30).collect(),
-        "1H" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_hours() <= 1).collect(),
-        "2H" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_hours() <= 2).collect(),
-        "4H" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_hours() <= 4).collect(),
-        "8H" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_hours() <= 8).collect(),
-        "12H" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_hours() <= 12).collect(),
-        "1D" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_days() <= 1).collect(),
-        "1W" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_weeks() <= 1).collect(),
-        "ALL" | _ => history,
-    };
+    write_config_file(&lines.join("\n"))?;
     
-    let mut chart_data: Vec<ChartDataPoint> = filtered_history
-        .iter()
-        .filter_map(|h| {
-            let value = match params.data_type.as_str() {
-                "sol" => h.sol_balance,
-                "wsol" => h.wsol_balance,
-                "total" => h.total_balance,
-                _ => h.total_balance,
-            };
-            
-            // 過濾掉無效數值
-            if value.is_finite() && !value.is_nan() {
-                Some(ChartDataPoint {
-                    time: h.timestamp.timestamp(),
-                    value,
-                })
-            } else {
-                None
+    Ok(())
+}
+
+// 先寫入同目錄的暫存檔再改名取代，寫入中途失敗或服務中止時不會留下不完整的配置文件
+fn write_config_file(content: &str) -> std::io::Result<()> {
+    let path = std::path::Path::new(config_path());
+    let temp = path.with_extension("toml.tmp");
+    let mut file = fs::File::create(&temp)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+    if let Ok(metadata) = fs::metadata(path) {
+        fs::set_permissions(&temp, metadata.permissions())?;
+    }
+    fs::rename(&temp, path)
+}
+
+// 設定 config.toml 中指定錢包區塊的欄位，value 為 None 時移除該欄位
+async fn set_wallet_config_field(address: &str, key: &str, value: Option<toml::Value>) -> Result<(), Box<dyn std::error::Error>> {
+    let config_content = fs::read_to_string(config_path())?;
+    let mut lines: Vec<String> = config_content.lines().map(|s| s.to_string()).collect();
+
+    // 找出目標錢包區塊的範圍
+    let mut block: Option<(usize, usize)> = None;
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].trim() == "[[wallets]]" {
+            let start = i;
+            let mut end = i + 1;
+            while end < lines.len() && !lines[end].trim().starts_with('[') {
+                end += 1;
             }
-        })
-        .collect();
-    
-    // 去除重複時間戳（保留最新的）
-    chart_data.dedup_by_key(|point| point.time);
-    
-        // 基於時間的均勻採樣到 1000 筆數據
-    let sampled_data = if chart_data.len() > 1000 {
-        if chart_data.is_empty() {
-            chart_data
-        } else {
-            let start_time = chart_data.first().unwrap().time;
-            let end_time = chart_data.last().unwrap().time;
-            let time_span = end_time - start_time;
-            
-            if time_span <= 0 {
-                // 如果時間跨度為0，直接返回原數據
-                chart_data
-            } else {
-                let mut sampled = Vec::new();
-                let sample_interval = time_span as f64 / 999.0; // 999個間隔產生1000個點
-                
-                for i in 0..1000 {
-                    let target_time = start_time + (i as f64 * sample_interval) as i64;
-                    
-                    // 找到最接近目標時間的數據點
-                    let closest_point = chart_data.iter()
-                        .min_by_key(|point| (point.time - target_time).abs())
-                        .unwrap();
-                    
-                    sampled.push(closest_point.clone());
-                }
-                
-                // 去除重複的時間點，保持時間順序
-                sampled.sort_by_key(|point| point.time);
-                sampled.dedup_by_key(|point| point.time);
-                
-                info!("📊 圖表數據時間採樣: 原始 {} 點 -> 採樣 {} 點 (時間跨度: {}秒)", 
-                      chart_data.len(), sampled.len(), time_span);
-                sampled
+            let is_target = lines[start..end].iter().any(|l| {
+                let l = l.trim();
+                l.starts_with("address") && l.contains(&format!("\"{}\"", address))
+            });
+            if is_target {
+                block = Some((start, end));
+                break;
             }
+            i = end;
+        } else {
+            i += 1;
         }
-    } else {
-        info!("📊 圖表數據無需採樣: {} 點 (上限: 1000 點)", chart_data.len());
-        chart_data
+    }
+
+    let (start, end) = block.ok_or("在配置文件中找不到錢包")?;
+    let key_prefix = |l: &str| {
+        let l = l.trim_start();
+        l.strip_prefix(key).map(|rest| rest.trim_start().starts_with('=')).unwrap_or(false)
     };
 
-    info!("📊 圖表數據準備完成: {} 點 (時間範圍: {})", sampled_data.len(), params.interval);
-    
-    Ok(Json(sampled_data))
+    // 移除舊值，並在區塊最後一個非空行之後插入新值
+    let mut block_lines: Vec<String> = lines[start..end].iter().filter(|l| !key_prefix(l)).cloned().collect();
+    if let Some(value) = value {
+        let insert_at = block_lines.iter().rposition(|l| !l.trim().is_empty()).map(|p| p + 1).unwrap_or(block_lines.len());
+        block_lines.insert(insert_at, format!("{} = {}", key, value));
+    }
+    lines.splice(start..end, block_lines);
+
+    write_config_file(&lines.join("\n"))?;
+    Ok(())
 }
 
-async fn get_wallets_profits(
-    Query(params): Query<ProfitsQueryParams>,
-    axum::extract::State(state): axum::extract::State<AppState>,
-) -> Result<Json<ProfitsResponse>, StatusCode> {
-    let wallets_guard = state.wallets.lock().unwrap();
-    let mut profits = Vec::new();
-    
-    info!("📊 批次計算獲利 - 時間間隔: {}, 數據類型: {}, 錢包數: {}", 
-          params.interval, params.data_type, wallets_guard.len());
+async fn remove_from_config_file(address: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let config_content = fs::read_to_string(config_path())?;
+    let lines: Vec<&str> = config_content.lines().collect();
+    let mut new_lines: Vec<&str> = Vec::new();
+    let mut i = 0;
     
-    for (address, wallet) in wallets_guard.iter() {
-        // 獲取歷史數據
-        let mut history: Vec<_> = wallet.history.iter().collect();
-        
-        // 排序歷史數據以確保時間順序
-        history.sort_by_key(|h| h.timestamp);
-        
-        // 根據時間範圍過濾數據
-        let now = Utc::now();
-        let filtered_history: Vec<_> = match params.interval.as_str() {
-            "5M" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_minutes() <= 5).collect(),
-            "10M" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_minutes() <= 10).collect(),
-            "30M" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_minutes() <= 30).collect(),
-            "1H" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_hours() <= 1).collect(),
-            "2H" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_hours() <= 2).collect(),
-            "4H" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_hours() <= 4).collect(),
-            "8H" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_hours() <= 8).collect(),
-            "12H" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_hours() <= 12).collect(),
-            "1D" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_days() <= 1).collect(),
-            "1W" => history.into_iter().filter(|h| now.signed_duration_since(h.timestamp).num_weeks() <= 1).collect(),
-            "ALL" | _ => history,
-        };
+    while i < lines.len() {
+        let line = lines[i];
         
-        // 計算獲利
-        let profit_info = if filtered_history.len() >= 2 {
-            let earliest = filtered_history.first().unwrap();
-            let latest = filtered_history.last().unwrap();
-            
-            let earliest_value = match params.data_type.as_str() {
-                "sol" => earliest.sol_balance,
-                "wsol" => earliest.wsol_balance,
-                "total" => earliest.total_balance,
-                _ => earliest.total_balance,
-            };
-            
-            let latest_value = match params.data_type.as_str() {
-                "sol" => latest.sol_balance,
-                "wsol" => latest.wsol_balance,
-                "total" => latest.total_balance,
-                _ => latest.total_balance,
-            };
-            
-            let profit = latest_value - earliest_value;
+        // 檢查是否是錢包區塊的開始
+        if line.trim() == "[[wallets]]" {
+            // 查看下一行是否包含要刪除的地址
+            let mut wallet_lines = vec![line];
+            let mut j = i + 1;
+            let mut found_target = false;
             
-            // 判斷獲利的顏色類別
-            let profit_class = if profit > 0.000001 {
-                "positive"
-            } else if profit < -0.000001 {
-                "negative"
-            } else {
-                "neutral"
-            };
+            // 收集這個錢包區塊的所有行
+            while j < lines.len() {
+                let next_line = lines[j];
+                
+                // 如果遇到下一個區塊或文件結束，停止收集
+                if next_line.trim().starts_with("[") && next_line.trim() != "[[wallets]]" {
+                    break;
+                }
+                
+                // 如果遇到下一個錢包區塊，停止收集
+                if next_line.trim() == "[[wallets]]" {
+                    break;
+                }
+                
+                wallet_lines.push(next_line);
+                
+                // 檢查是否包含目標地址
+                if next_line.trim().starts_with("address = ") && next_line.contains(address) {
+                    found_target = true;
+                }
+                
+                j += 1;
+                
+                // 如果遇到空行且已經有了 name，這個錢包區塊結束
+                if next_line.trim().is_empty() && wallet_lines.iter().any(|l| l.trim().starts_with("name = ")) {
+                    break;
+                }
+            }
             
-            WalletProfitInfo {
-                address: address.clone(),
-                profit,
-                profit_class: profit_class.to_string(),
+            // 如果不是目標錢包，保留這個區塊
+            if !found_target {
+                for wallet_line in wallet_lines {
+                    new_lines.push(wallet_line);
+                }
             }
+            
+            // 跳過已處理的行
+            i = j;
         } else {
-            // 沒有足夠的數據
-            WalletProfitInfo {
-                address: address.clone(),
-                profit: 0.0,
-                profit_class: "neutral".to_string(),
-            }
-        };
-        
-        profits.push(profit_info);
+            // 保留非錢包區塊的行
+            new_lines.push(line);
+            i += 1;
+        }
     }
     
-    info!("✅ 批次獲利計算完成，處理了 {} 個錢包", profits.len());
+    write_config_file(&new_lines.join("\n"))?;
     
-    Ok(Json(ProfitsResponse {
-        interval: params.interval,
-        data_type: params.data_type,
-        profits,
-    }))
+    Ok(())
 }
 
-async fn add_wallet(
-    axum::extract::State(state): axum::extract::State<AppState>,
-    Json(request): Json<AddWalletRequest>,
-) -> Result<Json<ApiResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let name = request.name.trim();
-    let address = request.address.trim();
-    
-    // 驗證輸入
-    if name.is_empty() {
-        return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse {
-            error: "錢包名稱不能為空".to_string(),
-        })));
+// 錢包依地址雜湊分配到固定的訂閱串流，新增錢包時不會影響其他分片
+#[derive(Debug, Clone, Copy)]
+struct GrpcShard {
+    index: usize,
+    count: usize,
+}
+
+impl GrpcShard {
+    fn contains(&self, address: &str) -> bool {
+        use std::hash::{Hash, Hasher};
+        if self.count <= 1 {
+            return true;
+        }
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        address.hash(&mut hasher);
+        hasher.finish() as usize % self.count == self.index
+    }
+}
+
+impl std::fmt::Display for GrpcShard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.count > 1 {
+            write!(f, " [分片 {}/{}]", self.index + 1, self.count)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// 訂閱 (或輪詢) 的帳戶清單與帳戶到錢包的映射，錢包清單變更時重新計算
+struct AccountSubscription {
+    wallet_addresses: Vec<String>,
+    ata_to_wallet_map: HashMap<String, String>,
+    stake_to_wallet_map: HashMap<String, String>,
+    lst_ata_map: HashMap<String, (String, usize)>,
+    // owner_token_accounts 模式：以 owner 欄位過濾訂閱的錢包，以及已知 token account 到錢包的映射
+    token_owners: Vec<String>,
+    token_account_map: HashMap<String, String>,
+    // validator 類型的帳戶，有的話另外訂閱區塊 metadata 取得出塊獎勵
+    validator_identities: Vec<String>,
+    // 可能收到 Jito tips 的 vote account 與 stake account -> 錢包地址，一併列入交易訂閱
+    tip_accounts: HashMap<String, String>,
+    // 串流發現新的 token account 時設置，提示重新訂閱以納入已知帳戶清單
+    new_token_accounts: std::sync::atomic::AtomicBool,
+    commitment: CommitmentLevel,
+}
+
+impl AccountSubscription {
+    async fn new(
+        wallets: &SharedWallets,
+        lst_tokens: &[LstToken],
+        commitment: CommitmentLevel,
+        shard: GrpcShard,
+        owner_token_accounts: bool,
+    ) -> Self {
+        // 已封存的錢包不訂閱，只取屬於此分片的錢包；WSOL 與 LST ATA 只對 wallet 類型計算
+        let (wallet_addresses, owners, token_account_map, validator_identities): (Vec<String>, Vec<String>, HashMap<String, String>, Vec<String>) = {
+            let wallets_guard = wallets.read().await;
+            let active: Vec<&WalletBalance> = wallets_guard.values().filter(|w| !w.archived && shard.contains(&w.address)).collect();
+            let owners: Vec<&WalletBalance> = active.iter().copied().filter(|w| w.account_type == AccountType::Wallet).collect();
+            (
+                active.iter().map(|w| w.address.clone()).collect(),
+                owners.iter().map(|w| w.address.clone()).collect(),
+                owners
+                    .iter()
+                    .filter(|_| owner_token_accounts)
+                    .flat_map(|w| w.token_accounts.keys().map(move |account| (account.clone(), w.address.clone())))
+                    .collect(),
+                active.iter().filter(|w| w.account_type == AccountType::Validator).map(|w| w.address.clone()).collect(),
+            )
+        };
+        // owner_token_accounts 模式下 WSOL 與 LST 由 token account 訂閱涵蓋，不另外計算 ATA
+        let (ata_owners, token_owners) = if owner_token_accounts { (vec![], owners) } else { (owners, vec![]) };
+
+        // 計算所有錢包的 WSOL ATA 地址並建立 ATA 到錢包地址的映射
+        let ata_addresses = calculate_all_wsol_atas(&ata_owners);
+        let ata_to_wallet_map: HashMap<String, String> = ata_addresses
+            .into_iter()
+            .zip(ata_owners.iter().cloned())
+            .collect();
+
+        // 創建 stake account 到錢包地址的映射，以及 validator 的 vote account
+        let (stake_to_wallet_map, vote_accounts): (HashMap<String, String>, Vec<(String, String)>) = {
+            let wallets_guard = wallets.read().await;
+            let active = || wallets_guard.values().filter(|w| !w.archived && shard.contains(&w.address));
+            (
+                active().flat_map(|w| w.stake_accounts.keys().map(move |s| (s.clone(), w.address.clone()))).collect(),
+                active().filter_map(|w| w.vote_account.clone().map(|vote_account| (vote_account, w.address.clone()))).collect(),
+            )
+        };
+        let tip_accounts: HashMap<String, String> = stake_to_wallet_map.clone().into_iter().chain(vote_accounts).collect();
+
+        // 計算所有錢包的 LST ATA 地址
+        let lst_ata_map = calculate_lst_atas(&ata_owners, lst_tokens);
+
+        Self {
+            wallet_addresses,
+            ata_to_wallet_map,
+            stake_to_wallet_map,
+            lst_ata_map,
+            token_owners,
+            token_account_map,
+            validator_identities,
+            tip_accounts,
+            new_token_accounts: std::sync::atomic::AtomicBool::new(false),
+            commitment,
+        }
     }
-    
-    if address.is_empty() {
-        return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse {
-            error: "錢包地址不能為空".to_string(),
-        })));
+
+    // 所有需要監聽的帳戶：錢包本身、WSOL ATA、stake account、LST ATA 與已知的 token account
+    fn addresses(&self) -> Vec<String> {
+        self.wallet_addresses
+            .iter()
+            .chain(self.ata_to_wallet_map.keys())
+            .chain(self.stake_to_wallet_map.keys())
+            .chain(self.lst_ata_map.keys())
+            .chain(self.token_account_map.keys())
+            .cloned()
+            .collect()
     }
-    
-    if address.len() < 32 || address.len() > 44 {
-        return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse {
-            error: "錢包地址長度不正確".to_string(),
-        })));
+
+    // 帳戶所屬的錢包地址
+    fn wallet_for(&self, address: &str) -> Option<String> {
+        if self.wallet_addresses.iter().any(|wallet| wallet == address) {
+            return Some(address.to_string());
+        }
+        self.ata_to_wallet_map
+            .get(address)
+            .or_else(|| self.stake_to_wallet_map.get(address))
+            .or_else(|| self.lst_ata_map.get(address).map(|(wallet, _)| wallet))
+            .or_else(|| self.token_account_map.get(address))
+            .cloned()
     }
-    
-    // 檢查錢包是否已存在
-    {
-        let wallets_guard = state.wallets.lock().unwrap();
-        if wallets_guard.contains_key(address) {
-            return Err((StatusCode::CONFLICT, Json(ErrorResponse {
-                error: "此錢包地址已存在".to_string(),
-            })));
+
+    fn request(&self) -> SubscribeRequest {
+        let account_filter = |accounts: Vec<String>| SubscribeRequestFilterAccounts {
+            account: accounts,
+            owner: vec![],
+            filters: vec![],
+            nonempty_txn_signature: None,
+        };
+
+        // 空的 account 清單代表不限帳戶，會收到全部帳戶更新，沒有地址的過濾條件一律不送出
+        let mut accounts_filter = HashMap::new();
+        if !self.wallet_addresses.is_empty() {
+            accounts_filter.insert("wallet_accounts".to_string(), account_filter(self.wallet_addresses.clone()));
         }
-        
-        // 檢查名稱是否已存在
-        for wallet in wallets_guard.values() {
-            if wallet.name == name {
-                return Err((StatusCode::CONFLICT, Json(ErrorResponse {
-                    error: "此錢包名稱已存在".to_string(),
-                })));
-            }
+        // 監聽 WSOL ATA 地址 (owner_token_accounts 模式下為空)
+        if !self.ata_to_wallet_map.is_empty() {
+            accounts_filter.insert("wsol_ata_accounts".to_string(), account_filter(self.ata_to_wallet_map.keys().cloned().collect()));
         }
-    }
-    
-    // 創建新錢包
-    let new_wallet = WalletBalance::new(address.to_string(), name.to_string());
-    
-    // 嘗試初始化錢包餘額 (使用配置中的RPC端點)
-    let rpc_endpoint = &state.config.rpc.endpoint;
-    match query_wallet_balance(address, rpc_endpoint).await {
-        Ok((sol_balance, wsol_balance)) => {
-            let mut new_wallet = new_wallet;
-            new_wallet.update_sol((sol_balance * 1_000_000_000.0) as u64);
-            new_wallet.initialize_wsol(wsol_balance);
-            
-            // 添加到錢包列表
-            // 保存初始記錄到資料庫
-            let initial_record = WalletHistoryRecord::new(
-                address.to_string(),
-                new_wallet.sol_balance,
-                new_wallet.wsol_balance,
+        // 監聽 LST ATA 地址
+        if !self.lst_ata_map.is_empty() {
+            accounts_filter.insert("lst_ata_accounts".to_string(), account_filter(self.lst_ata_map.keys().cloned().collect()));
+        }
+        // 監聽 stake accounts
+        if !self.stake_to_wallet_map.is_empty() {
+            accounts_filter.insert("stake_accounts".to_string(), account_filter(self.stake_to_wallet_map.keys().cloned().collect()));
+        }
+        // 以 owner 欄位過濾每個錢包擁有的 token account，非 ATA 與新建立的帳戶也能收到更新
+        for (i, owner) in self.token_owners.iter().enumerate() {
+            accounts_filter.insert(
+                format!("token_accounts_{}", i),
+                SubscribeRequestFilterAccounts {
+                    account: vec![],
+                    owner: vec![TOKEN_PROGRAM_ID.to_string()],
+                    filters: vec![
+                        SubscribeRequestFilterAccountsFilter {
+                            filter: Some(subscribe_request_filter_accounts_filter::Filter::Datasize(TOKEN_ACCOUNT_LEN as u64)),
+                        },
+                        SubscribeRequestFilterAccountsFilter {
+                            filter: Some(subscribe_request_filter_accounts_filter::Filter::Memcmp(SubscribeRequestFilterAccountsFilterMemcmp {
+                                offset: TOKEN_OWNER_OFFSET as u64,
+                                data: Some(subscribe_request_filter_accounts_filter_memcmp::Data::Base58(owner.clone())),
+                            })),
+                        },
+                    ],
+                    nonempty_txn_signature: None,
+                },
             );
-            if let Err(e) = save_wallet_history(&state.database, &initial_record) {
-                warn!("⚠️ 保存初始歷史記錄失敗: {}", e);
-            }
+        }
+        // 帳戶關閉時資料已清空，不再符合 owner 過濾條件，已知的 token account 需另外依地址監聽
+        if !self.token_account_map.is_empty() {
+            accounts_filter.insert("known_token_accounts".to_string(), account_filter(self.token_account_map.keys().cloned().collect()));
+        }
 
-            {
-                let mut wallets_guard = state.wallets.lock().unwrap();
-                wallets_guard.insert(address.to_string(), new_wallet);
-            }
-            
-            // 更新配置文件
-            if let Err(e) = update_config_file(address, name).await {
-                warn!("⚠️ 更新配置文件失敗: {}", e);
-            }
-            
-            // 觸發 gRPC 流重啟以訂閱新錢包
-            {
-                let mut restart_signal = state.grpc_restart_signal.lock().unwrap();
-                *restart_signal = true;
-            }
-            
-            info!("✅ 成功新增錢包: {} ({}) - 正在重啟gRPC訂閱", name, &address[..8]);
-            
-            Ok(Json(ApiResponse {
-                success: true,
-                message: format!("成功新增錢包 {}", name),
-            }))
+        // 監聽涉及監控錢包的交易 (不含投票交易)，以及 vote / stake account 的交易以辨識入帳到這些帳戶的 Jito tips；
+        // account_include 為空時會收到全部交易，沒有錢包時不訂閱
+        let mut transactions_filter = HashMap::new();
+        if !self.wallet_addresses.is_empty() {
+            transactions_filter.insert(
+                "wallet_transactions".to_string(),
+                SubscribeRequestFilterTransactions {
+                    vote: Some(false),
+                    failed: None,
+                    signature: None,
+                    account_include: self.wallet_addresses.iter().chain(self.tip_accounts.keys()).cloned().collect(),
+                    account_exclude: vec![],
+                    account_required: vec![],
+                },
+            );
         }
-        Err(e) => {
-            error!("❌ 初始化錢包餘額失敗: {}", e);
-            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse {
-                error: "無法獲取錢包餘額，請檢查地址是否正確".to_string(),
-            })))
+
+        // 有驗證者時監聽區塊 metadata，從 rewards 取得出塊獎勵
+        let mut blocks_meta_filter = HashMap::new();
+        if !self.validator_identities.is_empty() {
+            blocks_meta_filter.insert("block_rewards".to_string(), SubscribeRequestFilterBlocksMeta {});
+        }
+
+        // 追蹤串流的最新 slot，用於計算資料延遲
+        let mut slots_filter = HashMap::new();
+        slots_filter.insert(
+            "slots".to_string(),
+            SubscribeRequestFilterSlots {
+                filter_by_commitment: Some(true),
+                ..Default::default()
+            },
+        );
+
+        SubscribeRequest {
+            accounts: accounts_filter,
+            slots: slots_filter,
+            transactions: transactions_filter,
+            transactions_status: HashMap::new(),
+            blocks: HashMap::new(),
+            blocks_meta: blocks_meta_filter,
+            entry: HashMap::new(),
+            commitment: Some(self.commitment as i32),
+            // 只需要 token account 的 amount；SOL 與 stake account 只用 lamports，系統帳戶本身沒有 data
+            // owner_token_accounts 模式另需 mint 與 owner 以判斷帳戶所屬的錢包
+            accounts_data_slice: vec![if self.token_owners.is_empty() {
+                SubscribeRequestAccountsDataSlice {
+                    offset: TOKEN_AMOUNT_OFFSET as u64,
+                    length: TOKEN_AMOUNT_LEN as u64,
+                }
+            } else {
+                SubscribeRequestAccountsDataSlice {
+                    offset: 0,
+                    length: (TOKEN_AMOUNT_OFFSET + TOKEN_AMOUNT_LEN) as u64,
+                }
+            }],
+            ping: None,
+            from_slot: None,
         }
     }
 }
 
-async fn delete_wallet(
-    Path(address): Path<String>,
-    axum::extract::State(state): axum::extract::State<AppState>,
-) -> Result<Json<ApiResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let wallet_name = {
-        let mut wallets_guard = state.wallets.lock().unwrap();
-        if let Some(wallet) = wallets_guard.remove(&address) {
-            wallet.name.clone()
-        } else {
-            return Err((StatusCode::NOT_FOUND, Json(ErrorResponse {
-                error: "錢包不存在".to_string(),
-            })));
+fn read_slot_state(path: &str) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+// 定期保存串流的最新 slot，重啟後以 from_slot 補回停機期間的更新
+async fn slot_state_task(path: String, health: SharedHealth) {
+    let mut interval = tokio::time::interval(Duration::from_secs(SLOT_STATE_SAVE_INTERVAL_SECS));
+    let mut saved_slot = 0;
+    loop {
+        interval.tick().await;
+        let slot = health.current_slot();
+        if slot <= saved_slot {
+            continue;
+        }
+        match fs::write(&path, slot.to_string()) {
+            Ok(()) => saved_slot = slot,
+            Err(e) => warn!("⚠️ 保存 slot 狀態到 {} 失敗: {}", path, e),
         }
-    };
-    
-    // 刪除資料庫中的歷史記錄
-    if let Err(e) = delete_wallet_history(&state.database, &address) {
-        warn!("⚠️ 刪除錢包歷史記錄失敗: {}", e);
     }
-    
-    // 更新配置文件
-    if let Err(e) = remove_from_config_file(&address).await {
-        warn!("⚠️ 更新配置文件失敗: {}", e);
+}
+
+// 只含 ping 的訂閱請求，不會變更既有的訂閱條件
+fn ping_request() -> SubscribeRequest {
+    SubscribeRequest {
+        ping: Some(SubscribeRequestPing { id: 1 }),
+        ..Default::default()
     }
-    
-    // 觸發 gRPC 流重啟以停止訂閱已刪除的錢包
-    {
-        let mut restart_signal = state.grpc_restart_signal.lock().unwrap();
-        *restart_signal = true;
+}
+
+// 依 [grpc] 設定建立 Geyser 客戶端 (x-token、TLS 與逾時)
+fn build_geyser_client(config: &GrpcConfig) -> Result<GeyserGrpcBuilder, Box<dyn std::error::Error>> {
+    let mut builder = GeyserGrpcClient::build_from_shared(config.endpoint.clone())?
+        .x_token(config.x_token.clone())?
+        .connect_timeout(Duration::from_secs(config.connect_timeout_secs))
+        .timeout(Duration::from_secs(config.timeout_secs))
+        .max_decoding_message_size(config.max_decoding_message_size);
+
+    let tls = config.tls.clone().or_else(|| config.endpoint.starts_with("https://").then(GrpcTlsConfig::default));
+    if let Some(tls) = tls {
+        let mut tls_config = ClientTlsConfig::new().with_native_roots();
+        if let Some(path) = &tls.ca_cert_path {
+            let pem = fs::read(path).map_err(|e| format!("無法讀取 gRPC CA 憑證 {}: {}", path, e))?;
+            tls_config = tls_config.ca_certificate(Certificate::from_pem(pem));
+        }
+        if let Some(domain_name) = &tls.domain_name {
+            tls_config = tls_config.domain_name(domain_name.clone());
+        }
+        builder = builder.tls_config(tls_config)?;
     }
-    
-    info!("✅ 成功刪除錢包: {} ({}) - 正在重啟gRPC訂閱", wallet_name, &address[..8]);
-    
-    Ok(Json(ApiResponse {
-        success: true,
-        message: format!("成功刪除錢包 {}", wallet_name),
-    }))
+    Ok(builder)
 }
 
-async fn websocket_handler(
-    ws: WebSocketUpgrade,
-    axum::extract::State(state): axum::extract::State<AppState>,
-) -> Response {
-    ws.on_upgrade(|socket| websocket_connection(socket, state.wallets))
+// 以 getMultipleAccounts 批次查詢帳戶，返回 (地址, 查詢時的 slot, 帳戶)；帳戶不存在時為 None
+async fn fetch_accounts(rpc: &RpcPool, addresses: Vec<String>) -> Result<Vec<(String, u64, Option<solana_sdk::account::Account>)>, String> {
+    let mut accounts = Vec::with_capacity(addresses.len());
+    for chunk in addresses.chunks(RPC_MULTIPLE_ACCOUNTS_LIMIT) {
+        let pubkeys = chunk
+            .iter()
+            .map(|address| Pubkey::from_str(address).map_err(|e| format!("無效的地址 {}: {}", address, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let response = rpc
+            .call(|client| {
+                let pubkeys = pubkeys.clone();
+                async move { client.get_multiple_accounts_with_commitment(&pubkeys, solana_sdk::commitment_config::CommitmentConfig::confirmed()).await }
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+        let slot = response.context.slot;
+        accounts.extend(chunk.iter().cloned().zip(response.value).map(|(address, account)| (address, slot, account)));
+    }
+    Ok(accounts)
 }
 
-async fn websocket_connection(mut socket: WebSocket, wallets: SharedWallets) {
-    let mut interval = tokio::time::interval(Duration::from_secs(1));
-    let mut last_sent_data: Option<HashMap<String, (f64, f64, f64, DateTime<Utc>)>> = None; // address -> (sol, wsol, total, timestamp)
-    
-    loop {
-        tokio::select! {
-            _ = interval.tick() => {
-                let current_data: HashMap<String, (f64, f64, f64, DateTime<Utc>)> = {
-                    let wallets_guard = wallets.lock().unwrap();
-                    wallets_guard.iter().map(|(addr, wallet)| {
-                        (addr.clone(), (wallet.sol_balance, wallet.wsol_balance, wallet.total_balance(), wallet.last_update))
-                    }).collect()
-                };
-                
-                // 檢查變化並收集更新的錢包
-                let mut updates = Vec::new();
-                
-                for (address, (sol, wsol, total, timestamp)) in &current_data {
-                    let has_change = match &last_sent_data {
-                        None => true, // 第一次發送
-                        Some(last_data) => {
-                            match last_data.get(address) {
-                                None => true, // 新錢包
-                                Some((last_sol, last_wsol, last_total, last_timestamp)) => {
-                                    // 檢查餘額或時間戳是否有變化
-                                    (sol - last_sol).abs() > f64::EPSILON ||
-                                    (wsol - last_wsol).abs() > f64::EPSILON ||
-                                    (total - last_total).abs() > f64::EPSILON ||
-                                    timestamp != last_timestamp
-                                }
-                            }
-                        }
-                    };
-                    
-                    if has_change {
-                        // 獲取錢包詳細信息
-                        let wallets_guard = wallets.lock().unwrap();
-                        if let Some(wallet) = wallets_guard.get(address) {
-                            // 只發送最新的一筆歷史數據
-                            let latest_history = wallet.history.back().cloned();
-                            
-                            let update = serde_json::json!({
-                                "type": "update",
-                                "wallet": {
-                                    "address": wallet.address,
-                                    "name": wallet.name,
-                                    "sol_balance": wallet.sol_balance,
-                                    "wsol_balance": if wallet.wsol_initialized { wallet.wsol_balance } else { 0.0 },
-                                    "total_balance": wallet.total_balance(),
-                                    "last_update": wallet.last_update,
-                                    "latest_data": latest_history.map(|h| serde_json::json!({
-                                        "time": h.timestamp.timestamp(),
-                                        "sol_balance": h.sol_balance,
-                                        "wsol_balance": h.wsol_balance,
-                                        "total_balance": h.total_balance
-                                    }))
-                                }
-                            });
-                            updates.push(update);
-                        }
-                    }
-                }
-                
-                // 檢查是否有錢包被刪除
-                if let Some(ref last_data) = last_sent_data {
-                    for address in last_data.keys() {
-                        if !current_data.contains_key(address) {
-                            let delete_update = serde_json::json!({
-                                "type": "delete",
-                                "address": address
-                            });
-                            updates.push(delete_update);
-                        }
-                    }
-                }
-                
-                // 發送更新
-                if !updates.is_empty() {
-                    let message = serde_json::json!({
-                        "type": "batch_update",
-                        "updates": updates
-                    });
-                    
-                    if socket.send(axum::extract::ws::Message::Text(message.to_string())).await.is_err() {
-                        break;
-                    }
-                    
-                    info!("📡 WebSocket 發送 {} 個錢包更新 (只含最新數據)", updates.len());
-                    last_sent_data = Some(current_data);
-                } else {
-                    debug!("📡 WebSocket 無變化，跳過發送");
-                }
+// 監看配置文件，變更時套用日誌等級、通知規則與錢包清單；其他設定仍需重新啟動
+async fn config_reload_task(state: AppState, notifier: Notifier, log_handle: LogReloadHandle) {
+    let path = std::path::Path::new(config_path());
+    let Some(file_name) = path.file_name().map(|name| name.to_os_string()) else {
+        return;
+    };
+    // 編輯器常以「寫入暫存檔再改名」的方式存檔，因此監看所在目錄
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => std::path::PathBuf::from("."),
+    };
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            if event.paths.iter().any(|p| p.file_name() == Some(file_name.as_os_str())) {
+                let _ = tx.try_send(());
             }
-            msg = socket.recv() => {
-                if msg.is_none() {
-                    break;
+        }
+    });
+    let mut watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            warn!("⚠️ 無法監看配置文件: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = notify::Watcher::watch(&mut watcher, &dir, notify::RecursiveMode::NonRecursive) {
+        warn!("⚠️ 無法監看配置文件 {}: {}", path.display(), e);
+        return;
+    }
+    info!("👀 監看配置文件 {} 的變更", path.display());
+
+    let mut current = state.config.clone();
+    while rx.recv().await.is_some() {
+        // 一次存檔可能觸發多個事件，稍等後合併處理
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        while rx.try_recv().is_ok() {}
+
+        let config = match load_config() {
+            Ok(config) => config,
+            Err(e) => {
+                warn!("⚠️ 配置文件解析失敗，保留目前設定: {}", e);
+                continue;
+            }
+        };
+
+        if config.logging.level != current.logging.level {
+            if std::env::var_os("RUST_LOG").is_some() {
+                info!("🔧 已設定 RUST_LOG，略過配置文件中的日誌等級變更");
+            } else {
+                match tracing_subscriber::EnvFilter::try_new(&config.logging.level) {
+                    Ok(filter) => match log_handle.reload(filter) {
+                        Ok(()) => info!("🔧 日誌等級已更新為 {}", config.logging.level),
+                        Err(e) => warn!("⚠️ 更新日誌等級失敗: {}", e),
+                    },
+                    Err(e) => warn!("⚠️ 無效的日誌等級 \"{}\": {}，保留目前設定", config.logging.level, e),
                 }
             }
         }
-    }
-}
 
-async fn serve_index() -> Html<&'static str> {
-    Html(include_str!("../web/index.html"))
-}
+        if config.notifications != current.notifications {
+            config.warn_unsupported_notifications();
+            notifier.reload(&config.notifications);
+            info!("🔧 通知設定已重新載入");
+        }
 
-// 讀取配置檔案
-fn load_config() -> Result<Config, Box<dyn std::error::Error>> {
-    let config_content = fs::read_to_string("config.toml")?;
-    let mut config: Config = toml::from_str(&config_content)?;
-    
-    // 如果沒有server配置，使用默認值
-    if config_content.find("[server]").is_none() {
-        config.server = ServerConfig::default();
+        reload_wallets(&state, &config).await;
+        current = config;
     }
-    
-    Ok(config)
 }
 
-// 設定日誌
-fn setup_logging(level: &str) {
-    std::env::set_var("RUST_LOG", level);
-    env_logger::init();
+// 配置文件可修改的錢包欄位，重新載入時比較前後是否有變更
+#[derive(PartialEq)]
+struct WalletMetadata {
+    name: String,
+    group: Option<String>,
+    tags: Vec<String>,
+    note: Option<String>,
+    color: Option<String>,
+    domain: Option<String>,
+    archived: bool,
+    history_config: HistoryConfig,
+    account_type: AccountType,
 }
 
-// 計算錢包的 WSOL ATA 地址
-fn calculate_wsol_ata(wallet_address: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let wallet = ProgramPubkey::from_str(wallet_address)?;
-    let wsol_mint = ProgramPubkey::from_str(WSOL_MINT)?;
-    let associated_program_id = ProgramPubkey::from_str(ASSOCIATED_TOKEN_PROGRAM_ID)?;
-    let token_program_id = ProgramPubkey::from_str(TOKEN_PROGRAM_ID)?;
-    
-    let (ata, _) = ProgramPubkey::find_program_address(
-        &[wallet.as_ref(), token_program_id.as_ref(), wsol_mint.as_ref()],
-        &associated_program_id,
-    );
-    
-    Ok(ata.to_string())
+impl WalletMetadata {
+    fn of(wallet: &WalletBalance) -> Self {
+        Self {
+            name: wallet.name.clone(),
+            group: wallet.group.clone(),
+            tags: wallet.tags.clone(),
+            note: wallet.note.clone(),
+            color: wallet.color.clone(),
+            domain: wallet.domain.clone(),
+            archived: wallet.archived,
+            history_config: wallet.history_config,
+            account_type: wallet.account_type,
+        }
+    }
 }
 
-// 計算所有錢包的 WSOL ATA 地址
-fn calculate_all_wsol_atas(wallet_addresses: &[String]) -> Vec<String> {
-    let mut ata_addresses = Vec::new();
-    
-    for wallet_address in wallet_addresses {
-        match calculate_wsol_ata(wallet_address) {
-            Ok(ata) => {
-                info!("💎 錢包 {} 的 WSOL ATA: {}", &wallet_address[..8], &ata[..8]);
-                ata_addresses.push(ata);
-            }
-            Err(e) => {
-                error!("❌ 計算錢包 {} 的 WSOL ATA 失敗: {}", wallet_address, e);
+// 依配置文件同步錢包清單；透過 API 修改時配置文件與目前狀態一致，不會產生任何變更
+async fn reload_wallets(state: &AppState, config: &Config) {
+    let mut resubscribe = false;
+    let mut initialize = Vec::new();
+    {
+        let mut wallets_guard = state.wallets.write().await;
+
+        // 從配置文件移除的錢包停止監聽，歷史記錄保留 (可透過 API 刪除)；
+        // 透過 API 新增但寫入配置文件失敗的錢包不在配置文件中，仍保留監聽
+        let configured: std::collections::HashSet<&str> = config.wallets.iter().map(|w| w.address.as_str()).collect();
+        let removed: Vec<String> = {
+            let mut unsaved = state.unsaved_wallets.lock().unwrap_or_else(|e| e.into_inner());
+            unsaved.retain(|address| !configured.contains(address.as_str()));
+            wallets_guard.keys().filter(|address| !configured.contains(address.as_str()) && !unsaved.contains(*address)).cloned().collect()
+        };
+        for address in removed {
+            if let Some(wallet) = wallets_guard.remove(&address) {
+                state.events.publish_removed(&address, &wallet.name);
+                info!("🗑️ 配置文件已移除錢包 {} ({})，停止監聽", wallet.name, &address[..8]);
+                resubscribe |= !wallet.archived;
             }
         }
-    }
-    
-    ata_addresses
-}
 
-// 處理 WSOL Account 更新
-fn handle_wsol_account_update(
-    update: SubscribeUpdate,
-    wallets: &mut HashMap<String, WalletBalance>,
-    ata_to_wallet_map: &HashMap<String, String>,
-    db: &Database,
-) -> Result<(), Box<dyn std::error::Error>> {
-    if let Some(UpdateOneof::Account(account_update)) = update.update_oneof {
-        if let Some(account) = account_update.account {
-            let ata_address = bs58::encode(&account.pubkey).into_string();
-            
-            // 檢查是否是我們監聽的 ATA 地址
-            if let Some(wallet_address) = ata_to_wallet_map.get(&ata_address) {
-                // 解析 token account 數據
-                match TokenAccount::unpack(&account.data) {
-                    Ok(token_account) => {
-                        let wsol_balance = token_account.amount as f64 / 1_000_000_000.0; // WSOL decimals = 9
-                        
-                        if let Some(wallet) = wallets.get_mut(wallet_address) {
-                            let old_balance = wallet.wsol_balance;
-                            wallet.update_wsol(wsol_balance);
-                            
-                            if (wsol_balance - old_balance).abs() > 0.000001 {
-                                info!("💎 錢包 {} WSOL 餘額變化: {:.9} SOL (從 {:.9} 到 {:.9})", 
-                                      &wallet_address[..8], 
-                                      wsol_balance - old_balance, 
-                                      old_balance, 
-                                      wsol_balance);
-                                
-                                wallet.print_balance("WSOL帳戶更新");
-                                
-                                // 保存到資料庫
-                                let record = WalletHistoryRecord::new(
-                                    wallet.address.clone(),
-                                    wallet.sol_balance,
-                                    wallet.wsol_balance,
-                                );
-                                if let Err(e) = save_wallet_history(db, &record) {
-                                    warn!("⚠️ 保存WSOL帳戶更新記錄失敗 {}: {}", wallet.name, e);
-                                }
-                            }
+        for wallet_config in &config.wallets {
+            match wallets_guard.get_mut(&wallet_config.address) {
+                Some(wallet) => {
+                    let before = WalletMetadata::of(wallet);
+                    apply_wallet_config(wallet, wallet_config, config.history);
+                    if WalletMetadata::of(wallet) == before {
+                        continue;
+                    }
+                    if wallet.name != before.name {
+                        state.events.publish_renamed(&wallet.address, &before.name, &wallet.name);
+                    }
+                    state.events.publish_update(wallet);
+                    if wallet.archived != before.archived || wallet.account_type != before.account_type {
+                        resubscribe = true;
+                        // 取消封存或變更帳戶類型的錢包先從 RPC 取得最新餘額
+                        if !wallet.archived {
+                            initialize.push(wallet.clone());
                         }
                     }
-                    Err(e) => {
-                        warn!("⚠️ 解析 token account 數據失敗: {}", e);
+                    info!("🔧 配置文件已更新錢包 {} ({})", wallet.name, &wallet.address[..8]);
+                }
+                None => {
+                    if let Err(code) = check_config_wallet_address(wallet_config) {
+                        error!("❌ 配置文件中的錢包地址 {} 無效 ({})，略過", wallet_config.address, code.message(Language::Zh));
+                        continue;
                     }
+                    let wallet = wallet_from_config(wallet_config, config.history, &state.database);
+                    state.events.publish_added(&wallet);
+                    if !wallet.archived {
+                        resubscribe = true;
+                        initialize.push(wallet.clone());
+                    }
+                    info!("➕ 配置文件已新增錢包 {} ({})", wallet.name, &wallet.address[..8]);
+                    wallets_guard.insert(wallet.address.clone(), wallet);
                 }
             }
         }
     }
-    Ok(())
-}
 
-// 查詢錢包餘額 (初始化用)
-async fn query_wallet_balance(wallet_address: &str, rpc_endpoint: &str) -> Result<(f64, f64), Box<dyn std::error::Error + Send + Sync>> {
-    // 使用 Solana RPC Client
-    let client = RpcClient::new(rpc_endpoint.to_string());
-    
-    // 解析錢包地址
-    let owner_pubkey = Pubkey::from_str(wallet_address)?;
-    
-    // 查詢 SOL 餘額
-    let sol_lamports = client.get_balance(&owner_pubkey)?;
-    let sol_balance = sol_lamports as f64 / 1_000_000_000.0;
-    
-    // 查詢 WSOL 餘額 - 使用 ATA 方式
-    let wsol_mint = Pubkey::from_str(WSOL_MINT)?;
-    let ata = get_associated_token_address(&owner_pubkey, &wsol_mint);
-    
-    let wsol_balance = match client.get_token_account_balance(&ata) {
-        Ok(balance) => balance.ui_amount.unwrap_or(0.0),
-        Err(_) => 0.0, // ATA 不存在，餘額為 0
-    };
-    
-    Ok((sol_balance, wsol_balance))
+    if resubscribe {
+        state.grpc_resubscribe.send_replace(());
+    }
+    for wallet in initialize {
+        spawn_wallet_initialization(state.clone(), wallet);
+    }
+    if history_coalescing(config) && !state.history_flush_started.swap(true, std::sync::atomic::Ordering::SeqCst) {
+        info!("🔧 已啟用歷史記錄合併，開始定期補記合併中的餘額");
+        tokio::spawn(history_flush_task(state.wallets.clone(), state.database_writer.clone()));
+    }
 }
 
-// 從RPC初始化所有錢包餘額
-async fn initialize_wallets_from_rpc(wallets: &mut HashMap<String, WalletBalance>, db: &Database, rpc_endpoint: &str) {
-    let wallet_count = wallets.len();
-    info!("🔄 開始從RPC獲取 {} 個錢包的最新餘額 (使用ATA查詢)", wallet_count);
-    
-    let mut processed_count = 0;
-    
-    for (address, wallet) in wallets.iter_mut() {
-        processed_count += 1;
-        info!("📋 正在獲取錢包 {}/{} 的最新餘額: {} ({})", processed_count, wallet_count, wallet.name, &address[..8]);
-        
-        // 從RPC獲取最新的SOL和WSOL餘額
-        match query_wallet_balance(address, rpc_endpoint).await {
-            Ok((sol_balance, wsol_balance)) => {
-                wallet.update_sol((sol_balance * 1_000_000_000.0) as u64);
-                wallet.initialize_wsol(wsol_balance);
-                info!("   📊 最新餘額 - SOL: {:.6}, WSOL: {:.6}", sol_balance, wsol_balance);
+// 定期以 RPC 查詢所有帳戶的實際狀態，與串流累積的餘額比對；不一致時透過相同的處理流程修正並記錄
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(name = "reconciliation", skip_all)]
+async fn reconciliation_task(
+    rpc: SharedRpc,
+    interval_minutes: u64,
+    wallets: SharedWallets,
+    writer: DatabaseWriter,
+    notifier: Notifier,
+    lst_tokens: SharedLstTokens,
+    lst_rates: SharedLstRates,
+    events: WalletEvents,
+    health: SharedHealth,
+    owner_token_accounts: bool,
+) {
+    let shard = GrpcShard { index: 0, count: 1 };
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_minutes.max(1) * 60));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    interval.tick().await; // 啟動時已從 RPC 初始化餘額
+
+    loop {
+        interval.tick().await;
+        let subscription = AccountSubscription::new(&wallets, &lst_tokens, CommitmentLevel::Confirmed, shard, owner_token_accounts).await;
+        let accounts = match fetch_accounts(&rpc, subscription.addresses()).await {
+            Ok(accounts) => accounts,
+            Err(e) => {
+                warn!("⚠️ 餘額核對查詢 RPC 失敗: {}", e);
+                continue;
+            }
+        };
+
+        let mut wallets_guard = wallets.write().await;
+        let balances = |wallet: &WalletBalance| [
+            ("sol", wallet.sol_balance),
+            ("wsol", wallet.wsol_balance),
+            ("staked", wallet.staked_balance()),
+            ("lst", wallet.lst_balance()),
+        ];
+        let before: HashMap<String, [(&'static str, f64); 4]> = subscription
+            .wallet_addresses
+            .iter()
+            .filter_map(|address| wallets_guard.get(address).map(|wallet| (address.clone(), balances(wallet))))
+            .collect();
+
+        let mut corrected_slots: HashMap<String, u64> = HashMap::new();
+        for (pubkey, slot, account) in accounts {
+            let Some(wallet_address) = subscription.wallet_for(&pubkey) else {
+                continue;
+            };
+            // 串流已套用比此次查詢更新的資料時不覆寫
+            if wallets_guard.get(&wallet_address).is_some_and(|wallet| wallet.last_slot > slot) {
+                continue;
+            }
+            // 帳戶不存在視為餘額 0 (例如 ATA 或 stake account 已關閉)
+            let (lamports, data) = match account {
+                Some(account) => (account.lamports, account.data),
+                None => (0, vec![0; TOKEN_AMOUNT_LEN]),
+            };
+            let account = AccountUpdate { pubkey, lamports, data, slot };
+            apply_account_update(&account, &mut wallets_guard, &subscription, &lst_tokens, &lst_rates, &writer, &notifier, &events);
+            corrected_slots.insert(wallet_address, slot);
+        }
+
+        let mut corrections = Vec::new();
+        for (address, old_balances) in before {
+            let Some(wallet) = wallets_guard.get(&address) else {
+                continue;
+            };
+            for ((data_type, streamed), (_, actual)) in old_balances.into_iter().zip(balances(wallet)) {
+                if (actual - streamed).abs() > 0.000001 {
+                    warn!("🔍 錢包 {} 的 {} 餘額與 RPC 不一致，已修正: {:.9} → {:.9}", &address[..8], data_type, streamed, actual);
+                    corrections.push(ReconciliationEvent {
+                        address: address.clone(),
+                        name: wallet.name.clone(),
+                        data_type,
+                        streamed,
+                        actual,
+                        slot: corrected_slots.get(&address).copied().unwrap_or_default(),
+                        timestamp: Utc::now(),
+                    });
+                }
             }
+        }
+        drop(wallets_guard);
+
+        if corrections.is_empty() {
+            debug!("✅ 餘額核對完成，{} 個錢包皆與 RPC 一致", subscription.wallet_addresses.len());
+        } else {
+            info!("🔍 餘額核對完成，修正 {} 筆不一致", corrections.len());
+        }
+        health.record_reconciliation(corrections);
+    }
+}
+
+// 未設定 Geyser 端點時，定期以 getMultipleAccounts 批次查詢所有訂閱帳戶，
+// 只有 lamports 或 data 改變的帳戶才送入與串流相同的處理流程
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(name = "rpc_polling", skip_all)]
+async fn rpc_polling_task(
+    rpc: SharedRpc,
+    poll_interval_secs: u64,
+    wallets: SharedWallets,
+    writer: DatabaseWriter,
+    notifier: Notifier,
+    lst_tokens: SharedLstTokens,
+    lst_rates: SharedLstRates,
+    events: WalletEvents,
+    health: SharedHealth,
+) {
+    let shard = GrpcShard { index: 0, count: 1 };
+    let mut last_seen: HashMap<String, (u64, Vec<u8>)> = HashMap::new();
+    let mut connected = false;
+    let mut interval = tokio::time::interval(Duration::from_secs(poll_interval_secs.max(1)));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    info!("🔁 未設定 gRPC 端點，每 {} 秒透過 RPC 輪詢錢包餘額", poll_interval_secs.max(1));
+
+    loop {
+        interval.tick().await;
+        // 每輪重新計算帳戶清單，錢包新增、刪除或封存後自動生效
+        let subscription = AccountSubscription::new(&wallets, &lst_tokens, CommitmentLevel::Confirmed, shard, false).await;
+        let accounts = match fetch_accounts(&rpc, subscription.addresses()).await {
+            Ok(accounts) => accounts,
             Err(e) => {
-                error!("❌ 獲取錢包 {} 的SOL和WSOL餘額失敗: {}", wallet.name, e);
-                // 設置為0以避免未初始化狀態
-                wallet.initialize_wsol(0.0);
+                warn!("⚠️ RPC 輪詢失敗: {}", e);
+                if connected {
+                    health.set_grpc_connected(false);
+                    connected = false;
+                }
+                continue;
             }
+        };
+        if !connected {
+            health.set_grpc_connected(true);
+            connected = true;
         }
-        
-        wallet.print_balance("RPC初始化");
-        
-        // 保存最新餘額記錄到資料庫
-        if wallet.wsol_initialized {
-            let current_record = WalletHistoryRecord::new(
-                wallet.address.clone(),
-                wallet.sol_balance,
-                wallet.wsol_balance,
-            );
-            if let Err(e) = save_wallet_history(db, &current_record) {
-                warn!("⚠️ 保存最新餘額記錄失敗 {}: {}", wallet.name, e);
+        health.mark_grpc_update();
+
+        let mut wallets_guard = wallets.write().await;
+        for (pubkey, slot, account) in accounts {
+            health.mark_slot(slot);
+            // 帳戶不存在 (尚未建立或已關閉的 ATA) 視為 lamports 0、data 為空，與串流收到的關閉更新相同
+            let state = account.map(|account| (account.lamports, account.data)).unwrap_or_default();
+            if last_seen.get(&pubkey) == Some(&state) {
+                continue;
             }
+            let account = AccountUpdate { pubkey: pubkey.clone(), lamports: state.0, data: state.1.clone(), slot };
+            apply_account_update(&account, &mut wallets_guard, &subscription, &lst_tokens, &lst_rates, &writer, &notifier, &events);
+            last_seen.insert(pubkey, state);
         }
     }
-    
-    info!("✅ 所有錢包的最新餘額獲取完成！(無需等待間隔)");
 }
 
+// 以 accountSubscribe 訂閱所有帳戶，並以 programSubscribe 發現錢包新建立的 stake account；
+// 錢包清單變更時重新連線訂閱
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(name = "ws_rpc_stream", skip_all, fields(endpoint = %ws_endpoint.split('?').next().unwrap_or_default()))]
+async fn create_ws_rpc_stream(
+    ws_endpoint: String,
+    wallets: SharedWallets,
+    writer: DatabaseWriter,
+    resubscribe: GrpcResubscribeSignal,
+    restart: GrpcRestartSignal,
+    notifier: Notifier,
+    lst_tokens: SharedLstTokens,
+    lst_rates: SharedLstRates,
+    events: WalletEvents,
+    health: SharedHealth,
+) {
+    let shard = GrpcShard { index: 0, count: 1 };
+    let commitment = solana_sdk::commitment_config::CommitmentConfig::confirmed();
+    // 與 Geyser 訂閱相同，只取 token account 的 amount 欄位
+    let account_config = RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        data_slice: Some(UiDataSliceConfig { offset: TOKEN_AMOUNT_OFFSET, length: TOKEN_AMOUNT_LEN }),
+        commitment: Some(commitment),
+        min_context_slot: None,
+    };
+    let to_update = |pubkey: String, account: UiAccount, slot: u64| AccountUpdate {
+        pubkey,
+        lamports: account.lamports,
+        data: account.data.decode().unwrap_or_default(),
+        slot,
+    };
+
+    loop {
+        info!("🔄 嘗試連接到 WebSocket RPC 端點: {}", ws_endpoint);
+        let client = match PubsubClient::new(&ws_endpoint).await {
+            Ok(client) => client,
+            Err(e) => {
+                error!("❌ 連接失敗: {}", e);
+                warn!("⏳ 10秒後重新連接...");
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                continue;
+            }
+        };
+        info!("✅ 成功連接到 WebSocket RPC 端點");
+
+        // 先取得信號接收端再建立訂閱，避免遺漏兩者之間的錢包變更
+        let mut resubscribe_rx = resubscribe.subscribe();
+        let mut restart_rx = restart.subscribe();
+        let mut subscription = AccountSubscription::new(&wallets, &lst_tokens, CommitmentLevel::Confirmed, shard, false).await;
+        let addresses = subscription.addresses();
 
+        let mut resubscribing = false;
+        {
+            // 每個通知附帶 (新 stake account 所屬錢包, 帳戶更新)
+            let mut streams: Vec<futures::stream::BoxStream<'_, (Option<String>, AccountUpdate)>> = Vec::new();
+            let mut subscribe_error = None;
+            for address in &addresses {
+                let pubkey = match Pubkey::from_str(address) {
+                    Ok(pubkey) => pubkey,
+                    Err(e) => {
+                        warn!("⚠️ 無效的地址 {}: {}", address, e);
+                        continue;
+                    }
+                };
+                match client.account_subscribe(&pubkey, Some(account_config.clone())).await {
+                    Ok((stream, _unsubscribe)) => {
+                        let address = address.clone();
+                        streams.push(Box::pin(stream.map(move |response| (None, to_update(address.clone(), response.value, response.context.slot)))));
+                    }
+                    Err(e) => {
+                        subscribe_error = Some(e);
+                        break;
+                    }
+                }
+            }
+            if subscribe_error.is_none() {
+                let stake_program = Pubkey::from_str(STAKE_PROGRAM_ID).expect("valid stake program id");
+                for wallet_address in &subscription.wallet_addresses {
+                    let Ok(owner) = Pubkey::from_str(wallet_address) else {
+                        continue;
+                    };
+                    let config = RpcProgramAccountsConfig {
+                        filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(STAKE_WITHDRAWER_OFFSET, &owner.to_bytes()))]),
+                        account_config: account_config.clone(),
+                        ..RpcProgramAccountsConfig::default()
+                    };
+                    match client.program_subscribe(&stake_program, Some(config)).await {
+                        Ok((stream, _unsubscribe)) => {
+                            let wallet_address = wallet_address.clone();
+                            streams.push(Box::pin(stream.map(move |response| {
+                                let keyed = response.value;
+                                (Some(wallet_address.clone()), to_update(keyed.pubkey, keyed.account, response.context.slot))
+                            })));
+                        }
+                        Err(e) => {
+                            subscribe_error = Some(e);
+                            break;
+                        }
+                    }
+                }
+            }
 
-// 處理 SOL Account 更新
-fn handle_sol_account_update(
-    update: SubscribeUpdate,
-    wallets: &mut HashMap<String, WalletBalance>,
-    wallet_addresses: &[String],
-    db: &Database,
-) -> Result<(), Box<dyn std::error::Error>> {
-    if let Some(UpdateOneof::Account(account_update)) = update.update_oneof {
-        if let Some(account) = account_update.account {
-            let wallet_address = bs58::encode(&account.pubkey).into_string();
-            
-            // 檢查是否是我們監聽的錢包地址
-            if wallet_addresses.contains(&wallet_address) {
-                if let Some(wallet) = wallets.get_mut(&wallet_address) {
-                    let old_balance = wallet.sol_balance;
-                    wallet.update_sol(account.lamports);
-                    
-                    if (wallet.sol_balance - old_balance).abs() > 0.000001 {
-                        info!("💰 錢包 {} SOL 餘額變化: {:.6} SOL (從 {:.6} 到 {:.6})", 
-                              &wallet_address[..8], 
-                              wallet.sol_balance - old_balance, 
-                              old_balance, 
-                              wallet.sol_balance);
-                        
-                        wallet.print_balance("SOL帳戶更新");
-                        
-                        // 保存到資料庫
-                        let record = WalletHistoryRecord::new(
-                            wallet.address.clone(),
-                            wallet.sol_balance,
-                            wallet.wsol_balance,
-                        );
-                        if let Err(e) = save_wallet_history(db, &record) {
-                            warn!("⚠️ 保存SOL帳戶更新記錄失敗 {}: {}", wallet.name, e);
+            if let Some(e) = subscribe_error {
+                error!("❌ 建立訂閱失敗: {}", e);
+            } else {
+                info!("✅ 已透過 WebSocket RPC 訂閱 {} 個帳戶", addresses.len());
+                let mut merged = futures::stream::select_all(streams);
+                health.set_grpc_connected(true);
+                loop {
+                    tokio::select! {
+                        changed = resubscribe_rx.changed() => {
+                            if changed.is_ok() {
+                                info!("🔄 錢包清單已變更，重新訂閱");
+                                resubscribing = true;
+                            }
+                            break;
+                        }
+                        changed = restart_rx.changed() => {
+                            if changed.is_ok() {
+                                info!("🔁 管理員要求重新連接 WebSocket RPC");
+                                resubscribing = true;
+                            }
+                            break;
+                        }
+                        // 沒有任何帳戶時 select_all 會立即結束，只等待錢包清單變更
+                        item = merged.next(), if !addresses.is_empty() => {
+                            let Some((stake_owner, account)) = item else {
+                                error!("❌ WebSocket RPC 訂閱已中斷");
+                                break;
+                            };
+                            health.mark_grpc_update();
+                            health.mark_slot(account.slot);
+                            // programSubscribe 發現的新 stake account 加入映射後照常處理
+                            if let Some(wallet_address) = stake_owner {
+                                if !subscription.stake_to_wallet_map.contains_key(&account.pubkey) {
+                                    info!("🥩 發現錢包 {} 新的 stake account {}", &wallet_address[..8], &account.pubkey[..8]);
+                                    subscription.stake_to_wallet_map.insert(account.pubkey.clone(), wallet_address);
+                                }
+                            }
+                            let mut wallets_guard = wallets.write().await;
+                            apply_account_update(&account, &mut wallets_guard, &subscription, &lst_tokens, &lst_rates, &writer, &notifier, &events);
                         }
                     }
                 }
+                health.set_grpc_connected(false);
             }
         }
+        if let Err(e) = client.shutdown().await {
+            warn!("⚠️ 關閉 WebSocket RPC 連線失敗: {}", e);
+        }
+
+        if !resubscribing {
+            warn!("⏳ 10秒後重新連接...");
+            tokio::time::sleep(Duration::from_secs(10)).await;
+        }
     }
-    Ok(())
 }
 
-// 配置文件操作函數
-async fn update_config_file(address: &str, name: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let config_content = fs::read_to_string("config.toml")?;
-    let mut lines: Vec<String> = config_content.lines().map(|s| s.to_string()).collect();
-    
-    // 添加新的錢包配置
-    lines.push(String::new());
-    lines.push("[[wallets]]".to_string());
-    lines.push(format!("address = \"{}\"", address));
-    lines.push(format!("name = \"{}\"", name));
-    
-    let updated_content = lines.join("\n");
-    fs::write("config.toml", updated_content)?;
-    
-    Ok(())
+// 串流連線期間計入 grpc_connected_streams，任務被中止時也會扣回
+struct GrpcConnectedGuard(SharedHealth);
+
+impl GrpcConnectedGuard {
+    fn new(health: SharedHealth) -> Self {
+        health.set_grpc_connected(true);
+        Self(health)
+    }
 }
 
-async fn remove_from_config_file(address: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let config_content = fs::read_to_string("config.toml")?;
-    let lines: Vec<&str> = config_content.lines().collect();
-    let mut new_lines: Vec<&str> = Vec::new();
-    let mut i = 0;
-    
-    while i < lines.len() {
-        let line = lines[i];
-        
-        // 檢查是否是錢包區塊的開始
-        if line.trim() == "[[wallets]]" {
-            // 查看下一行是否包含要刪除的地址
-            let mut wallet_lines = vec![line];
-            let mut j = i + 1;
-            let mut found_target = false;
-            
-            // 收集這個錢包區塊的所有行
-            while j < lines.len() {
-                let next_line = lines[j];
-                
-                // 如果遇到下一個區塊或文件結束，停止收集
-                if next_line.trim().starts_with("[") && next_line.trim() != "[[wallets]]" {
-                    break;
-                }
-                
-                // 如果遇到下一個錢包區塊，停止收集
-                if next_line.trim() == "[[wallets]]" {
-                    break;
-                }
-                
-                wallet_lines.push(next_line);
-                
-                // 檢查是否包含目標地址
-                if next_line.trim().starts_with("address = ") && next_line.contains(address) {
-                    found_target = true;
+impl Drop for GrpcConnectedGuard {
+    fn drop(&mut self) {
+        self.0.set_grpc_connected(false);
+    }
+}
+
+// 依目前未封存的錢包數決定分片數，錢包新增、封存或刪除後重新計算；沒有錢包的分片不建立串流
+#[allow(clippy::too_many_arguments)]
+async fn grpc_shard_supervisor(
+    grpc: GrpcConfig,
+    wallets: SharedWallets,
+    writer: DatabaseWriter,
+    resubscribe: GrpcResubscribeSignal,
+    restart: GrpcRestartSignal,
+    notifier: Notifier,
+    lst_tokens: SharedLstTokens,
+    lst_rates: SharedLstRates,
+    events: WalletEvents,
+    health: SharedHealth,
+) {
+    let endpoints: Vec<String> = std::iter::once(grpc.endpoint.clone()).chain(grpc.shard_endpoints.iter().cloned()).collect();
+    let mut resubscribe_rx = resubscribe.subscribe();
+    // JoinSet 被丟棄時 (服務關閉) 會一併中止所有分片串流
+    let mut streams = tokio::task::JoinSet::new();
+    let mut shards: HashMap<usize, tokio::task::AbortHandle> = HashMap::new();
+    let mut shard_count = 0;
+    loop {
+        let active: Vec<String> = wallets.read().await.values().filter(|w| !w.archived).map(|w| w.address.clone()).collect();
+        let count = match grpc.max_wallets_per_stream {
+            0 => 1,
+            max => active.len().div_ceil(max).max(1),
+        };
+        // 分片數改變時錢包的分配全部重算，所有串流重新建立
+        if count != shard_count {
+            for (_, stream) in shards.drain() {
+                stream.abort();
+            }
+            if count > 1 {
+                info!("🧩 {} 個錢包分散到 {} 條 gRPC 訂閱串流 ({} 個端點)", active.len(), count, endpoints.len().min(count));
+            } else if shard_count > 1 {
+                info!("🧩 錢包數已減少，改回單一 gRPC 訂閱串流");
+            }
+            shard_count = count;
+        }
+        // 雜湊分配可能讓部分分片沒有錢包，只為有錢包的分片建立串流；完全沒有錢包時保留一條串流追蹤 slot
+        let mut occupied: BTreeSet<usize> = (0..count)
+            .filter(|&index| active.iter().any(|address| GrpcShard { index, count }.contains(address)))
+            .collect();
+        if occupied.is_empty() {
+            occupied.insert(0);
+        }
+        // 無法建立客戶端而結束的串流重新建立
+        shards.retain(|_, stream| !stream.is_finished());
+        shards.retain(|index, stream| {
+            let keep = occupied.contains(index);
+            if !keep {
+                info!("🧩 分片 {}/{} 已沒有錢包，停止訂閱串流", index + 1, count);
+                stream.abort();
+            }
+            keep
+        });
+        for index in occupied {
+            if shards.contains_key(&index) {
+                continue;
+            }
+            let shard = GrpcShard { index, count };
+            let mut grpc_config = grpc.clone();
+            grpc_config.endpoint = endpoints[index % endpoints.len()].clone();
+            let stream_wallets = wallets.clone();
+            let stream_writer = writer.clone();
+            let stream_resubscribe = resubscribe.clone();
+            let stream_restart = restart.clone();
+            let stream_notifier = notifier.clone();
+            let stream_lst_tokens = lst_tokens.clone();
+            let stream_lst_rates = lst_rates.clone();
+            let stream_events = events.clone();
+            let stream_health = health.clone();
+            let stream = streams.spawn(async move {
+                if let Err(e) = create_grpc_stream(grpc_config, shard, stream_wallets, stream_writer, stream_resubscribe, stream_restart, stream_notifier, stream_lst_tokens, stream_lst_rates, stream_events, stream_health).await {
+                    error!("❌ gRPC 流任務失敗{}: {}", shard, e);
                 }
-                
-                j += 1;
-                
-                // 如果遇到空行且已經有了 name，這個錢包區塊結束
-                if next_line.trim().is_empty() && wallet_lines.iter().any(|l| l.trim().starts_with("name = ")) {
+            });
+            shards.insert(index, stream);
+        }
+        health.grpc_streams.store(shards.len(), std::sync::atomic::Ordering::Relaxed);
+
+        loop {
+            tokio::select! {
+                changed = resubscribe_rx.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
                     break;
                 }
+                // 回收已結束 (中止) 的分片任務
+                Some(_) = streams.join_next() => {}
             }
-            
-            // 如果不是目標錢包，保留這個區塊
-            if !found_target {
-                for wallet_line in wallet_lines {
-                    new_lines.push(wallet_line);
-                }
-            }
-            
-            // 跳過已處理的行
-            i = j;
-        } else {
-            // 保留非錢包區塊的行
-            new_lines.push(line);
-            i += 1;
         }
     }
-    
-    let updated_content = new_lines.join("\n");
-    fs::write("config.toml", updated_content)?;
-    
-    Ok(())
 }
 
 // 創建gRPC流
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(name = "grpc_stream", skip_all, fields(endpoint = %grpc_config.endpoint, shard = %shard))]
 async fn create_grpc_stream(
-    grpc_endpoint: String,
+    grpc_config: GrpcConfig,
+    shard: GrpcShard,
     wallets: SharedWallets,
-    db: SharedDatabase,
-    restart_signal: GrpcRestartSignal,
+    writer: DatabaseWriter,
+    resubscribe: GrpcResubscribeSignal,
+    restart: GrpcRestartSignal,
+    notifier: Notifier,
+    lst_tokens: SharedLstTokens,
+    lst_rates: SharedLstRates,
+    events: WalletEvents,
+    health: SharedHealth,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    // 每個帳戶最後套用的 (slot, write_version)，跨重連保留
+    let mut account_versions: HashMap<Vec<u8>, (u64, u64)> = HashMap::new();
+    // 最近處理過的區塊 slot，跨重連保留
+    let mut recent_block_slots: BTreeSet<u64> = BTreeSet::new();
+    // 最後處理的 slot，重連時作為 from_slot；啟動時讀取上次保存的值
+    let mut resume_slot = grpc_config.slot_state_path.as_deref().and_then(read_slot_state);
     loop {
-        info!("🔄 嘗試連接到 gRPC 端點: {}", grpc_endpoint);
+        info!("🔄 嘗試連接到 gRPC 端點: {}{}", grpc_config.endpoint, shard);
+        let mut restarting = false;
         
-        match GeyserGrpcClient::build_from_shared(grpc_endpoint.clone()) {
+        match build_geyser_client(&grpc_config) {
             Ok(client_builder) => {
                 match client_builder.connect().await {
                     Ok(mut client) => {
-                        info!("✅ 成功連接到 gRPC 伺服器");
+                        info!("✅ 成功連接到 gRPC 伺服器{}", shard);
                         
-                        let wallet_addresses: Vec<String> = {
-                            let wallets_guard = wallets.lock().unwrap();
-                            wallets_guard.keys().cloned().collect()
-                        };
+                        // 先取得信號接收端再建立訂閱，避免遺漏兩者之間的錢包變更
+                        let mut resubscribe_rx = resubscribe.subscribe();
+                        let mut restart_rx = restart.subscribe();
+                        let mut subscription = AccountSubscription::new(&wallets, &lst_tokens, grpc_config.commitment.into(), shard, grpc_config.owner_token_accounts).await;
                         
-                        info!("📋 準備訂閱 {} 個錢包:", wallet_addresses.len());
-                        for (i, address) in wallet_addresses.iter().enumerate() {
-                            let wallets_guard = wallets.lock().unwrap();
+                        info!("📋 準備訂閱 {} 個錢包:", subscription.wallet_addresses.len());
+                        for (i, address) in subscription.wallet_addresses.iter().enumerate() {
+                            let wallets_guard = wallets.read().await;
                             if let Some(wallet) = wallets_guard.get(address) {
                                 info!("   {}: {} ({})", i + 1, wallet.name, &address[..8]);
                             } else {
                                 info!("   {}: 未知錢包 ({})", i + 1, &address[..8]);
                             }
                         }
-                        
-                        // 計算所有錢包的 WSOL ATA 地址
-                        let ata_addresses = calculate_all_wsol_atas(&wallet_addresses);
-                        
-                        // 創建 ATA 到錢包地址的映射
-                        let mut ata_to_wallet_map: HashMap<String, String> = HashMap::new();
-                        for (wallet_addr, ata_addr) in wallet_addresses.iter().zip(ata_addresses.iter()) {
-                            ata_to_wallet_map.insert(ata_addr.clone(), wallet_addr.clone());
+                        info!("💎 準備監聽 {} 個 WSOL ATA 地址", subscription.ata_to_wallet_map.len());
+                        info!("🥩 準備監聽 {} 個 stake account", subscription.stake_to_wallet_map.len());
+                        if !subscription.token_owners.is_empty() {
+                            info!("🪙 以 owner 過濾監聽 {} 個錢包的 token account (已知 {} 個)", subscription.token_owners.len(), subscription.token_account_map.len());
                         }
-                        
-                        info!("💎 準備監聽 {} 個 WSOL ATA 地址", ata_addresses.len());
-                        
-                        let mut accounts_filter = HashMap::new();
-                        accounts_filter.insert(
-                            "wallet_accounts".to_string(),
-                            SubscribeRequestFilterAccounts {
-                                account: wallet_addresses.clone(),
-                                owner: vec![],
-                                filters: vec![],
-                                nonempty_txn_signature: None,
-                            },
-                        );
-                        
-                        // 監聽 WSOL ATA 地址
-                        accounts_filter.insert(
-                            "wsol_ata_accounts".to_string(),
-                            SubscribeRequestFilterAccounts {
-                                account: ata_addresses.clone(),
-                                owner: vec![],
-                                filters: vec![],
-                                nonempty_txn_signature: None,
-                            },
-                        );
-
-                        let request = SubscribeRequest {
-                            accounts: accounts_filter,
-                            slots: HashMap::new(),
-                            transactions: HashMap::new(), // 不再監聽交易
-                            transactions_status: HashMap::new(),
-                            blocks: HashMap::new(),
-                            blocks_meta: HashMap::new(),
-                            entry: HashMap::new(),
-                            commitment: Some(CommitmentLevel::Confirmed as i32),
-                            accounts_data_slice: vec![],
-                            ping: None,
-                            from_slot: None,
-                        };
 
                         match client.subscribe().await {
                             Ok((mut subscribe_tx, mut subscribe_rx)) => {
+                                let mut request = subscription.request();
+                                if grpc_config.replay_from_slot {
+                                    request.from_slot = resume_slot;
+                                }
+                                let replaying = request.from_slot.is_some();
                                 if let Err(e) = subscribe_tx.send(request).await {
                                     error!("❌ 發送訂閱請求失敗: {}", e);
                                     continue;
                                 }
+                                if let Some(slot) = resume_slot.filter(|_| replaying) {
+                                    info!("⏪ 從 slot {} 補回斷線期間的更新{}", slot, shard);
+                                }
                                 
                                 info!("✅ gRPC 訂閱請求發送成功！");
-                                info!("🎯 開始監聽 {} 個錢包的變化...", wallet_addresses.len());
+                                info!("🎯 開始監聽 {} 個錢包的變化...{}", subscription.wallet_addresses.len(), shard);
+                                // 分片數變更時串流任務會被中止，連線計數由 guard 在任務結束時扣回
+                                let connected = GrpcConnectedGuard::new(health.clone());
                                 
                                 let mut first_message_received = false;
+                                // 半斷線的 TCP 連線不會回報錯誤，定期 ping 並在長時間沒有任何訊息時強制重連
+                                let ping_period = Duration::from_secs(grpc_config.ping_interval_secs.max(1));
+                                let mut ping_timer = tokio::time::interval_at(tokio::time::Instant::now() + ping_period, ping_period);
+                                let stale_timeout = Duration::from_secs(grpc_config.stale_timeout_secs);
+                                let mut last_message_at = tokio::time::Instant::now();
                                 
-                                while let Some(message) = subscribe_rx.next().await {
-                                    // 檢查是否需要重啟
-                                    {
-                                        let mut signal = restart_signal.lock().unwrap();
-                                        if *signal {
-                                            *signal = false; // 重置信號
-                                            info!("🔄 收到重啟信號，正在重新建立gRPC訂閱...");
-                                            break; // 跳出內層循環，重新建立連接
+                                loop {
+                                    // 錢包清單變更時立即在同一條串流送出新的訂閱請求，串流沒有流量時也不會延遲
+                                    let message = tokio::select! {
+                                        _ = resubscribe_rx.changed() => {
+                                            subscription = AccountSubscription::new(&wallets, &lst_tokens, grpc_config.commitment.into(), shard, grpc_config.owner_token_accounts).await;
+                                            // 最後一個錢包被封存或刪除時，請求中不含任何帳戶與交易過濾條件，等同取消訂閱
+                                            if let Err(e) = subscribe_tx.send(subscription.request()).await {
+                                                error!("❌ 更新訂閱請求失敗: {}", e);
+                                                break;
+                                            }
+                                            if subscription.addresses().is_empty() {
+                                                info!("⏸️ 沒有需要監聽的錢包，已清除 gRPC 訂閱條件{}", shard);
+                                            } else {
+                                                info!("🔄 已更新 gRPC 訂閱，監聽 {} 個錢包{}", subscription.wallet_addresses.len(), shard);
+                                            }
+                                            continue;
                                         }
-                                    }
+                                        _ = restart_rx.changed() => {
+                                            info!("🔁 管理員要求重新連接 gRPC{}", shard);
+                                            restarting = true;
+                                            break;
+                                        }
+                                        _ = ping_timer.tick(), if grpc_config.ping_interval_secs > 0 => {
+                                            if let Err(e) = subscribe_tx.send(ping_request()).await {
+                                                error!("❌ 發送 gRPC ping 失敗: {}", e);
+                                                break;
+                                            }
+                                            continue;
+                                        }
+                                        _ = tokio::time::sleep_until(last_message_at + stale_timeout), if grpc_config.stale_timeout_secs > 0 => {
+                                            warn!("⚠️ {} 秒未收到任何 gRPC 訊息，串流可能已中斷，強制重新連接", grpc_config.stale_timeout_secs);
+                                            health.record_grpc_stale_reconnect();
+                                            break;
+                                        }
+                                        message = subscribe_rx.next() => message,
+                                    };
+                                    let Some(message) = message else {
+                                        break;
+                                    };
                                     
                                     match message {
                                         Ok(update) => {
+                                            last_message_at = tokio::time::Instant::now();
+                                            health.mark_grpc_update();
+                                            // 回應伺服器的 ping，部分供應商會關閉沒有回應的串流
+                                            if let Some(UpdateOneof::Ping(_)) = &update.update_oneof {
+                                                if let Err(e) = subscribe_tx.send(ping_request()).await {
+                                                    error!("❌ 回應 gRPC ping 失敗: {}", e);
+                                                    break;
+                                                }
+                                            }
                                             if !first_message_received {
                                                 info!("🎉 成功接收到第一個gRPC消息，訂閱正常工作！");
                                                 first_message_received = true;
                                             }
+                                            // 重連後可能收到重複或亂序的帳戶更新，只套用比上次更新的版本
+                                            if let Some(UpdateOneof::Account(account_update)) = &update.update_oneof {
+                                                if let Some(account) = &account_update.account {
+                                                    let version = (account_update.slot, account.write_version);
+                                                    match account_versions.get(&account.pubkey) {
+                                                        Some(last) if *last >= version => {
+                                                            debug!("⏭️ 略過重複或較舊的帳戶更新 (slot {}, write_version {})", version.0, version.1);
+                                                            continue;
+                                                        }
+                                                        _ => {
+                                                            account_versions.insert(account.pubkey.clone(), version);
+                                                        }
+                                                    }
+                                                }
+                                            }
+                                            // 重連補回時可能重複收到同一區塊，出塊獎勵只記錄一次
+                                            if let Some(UpdateOneof::BlockMeta(block_meta)) = &update.update_oneof {
+                                                if recent_block_slots.insert(block_meta.slot) {
+                                                    while recent_block_slots.len() > RECENT_BLOCK_SLOTS {
+                                                        recent_block_slots.pop_first();
+                                                    }
+                                                    handle_block_meta_update(block_meta, &subscription.validator_identities, &writer);
+                                                }
+                                                continue;
+                                            }
                                             {
-                                                let mut wallets_guard = wallets.lock().unwrap();
+                                                let mut wallets_guard = wallets.write().await;
                                                 
-                                                // 只處理 Account 更新（SOL 和 WSOL）
-                                                if let Some(UpdateOneof::Account(_)) = &update.update_oneof {
-                                                    // 處理 SOL 帳戶更新
-                                                    if let Err(e) = handle_sol_account_update(update.clone(), &mut wallets_guard, &wallet_addresses, &db) {
-                                                        warn!("⚠️ 處理SOL帳戶更新時出錯: {}", e);
+                                                // 處理交易更新
+                                                if let Some(UpdateOneof::Transaction(_)) = &update.update_oneof {
+                                                    if let Err(e) = handle_transaction_update(update.clone(), &subscription, &wallets_guard, &writer, &notifier) {
+                                                        warn!("⚠️ 處理交易更新時出錯: {}", e);
                                                     }
-                                                    // 處理 WSOL ATA 帳戶更新
-                                                    if let Err(e) = handle_wsol_account_update(update, &mut wallets_guard, &ata_to_wallet_map, &db) {
-                                                        warn!("⚠️ 處理WSOL帳戶更新時出錯: {}", e);
+                                                }
+
+                                                // 處理 Account 更新（SOL、WSOL、Stake、LST）
+                                                if let Some(UpdateOneof::Slot(slot_update)) = &update.update_oneof {
+                                                    health.mark_slot(slot_update.slot);
+                                                    resume_slot = resume_slot.max(Some(slot_update.slot));
+                                                }
+
+                                                if let Some(UpdateOneof::Account(account_update)) = &update.update_oneof {
+                                                    let slot = account_update.slot;
+                                                    health.mark_slot(slot);
+                                                    resume_slot = resume_slot.max(Some(slot));
+                                                    if let Some(account) = &account_update.account {
+                                                        let account = AccountUpdate {
+                                                            pubkey: bs58::encode(&account.pubkey).into_string(),
+                                                            lamports: account.lamports,
+                                                            data: account.data.clone(),
+                                                            slot,
+                                                        };
+                                                        apply_account_update(&account, &mut wallets_guard, &subscription, &lst_tokens, &lst_rates, &writer, &notifier, &events);
                                                     }
                                                 }
                                             }
+                                            // 新的 token account 加入已知清單，之後關閉時才能依地址收到更新；只更新所屬分片的訂閱
+                                            if subscription.new_token_accounts.swap(false, std::sync::atomic::Ordering::Relaxed) {
+                                                subscription = AccountSubscription::new(&wallets, &lst_tokens, grpc_config.commitment.into(), shard, grpc_config.owner_token_accounts).await;
+                                                if let Err(e) = subscribe_tx.send(subscription.request()).await {
+                                                    error!("❌ 更新訂閱請求失敗: {}", e);
+                                                    break;
+                                                }
+                                                info!("🪙 已將新的 token account 加入訂閱{}", shard);
+                                            }
                                         }
                                         Err(e) => {
                                             error!("❌ gRPC 流錯誤: {}", e);
+                                            // 端點不支援 from_slot 或該 slot 已不可用，下次改為不補回直接訂閱
+                                            if replaying && !first_message_received {
+                                                warn!("⚠️ 以 from_slot 訂閱失敗，下次重連將不補回歷史更新");
+                                                resume_slot = None;
+                                            }
                                             break;
                                         }
                                     }
                                 }
+                                drop(connected);
                             }
                             Err(e) => {
                                 error!("❌ 建立訂閱失敗: {}", e);
@@ -1339,101 +14328,571 @@ async fn create_grpc_stream(
             }
         }
         
-        warn!("⏳ 10秒後重新連接...");
-        tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
+        if !restarting {
+            warn!("⏳ 10秒後重新連接...");
+            tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
+        }
     }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // 載入配置
-    let config = load_config()?;
-    setup_logging(&config.logging.level);
+    let cli = <cli::Cli as clap::Parser>::parse();
+    let _ = CONFIG_PATH.set(cli.config.clone());
+    cli::run(cli).await
+}
+
+async fn serve(config: Config) -> Result<(), Box<dyn std::error::Error>> {
+    let log_handle = setup_logging(&config.logging);
     
     info!("🚀 SOL錢包監控器啟動");
     info!("📊 監控 {} 個錢包", config.wallets.len());
     
+    // SQLite 後端沒有分層彙總表，降採樣設定不會生效，啟動時直接拒絕
+    if config.database.backend == "sqlite" && config.retention.enabled {
+        let message = "SQLite 後端不支援 [retention] 降採樣，請改用 [database] retention_days / max_history_per_wallet 並將 [retention] enabled 設為 false";
+        error!("❌ {}", message);
+        return Err(message.into());
+    }
+
     // 初始化資料庫
-    let database = match initialize_database() {
-        Ok(db) => Arc::new(db),
+    let database = match open_history_store(&config.database) {
+        Ok(db) => db,
         Err(e) => {
             error!("❌ 資料庫初始化失敗: {}", e);
             return Err(e);
         }
     };
 
-    // 載入歷史資料
-    let history_data = match load_all_wallet_history(&database) {
-        Ok(data) => {
-            info!("📖 成功載入歷史資料，包含 {} 個錢包的記錄", data.len());
-            data
-        }
-        Err(e) => {
-            warn!("⚠️ 載入歷史資料失敗: {}，將從空白開始", e);
-            HashMap::new()
-        }
-    };
-
     // 初始化錢包追蹤器
     let mut wallets_map = HashMap::new();
     for wallet_config in &config.wallets {
-        let mut wallet = WalletBalance::new(wallet_config.address.clone(), wallet_config.name.clone());
-        
-        // 從資料庫載入歷史數據（但不使用WSOL餘額，因為可能過時）
-        if let Some(records) = history_data.get(&wallet_config.address) {
-            info!("📚 為錢包 {} 載入 {} 條歷史記錄", wallet.name, records.len());
-            wallet.load_history_from_db(records.clone());
+        // 無效地址會讓整個 gRPC 訂閱失敗，啟動時直接略過
+        if let Err(code) = check_config_wallet_address(wallet_config) {
+            error!("❌ 錢包 {} 的地址 {} 無效 ({})，略過", wallet_config.name, wallet_config.address, code.message(Language::Zh));
+            continue;
         }
-        
-        wallets_map.insert(wallet_config.address.clone(), wallet);
+        wallets_map.insert(wallet_config.address.clone(), wallet_from_config(wallet_config, config.history, &database));
     }
     
+    let rpc: SharedRpc = Arc::new(RpcPool::new(&config.rpc));
+    if rpc.endpoints.len() > 1 {
+        info!("🔀 使用 {} 個 RPC 端點輪替", rpc.endpoints.len());
+    }
+
+    // 解析 LST mint 與初始匯率
+    let (lst_tokens, lst_rates) = if config.lst.enabled {
+        resolve_lst_tokens(&rpc).await
+    } else {
+        (Vec::new(), HashMap::new())
+    };
+
     // 所有錢包都需要從RPC獲取最新的SOL和WSOL餘額，確保數據準確性
+    let exporter = config.export.as_ref().map(HistoryExporter::start).transpose()?;
+    let database_writer = DatabaseWriter::start(database.clone(), exporter);
     info!("🔄 正在從RPC獲取所有錢包的最新餘額...");
-    initialize_wallets_from_rpc(&mut wallets_map, &database, &config.rpc.endpoint).await;
+    initialize_wallets_from_rpc(&mut wallets_map, &database_writer, &rpc, &lst_tokens, &lst_rates, config.owner_token_accounts()).await;
     
-    let shared_wallets = Arc::new(Mutex::new(wallets_map));
-    let grpc_restart_signal = Arc::new(Mutex::new(false));
+    let shared_wallets = Arc::new(tokio::sync::RwLock::new(wallets_map));
+    let grpc_resubscribe: GrpcResubscribeSignal = Arc::new(tokio::sync::watch::Sender::new(()));
+    let grpc_restart: GrpcRestartSignal = Arc::new(tokio::sync::watch::Sender::new(()));
+    let lst_tokens: SharedLstTokens = Arc::new(lst_tokens);
+    let lst_rates: SharedLstRates = Arc::new(Mutex::new(lst_rates));
     
+    // 收到停止信號後通知所有服務
+    let (shutdown_sender, shutdown) = tokio::sync::watch::channel(false);
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        info!("🛑 收到停止信號，正在關閉...");
+        shutdown_sender.send_replace(true);
+    });
+
     // 創建應用狀態
+    let events = WalletEvents::new();
+    let health: SharedHealth = Arc::new(HealthState::new());
+    // 以資料庫中最後一筆價格作為初始值，價格來源暫時無法連線時 PnL 仍可計算
+    let price: SharedPrice = Arc::new(std::sync::RwLock::new(match database.latest_price() {
+        Ok(point) => point,
+        Err(e) => {
+            warn!("⚠️ 讀取價格歷史失敗: {}", e);
+            None
+        }
+    }));
+
+    let validators: SharedValidators = Arc::default();
+
+    config.warn_unsupported_notifications();
+    let grpc_notifier = Notifier::start(&config.notifications, database.clone(), database_writer.clone());
     let app_state = AppState {
         wallets: shared_wallets.clone(),
         database: database.clone(),
-        grpc_restart_signal: grpc_restart_signal.clone(),
+        database_writer: database_writer.clone(),
+        grpc_resubscribe: grpc_resubscribe.clone(),
+        grpc_restart: grpc_restart.clone(),
+        lst_tokens: lst_tokens.clone(),
+        lst_rates: lst_rates.clone(),
+        events: events.clone(),
+        health: health.clone(),
+        rpc: rpc.clone(),
+        websocket_slots: Arc::new(tokio::sync::Semaphore::new(match config.websocket.max_connections {
+            0 => tokio::sync::Semaphore::MAX_PERMITS,
+            max => max,
+        })),
+        shutdown: shutdown.clone(),
+        backfills: Arc::default(),
+        notifier: grpc_notifier.clone(),
+        unsaved_wallets: Arc::default(),
+        history_flush_started: Arc::default(),
+        price: price.clone(),
+        validators: validators.clone(),
         config: config.clone(),
     };
     
+    // API 路由：/api/v1 使用統一回應格式，/api 保留為舊版別名
+    let rate_limiter = Arc::new(RateLimiter::new(config.rate_limit.clone()));
+    // 管理端點另外要求完整權杖，唯讀權杖與 protect_reads = false 都不能讀取
+    let admin = Router::new()
+        .route("/admin/backup", post(create_backup))
+        .route("/admin/backups", get(get_backups))
+        .route("/admin/db", get(get_database_stats))
+        .route("/admin/db/compact", post(compact_database))
+        .route("/admin/grpc/restart", post(restart_grpc_stream))
+        .route("/admin/audit", get(get_audit_log))
+        .route("/alerts/history", get(get_alert_history))
+        .route("/admin/restore", post(restore_backup).layer(DefaultBodyLimit::max(512 * 1024 * 1024)))
+        .layer(axum::middleware::from_fn_with_state(config.auth.clone(), require_admin_token));
+    let api = Router::new()
+        .route("/wallets", get(get_wallets).post(add_wallet))
+        .route("/wallets/export", get(export_wallets))
+        .route("/wallets/:address", get(get_wallet_detail).patch(update_wallet).delete(delete_wallet))
+        .route("/wallets/:address/transactions", get(get_wallet_transactions))
+        .route("/wallets/:address/token-events", get(get_wallet_token_events))
+        .route("/wallets/:address/token-history", get(get_wallet_token_history))
+        .route("/wallets/:address/flows", get(get_wallet_flows))
+        .route("/wallets/:address/stats", get(get_wallet_stats))
+        .route("/wallets/:address/history.csv", get(export_wallet_history_csv))
+        .route("/wallets/:address/ledger.csv", get(export_wallet_ledger_csv))
+        .route("/wallets/:address/history", delete(delete_wallet_history_handler))
+        .route("/wallets/:address/refresh", post(refresh_wallet))
+        .route("/wallets/:address/backfill", get(get_backfill_status).post(backfill_wallet))
+        .route("/wallets/:address/cost-basis", get(get_cost_basis).put(set_cost_basis).delete(delete_cost_basis))
+        .route("/wallets/:address/pnl", get(get_wallet_pnl))
+        .route("/wallets/:address/rewards", get(get_wallet_rewards))
+        .route("/wallets/:address/validator", get(get_validator_status))
+        .route("/epochs", get(get_epochs))
+        .route("/price", get(get_price))
+        .route("/portfolio", get(get_portfolio))
+        .route("/portfolio/breakdown", get(get_portfolio_breakdown))
+        .route("/groups", get(get_groups))
+        .route("/groups/:name/chart", get(get_group_chart_data))
+        .route("/chart", get(get_chart_data))
+        .route("/candles", get(get_candles))
+        .route("/history", get(get_history))
+        .route("/wallets/profits", get(get_wallets_profits))
+        .route("/reports/daily", get(get_daily_report))
+        .route("/alerts/:id/ack", post(acknowledge_alert))
+        .merge(admin)
+        .layer(axum::middleware::from_fn_with_state(config.auth.clone(), require_api_token))
+        .layer(axum::middleware::from_fn_with_state(rate_limiter.clone(), rate_limit_requests))
+        .layer(axum::middleware::from_fn_with_state(config.logging.language, localize_errors));
+    // GraphQL：GET 開啟 GraphiQL、POST 執行查詢，訂閱走 /graphql/ws
+    let schema = graphql::build_schema(app_state.clone());
+    let graphql_routes = Router::new()
+        .route("/", get(graphql::graphiql).post(graphql::graphql_handler))
+        .layer(axum::middleware::from_fn_with_state(config.auth.clone(), require_read_token))
+        .merge(
+            Router::new()
+                .route_service("/ws", async_graphql_axum::GraphQLSubscription::new(schema.clone()))
+                .layer(axum::middleware::from_fn_with_state(config.auth.clone(), require_ws_token)),
+        )
+        .with_state(schema)
+        .layer(axum::middleware::from_fn_with_state(rate_limiter.clone(), rate_limit_requests));
+    if config.auth.enabled() {
+        info!("🔐 API 權杖驗證已啟用 ({} 個完整權杖, {} 個唯讀權杖)", config.auth.tokens.len(), config.auth.read_tokens.len());
+    }
+    if rate_limiter.enabled() {
+        info!("🚦 API 速率限制已啟用 (每個 IP {} 次/秒, 突發 {} 次)", config.rate_limit.requests_per_second, config.rate_limit.burst);
+    }
+
     // 創建Web應用
     let app = Router::new()
         .route("/", get(serve_index))
-        .route("/api/wallets", get(get_wallets).post(add_wallet))
-        .route("/api/wallets/:address", get(get_wallet_detail).delete(delete_wallet))
-        .route("/api/chart", get(get_chart_data))
-        .route("/api/wallets/profits", get(get_wallets_profits))
-        .route("/ws", get(websocket_handler))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .nest("/api/v1", api.clone().layer(axum::middleware::from_fn(api_v1_envelope)))
+        .nest("/api", api.layer(axum::middleware::from_fn(deprecated_api_alias)))
+        .nest("/graphql", graphql_routes)
+        .route(
+            "/ws",
+            get(websocket_handler)
+                .layer(axum::middleware::from_fn_with_state(config.auth.clone(), require_ws_token))
+                .layer(axum::middleware::from_fn_with_state(rate_limiter.clone(), rate_limit_requests))
+                .layer(axum::middleware::from_fn_with_state(config.logging.language, localize_errors)),
+        )
         .layer(CorsLayer::permissive())
-        .with_state(app_state);
+        .layer(axum::middleware::from_fn_with_state(RequestLogConfig::from_logging(&config.logging), log_requests))
+        .with_state(app_state.clone());
     
-    // 啟動背景任務
-    let grpc_wallets = shared_wallets.clone();
-    let grpc_database = database.clone();
-    let grpc_signal = grpc_restart_signal.clone();
-    let grpc_endpoint = config.grpc.endpoint.clone();
-    tokio::spawn(async move {
-        if let Err(e) = create_grpc_stream(grpc_endpoint, grpc_wallets, grpc_database, grpc_signal).await {
-            error!("❌ gRPC 流任務失敗: {}", e);
+    // 啟動背景任務；會修改錢包狀態的任務在關閉時先停止，再寫入最終狀態
+    let mut balance_tasks = Vec::new();
+    if config.watch_config {
+        tokio::spawn(config_reload_task(app_state.clone(), grpc_notifier.clone(), log_handle));
+    }
+    // 告警規則可熱重載，無活動檢查任務一律啟動，沒有 stale_wallet 規則時不做任何事
+    tokio::spawn(stale_wallet_task(shared_wallets.clone(), grpc_notifier.clone()));
+    tokio::spawn(validator_task(shared_wallets.clone(), rpc.clone(), grpc_notifier.clone(), validators.clone()));
+    if let Some(price_config) = &config.price {
+        info!("💲 SOL 價格來源已啟用 ({}，每 {} 秒)", price_config.url, price_config.interval_secs);
+        tokio::spawn(price_feed_task(database.clone(), price.clone(), price_config.clone()));
+        if let Some(fx_config) = &price_config.fx {
+            info!("💱 匯率來源已啟用 ({}，{}，每 {} 秒)", fx_config.url, fx_config.currencies.join("/"), fx_config.interval_secs);
+            tokio::spawn(fx_rates_task(database.clone(), fx_config.clone()));
         }
-    });
+    }
+    tokio::spawn(epoch_rewards_task(shared_wallets.clone(), database.clone(), rpc.clone()));
+    if config.reports.daily {
+        tokio::spawn(daily_report_task(shared_wallets.clone(), database.clone(), grpc_notifier.clone(), config.reports.clone(), config.price.clone()));
+    }
+    // 串流模式下定期以 RPC 核對餘額；輪詢模式本身即為 RPC 查詢，不需另外核對
+    let streaming = config.transport == Transport::WsRpc || config.grpc.is_some();
+    if streaming && config.reconciliation.interval_minutes > 0 {
+        balance_tasks.push(tokio::spawn(reconciliation_task(
+            rpc.clone(),
+            config.reconciliation.interval_minutes,
+            shared_wallets.clone(),
+            database_writer.clone(),
+            grpc_notifier.clone(),
+            lst_tokens.clone(),
+            lst_rates.clone(),
+            events.clone(),
+            health.clone(),
+            config.owner_token_accounts(),
+        )));
+    }
+    match (config.transport, config.grpc.clone()) {
+        (Transport::WsRpc, _) => {
+            health.grpc_streams.store(1, std::sync::atomic::Ordering::Relaxed);
+            balance_tasks.push(tokio::spawn(create_ws_rpc_stream(
+                config.rpc.websocket_endpoint(),
+                shared_wallets.clone(),
+                database_writer.clone(),
+                grpc_resubscribe.clone(),
+                grpc_restart.clone(),
+                grpc_notifier,
+                lst_tokens.clone(),
+                lst_rates.clone(),
+                events.clone(),
+                health.clone(),
+            )));
+        }
+        (Transport::Geyser, Some(grpc)) => {
+            // 錢包數超過 max_wallets_per_stream 時分散到多條串流，並輪流使用 shard_endpoints
+            balance_tasks.push(tokio::spawn(grpc_shard_supervisor(
+                grpc.clone(),
+                shared_wallets.clone(),
+                database_writer.clone(),
+                grpc_resubscribe.clone(),
+                grpc_restart.clone(),
+                grpc_notifier,
+                lst_tokens.clone(),
+                lst_rates.clone(),
+                events.clone(),
+                health.clone(),
+            )));
+
+            if let Some(path) = grpc.slot_state_path.clone() {
+                tokio::spawn(slot_state_task(path, health.clone()));
+            }
+        }
+        (Transport::Geyser, None) => {
+            // 沒有 Geyser 端點時以 RPC 輪詢取代串流
+            health.grpc_streams.store(1, std::sync::atomic::Ordering::Relaxed);
+            balance_tasks.push(tokio::spawn(rpc_polling_task(
+                rpc.clone(),
+                config.rpc.poll_interval_secs,
+                shared_wallets.clone(),
+                database_writer.clone(),
+                grpc_notifier,
+                lst_tokens.clone(),
+                lst_rates.clone(),
+                events.clone(),
+                health.clone(),
+            )));
+        }
+    }
+
+    // 定期更新 LST 匯率
+    if !lst_tokens.is_empty() {
+        balance_tasks.push(tokio::spawn(lst_rate_refresh_task(
+            shared_wallets.clone(),
+            lst_tokens.clone(),
+            lst_rates.clone(),
+            events.clone(),
+            rpc.clone(),
+            config.lst.refresh_secs,
+        )));
+    }
+    
+    // 定期確認 .sol 網域仍指向原地址
+    if config.sns.refresh_secs > 0 {
+        tokio::spawn(domain_refresh_task(shared_wallets.clone(), events.clone(), rpc.clone(), config.sns.refresh_secs));
+    }
     
+    // 定期降採樣歷史資料
+    if config.retention.enabled {
+        tokio::spawn(history_compaction_task(database.clone(), config.retention.clone()));
+    }
+
+    // 合併高頻變化時，定期補記間隔內的最後狀態；之後才透過熱重載啟用時由 reload_wallets 啟動
+    if history_coalescing(&config) && !app_state.history_flush_started.swap(true, std::sync::atomic::Ordering::SeqCst) {
+        balance_tasks.push(tokio::spawn(history_flush_task(shared_wallets.clone(), database_writer.clone())));
+    }
+
+    // 定期備份資料庫
+    if config.backup.interval_hours > 0 {
+        tokio::spawn(backup_task(database.clone(), config.backup.clone()));
+    }
+
+    // 定期清理超出保留天數或數量上限的歷史記錄
+    if config.database.retention_days > 0 || config.database.max_history_per_wallet > 0 {
+        tokio::spawn(history_pruning_task(database.clone(), config.database.clone()));
+    }
+
+    // 對外提供 gRPC 串流服務
+    if let Some(grpc_server) = config.grpc_server.clone() {
+        let service = WalletMonitorService { wallets: shared_wallets.clone(), events: events.clone() };
+        tokio::spawn(serve_grpc(grpc_server, config.auth.clone(), config.logging.language, service, shutdown.clone()));
+    }
+
     // 移除定期WSOL更新任務，改為只從交易中更新WSOL
     
     // 啟動Web服務器
     let server_addr = format!("{}:{}", config.server.host, config.server.port);
-    info!("🌐 Web服務器啟動於 http://{}", server_addr);
-    
-    let listener = tokio::net::TcpListener::bind(&server_addr).await?;
-    axum::serve(listener, app).await?;
+    let served = if let Some(socket_path) = &config.server.unix_socket {
+        serve_unix_socket(app, socket_path, shutdown.clone()).await
+    } else if let Some(tls) = &config.server.tls {
+        serve_https(app, &server_addr, &config.server.host, config.server.port, tls, shutdown.clone()).await
+    } else {
+        info!("🌐 Web服務器啟動於 http://{}", server_addr);
+        match tokio::net::TcpListener::bind(&server_addr).await {
+            Ok(listener) => axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+                .with_graceful_shutdown(wait_for_shutdown(shutdown.clone()))
+                .await
+                .map_err(Into::into),
+            Err(e) => Err(e.into()),
+        }
+    };
+
+    // 停止帳戶串流與背景更新，之後的餘額不再變動
+    for task in &balance_tasks {
+        task.abort();
+    }
+    for task in balance_tasks {
+        let _ = task.await;
+    }
+    info!("⏹️ 帳戶串流已停止");
+
+    // 寫入合併中或低於門檻、尚未保存的最新餘額，下次啟動時即可載入
+    let mut persisted = 0;
+    for wallet in shared_wallets.write().await.values_mut() {
+        if wallet.persist_latest_history() {
+            database_writer.save_history(wallet.history_record());
+            persisted += 1;
+        }
+    }
+
+    // 寫入佇列中尚未提交的記錄並結束寫入執行緒
+    database_writer.shutdown().await;
+    info!("💾 資料庫寫入完成 (保存 {} 個錢包的最新餘額)", persisted);
     
-    Ok(())
+    served
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn point(seconds: i64, sol: f64, total: f64) -> BalanceHistory {
+        BalanceHistory {
+            timestamp: DateTime::from_timestamp(seconds, 0).unwrap(),
+            sol_balance: sol,
+            wsol_balance: 0.0,
+            staked_balance: 0.0,
+            lst_balance: 0.0,
+            total_balance: total,
+        }
+    }
+
+    fn transaction_record(fee: f64, priority_fee: f64) -> WalletTransactionRecord {
+        serde_json::from_value(json!({
+            "signature": "sig",
+            "slot": 1,
+            "timestamp": "2024-01-01T00:00:00Z",
+            "address": "wallet",
+            "direction": "outgoing",
+            "amount": 1.0,
+            "change": -1.0 - fee,
+            "fee": fee,
+            "priority_fee": priority_fee,
+            "success": true,
+        }))
+        .unwrap()
+    }
+
+    fn alert(state: AlertState) -> AlertRecord {
+        AlertRecord {
+            id: 1,
+            rule: "rule".to_string(),
+            kind: AlertKind::BalanceBelow,
+            address: "wallet".to_string(),
+            name: "wallet".to_string(),
+            state,
+            detail: None,
+            fired_at: Utc::now(),
+            acknowledged_at: None,
+            resolved_at: None,
+        }
+    }
+
+    #[test]
+    fn cost_position_uses_average_cost() {
+        let mut position = CostPosition::default();
+        position.apply(10.0, 100.0);
+        position.apply(10.0, 200.0);
+        assert_eq!(position.amount, 20.0);
+        assert_eq!(position.average_cost(), 150.0);
+
+        position.apply(-5.0, 300.0);
+        assert_eq!(position.amount, 15.0);
+        assert_eq!(position.cost, 2250.0);
+        assert_eq!(position.realized, 750.0);
+        assert_eq!(position.average_cost(), 150.0);
+    }
+
+    #[test]
+    fn cost_position_oversell_is_fully_realized() {
+        let mut position = CostPosition::default();
+        position.apply(2.0, 100.0);
+        position.apply(-3.0, 150.0);
+        assert_eq!(position.amount, 0.0);
+        assert_eq!(position.cost, 0.0);
+        assert_eq!(position.realized, 250.0);
+        assert_eq!(position.average_cost(), 0.0);
+    }
+
+    #[test]
+    fn parse_quiet_hours_accepts_ranges() {
+        let time = |h, m| chrono::NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        assert_eq!(parse_quiet_hours("22:00-07:30"), Some((time(22, 0), time(7, 30))));
+        assert_eq!(parse_quiet_hours(" 01:00 - 02:00 "), Some((time(1, 0), time(2, 0))));
+        assert_eq!(parse_quiet_hours("22:00"), None);
+        assert_eq!(parse_quiet_hours("25:00-07:00"), None);
+        assert_eq!(parse_quiet_hours("22:00-7pm"), None);
+    }
+
+    #[test]
+    fn series_values_at_carries_last_point() {
+        let series = [point(10, 1.0, 1.5), point(20, 2.0, 2.5)];
+        assert_eq!(series_values_at(&series, &[5, 10, 15, 20, 25], "sol"), vec![0.0, 1.0, 1.0, 2.0, 2.0]);
+        assert_eq!(series_values_at(&series, &[15, 30], "total"), vec![1.5, 2.5]);
+        assert_eq!(series_values_at(&[], &[15], "total"), vec![0.0]);
+    }
+
+    #[test]
+    fn build_candles_opens_with_prior_balance() {
+        let candle = |c: &Candle| (c.time, c.open, c.high, c.low, c.close);
+        // from 之前的餘額 1.0 以 first_start 為時間，之後在 250 秒變為 2.0
+        let history = [point(100, 1.0, 1.0), point(250, 2.0, 2.0)];
+        let candles: Vec<_> = build_candles(&history, "sol", 100, 300, 60).iter().map(candle).collect();
+        assert_eq!(candles, vec![
+            (100, 1.0, 1.0, 1.0, 1.0),
+            (160, 1.0, 1.0, 1.0, 1.0),
+            (220, 1.0, 2.0, 1.0, 2.0),
+            (280, 2.0, 2.0, 2.0, 2.0),
+        ]);
+        // 沒有更早的記錄時，第一次變化之前的區間不產生 K 線
+        let candles: Vec<_> = build_candles(&history[1..], "sol", 100, 300, 60).iter().map(candle).collect();
+        assert_eq!(candles, vec![(220, 2.0, 2.0, 2.0, 2.0), (280, 2.0, 2.0, 2.0, 2.0)]);
+        // 接近 i64 上限的區間不會溢位
+        assert!(build_candles(&[], "sol", i64::MAX - 100, i64::MAX, 60).is_empty());
+        assert!(build_candles(&[], "sol", 300, 100, 60).is_empty());
+    }
+
+    #[test]
+    fn fee_lamports_splits_base_and_priority() {
+        assert_eq!(fee_lamports(&transaction_record(0.000015, 0.00001)), (5000, 10000));
+        assert_eq!(fee_lamports(&transaction_record(0.000005, 0.0)), (5000, 0));
+        // 優先費不會超過總手續費
+        assert_eq!(fee_lamports(&transaction_record(0.000005, 0.00001)), (0, 5000));
+    }
+
+    #[test]
+    fn transaction_balances_reads_alt_accounts_and_wsol() {
+        let owner = "Wa11et1111111111111111111111111111111111111";
+        let transaction: EncodedConfirmedTransactionWithStatusMeta = serde_json::from_value(json!({
+            "slot": 100,
+            "blockTime": 1_700_000_000,
+            "transaction": {
+                "signatures": ["sig"],
+                "message": {
+                    "header": { "numRequiredSignatures": 1, "numReadonlySignedAccounts": 0, "numReadonlyUnsignedAccounts": 1 },
+                    "accountKeys": ["Payer111111111111111111111111111111111111111", "11111111111111111111111111111111"],
+                    "recentBlockhash": "11111111111111111111111111111111",
+                    "instructions": [],
+                },
+            },
+            "meta": {
+                "err": null,
+                "status": { "Ok": null },
+                "fee": 5000,
+                "preBalances": [10_000_000_000u64, 1, 2_000_000_000u64],
+                "postBalances": [9_999_995_000u64, 1, 2_500_000_000u64],
+                "preTokenBalances": [{
+                    "accountIndex": 3,
+                    "mint": WSOL_MINT,
+                    "owner": owner,
+                    "uiTokenAmount": { "uiAmount": 1.0, "decimals": 9, "amount": "1000000000", "uiAmountString": "1" },
+                }],
+                "postTokenBalances": [],
+                "loadedAddresses": { "writable": [owner], "readonly": [] },
+            },
+        }))
+        .unwrap();
+
+        let (timestamp, sol, wsol) = transaction_balances(&transaction, owner).unwrap();
+        assert_eq!(timestamp.timestamp(), 1_700_000_000);
+        assert_eq!(sol, 2.5);
+        assert_eq!(wsol, Some((1.0, 0.0)));
+
+        let (_, sol, wsol) = transaction_balances(&transaction, "Payer111111111111111111111111111111111111111").unwrap();
+        assert_eq!(sol, 9.999995);
+        assert_eq!(wsol, None);
+
+        assert!(transaction_balances(&transaction, "Missing11111111111111111111111111111111111").is_none());
+    }
+
+    #[test]
+    fn alert_transition_only_moves_forward() {
+        let mut record = alert(AlertState::Open);
+        record.transition(AlertState::Acknowledged);
+        assert_eq!(record.state, AlertState::Acknowledged);
+        assert!(record.acknowledged_at.is_some());
+
+        record.transition(AlertState::Open);
+        assert_eq!(record.state, AlertState::Acknowledged);
+
+        record.transition(AlertState::Resolved);
+        assert_eq!(record.state, AlertState::Resolved);
+        assert!(record.resolved_at.is_some());
+
+        record.transition(AlertState::Acknowledged);
+        assert_eq!(record.state, AlertState::Resolved);
+
+        let mut record = alert(AlertState::Open);
+        record.transition(AlertState::Resolved);
+        assert_eq!(record.state, AlertState::Resolved);
+        assert!(record.acknowledged_at.is_none());
+    }
+}
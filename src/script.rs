@@ -0,0 +1,164 @@
+// Rhai 告警條件 (需啟用 rhai feature)：條件為單一運算式，可讀取目前與前一次的餘額、錢包資訊與歷史區間統計
+use {
+    crate::{BalanceHistory, WalletBalance},
+    chrono::{DateTime, Duration, Utc},
+    rhai::{Array, Dynamic, Engine, EvalAltResult, Scope, AST},
+    std::sync::Arc,
+};
+
+// 限制每次執行的運算量與巢狀深度，避免條件拖慢更新流程
+const MAX_OPERATIONS: u64 = 10_000;
+const MAX_EXPR_DEPTH: usize = 32;
+
+pub(crate) struct ScriptCondition {
+    ast: AST,
+    window: Duration, // 條件中出現的最大時間區間，只複製此範圍內的歷史記錄
+}
+
+// 腳本中的 wallet 物件
+#[derive(Clone)]
+struct ScriptWallet {
+    address: String,
+    name: String,
+    group: String,
+    tags: Array,
+    account_type: String,
+    note: String,
+    history: Arc<Vec<BalanceHistory>>, // 由舊到新，最後一筆為目前餘額
+}
+
+pub(crate) struct ScriptEngine {
+    engine: Engine,
+}
+
+impl Default for ScriptEngine {
+    fn default() -> Self {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_expr_depths(MAX_EXPR_DEPTH, MAX_EXPR_DEPTH);
+        engine.set_max_string_size(1024);
+        engine.set_max_array_size(1024);
+        engine.set_max_map_size(256);
+        engine.on_print(|_| {});
+
+        engine
+            .register_type_with_name::<ScriptWallet>("Wallet")
+            .register_get("address", |w: &mut ScriptWallet| w.address.clone())
+            .register_get("name", |w: &mut ScriptWallet| w.name.clone())
+            .register_get("group", |w: &mut ScriptWallet| w.group.clone())
+            .register_get("tags", |w: &mut ScriptWallet| w.tags.clone())
+            .register_get("account_type", |w: &mut ScriptWallet| w.account_type.clone())
+            .register_get("note", |w: &mut ScriptWallet| w.note.clone());
+        engine.register_fn("sma", |w: &mut ScriptWallet, field: &str, window: &str| -> Result<f64, Box<EvalAltResult>> {
+            let values = window_values(w, field, window)?;
+            Ok(values.iter().sum::<f64>() / values.len() as f64)
+        });
+        engine.register_fn("window_min", |w: &mut ScriptWallet, field: &str, window: &str| -> Result<f64, Box<EvalAltResult>> {
+            Ok(window_values(w, field, window)?.into_iter().fold(f64::INFINITY, f64::min))
+        });
+        engine.register_fn("window_max", |w: &mut ScriptWallet, field: &str, window: &str| -> Result<f64, Box<EvalAltResult>> {
+            Ok(window_values(w, field, window)?.into_iter().fold(f64::NEG_INFINITY, f64::max))
+        });
+        // 區間內第一筆記錄到目前的變化
+        engine.register_fn("change", |w: &mut ScriptWallet, field: &str, window: &str| -> Result<f64, Box<EvalAltResult>> {
+            let values = window_values(w, field, window)?;
+            Ok(values.last().copied().unwrap_or_default() - values.first().copied().unwrap_or_default())
+        });
+
+        Self { engine }
+    }
+}
+
+impl ScriptCondition {
+    pub(crate) fn window(&self) -> Duration {
+        self.window
+    }
+}
+
+impl ScriptEngine {
+    pub(crate) fn compile(&self, condition: &str) -> Result<ScriptCondition, String> {
+        let ast = self.engine.compile_expression(condition).map_err(|e| e.to_string())?;
+        let window = string_literals(condition).filter_map(parse_window).max().unwrap_or_else(Duration::zero);
+        Ok(ScriptCondition { ast, window })
+    }
+
+    // history 為呼叫端準備的區間歷史 (由舊到新，包含目前餘額)，涵蓋所有規則中最大的時間區間
+    pub(crate) fn evaluate(
+        &self,
+        condition: &ScriptCondition,
+        wallet: &WalletBalance,
+        previous: &BalanceHistory,
+        history: Arc<Vec<BalanceHistory>>,
+    ) -> Result<bool, String> {
+        let current = wallet.balance_point();
+
+        let mut scope = Scope::new();
+        for (prefix, point) in [("", &current), ("prev_", previous)] {
+            scope.push_constant(format!("{}sol", prefix), point.sol_balance);
+            scope.push_constant(format!("{}wsol", prefix), point.wsol_balance);
+            scope.push_constant(format!("{}staked", prefix), point.staked_balance);
+            scope.push_constant(format!("{}lst", prefix), point.lst_balance);
+            scope.push_constant(format!("{}total", prefix), point.total_balance);
+        }
+        scope.push_constant("delta", current.total_balance - previous.total_balance);
+        scope.push_constant(
+            "wallet",
+            ScriptWallet {
+                address: wallet.address.clone(),
+                name: wallet.name.clone(),
+                group: wallet.group.clone().unwrap_or_default(),
+                tags: wallet.tags.iter().map(|tag| Dynamic::from(tag.clone())).collect(),
+                account_type: wallet.account_type.as_str().to_string(),
+                note: wallet.note.clone().unwrap_or_default(),
+                history,
+            },
+        );
+        self.engine.eval_ast_with_scope::<bool>(&mut scope, &condition.ast).map_err(|e| e.to_string())
+    }
+}
+
+fn window_values(wallet: &ScriptWallet, field: &str, window: &str) -> Result<Vec<f64>, Box<EvalAltResult>> {
+    let window = parse_window(window).ok_or_else(|| format!("不支援的時間區間: {}", window))?;
+    let since = window_start(window);
+    let last = wallet.history.len().saturating_sub(1);
+    wallet
+        .history
+        .iter()
+        .enumerate()
+        .filter(|(i, point)| *i == last || point.timestamp >= since)
+        .map(|(_, point)| match field {
+            "sol" => Ok(point.sol_balance),
+            "wsol" => Ok(point.wsol_balance),
+            "staked" => Ok(point.staked_balance),
+            "lst" => Ok(point.lst_balance),
+            "total" => Ok(point.total_balance),
+            other => Err(format!("不支援的欄位: {}", other).into()),
+        })
+        .collect()
+}
+
+// 時間區間格式為數字加單位：s、m、h、d、w，例如 "30m"、"1h"、"7d"
+fn parse_window(window: &str) -> Option<Duration> {
+    let window = window.trim();
+    let unit = window.chars().last()?;
+    let value: i64 = window[..window.len() - unit.len_utf8()].parse().ok().filter(|value| *value >= 0)?;
+    // 數值過大時 Duration 的建構函式會 panic，改用 try_* 版本視為不支援的區間
+    match unit.to_ascii_lowercase() {
+        's' => Duration::try_seconds(value),
+        'm' => Duration::try_minutes(value),
+        'h' => Duration::try_hours(value),
+        'd' => Duration::try_days(value),
+        'w' => Duration::try_weeks(value),
+        _ => None,
+    }
+}
+
+// 區間的起始時間，超出可表示範圍時視為最早時間
+pub(crate) fn window_start(window: Duration) -> DateTime<Utc> {
+    Utc::now().checked_sub_signed(window).unwrap_or(DateTime::<Utc>::MIN_UTC)
+}
+
+// 條件中以雙引號包住的字串
+fn string_literals(source: &str) -> impl Iterator<Item = &str> {
+    source.split('"').skip(1).step_by(2)
+}
@@ -0,0 +1,974 @@
+// SQLite 歷史資料儲存後端 (需啟用 sqlite feature)
+use {
+    crate::{
+        fee_day_millis, fee_lamports, finish_backup, history_key_millis, jito_tip_lamports, send_history_csv, AlertRecord, AlertState,
+        AuditEntry, BackupConfig, BackupInfo, BalanceHistory, BlockRewardRecord, CompactResponse, CostBasis, CsvSender, DailyFees, DailyIncome,
+        DailyReport, DatabaseConfig, DatabaseStats, EpochBoundary, FxRate, HistoryStore, IncomeKind, PricePoint, RestoreResponse,
+        RetentionConfig, StakeReward, TokenAmountRecord, TokenAccountEvent, WalletDatabaseStats, WalletHistoryRecord, WalletTransactionRecord, CSV_PAGE_SIZE,
+        DAY_MS, HOUR_MS,
+    },
+    chrono::{DateTime, Utc},
+    tracing::info,
+    rusqlite::{params, Connection, OptionalExtension, Row},
+    std::{collections::BTreeMap, fs, sync::Mutex},
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS wallet_history (
+        address TEXT NOT NULL,
+        timestamp_ms INTEGER NOT NULL,
+        sol_balance REAL NOT NULL,
+        wsol_balance REAL NOT NULL,
+        staked_balance REAL NOT NULL,
+        lst_balance REAL NOT NULL,
+        total_balance REAL NOT NULL,
+        PRIMARY KEY (address, timestamp_ms)
+    ) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS wallet_transactions (
+        address TEXT NOT NULL,
+        slot INTEGER NOT NULL,
+        signature TEXT NOT NULL,
+        timestamp_ms INTEGER NOT NULL,
+        direction TEXT NOT NULL,
+        amount REAL NOT NULL,
+        change REAL NOT NULL,
+        fee REAL NOT NULL,
+        counterparty TEXT,
+        success INTEGER NOT NULL,
+        priority_fee REAL NOT NULL DEFAULT 0,
+        income TEXT,
+        account_tips REAL NOT NULL DEFAULT 0,
+        PRIMARY KEY (address, slot, signature)
+    ) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS wallet_fees (
+        address TEXT NOT NULL,
+        day_ms INTEGER NOT NULL,
+        base_lamports INTEGER NOT NULL,
+        priority_lamports INTEGER NOT NULL,
+        transactions INTEGER NOT NULL,
+        PRIMARY KEY (address, day_ms)
+    ) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS wallet_income (
+        address TEXT NOT NULL,
+        day_ms INTEGER NOT NULL,
+        jito_lamports INTEGER NOT NULL,
+        tip_claims INTEGER NOT NULL,
+        block_reward_lamports INTEGER NOT NULL,
+        blocks INTEGER NOT NULL,
+        PRIMARY KEY (address, day_ms)
+    ) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS audit_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        timestamp_ms INTEGER NOT NULL,
+        address TEXT NOT NULL,
+        entry TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS token_events (
+        address TEXT NOT NULL,
+        timestamp_ms INTEGER NOT NULL,
+        token_account TEXT NOT NULL,
+        event TEXT NOT NULL,
+        PRIMARY KEY (address, timestamp_ms, token_account)
+    ) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS seen_mints (
+        address TEXT NOT NULL,
+        mint TEXT NOT NULL,
+        first_seen_ms INTEGER NOT NULL,
+        PRIMARY KEY (address, mint)
+    ) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS alerts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        state TEXT NOT NULL,
+        entry TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS alert_snoozes (
+        rule TEXT NOT NULL,
+        address TEXT NOT NULL,
+        until_ms INTEGER NOT NULL,
+        PRIMARY KEY (rule, address)
+    ) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS daily_reports (
+        date TEXT PRIMARY KEY,
+        report TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS sol_prices (
+        timestamp_ms INTEGER PRIMARY KEY,
+        usd REAL NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS cost_basis (
+        address TEXT PRIMARY KEY,
+        basis TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS fx_rates (
+        currency TEXT NOT NULL,
+        timestamp_ms INTEGER NOT NULL,
+        rate REAL NOT NULL,
+        PRIMARY KEY (currency, timestamp_ms)
+    ) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS epochs (
+        epoch INTEGER PRIMARY KEY,
+        start_slot INTEGER NOT NULL,
+        started_at_ms INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS stake_rewards (
+        address TEXT NOT NULL,
+        epoch INTEGER NOT NULL,
+        credited_at_ms INTEGER NOT NULL,
+        reward TEXT NOT NULL,
+        PRIMARY KEY (address, epoch)
+    ) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS token_amounts (
+        address TEXT NOT NULL,
+        timestamp_ms INTEGER NOT NULL,
+        amount REAL NOT NULL,
+        PRIMARY KEY (address, timestamp_ms)
+    ) WITHOUT ROWID;
+";
+
+const TRANSACTION_COLUMNS: &str = "address, slot, signature, timestamp_ms, direction, amount, change, fee, counterparty, success";
+
+// 累加某錢包某天的驗證者收入：(Jito tips, 領取次數, 出塊獎勵, 區塊數)
+const INCOME_UPSERT: &str = "INSERT INTO wallet_income (address, day_ms, jito_lamports, tip_claims, block_reward_lamports, blocks)
+     VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+     ON CONFLICT (address, day_ms) DO UPDATE SET
+         jito_lamports = jito_lamports + excluded.jito_lamports,
+         tip_claims = tip_claims + excluded.tip_claims,
+         block_reward_lamports = block_reward_lamports + excluded.block_reward_lamports,
+         blocks = blocks + excluded.blocks";
+
+const HISTORY_COLUMNS: &str =
+    "address, timestamp_ms, sol_balance, wsol_balance, staked_balance, lst_balance, total_balance";
+
+pub(crate) struct SqliteStore {
+    conn: Mutex<Connection>,
+    path: String,
+}
+
+impl SqliteStore {
+    pub(crate) fn open(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.execute_batch(SCHEMA)?;
+        // 舊版資料庫的 wallet_transactions 沒有 priority_fee 欄位
+        let has_priority_fee: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('wallet_transactions') WHERE name = 'priority_fee'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_priority_fee {
+            conn.execute("ALTER TABLE wallet_transactions ADD COLUMN priority_fee REAL NOT NULL DEFAULT 0", [])?;
+        }
+        // 舊版資料庫的 wallet_transactions 沒有 income 欄位
+        let has_income: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('wallet_transactions') WHERE name = 'income'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_income {
+            conn.execute("ALTER TABLE wallet_transactions ADD COLUMN income TEXT", [])?;
+        }
+        // 舊版資料庫的 wallet_transactions 沒有 account_tips 欄位
+        let has_account_tips: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('wallet_transactions') WHERE name = 'account_tips'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_account_tips {
+            conn.execute("ALTER TABLE wallet_transactions ADD COLUMN account_tips REAL NOT NULL DEFAULT 0", [])?;
+        }
+        Ok(Self { conn: Mutex::new(conn), path: path.to_string() })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn file_size(&self) -> Result<u64, Box<dyn std::error::Error>> {
+        Ok(fs::metadata(&self.path)?.len())
+    }
+}
+
+fn millis_to_datetime(millis: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(millis).unwrap_or_default()
+}
+
+fn epoch_from_row(row: &Row) -> rusqlite::Result<EpochBoundary> {
+    Ok(EpochBoundary {
+        epoch: row.get::<_, i64>(0)? as u64,
+        start_slot: row.get::<_, i64>(1)? as u64,
+        started_at: millis_to_datetime(row.get(2)?),
+    })
+}
+
+fn history_from_row(row: &Row) -> rusqlite::Result<WalletHistoryRecord> {
+    Ok(WalletHistoryRecord {
+        address: row.get(0)?,
+        timestamp: millis_to_datetime(row.get(1)?),
+        sol_balance: row.get(2)?,
+        wsol_balance: row.get(3)?,
+        staked_balance: row.get(4)?,
+        lst_balance: row.get(5)?,
+        total_balance: row.get(6)?,
+    })
+}
+
+fn transaction_from_row(row: &Row) -> rusqlite::Result<WalletTransactionRecord> {
+    Ok(WalletTransactionRecord {
+        address: row.get(0)?,
+        slot: row.get::<_, i64>(1)? as u64,
+        signature: row.get(2)?,
+        timestamp: millis_to_datetime(row.get(3)?),
+        direction: row.get(4)?,
+        amount: row.get(5)?,
+        change: row.get(6)?,
+        fee: row.get(7)?,
+        counterparty: row.get(8)?,
+        success: row.get(9)?,
+        priority_fee: row.get(10)?,
+        income: row.get::<_, Option<String>>(11)?.as_deref().and_then(IncomeKind::parse),
+        account_tips: row.get(12)?,
+    })
+}
+
+fn range_millis(from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> (i64, i64) {
+    let start = from.map(history_key_millis).unwrap_or(0) as i64;
+    let end = to.map(|t| history_key_millis(t) as i64).unwrap_or(i64::MAX);
+    (start, end)
+}
+
+// 以 INSERT OR IGNORE 寫入歷史記錄，返回 (匯入數, 略過數)
+fn insert_history_ignore(conn: &Connection, records: &[WalletHistoryRecord]) -> Result<RestoreResponse, Box<dyn std::error::Error>> {
+    let mut response = RestoreResponse::default();
+    let mut stmt = conn.prepare_cached(&format!(
+        "INSERT OR IGNORE INTO wallet_history ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        HISTORY_COLUMNS
+    ))?;
+    for record in records {
+        let changed = stmt.execute(params![
+            record.address,
+            history_key_millis(record.timestamp) as i64,
+            record.sol_balance,
+            record.wsol_balance,
+            record.staked_balance,
+            record.lst_balance,
+            record.total_balance,
+        ])?;
+        if changed > 0 {
+            response.imported += 1;
+        } else {
+            response.skipped += 1;
+        }
+    }
+    Ok(response)
+}
+
+fn distinct_addresses(conn: &Connection) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut stmt = conn.prepare("SELECT DISTINCT address FROM wallet_history ORDER BY address")?;
+    let addresses = stmt.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(addresses)
+}
+
+impl HistoryStore for SqliteStore {
+    fn write_batch(&self, history: &[WalletHistoryRecord], transactions: &[WalletTransactionRecord]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(&format!(
+                "INSERT OR REPLACE INTO wallet_history ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                HISTORY_COLUMNS
+            ))?;
+            for record in history {
+                stmt.execute(params![
+                    record.address,
+                    history_key_millis(record.timestamp) as i64,
+                    record.sol_balance,
+                    record.wsol_balance,
+                    record.staked_balance,
+                    record.lst_balance,
+                    record.total_balance,
+                ])?;
+            }
+
+            let mut exists = tx.prepare_cached("SELECT 1 FROM wallet_transactions WHERE address = ?1 AND slot = ?2 AND signature = ?3")?;
+            let mut fees = tx.prepare_cached(
+                "INSERT INTO wallet_fees (address, day_ms, base_lamports, priority_lamports, transactions) VALUES (?1, ?2, ?3, ?4, 1)
+                 ON CONFLICT (address, day_ms) DO UPDATE SET
+                     base_lamports = base_lamports + excluded.base_lamports,
+                     priority_lamports = priority_lamports + excluded.priority_lamports,
+                     transactions = transactions + 1",
+            )?;
+            let mut income = tx.prepare_cached(INCOME_UPSERT)?;
+            let mut stmt = tx.prepare_cached(&format!(
+                "INSERT OR REPLACE INTO wallet_transactions ({}, priority_fee, income, account_tips) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                TRANSACTION_COLUMNS
+            ))?;
+            for record in transactions {
+                // 重新連線後重複收到的交易不重複累計手續費與收入
+                let tips = jito_tip_lamports(record);
+                if (record.fee > 0.0 || tips.is_some()) && !exists.exists(params![record.address, record.slot as i64, record.signature])? {
+                    let day_ms = fee_day_millis(record.timestamp) as i64;
+                    if record.fee > 0.0 {
+                        let (base, priority) = fee_lamports(record);
+                        fees.execute(params![record.address, day_ms, base as i64, priority as i64])?;
+                    }
+                    if let Some(tips) = tips {
+                        income.execute(params![record.address, day_ms, tips as i64, 1, 0, 0])?;
+                    }
+                }
+                stmt.execute(params![
+                    record.address,
+                    record.slot as i64,
+                    record.signature,
+                    history_key_millis(record.timestamp) as i64,
+                    record.direction,
+                    record.amount,
+                    record.change,
+                    record.fee,
+                    record.counterparty,
+                    record.success,
+                    record.priority_fee,
+                    record.income.map(IncomeKind::as_str),
+                    record.account_tips,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn load_history(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>, limit: usize) -> Result<Vec<WalletHistoryRecord>, Box<dyn std::error::Error>> {
+        let (start, end) = range_millis(from, to);
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {} FROM wallet_history WHERE address = ?1 AND timestamp_ms BETWEEN ?2 AND ?3 ORDER BY timestamp_ms LIMIT ?4",
+            HISTORY_COLUMNS
+        ))?;
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let records = stmt.query_map(params![address, start, end, limit], history_from_row)?.collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(records)
+    }
+
+    fn load_history_series(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<BalanceHistory>, Box<dyn std::error::Error>> {
+        let mut series: Vec<BalanceHistory> = self.load_history(address, from, to, usize::MAX)?.into_iter().map(BalanceHistory::from).collect();
+
+        // 與 redb 後端一致：以 from 之前的最後一筆作為起點
+        if let Some(from) = from {
+            let conn = self.conn();
+            let before = conn
+                .query_row(
+                    &format!(
+                        "SELECT {} FROM wallet_history WHERE address = ?1 AND timestamp_ms < ?2 ORDER BY timestamp_ms DESC LIMIT 1",
+                        HISTORY_COLUMNS
+                    ),
+                    params![address, history_key_millis(from) as i64],
+                    history_from_row,
+                )
+                .optional()?;
+            if let Some(record) = before {
+                let mut first = BalanceHistory::from(record);
+                first.timestamp = from;
+                series.insert(0, first);
+            }
+        }
+        Ok(series)
+    }
+
+    fn load_recent_history(&self, address: &str, limit: usize) -> Result<Vec<WalletHistoryRecord>, Box<dyn std::error::Error>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {} FROM wallet_history WHERE address = ?1 ORDER BY timestamp_ms DESC LIMIT ?2",
+            HISTORY_COLUMNS
+        ))?;
+        let mut records = stmt
+            .query_map(params![address, limit.min(i64::MAX as usize) as i64], history_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        records.reverse();
+        Ok(records)
+    }
+
+    fn load_latest(&self, address: &str) -> Result<Option<WalletHistoryRecord>, Box<dyn std::error::Error>> {
+        Ok(self.load_recent_history(address, 1)?.pop())
+    }
+
+    fn earliest_history(&self, address: &str) -> Result<Option<DateTime<Utc>>, Box<dyn std::error::Error>> {
+        // SQLite 後端不做降採樣，只有原始表格
+        let earliest: Option<i64> = self.conn().query_row("SELECT MIN(timestamp_ms) FROM wallet_history WHERE address = ?1", params![address], |row| row.get(0))?;
+        Ok(earliest.and_then(DateTime::from_timestamp_millis))
+    }
+
+    fn load_transactions(&self, address: &str, page: usize, page_size: usize) -> Result<(usize, Vec<WalletTransactionRecord>), Box<dyn std::error::Error>> {
+        let conn = self.conn();
+        let total: i64 = conn.query_row("SELECT COUNT(*) FROM wallet_transactions WHERE address = ?1", params![address], |row| row.get(0))?;
+        let offset = page.saturating_sub(1).saturating_mul(page_size);
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {}, priority_fee, income, account_tips FROM wallet_transactions WHERE address = ?1
+             ORDER BY slot DESC, signature DESC LIMIT ?2 OFFSET ?3",
+            TRANSACTION_COLUMNS
+        ))?;
+        let records = stmt
+            .query_map(params![address, page_size as i64, offset as i64], transaction_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok((total as usize, records))
+    }
+
+    fn load_daily_fees(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<DailyFees>, Box<dyn std::error::Error>> {
+        let start = from.map(fee_day_millis).unwrap_or(0) as i64;
+        let end = to.map(history_key_millis).map_or(i64::MAX, |end| end as i64);
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT day_ms, base_lamports, priority_lamports, transactions FROM wallet_fees
+             WHERE address = ?1 AND day_ms BETWEEN ?2 AND ?3 ORDER BY day_ms",
+        )?;
+        let days = stmt
+            .query_map(params![address, start, end], |row| {
+                Ok(DailyFees {
+                    day_ms: row.get::<_, i64>(0)? as u64,
+                    base_lamports: row.get::<_, i64>(1)? as u64,
+                    priority_lamports: row.get::<_, i64>(2)? as u64,
+                    transactions: row.get::<_, i64>(3)? as u64,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(days)
+    }
+
+    fn save_block_rewards(&self, rewards: &[BlockRewardRecord]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(INCOME_UPSERT)?;
+            for reward in rewards {
+                stmt.execute(params![reward.address, fee_day_millis(reward.timestamp) as i64, 0, 0, reward.lamports as i64, 1])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn load_daily_income(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<DailyIncome>, Box<dyn std::error::Error>> {
+        let start = from.map(fee_day_millis).unwrap_or(0) as i64;
+        let end = to.map(history_key_millis).map_or(i64::MAX, |end| end as i64);
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT day_ms, jito_lamports, tip_claims, block_reward_lamports, blocks FROM wallet_income
+             WHERE address = ?1 AND day_ms BETWEEN ?2 AND ?3 ORDER BY day_ms",
+        )?;
+        let days = stmt
+            .query_map(params![address, start, end], |row| {
+                Ok(DailyIncome {
+                    day_ms: row.get::<_, i64>(0)? as u64,
+                    jito_lamports: row.get::<_, i64>(1)? as u64,
+                    tip_claims: row.get::<_, i64>(2)? as u64,
+                    block_reward_lamports: row.get::<_, i64>(3)? as u64,
+                    blocks: row.get::<_, i64>(4)? as u64,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(days)
+    }
+
+    fn delete_wallet(&self, address: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM wallet_history WHERE address = ?1", params![address])?;
+        tx.execute("DELETE FROM wallet_transactions WHERE address = ?1", params![address])?;
+        tx.execute("DELETE FROM wallet_fees WHERE address = ?1", params![address])?;
+        tx.execute("DELETE FROM wallet_income WHERE address = ?1", params![address])?;
+        tx.execute("DELETE FROM cost_basis WHERE address = ?1", params![address])?;
+        tx.execute("DELETE FROM stake_rewards WHERE address = ?1", params![address])?;
+        tx.execute("DELETE FROM token_amounts WHERE address = ?1", params![address])?;
+        tx.execute("DELETE FROM token_events WHERE address = ?1", params![address])?;
+        tx.execute("DELETE FROM seen_mints WHERE address = ?1", params![address])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn delete_history(&self, address: &str, after: Option<DateTime<Utc>>, before: Option<DateTime<Utc>>) -> Result<usize, Box<dyn std::error::Error>> {
+        let start = after.map(history_key_millis).unwrap_or(0) as i64;
+        let end = before.map(|t| history_key_millis(t) as i64).unwrap_or(i64::MAX);
+        let removed = self.conn().execute(
+            "DELETE FROM wallet_history WHERE address = ?1 AND timestamp_ms >= ?2 AND timestamp_ms < ?3",
+            params![address, start, end],
+        )?;
+        info!("🗑️ 已刪除錢包 {} 的 {} 條歷史記錄", address, removed);
+        Ok(removed)
+    }
+
+    // 每頁查詢後即釋放連線的鎖，送出時其他請求與寫入執行緒可以繼續使用資料庫
+    fn stream_history_csv(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>, sender: &CsvSender) -> Result<(), Box<dyn std::error::Error>> {
+        send_history_csv(from, |from| self.load_history(address, from, to, CSV_PAGE_SIZE), sender)
+    }
+
+    // SQLite 後端沒有分層彙總表，啟用 [retention] 時啟動即失敗，只依 [database] 設定清理
+    fn compact(&self, _retention: &RetentionConfig) -> Result<usize, Box<dyn std::error::Error>> {
+        Err("SQLite 後端不支援 [retention] 降採樣".into())
+    }
+
+    fn prune(&self, config: &DatabaseConfig) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let mut removed = 0usize;
+
+        if config.retention_days > 0 {
+            let cutoff = history_key_millis(Utc::now()).saturating_sub(config.retention_days * DAY_MS) as i64;
+            removed += tx.execute("DELETE FROM wallet_history WHERE timestamp_ms < ?1", params![cutoff])?;
+        }
+
+        if config.max_history_per_wallet > 0 {
+            removed += tx.execute(
+                "DELETE FROM wallet_history WHERE (address, timestamp_ms) IN (
+                     SELECT address, timestamp_ms FROM (
+                         SELECT address, timestamp_ms,
+                                ROW_NUMBER() OVER (PARTITION BY address ORDER BY timestamp_ms DESC) AS rn
+                         FROM wallet_history
+                     ) WHERE rn > ?1
+                 )",
+                params![config.max_history_per_wallet as i64],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    fn backup(&self, config: &BackupConfig) -> Result<BackupInfo, Box<dyn std::error::Error>> {
+        fs::create_dir_all(&config.dir)?;
+        let created_at = Utc::now();
+        let file = format!("wallet_history-{}.sqlite", created_at.format("%Y%m%d-%H%M%S"));
+        let path = std::path::Path::new(&config.dir).join(&file);
+        let temp_path = path.with_extension("sqlite.tmp");
+
+        self.conn().execute("VACUUM INTO ?1", params![temp_path.to_string_lossy()])?;
+        // 寫完才改名，避免列出未完成的備份
+        fs::rename(&temp_path, &path)?;
+        finish_backup(config, file, created_at)
+    }
+
+    fn restore_backup(&self, path: &std::path::Path) -> Result<(RestoreResponse, Vec<String>), Box<dyn std::error::Error>> {
+        let conn = self.conn();
+        conn.execute("ATTACH DATABASE ?1 AS backup", params![path.to_string_lossy()])?;
+        let result = (|| -> Result<RestoreResponse, Box<dyn std::error::Error>> {
+            let total: i64 = conn.query_row("SELECT COUNT(*) FROM backup.wallet_history", [], |row| row.get(0))?;
+            let imported = conn.execute(
+                &format!(
+                    "INSERT OR IGNORE INTO main.wallet_history ({0}) SELECT {0} FROM backup.wallet_history",
+                    HISTORY_COLUMNS
+                ),
+                [],
+            )?;
+            // 舊版備份沒有 priority_fee 欄位與 wallet_fees 表格
+            conn.execute(
+                &format!("INSERT OR IGNORE INTO main.wallet_transactions ({0}) SELECT {0} FROM backup.wallet_transactions", TRANSACTION_COLUMNS),
+                [],
+            )?;
+            for table in ["wallet_fees", "wallet_income", "sol_prices", "cost_basis", "fx_rates", "epochs", "stake_rewards", "token_amounts", "seen_mints"] {
+                let exists: bool = conn.query_row(
+                    "SELECT COUNT(*) > 0 FROM backup.sqlite_master WHERE type = 'table' AND name = ?1",
+                    params![table],
+                    |row| row.get(0),
+                )?;
+                if exists {
+                    conn.execute(&format!("INSERT OR IGNORE INTO main.{0} SELECT * FROM backup.{0}", table), [])?;
+                }
+            }
+            Ok(RestoreResponse { imported, skipped: total as usize - imported })
+        })();
+        conn.execute("DETACH DATABASE backup", [])?;
+        Ok((result?, distinct_addresses(&conn)?))
+    }
+
+    fn import_records(&self, records: &[WalletHistoryRecord]) -> Result<(RestoreResponse, Vec<String>), Box<dyn std::error::Error>> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let response = insert_history_ignore(&tx, records)?;
+        tx.commit()?;
+        Ok((response, distinct_addresses(&conn)?))
+    }
+
+    fn stats(&self) -> Result<DatabaseStats, Box<dyn std::error::Error>> {
+        let conn = self.conn();
+        let mut wallets: BTreeMap<String, WalletDatabaseStats> = BTreeMap::new();
+
+        let mut stmt = conn.prepare(
+            "SELECT address, COUNT(*), MIN(timestamp_ms), MAX(timestamp_ms) FROM wallet_history GROUP BY address",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let address: String = row.get(0)?;
+            wallets.insert(address.clone(), WalletDatabaseStats {
+                address,
+                history_records: row.get::<_, i64>(1)? as usize,
+                oldest: Some(millis_to_datetime(row.get(2)?)),
+                newest: Some(millis_to_datetime(row.get(3)?)),
+                ..Default::default()
+            });
+        }
+
+        let mut stmt = conn.prepare("SELECT address, COUNT(*) FROM wallet_transactions GROUP BY address")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let address: String = row.get(0)?;
+            wallets
+                .entry(address.clone())
+                .or_insert_with(|| WalletDatabaseStats { address, ..Default::default() })
+                .transactions = row.get::<_, i64>(1)? as usize;
+        }
+
+        let wallets: Vec<WalletDatabaseStats> = wallets.into_values().collect();
+        Ok(DatabaseStats {
+            backend: "sqlite",
+            path: self.path.clone(),
+            file_size: self.file_size()?,
+            history_records: wallets.iter().map(|w| w.history_records).sum(),
+            transactions: wallets.iter().map(|w| w.transactions).sum(),
+            wallets,
+        })
+    }
+
+    fn compact_file(&self) -> Result<CompactResponse, Box<dyn std::error::Error>> {
+        let conn = self.conn();
+        let size_before = self.file_size()?;
+        conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE); VACUUM;")?;
+        let size_after = self.file_size()?;
+        info!("🗜️ 資料庫壓縮完成: {} → {} bytes", size_before, size_after);
+        Ok(CompactResponse { compacted: size_after < size_before, size_before, size_after })
+    }
+
+    fn check_writable(&self) -> Result<(), Box<dyn std::error::Error>> {
+        // 取得寫入鎖後立即回滾，不留下任何變更
+        self.conn().execute_batch("BEGIN IMMEDIATE; ROLLBACK;")?;
+        Ok(())
+    }
+
+    fn append_audit(&self, entry: &AuditEntry) -> Result<(), Box<dyn std::error::Error>> {
+        self.conn().execute(
+            "INSERT INTO audit_log (timestamp_ms, address, entry) VALUES (?1, ?2, ?3)",
+            params![history_key_millis(entry.timestamp) as i64, entry.address, serde_json::to_string(entry)?],
+        )?;
+        Ok(())
+    }
+
+    fn load_audit(&self, address: Option<&str>, before: Option<u64>, limit: usize) -> Result<Vec<AuditEntry>, Box<dyn std::error::Error>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, entry FROM audit_log
+             WHERE id < ?1 AND (?2 IS NULL OR address = ?2)
+             ORDER BY id DESC LIMIT ?3",
+        )?;
+        let rows = stmt
+            .query_map(params![before.map(|id| id as i64).unwrap_or(i64::MAX), address, limit as i64], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.into_iter()
+            .map(|(id, entry)| -> Result<AuditEntry, Box<dyn std::error::Error>> {
+                Ok(AuditEntry { id: id as u64, ..serde_json::from_str(&entry)? })
+            })
+            .collect()
+    }
+    fn append_token_event(&self, event: &TokenAccountEvent) -> Result<(), Box<dyn std::error::Error>> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO token_events (address, timestamp_ms, token_account, event) VALUES (?1, ?2, ?3, ?4)",
+            params![event.address, history_key_millis(event.timestamp) as i64, event.token_account, serde_json::to_string(event)?],
+        )?;
+        Ok(())
+    }
+
+    fn load_token_events(&self, address: &str, limit: usize) -> Result<Vec<TokenAccountEvent>, Box<dyn std::error::Error>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached("SELECT event FROM token_events WHERE address = ?1 ORDER BY timestamp_ms DESC LIMIT ?2")?;
+        let rows = stmt
+            .query_map(params![address, limit as i64], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.iter().map(|event| -> Result<TokenAccountEvent, Box<dyn std::error::Error>> { Ok(serde_json::from_str(event)?) }).collect()
+    }
+
+    fn save_seen_mints(&self, address: &str, mints: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached("INSERT OR IGNORE INTO seen_mints (address, mint, first_seen_ms) VALUES (?1, ?2, ?3)")?;
+            let now = Utc::now().timestamp_millis();
+            for mint in mints {
+                stmt.execute(params![address, mint, now])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn load_seen_mints(&self, address: &str) -> Result<std::collections::HashSet<String>, Box<dyn std::error::Error>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached("SELECT mint FROM seen_mints WHERE address = ?1")?;
+        let mints = stmt.query_map(params![address], |row| row.get::<_, String>(0))?.collect::<rusqlite::Result<_>>()?;
+        Ok(mints)
+    }
+    fn insert_alert(&self, record: &AlertRecord) -> Result<(), Box<dyn std::error::Error>> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO alerts (id, state, entry) VALUES (?1, ?2, ?3)",
+            params![record.id as i64, alert_state_name(record.state), serde_json::to_string(record)?],
+        )?;
+        Ok(())
+    }
+
+    fn save_alert_snooze(&self, rule: &str, address: &str, until: DateTime<Utc>) -> Result<(), Box<dyn std::error::Error>> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM alert_snoozes WHERE until_ms <= ?1", params![Utc::now().timestamp_millis()])?;
+        tx.execute(
+            "INSERT OR REPLACE INTO alert_snoozes (rule, address, until_ms) VALUES (?1, ?2, ?3)",
+            params![rule, address, until.timestamp_millis()],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn load_alert_snoozes(&self) -> Result<Vec<(String, String, DateTime<Utc>)>, Box<dyn std::error::Error>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached("SELECT rule, address, until_ms FROM alert_snoozes WHERE until_ms > ?1")?;
+        let snoozes = stmt
+            .query_map(params![Utc::now().timestamp_millis()], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, millis_to_datetime(row.get(2)?)))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(snoozes)
+    }
+
+    fn set_alert_state(&self, id: u64, state: AlertState) -> Result<Option<AlertRecord>, Box<dyn std::error::Error>> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let entry: Option<String> = tx.query_row("SELECT entry FROM alerts WHERE id = ?1", params![id as i64], |row| row.get(0)).optional()?;
+        let Some(entry) = entry else {
+            return Ok(None);
+        };
+        let mut record = AlertRecord { id, ..serde_json::from_str(&entry)? };
+        record.transition(state);
+        tx.execute(
+            "UPDATE alerts SET state = ?1, entry = ?2 WHERE id = ?3",
+            params![alert_state_name(record.state), serde_json::to_string(&record)?, id as i64],
+        )?;
+        tx.commit()?;
+        Ok(Some(record))
+    }
+
+    fn load_alerts(&self, state: Option<AlertState>, before: Option<u64>, limit: usize) -> Result<Vec<AlertRecord>, Box<dyn std::error::Error>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, entry FROM alerts
+             WHERE id < ?1 AND (?2 IS NULL OR state = ?2)
+             ORDER BY id DESC LIMIT ?3",
+        )?;
+        let rows = stmt
+            .query_map(params![before.map(|id| id as i64).unwrap_or(i64::MAX), state.map(alert_state_name), limit as i64], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.into_iter()
+            .map(|(id, entry)| -> Result<AlertRecord, Box<dyn std::error::Error>> {
+                Ok(AlertRecord { id: id as u64, ..serde_json::from_str(&entry)? })
+            })
+            .collect()
+    }
+
+    fn save_daily_report(&self, report: &DailyReport) -> Result<(), Box<dyn std::error::Error>> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO daily_reports (date, report) VALUES (?1, ?2)",
+            params![report.date.to_string(), serde_json::to_string(report)?],
+        )?;
+        Ok(())
+    }
+
+    fn load_daily_report(&self, date: chrono::NaiveDate) -> Result<Option<DailyReport>, Box<dyn std::error::Error>> {
+        let report: Option<String> = self
+            .conn()
+            .query_row("SELECT report FROM daily_reports WHERE date = ?1", params![date.to_string()], |row| row.get(0))
+            .optional()?;
+        Ok(report.map(|report| serde_json::from_str(&report)).transpose()?)
+    }
+
+    fn append_price(&self, point: &PricePoint) -> Result<(), Box<dyn std::error::Error>> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO sol_prices (timestamp_ms, usd) VALUES (?1, ?2)",
+            params![history_key_millis(point.timestamp) as i64, point.usd],
+        )?;
+        Ok(())
+    }
+
+    fn load_prices(&self, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<PricePoint>, Box<dyn std::error::Error>> {
+        let (start, end) = range_millis(from, to);
+        let conn = self.conn();
+        let to_point = |row: &Row| -> rusqlite::Result<PricePoint> {
+            Ok(PricePoint { timestamp: millis_to_datetime(row.get(0)?), usd: row.get(1)? })
+        };
+
+        // 與 redb 後端一致：以 from 之前的最後一筆作為起點
+        let mut prices = Vec::new();
+        if let Some(from) = from {
+            let before = conn
+                .query_row("SELECT timestamp_ms, usd FROM sol_prices WHERE timestamp_ms < ?1 ORDER BY timestamp_ms DESC LIMIT 1", params![start], to_point)
+                .optional()?;
+            prices.extend(before.map(|point| PricePoint { timestamp: from, ..point }));
+        }
+        let mut stmt = conn.prepare_cached("SELECT timestamp_ms, usd FROM sol_prices WHERE timestamp_ms BETWEEN ?1 AND ?2 ORDER BY timestamp_ms")?;
+        for point in stmt.query_map(params![start, end], to_point)? {
+            prices.push(point?);
+        }
+        Ok(prices)
+    }
+
+    fn latest_price(&self) -> Result<Option<PricePoint>, Box<dyn std::error::Error>> {
+        Ok(self
+            .conn()
+            .query_row("SELECT timestamp_ms, usd FROM sol_prices ORDER BY timestamp_ms DESC LIMIT 1", [], |row| {
+                Ok(PricePoint { timestamp: millis_to_datetime(row.get(0)?), usd: row.get(1)? })
+            })
+            .optional()?)
+    }
+
+    fn prune_prices(&self, before: DateTime<Utc>) -> Result<usize, Box<dyn std::error::Error>> {
+        // 每小時保留第一筆
+        let removed = self.conn().execute(
+            "DELETE FROM sol_prices WHERE timestamp_ms < ?1 AND timestamp_ms NOT IN \
+             (SELECT MIN(timestamp_ms) FROM sol_prices WHERE timestamp_ms < ?1 GROUP BY timestamp_ms / ?2)",
+            params![history_key_millis(before) as i64, HOUR_MS as i64],
+        )?;
+        Ok(removed)
+    }
+
+    fn append_fx_rates(&self, timestamp: DateTime<Utc>, rates: &[(String, f64)]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached("INSERT OR REPLACE INTO fx_rates (currency, timestamp_ms, rate) VALUES (?1, ?2, ?3)")?;
+            for (currency, rate) in rates {
+                stmt.execute(params![currency, history_key_millis(timestamp) as i64, rate])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn load_fx_rates(&self, currency: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<FxRate>, Box<dyn std::error::Error>> {
+        let (start, end) = range_millis(from, to);
+        let conn = self.conn();
+        let to_rate = |row: &Row| -> rusqlite::Result<FxRate> {
+            Ok(FxRate { timestamp: millis_to_datetime(row.get(0)?), rate: row.get(1)? })
+        };
+
+        let mut rates = Vec::new();
+        if let Some(from) = from {
+            let before = conn
+                .query_row(
+                    "SELECT timestamp_ms, rate FROM fx_rates WHERE currency = ?1 AND timestamp_ms < ?2 ORDER BY timestamp_ms DESC LIMIT 1",
+                    params![currency, start],
+                    to_rate,
+                )
+                .optional()?;
+            rates.extend(before.map(|rate| FxRate { timestamp: from, ..rate }));
+        }
+        let mut stmt =
+            conn.prepare_cached("SELECT timestamp_ms, rate FROM fx_rates WHERE currency = ?1 AND timestamp_ms BETWEEN ?2 AND ?3 ORDER BY timestamp_ms")?;
+        for rate in stmt.query_map(params![currency, start, end], to_rate)? {
+            rates.push(rate?);
+        }
+        Ok(rates)
+    }
+
+    fn save_cost_basis(&self, address: &str, basis: Option<&CostBasis>) -> Result<(), Box<dyn std::error::Error>> {
+        let conn = self.conn();
+        match basis {
+            Some(basis) => conn.execute(
+                "INSERT OR REPLACE INTO cost_basis (address, basis) VALUES (?1, ?2)",
+                params![address, serde_json::to_string(basis)?],
+            )?,
+            None => conn.execute("DELETE FROM cost_basis WHERE address = ?1", params![address])?,
+        };
+        Ok(())
+    }
+
+    fn load_cost_basis(&self, address: &str) -> Result<Option<CostBasis>, Box<dyn std::error::Error>> {
+        let basis: Option<String> = self
+            .conn()
+            .query_row("SELECT basis FROM cost_basis WHERE address = ?1", params![address], |row| row.get(0))
+            .optional()?;
+        Ok(basis.map(|basis| serde_json::from_str(&basis)).transpose()?)
+    }
+
+    fn save_epoch(&self, boundary: &EpochBoundary) -> Result<(), Box<dyn std::error::Error>> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO epochs (epoch, start_slot, started_at_ms) VALUES (?1, ?2, ?3)",
+            params![boundary.epoch as i64, boundary.start_slot as i64, history_key_millis(boundary.started_at) as i64],
+        )?;
+        Ok(())
+    }
+
+    fn load_epoch(&self, epoch: u64) -> Result<Option<EpochBoundary>, Box<dyn std::error::Error>> {
+        let boundary = self
+            .conn()
+            .query_row("SELECT epoch, start_slot, started_at_ms FROM epochs WHERE epoch = ?1", params![epoch as i64], epoch_from_row)
+            .optional()?;
+        Ok(boundary)
+    }
+
+    fn load_epochs(&self, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<EpochBoundary>, Box<dyn std::error::Error>> {
+        let (start, end) = range_millis(from, to);
+        let conn = self.conn();
+        let mut stmt =
+            conn.prepare_cached("SELECT epoch, start_slot, started_at_ms FROM epochs WHERE started_at_ms BETWEEN ?1 AND ?2 ORDER BY epoch")?;
+        let epochs = stmt.query_map(params![start, end], epoch_from_row)?.collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(epochs)
+    }
+
+    fn save_stake_reward(&self, address: &str, reward: &StakeReward) -> Result<(), Box<dyn std::error::Error>> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO stake_rewards (address, epoch, credited_at_ms, reward) VALUES (?1, ?2, ?3, ?4)",
+            params![address, reward.epoch as i64, history_key_millis(reward.credited_at) as i64, serde_json::to_string(reward)?],
+        )?;
+        Ok(())
+    }
+
+    fn load_stake_rewards(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<StakeReward>, Box<dyn std::error::Error>> {
+        let (start, end) = range_millis(from, to);
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT reward FROM stake_rewards WHERE address = ?1 AND credited_at_ms BETWEEN ?2 AND ?3 ORDER BY epoch",
+        )?;
+        let rewards = stmt.query_map(params![address, start, end], |row| row.get::<_, String>(0))?.collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rewards.iter().map(|reward| serde_json::from_str(reward)).collect::<Result<_, _>>()?)
+    }
+
+    fn append_token_amount(&self, record: &TokenAmountRecord) -> Result<(), Box<dyn std::error::Error>> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO token_amounts (address, timestamp_ms, amount) VALUES (?1, ?2, ?3)",
+            params![record.address, history_key_millis(record.timestamp) as i64, record.amount],
+        )?;
+        Ok(())
+    }
+
+    fn load_token_amounts(&self, address: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Vec<TokenAmountRecord>, Box<dyn std::error::Error>> {
+        let (start, end) = range_millis(from, to);
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT timestamp_ms, amount FROM token_amounts WHERE address = ?1 AND timestamp_ms BETWEEN ?2 AND ?3 ORDER BY timestamp_ms",
+        )?;
+        let records = stmt
+            .query_map(params![address, start, end], |row| {
+                Ok(TokenAmountRecord {
+                    address: address.to_string(),
+                    timestamp: DateTime::from_timestamp_millis(row.get(0)?).unwrap_or_default(),
+                    amount: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(records)
+    }
+}
+
+fn alert_state_name(state: AlertState) -> &'static str {
+    match state {
+        AlertState::Open => "open",
+        AlertState::Acknowledged => "acknowledged",
+        AlertState::Resolved => "resolved",
+    }
+}